@@ -0,0 +1,69 @@
+/*!
+ * BiBi-Sync Power-of-Two Indexing Benchmark
+ *
+ * One push and one pop per iteration through buffers of the same size,
+ * comparing:
+ * - RingBuffer::new / ByteRingBuffer::new           (slot index via %)
+ * - RingBuffer::new_pow2 / ByteRingBuffer::new_pow2 (slot index via &)
+ *
+ * Run with: cargo bench --bench pow2_indexing
+ */
+
+use bibi_sync::{ByteRingBuffer, RingBuffer};
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::hint::black_box;
+
+const CAPACITY: usize = 4096;
+
+fn ring_buffer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("RingBuffer<u64>");
+
+    let modulo = RingBuffer::new(CAPACITY);
+    let mut i = 0u64;
+    group.bench_function("modulo", |b| {
+        b.iter(|| {
+            i += 1;
+            modulo.push(i);
+            black_box(modulo.pop())
+        })
+    });
+
+    let mask = RingBuffer::new_pow2(CAPACITY);
+    let mut i = 0u64;
+    group.bench_function("mask", |b| {
+        b.iter(|| {
+            i += 1;
+            mask.push(i);
+            black_box(mask.pop())
+        })
+    });
+
+    group.finish();
+}
+
+fn byte_ring_buffer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ByteRingBuffer");
+    let payload = [0xA5u8; 40];
+    let mut out = Vec::new();
+
+    let modulo = ByteRingBuffer::new(CAPACITY);
+    group.bench_function("modulo", |b| {
+        b.iter(|| {
+            modulo.push(&payload);
+            black_box(modulo.pop_into(&mut out))
+        })
+    });
+
+    let mask = ByteRingBuffer::new_pow2(CAPACITY);
+    group.bench_function("mask", |b| {
+        b.iter(|| {
+            mask.push(&payload);
+            black_box(mask.pop_into(&mut out))
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, ring_buffer, byte_ring_buffer);
+criterion_main!(benches);
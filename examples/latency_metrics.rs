@@ -1,4 +1,4 @@
-/**
+/*!
  * BiBi-Sync Latency Metrics Test
  * 
  * Measures round-trip latency:
@@ -9,74 +9,15 @@
  * Outputs CSV for analysis and prints summary statistics.
  */
 
-use bibi_sync::{MsgType, ImuMsg, OrientationMsg, DepthMsg};
+use bibi_sync::{decode_frame, MsgType};
 use std::io::Read;
 use std::time::{Duration, Instant};
 use std::fs::File;
 use std::io::Write;
 
 const BAUD_RATE: u32 = 9600;
-const SYNC_BYTE: u8 = 0xAA;
-const MAX_MSG_SIZE: usize = 244;
 const NUM_SAMPLES: usize = 1000;
 
-fn calculate_checksum(data: &[u8]) -> u8 {
-    data.iter().fold(0u8, |acc, &b| acc.wrapping_add(b))
-}
-
-fn try_parse_frame(buffer: &mut Vec<u8>) -> Option<(MsgType, Vec<u8>, Instant)> {
-    let parse_start = Instant::now();
-    
-    if buffer.len() < 4 {
-        return None;
-    }
-    
-    let sync_pos = buffer.iter().position(|&b| b == SYNC_BYTE)?;
-    if sync_pos > 0 {
-        buffer.drain(0..sync_pos);
-    }
-    
-    if buffer.len() < 4 {
-        return None;
-    }
-    
-    let msg_type_byte = buffer[1];
-    let len = buffer[2] as usize;
-    
-    if len > MAX_MSG_SIZE {
-        buffer.remove(0);
-        return None;
-    }
-    
-    let frame_len = 4 + len;
-    if buffer.len() < frame_len {
-        return None;
-    }
-    
-    let checksum = buffer[3 + len];
-    let calculated = calculate_checksum(&buffer[1..3 + len]);
-    
-    if checksum != calculated {
-        buffer.remove(0);
-        return None;
-    }
-    
-    let msg_type = match msg_type_byte {
-        0x01 => MsgType::Imu,
-        0x02 => MsgType::Depth,
-        0x05 => MsgType::Orientation,
-        _ => {
-            buffer.drain(0..frame_len);
-            return None;
-        }
-    };
-    
-    let payload = buffer[3..3 + len].to_vec();
-    buffer.drain(0..frame_len);
-    
-    Some((msg_type, payload, parse_start))
-}
-
 fn main() {
     println!("==============================================");
     println!("  BiBi-Sync Latency Metrics Test");
@@ -93,7 +34,7 @@ fn main() {
     let mut port = serialport::new(port_name, BAUD_RATE)
         .timeout(Duration::from_millis(100))
         .open()
-        .expect(&format!("Failed to open port {}", port_name));
+        .unwrap_or_else(|e| panic!("Failed to open port {}: {}", port_name, e));
     
     println!("✅ Port opened, collecting {} samples...\n", NUM_SAMPLES);
     
@@ -120,7 +61,12 @@ fn main() {
                 let rx_time = rx_start.elapsed();
                 rx_buffer.extend_from_slice(&read_buf[..n]);
                 
-                while let Some((msg_type, payload, parse_start)) = try_parse_frame(&mut rx_buffer) {
+                loop {
+                    let parse_start = Instant::now();
+                    let msg_type = match decode_frame(&mut rx_buffer) {
+                        Some(frame) => frame.msg_type,
+                        None => break,
+                    };
                     let parse_time = parse_start.elapsed();
                     let total_time = rx_start.elapsed();
                     
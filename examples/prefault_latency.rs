@@ -0,0 +1,60 @@
+/*!
+ * BiBi-Sync Prefault Latency Benchmark
+ *
+ * Publishes into freshly allocated ring buffers and compares the latency of
+ * the first lap (every slot written for the first time) between:
+ * - ByteRingBuffer::new            (pages faulted in on first write)
+ * - ByteRingBuffer::new_prefaulted (pages touched at construction)
+ *
+ * Run in release mode: cargo run --release --example prefault_latency
+ */
+
+use bibi_sync::ByteRingBuffer;
+use std::time::Instant;
+
+const CAPACITY: usize = 16384;
+const TRIALS: usize = 10;
+
+/// Per-publish latencies in nanoseconds for one full lap
+fn first_lap(rb: &ByteRingBuffer) -> Vec<u64> {
+    let payload = [0xA5u8; 40];
+    (0..CAPACITY)
+        .map(|_| {
+            let start = Instant::now();
+            rb.push(&payload);
+            start.elapsed().as_nanos() as u64
+        })
+        .collect()
+}
+
+fn summarize(label: &str, mut samples: Vec<u64>) {
+    samples.sort_unstable();
+    let p = |q: f64| samples[((samples.len() - 1) as f64 * q) as usize];
+    let mean = samples.iter().sum::<u64>() / samples.len() as u64;
+    println!(
+        "{:<12} mean {:>6} ns | p50 {:>6} ns | p99 {:>6} ns | p99.9 {:>7} ns | max {:>8} ns",
+        label, mean, p(0.5), p(0.99), p(0.999), samples[samples.len() - 1]
+    );
+}
+
+fn main() {
+    println!("==============================================");
+    println!("  BiBi-Sync Prefault Latency Benchmark");
+    println!("==============================================\n");
+    println!(
+        "Capacity: {} slots ({} KiB), {} trials\n",
+        CAPACITY,
+        ByteRingBuffer::new(CAPACITY).memory_footprint() / 1024,
+        TRIALS
+    );
+
+    let mut cold = Vec::new();
+    let mut warm = Vec::new();
+    for _ in 0..TRIALS {
+        cold.extend(first_lap(&ByteRingBuffer::new(CAPACITY)));
+        warm.extend(first_lap(&ByteRingBuffer::new_prefaulted(CAPACITY)));
+    }
+
+    summarize("cold", cold);
+    summarize("prefaulted", warm);
+}
@@ -1,4 +1,4 @@
-/**
+/*!
  * End-to-End Test for BiBi-Sync UART Bridge
  * 
  * Tests:
@@ -8,92 +8,22 @@
  */
 
 use bibi_sync::{
-    MsgType, ThrusterPwmCmd, ImuMsg, OrientationMsg, DepthMsg,
+    FrameCodec, MsgType, ThrusterPwmCmd, ImuMsg, OrientationMsg, DepthMsg,
 };
 use std::io::{Read, Write};
 use std::time::{Duration, Instant};
 use serialport::SerialPort;
 
 const BAUD_RATE: u32 = 9600;
-const SYNC_BYTE: u8 = 0xAA;
-const MAX_MSG_SIZE: usize = 244;
 
-fn calculate_checksum(data: &[u8]) -> u8 {
-    data.iter().fold(0u8, |acc, &b| acc.wrapping_add(b))
-}
-
-fn send_frame(port: &mut Box<dyn SerialPort>, msg_type: MsgType, payload: &[u8]) -> std::io::Result<()> {
-    let mut frame = Vec::with_capacity(4 + payload.len());
-    frame.push(SYNC_BYTE);
-    frame.push(msg_type as u8);
-    frame.push(payload.len() as u8);
-    frame.extend_from_slice(payload);
-    
-    let checksum = calculate_checksum(&frame[1..]);
-    frame.push(checksum);
-    
-    port.write_all(&frame)?;
+fn send_frame(port: &mut Box<dyn SerialPort>, codec: &FrameCodec, msg_type: MsgType, payload: &[u8]) -> std::io::Result<()> {
+    port.write_all(&codec.encode(msg_type, payload))?;
     port.flush()?;
     
     println!("[TX] Sent {:?} frame, {} bytes payload", msg_type, payload.len());
     Ok(())
 }
 
-fn try_parse_frame(buffer: &mut Vec<u8>) -> Option<(MsgType, Vec<u8>)> {
-    if buffer.len() < 4 {
-        return None;
-    }
-    
-    // Find sync byte
-    let sync_pos = buffer.iter().position(|&b| b == SYNC_BYTE)?;
-    if sync_pos > 0 {
-        buffer.drain(0..sync_pos);
-    }
-    
-    if buffer.len() < 4 {
-        return None;
-    }
-    
-    let msg_type_byte = buffer[1];
-    let len = buffer[2] as usize;
-    
-    if len > MAX_MSG_SIZE {
-        buffer.remove(0);
-        return None;
-    }
-    
-    let frame_len = 4 + len;
-    if buffer.len() < frame_len {
-        return None;
-    }
-    
-    // Verify checksum
-    let checksum = buffer[3 + len];
-    let calculated = calculate_checksum(&buffer[1..3 + len]);
-    
-    if checksum != calculated {
-        println!("[RX] Checksum mismatch: expected {}, got {}", calculated, checksum);
-        buffer.remove(0);
-        return None;
-    }
-    
-    let msg_type = match msg_type_byte {
-        0x01 => MsgType::Imu,
-        0x02 => MsgType::Depth,
-        0x05 => MsgType::Orientation,
-        _ => {
-            println!("[RX] Unknown message type: 0x{:02X}", msg_type_byte);
-            buffer.drain(0..frame_len);
-            return None;
-        }
-    };
-    
-    let payload = buffer[3..3 + len].to_vec();
-    buffer.drain(0..frame_len);
-    
-    Some((msg_type, payload))
-}
-
 fn main() {
     println!("==============================================");
     println!("  BiBi-Sync STM32 End-to-End Test");
@@ -121,17 +51,19 @@ fn main() {
     let mut port = serialport::new(&port_name, BAUD_RATE)
         .timeout(Duration::from_millis(100))
         .open()
-        .expect(&format!("Failed to open port {}", port_name));
+        .unwrap_or_else(|e| panic!("Failed to open port {}: {}", port_name, e));
     
     println!("✅ Port opened successfully!\n");
     
+    let codec = FrameCodec::new();
+    
     // Give STM32 time to initialize
     std::thread::sleep(Duration::from_secs(2));
     
     // Test 1: Send neutral PWM values
     println!("--- Test 1: Sending neutral PWM (1500) ---");
     let pwm_cmd = ThrusterPwmCmd::new([1500, 1500, 1500, 1500, 1500, 1500]);
-    send_frame(&mut port, MsgType::Thruster, &pwm_cmd.to_bytes()).expect("Failed to send PWM");
+    send_frame(&mut port, &codec, MsgType::Thruster, &pwm_cmd.to_bytes()).expect("Failed to send PWM");
     
     // Receive sensor data for 5 seconds
     println!("\n--- Receiving sensor data for 10 seconds ---\n");
@@ -149,8 +81,9 @@ fn main() {
             Ok(n) if n > 0 => {
                 rx_buffer.extend_from_slice(&read_buf[..n]);
                 
-                while let Some((msg_type, payload)) = try_parse_frame(&mut rx_buffer) {
-                    match msg_type {
+                while let Some(frame) = codec.decode(&mut rx_buffer) {
+                    let payload = frame.payload;
+                    match frame.msg_type {
                         MsgType::Imu => {
                             if let Some(imu) = ImuMsg::from_bytes(&payload) {
                                 imu_count += 1;
@@ -207,7 +140,7 @@ fn main() {
     // Ramp up thrusters slightly
     for pwm in [1500, 1520, 1540, 1520, 1500] {
         let pwm_cmd = ThrusterPwmCmd::new([pwm; 6]);
-        send_frame(&mut port, MsgType::Thruster, &pwm_cmd.to_bytes()).expect("Failed to send PWM");
+        send_frame(&mut port, &codec, MsgType::Thruster, &pwm_cmd.to_bytes()).expect("Failed to send PWM");
         println!("[TX] PWM = {}", pwm);
         std::thread::sleep(Duration::from_millis(500));
     }
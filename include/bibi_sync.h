@@ -12,10 +12,24 @@
 
 #define MAX_PAYLOAD_SIZE (SLOT_SIZE - HEADER_SIZE)
 
+#define PRIORITY_LANES 4
+
+#define LOG_VERSION 1
+
+#define MAX_RECORD_PAYLOAD MAX_PAYLOAD_SIZE
+
+#define MAX_AUTO_CAPACITY 4096
+
 #define SYNC_BYTE 170
 
 #define MAX_MSG_SIZE 244
 
+#define FRAME_OVERHEAD 4
+
+#define RX_BUFFER_CAPACITY 512
+
+#define ESCAPE_BYTE 125
+
 #define IMU_MSG_SIZE 36
 
 #define ORIENTATION_MSG_SIZE 12
@@ -28,6 +42,12 @@
 
 #define CALIBRATION_CMD_SIZE 1
 
+#define PWM_MIN 1000
+
+#define PWM_MAX 2000
+
+#define MAX_THRUSTER_CHANNELS 8
+
 typedef struct BibiByteTopic BibiByteTopic;
 
 typedef struct BibiRegistry BibiRegistry;
@@ -51,6 +71,12 @@ int32_t bibi_byte_topic_try_receive(struct BibiByteTopic *topic,
                                     uintptr_t *out_len,
                                     uintptr_t max_len);
 
+int32_t bibi_byte_topic_try_receive_epoch(struct BibiByteTopic *topic,
+                                          uint8_t *out_data,
+                                          uintptr_t *out_len,
+                                          uint64_t *out_epoch,
+                                          uintptr_t max_len);
+
 int32_t bibi_byte_topic_peek_latest(struct BibiByteTopic *topic,
                                     uint8_t *out_data,
                                     uintptr_t *out_len,
@@ -63,17 +89,36 @@ bool bibi_byte_topic_is_empty(struct BibiByteTopic *topic);
 
 uint64_t bibi_byte_topic_latest_epoch(struct BibiByteTopic *topic);
 
+void bibi_byte_topic_ack(struct BibiByteTopic *topic, uint64_t epoch);
+
+uint64_t bibi_byte_topic_acked_epoch(struct BibiByteTopic *topic);
+
+uintptr_t bibi_byte_topic_capacity(struct BibiByteTopic *topic);
+
+uint64_t bibi_byte_topic_dropped(struct BibiByteTopic *topic);
+
+bool bibi_byte_topic_is_full(struct BibiByteTopic *topic);
+
 struct BibiTypedTopic *bibi_registry_get_typed_topic(struct BibiRegistry *registry,
                                                      const char *name,
                                                      uintptr_t capacity,
                                                      uintptr_t msg_size);
 
+struct BibiTypedTopic *bibi_registry_get_protocol_topic(struct BibiRegistry *registry,
+                                                        const char *name,
+                                                        uintptr_t capacity,
+                                                        uint8_t msg_type);
+
 void bibi_typed_topic_free(struct BibiTypedTopic *topic);
 
 uint64_t bibi_typed_topic_publish(struct BibiTypedTopic *topic, const uint8_t *data);
 
 int32_t bibi_typed_topic_try_receive(struct BibiTypedTopic *topic, uint8_t *out_data);
 
+int32_t bibi_typed_topic_try_receive_validated(struct BibiTypedTopic *topic,
+                                               uint8_t *out_data,
+                                               uint64_t *out_epoch);
+
 int32_t bibi_typed_topic_peek_latest(struct BibiTypedTopic *topic,
                                      uint8_t *out_data,
                                      uint64_t *out_epoch);
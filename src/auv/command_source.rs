@@ -0,0 +1,149 @@
+/*!
+ * Command Sources
+ *
+ * Pluggable thrust command inputs (gamepad, network socket, mission
+ * script) and the arbitration that picks one of them every TX tick.
+ */
+
+use std::time::{Duration, Instant};
+
+use super::thrust_mixer::ThrustCommand;
+
+/// How long a source stays in control after its last command
+pub const DEFAULT_SOURCE_HOLD: Duration = Duration::from_millis(200);
+
+/// Anything that can produce thrust commands
+///
+/// `poll` is called once per TX tick and must not block. Return `None` when
+/// there is nothing new; the last command is held for the arbiter's hold
+/// window, so sources only need to report changes.
+pub trait CommandSource: Send {
+    fn poll(&mut self) -> Option<ThrustCommand>;
+}
+
+struct Entry {
+    priority: u8,
+    source: Box<dyn CommandSource>,
+    last: Option<(ThrustCommand, Instant)>,
+}
+
+/// Selects among command sources by priority
+///
+/// A source is active while its last command is younger than the hold
+/// window. The active source with the highest priority wins; between equal
+/// priorities the one added first wins.
+pub struct CommandArbiter {
+    entries: Vec<Entry>,
+    hold: Duration,
+}
+
+impl CommandArbiter {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            hold: DEFAULT_SOURCE_HOLD,
+        }
+    }
+
+    /// Keep a silent source in control for `hold` after its last command
+    pub fn with_hold(mut self, hold: Duration) -> Self {
+        self.hold = hold;
+        self
+    }
+
+    pub fn add(&mut self, priority: u8, source: Box<dyn CommandSource>) {
+        self.entries.push(Entry { priority, source, last: None });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Poll every source and return the winning command, if any is active
+    pub fn select(&mut self, now: Instant) -> Option<ThrustCommand> {
+        let mut winner: Option<(u8, ThrustCommand)> = None;
+
+        for entry in self.entries.iter_mut() {
+            if let Some(cmd) = entry.source.poll() {
+                entry.last = Some((cmd, now));
+            }
+            let cmd = match entry.last {
+                Some((cmd, at)) if now.duration_since(at) <= self.hold => cmd,
+                _ => continue,
+            };
+            if winner.is_none_or(|(priority, _)| entry.priority > priority) {
+                winner = Some((entry.priority, cmd));
+            }
+        }
+
+        winner.map(|(_, cmd)| cmd)
+    }
+}
+
+impl Default for CommandArbiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Source whose next poll result is set from the test
+    #[derive(Clone, Default)]
+    pub(crate) struct MockSource(pub Arc<Mutex<Option<ThrustCommand>>>);
+
+    impl MockSource {
+        pub(crate) fn send(&self, surge: f32) {
+            *self.0.lock().unwrap() = Some(ThrustCommand { surge, ..Default::default() });
+        }
+    }
+
+    impl CommandSource for MockSource {
+        fn poll(&mut self) -> Option<ThrustCommand> {
+            self.0.lock().unwrap().take()
+        }
+    }
+
+    #[test]
+    fn test_higher_priority_wins_when_both_active() {
+        let script = MockSource::default();
+        let gamepad = MockSource::default();
+        let mut arbiter = CommandArbiter::new();
+        arbiter.add(1, Box::new(script.clone()));
+        arbiter.add(10, Box::new(gamepad.clone()));
+
+        let t0 = Instant::now();
+        assert!(arbiter.select(t0).is_none());
+
+        script.send(0.2);
+        assert_eq!(arbiter.select(t0).unwrap().surge, 0.2);
+
+        script.send(0.3);
+        gamepad.send(-0.5);
+        assert_eq!(arbiter.select(t0).unwrap().surge, -0.5);
+
+        // gamepad still held even though only the script spoke
+        script.send(0.4);
+        assert_eq!(arbiter.select(t0 + Duration::from_millis(100)).unwrap().surge, -0.5);
+    }
+
+    #[test]
+    fn test_silent_source_hands_back_control() {
+        let script = MockSource::default();
+        let gamepad = MockSource::default();
+        let mut arbiter = CommandArbiter::new().with_hold(Duration::from_millis(50));
+        arbiter.add(1, Box::new(script.clone()));
+        arbiter.add(10, Box::new(gamepad.clone()));
+
+        let t0 = Instant::now();
+        gamepad.send(-0.5);
+        assert_eq!(arbiter.select(t0).unwrap().surge, -0.5);
+
+        script.send(0.3);
+        assert_eq!(arbiter.select(t0 + Duration::from_millis(60)).unwrap().surge, 0.3);
+        assert!(arbiter.select(t0 + Duration::from_millis(200)).is_none());
+    }
+}
@@ -1,60 +1,333 @@
-/**
+/*!
  * AUV Controller
  * 
  * Main controller that:
  * 1. Connects to STM32 via UART
  * 2. Receives sensor data (IMU, depth, orientation)
- * 3. Accepts thrust commands from Python/threads
+ * 3. Accepts thrust commands from Python/threads and command sources
  * 4. Sends PWM commands to STM32
+ * 5. Disarms (neutral PWM) if the STM32 heartbeat goes stale
  */
 
-use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex, PoisonError, TryLockError, atomic::{AtomicBool, AtomicU64, Ordering}};
 use std::thread;
-use std::time::Duration;
-use std::io::{Read, Write};
+use std::time::{Duration, Instant};
 
+use crate::poison::{MutexExt, RwLockExt};
 use crate::pubsub::TopicRegistry;
-use crate::{MsgType, ThrusterPwmCmd, ImuMsg, OrientationMsg, DepthMsg};
+use crate::uart::{ChecksumCoverage, ChecksumKind, Endianness, FrameCodec, Preamble, RetryPolicy, Transport, RX_BUFFER_CAPACITY, write_with_retry};
+use crate::{MsgType, ThrusterPwmCmd, LedCmd, ImuMsg, OrientationMsg, DepthMsg};
+use crate::uart::MAX_THRUSTER_CHANNELS;
+use super::clock::{Clock, SystemClock};
+use super::command_source::{CommandArbiter, CommandSource};
+use super::pid::Pid;
+use super::seqlock::SeqLock;
 use super::thrust_mixer::{ThrustMixer, ThrustCommand};
 
-const SYNC_BYTE: u8 = 0xAA;
-const MAX_MSG_SIZE: usize = 244;
 const DEFAULT_BAUD: u32 = 9600;
+const DEFAULT_HEARTBEAT_TIMEOUT: Duration = Duration::from_millis(1000);
+const DEFAULT_SENSOR_TIMEOUT: Duration = Duration::from_millis(500);
+/// How long the STM32 gets to send its first frame before silence counts
+const DEFAULT_SENSOR_STARTUP_GRACE: Duration = Duration::from_secs(2);
+const TX_PERIOD: Duration = Duration::from_millis(20);
+const NEUTRAL_PWM: [i32; 6] = [1500; 6];
+/// Frames decoded per loop iteration before yielding to the TX check
+const DEFAULT_MAX_FRAMES_PER_ITER: usize = 64;
+/// Thruster channels in the standard STM32 PWM frame; a mixer with fewer
+/// leaves the rest neutral, one with more extends the frame
+const THRUSTER_CHANNELS: usize = 6;
+/// Slots per RX topic, matching the UART bridge
+const RX_TOPIC_CAPACITY: usize = 32;
+/// Frames `queue_frame` holds before the drop policy kicks in
+const DEFAULT_TX_QUEUE_DEPTH: usize = 64;
+
+/// Vehicle state shown on the STM32 indicator LED when `with_status_led` is on
+///
+/// The discriminant is the `LedCmd::indicator` value sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[repr(i16)]
+pub enum LedStatus {
+    /// Disarmed by the operator
+    Off = 0,
+    /// Armed, thrust commands pass through
+    Solid = 1,
+    /// Disarmed by a failsafe (heartbeat lost or a PWM frame failed to send)
+    Blink = 2,
+}
+
+/// What `queue_frame` gives up when the TX queue is already full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TxDropPolicy {
+    /// Refuse the frame being queued, keeping everything already waiting
+    #[default]
+    DropNewest,
+    /// Discard the oldest waiting frame to make room for the new one
+    DropOldest,
+}
+
+/// Why a failsafe disarmed the vehicle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailsafeCause {
+    /// The STM32 heartbeat went silent for longer than the timeout
+    HeartbeatLost,
+    /// A thruster frame could not be written
+    CommandLost,
+}
+
+/// State transition reported to `AuvController::on_event` callbacks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControllerEvent {
+    /// `arm()` took the vehicle out of the disarmed state
+    Armed,
+    /// The operator disarmed
+    Disarmed,
+    /// A failsafe disarmed; sent instead of `Disarmed`
+    Failsafe { cause: FailsafeCause },
+    /// No heartbeat for longer than the timeout, armed or not
+    HeartbeatLost { last_seen: Duration },
+    /// A heartbeat arrived again after `HeartbeatLost`
+    HeartbeatRestored,
+    /// A frame failed to go out; `link_ok()` turned false
+    LinkDown,
+    /// A frame went out again after `LinkDown`
+    LinkUp,
+}
 
 /// Latest sensor readings from STM32
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct SensorData {
     pub imu: Option<ImuMsg>,
     pub orientation: Option<OrientationMsg>,
     pub depth: Option<DepthMsg>,
 }
 
+/// Traffic on one RX topic, as reported by `AuvController::status`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TopicStatus {
+    pub name: String,
+    pub len: usize,
+    pub capacity: usize,
+    pub published: u64,
+    pub dropped: u64,
+    /// Messages per second since the previous `status()` call; `None` on the first
+    pub rate_hz: Option<f64>,
+}
+
+/// One-call snapshot of the whole controller: link, arming, sensors, output
+///
+/// Ages are milliseconds on the controller clock and `None` until the first
+/// frame of that kind arrives.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct AuvStatus {
+    pub armed: bool,
+    pub failsafe: bool,
+    pub led: LedStatus,
+    pub link_ok: bool,
+    pub tx_errors: u64,
+    /// Frame headers dropped for declaring an impossible payload length
+    pub rx_bad_lengths: u64,
+    pub heartbeat_age_ms: Option<u64>,
+    pub imu_age_ms: Option<u64>,
+    pub orientation_age_ms: Option<u64>,
+    pub depth_age_ms: Option<u64>,
+    pub orientation: Option<(f32, f32, f32)>,
+    pub depth: Option<f32>,
+    /// PWM of the last thruster frame that reached the STM32
+    pub last_pwm: Vec<i32>,
+    pub topics: Vec<TopicStatus>,
+}
+
+/// A PID loop and the setpoint it holds, if any
+#[derive(Debug, Clone, Copy)]
+struct Hold {
+    pid: Pid,
+    setpoint: Option<f32>,
+}
+
+impl Hold {
+    fn new(pid: Pid) -> Self {
+        Self { pid, setpoint: None }
+    }
+    
+    /// Engaging or releasing the hold starts the loop afresh
+    fn set(&mut self, setpoint: Option<f32>) {
+        if setpoint.is_none() || self.setpoint.is_none() {
+            self.pid.reset();
+        }
+        self.setpoint = setpoint;
+    }
+    
+    /// Correction for this step; none while released or without a reading.
+    /// `error` maps (setpoint, measured) to the remaining error
+    fn correction(&mut self, measured: Option<f32>, dt: f32, error: impl Fn(f32, f32) -> f32) -> f32 {
+        match (self.setpoint, measured) {
+            (Some(setpoint), Some(measured)) => self.pid.update(error(setpoint, measured), 0.0, dt),
+            _ => 0.0,
+        }
+    }
+}
+
+/// Shortest signed turn from `from` to `to`, in degrees within -180..=180
+fn heading_error(to: f32, from: f32) -> f32 {
+    let error = (to - from).rem_euclid(360.0);
+    if error > 180.0 { error - 360.0 } else { error }
+}
+
+/// One sequence lock per reading, so the control loop copies only the
+/// readings it uses instead of the whole `SensorData`
+#[derive(Default)]
+struct SensorCache {
+    imu: SeqLock<Option<ImuMsg>>,
+    orientation: SeqLock<Option<OrientationMsg>>,
+    depth: SeqLock<Option<DepthMsg>>,
+}
+
+/// Decoder invoked with the payload of every frame of its registered type
+pub type SensorDecoder = Arc<dyn Fn(&[u8]) + Send + Sync>;
+
+/// Callback invoked with every `ControllerEvent`
+pub type EventCallback = Box<dyn Fn(ControllerEvent) + Send>;
+
 /// AUV Controller - unified control system
 pub struct AuvController {
+    // Every RX payload is republished here, one byte topic per message type
     registry: Arc<TopicRegistry>,
     mixer: ThrustMixer,
     running: Arc<AtomicBool>,
     port_name: String,
     baud_rate: u32,
     
-    // Latest sensor data (thread-safe)
-    sensors: Arc<std::sync::RwLock<SensorData>>,
+    // Latest sensor data; readers never block the RX thread's writes
+    sensors: Arc<SensorCache>,
     
     // Current thrust command
     thrust_cmd: Arc<std::sync::RwLock<ThrustCommand>>,
+    
+    // Pluggable inputs that override thrust_cmd while active
+    sources: Mutex<CommandArbiter>,
+    
+    // Wire framing shared with the bridge
+    codec: FrameCodec,
+    
+    // Payload decoders keyed by message type
+    decoders: Mutex<HashMap<MsgType, SensorDecoder>>,
+    max_frames_per_iter: usize,
+    // Bytes waiting for the rest of their frame; kept between runs so every
+    // reconnect reuses the allocation
+    rx_buffer: Mutex<Vec<u8>>,
+    // When each message type was last received
+    last_rx: Mutex<HashMap<MsgType, Instant>>,
+    
+    // Thrusters only follow commands while armed
+    armed: AtomicBool,
+    // Set when a failsafe (not the operator) disarmed, cleared by arm()
+    failsafe: AtomicBool,
+    armed_at: Mutex<Option<Instant>>,
+    arm_ramp: Duration,
+    // Set by emergency_stop: send neutral on the next iteration, unramped
+    estop_pending: AtomicBool,
+    
+    // Closed-loop depth and heading, added to heave and yaw before mixing
+    depth_hold: Mutex<Hold>,
+    heading_hold: Mutex<Hold>,
+    last_hold_step: Mutex<Option<Instant>>,
+    
+    // Non-PWM frames (LED, calibration, ...) waiting for the loop
+    tx_queue: Mutex<VecDeque<(MsgType, Vec<u8>)>>,
+    tx_queue_depth: usize,
+    tx_drop_policy: TxDropPolicy,
+    tx_dropped: AtomicU64,
+    // None: the mixer's neutral on every channel
+    shutdown_pwm: Option<Vec<i32>>,
+    status_led: bool,
+    last_led: Mutex<Option<LedStatus>>,
+    // Outcome of the last exit flush: Some(true) once the shutdown PWM went out
+    shutdown_done: Mutex<Option<bool>>,
+    shutdown_cv: Condvar,
+    last_pwm: Mutex<Vec<i32>>,
+    // Published count per topic at the previous status() call, for rates
+    rate_marks: Mutex<HashMap<String, (Instant, u64)>>,
+    
+    // Link supervision
+    clock: Arc<dyn Clock>,
+    heartbeat_timeout: Duration,
+    last_heartbeat: Mutex<Option<Instant>>,
+    // Set once HeartbeatLost has been reported, until the next heartbeat
+    heartbeat_lost: AtomicBool,
+    // Any valid frame counts; silence holds the thrusters at neutral
+    sensor_timeout: Duration,
+    last_frame: Mutex<Option<Instant>>,
+    // Until the first frame: the grace, counted from the first control step
+    sensor_startup_grace: Duration,
+    first_step: Mutex<Option<Instant>>,
+    connected: AtomicBool,
+    link_ok: AtomicBool,
+    tx_errors: AtomicU64,
+    tx_retry: RetryPolicy,
+    rx_bad_lengths: AtomicU64,
+    
+    // Lifecycle callbacks, and events waiting for whoever is dispatching
+    listeners: Mutex<Vec<EventCallback>>,
+    pending_events: Mutex<VecDeque<ControllerEvent>>,
 }
 
 impl AuvController {
     pub fn new(port_name: &str) -> Self {
-        Self {
+        let controller = Self {
             registry: Arc::new(TopicRegistry::new()),
             mixer: ThrustMixer::default(),
             running: Arc::new(AtomicBool::new(false)),
             port_name: port_name.to_string(),
             baud_rate: DEFAULT_BAUD,
-            sensors: Arc::new(std::sync::RwLock::new(SensorData::default())),
+            sensors: Arc::new(SensorCache::default()),
             thrust_cmd: Arc::new(std::sync::RwLock::new(ThrustCommand::default())),
-        }
+            sources: Mutex::new(CommandArbiter::new()),
+            codec: FrameCodec::new(),
+            decoders: Mutex::new(HashMap::new()),
+            max_frames_per_iter: DEFAULT_MAX_FRAMES_PER_ITER,
+            rx_buffer: Mutex::new(Vec::with_capacity(RX_BUFFER_CAPACITY)),
+            last_rx: Mutex::new(HashMap::new()),
+            armed: AtomicBool::new(true),
+            failsafe: AtomicBool::new(false),
+            armed_at: Mutex::new(None),
+            arm_ramp: Duration::ZERO,
+            estop_pending: AtomicBool::new(false),
+            depth_hold: Mutex::new(Hold::new(Pid::new(40.0, 4.0, 15.0).with_integral_limit(10.0))),
+            heading_hold: Mutex::new(Hold::new(Pid::new(1.0, 0.1, 0.3).with_integral_limit(100.0))),
+            last_hold_step: Mutex::new(None),
+            tx_queue: Mutex::new(VecDeque::new()),
+            tx_queue_depth: DEFAULT_TX_QUEUE_DEPTH,
+            tx_drop_policy: TxDropPolicy::default(),
+            tx_dropped: AtomicU64::new(0),
+            shutdown_pwm: None,
+            status_led: false,
+            last_led: Mutex::new(None),
+            shutdown_done: Mutex::new(None),
+            shutdown_cv: Condvar::new(),
+            last_pwm: Mutex::new(NEUTRAL_PWM.to_vec()),
+            rate_marks: Mutex::new(HashMap::new()),
+            clock: Arc::new(SystemClock),
+            heartbeat_timeout: DEFAULT_HEARTBEAT_TIMEOUT,
+            last_heartbeat: Mutex::new(None),
+            heartbeat_lost: AtomicBool::new(false),
+            sensor_timeout: DEFAULT_SENSOR_TIMEOUT,
+            last_frame: Mutex::new(None),
+            sensor_startup_grace: DEFAULT_SENSOR_STARTUP_GRACE,
+            first_step: Mutex::new(None),
+            connected: AtomicBool::new(true),
+            link_ok: AtomicBool::new(true),
+            tx_errors: AtomicU64::new(0),
+            rx_bad_lengths: AtomicU64::new(0),
+            tx_retry: RetryPolicy::default(),
+            listeners: Mutex::new(Vec::new()),
+            pending_events: Mutex::new(VecDeque::new()),
+        };
+        controller.register_default_decoders();
+        controller
     }
     
     pub fn with_baud(mut self, baud: u32) -> Self {
@@ -62,50 +335,473 @@ impl AuvController {
         self
     }
     
+    /// Use `mixer` instead of the default `Vectored6` layout
+    ///
+    /// Its slew limit applies to every PWM the loop sends, failsafe neutral
+    /// included; only the final shutdown PWM goes out unramped.
+    ///
+    /// The PWM frame grows past 6 channels to fit the mixer; panics if it
+    /// drives more thrusters than `MAX_THRUSTER_CHANNELS`.
+    pub fn with_mixer(mut self, mixer: ThrustMixer) -> Self {
+        assert!(
+            mixer.thruster_count() <= MAX_THRUSTER_CHANNELS,
+            "mixer drives {} thrusters but a PWM frame carries at most {}",
+            mixer.thruster_count(), MAX_THRUSTER_CHANNELS
+        );
+        self.mixer = mixer;
+        // The first ramp starts from this mixer's neutral, not the default
+        self.last_pwm = Mutex::new(self.neutral_pwm());
+        self
+    }
+    
+    /// Frame checksums following the firmware's `coverage` convention
+    pub fn with_checksum_coverage(mut self, coverage: ChecksumCoverage) -> Self {
+        self.codec = self.codec.with_checksum_coverage(coverage);
+        self
+    }
+    
+    /// Byte-stuff frames both ways so a dropped byte can't desync the parser
+    /// on a sync byte inside a payload; the firmware must escape too
+    pub fn with_escaping(mut self, enabled: bool) -> Self {
+        self.codec = self.codec.with_escaping(enabled);
+        self
+    }
+    
+    /// Send and expect `msg_type` as a fixed-length frame without LEN or
+    /// checksum, trimming the hot path on a clean direct link
+    ///
+    /// Corruption in those frames goes undetected, and the firmware must
+    /// frame them the same way. Other types keep their checksum.
+    pub fn with_fast_frame(mut self, msg_type: MsgType, len: usize) -> Self {
+        self.codec = self.codec.with_fast_frame(msg_type, len);
+        self
+    }
+    
+    /// Byte order of sensor and thruster payloads; the STM32 default is
+    /// little-endian whatever the host. Re-registers the built-in sensor
+    /// decoders, so call it before replacing any of them.
+    pub fn with_endianness(mut self, endianness: Endianness) -> Self {
+        self.codec = self.codec.with_endianness(endianness);
+        self.register_default_decoders();
+        self
+    }
+    
+    /// Frame check algorithm; `ChecksumKind::Crc16` for noisy tethers, if the
+    /// firmware sends it
+    pub fn with_checksum(mut self, kind: ChecksumKind) -> Self {
+        self.codec = self.codec.with_checksum(kind);
+        self
+    }
+    
+    /// Open frames with `preamble` instead of the single sync byte, matching
+    /// firmware that uses a two-byte sync
+    pub fn with_preamble(mut self, preamble: Preamble) -> Self {
+        self.codec = self.codec.with_preamble(preamble);
+        self
+    }
+    
+    /// Bound the `queue_frame` queue to `depth` frames (64 by default),
+    /// dropping by `policy` once it is full
+    ///
+    /// Panics if `depth` is zero.
+    pub fn with_tx_queue(mut self, depth: usize, policy: TxDropPolicy) -> Self {
+        assert!(depth > 0, "TX queue depth must be at least 1");
+        self.tx_queue_depth = depth;
+        self.tx_drop_policy = policy;
+        self
+    }
+    
+    /// PWM written as the very last frame when the loop exits (the mixer's
+    /// neutral by default). Channels `pwm` doesn't cover stay neutral
+    pub fn with_shutdown_pwm(mut self, pwm: impl Into<Vec<i32>>) -> Self {
+        self.shutdown_pwm = Some(pwm.into());
+        self
+    }
+    
+    /// Drive the STM32 indicator LED from the arm/failsafe state, sending an
+    /// `LedCmd` whenever it changes
+    pub fn with_status_led(mut self, enabled: bool) -> Self {
+        self.status_led = enabled;
+        self
+    }
+    
+    /// Decode at most `frames` RX frames per loop iteration, so a burst
+    /// cannot delay the 50Hz PWM output; the rest wait for the next pass
+    ///
+    /// Panics if `frames` is zero.
+    pub fn with_max_frames_per_iter(mut self, frames: usize) -> Self {
+        assert!(frames > 0, "RX budget must allow at least one frame");
+        self.max_frames_per_iter = frames;
+        self
+    }
+    
+    /// Start the RX buffer with room for `capacity` bytes instead of
+    /// `RX_BUFFER_CAPACITY`
+    pub fn with_rx_capacity(self, capacity: usize) -> Self {
+        *self.rx_buffer.lock_unpoisoned() = Vec::with_capacity(capacity);
+        self
+    }
+    
+    /// Disarm if no heartbeat arrives within `timeout` of the last one
+    pub fn with_heartbeat_timeout(mut self, timeout: Duration) -> Self {
+        self.heartbeat_timeout = timeout;
+        self
+    }
+    
+    /// Hold the thrusters at neutral while no frame of any kind has arrived
+    /// within `timeout` of the last one (500ms by default)
+    pub fn with_sensor_timeout(mut self, timeout: Duration) -> Self {
+        self.sensor_timeout = timeout;
+        self
+    }
+    
+    /// Give a link that hasn't sent anything yet `grace` from the first
+    /// control step (2s by default) before holding the thrusters at neutral
+    pub fn with_sensor_startup_grace(mut self, grace: Duration) -> Self {
+        self.sensor_startup_grace = grace;
+        self
+    }
+    
+    /// Use `clock` for TX scheduling and heartbeat supervision
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+    
+    /// Ease thrust in over `ramp` after each `arm()` instead of jumping
+    /// straight to the commanded value
+    pub fn with_arm_ramp(mut self, ramp: Duration) -> Self {
+        self.arm_ramp = ramp;
+        self
+    }
+    
+    /// PID gains for depth hold, turning metres of depth error into heave
+    ///
+    /// Positive heave is taken to drive the vehicle deeper; flip the sign of
+    /// the gains if it lifts yours. The defaults are a starting point, not
+    /// tuned for any vehicle.
+    pub fn with_depth_pid(self, pid: Pid) -> Self {
+        self.depth_hold.lock_unpoisoned().pid = pid;
+        self
+    }
+    
+    /// PID gains for heading hold, turning degrees of yaw error into yaw
+    /// thrust; positive yaw is taken to increase the heading
+    pub fn with_heading_pid(self, pid: Pid) -> Self {
+        self.heading_hold.lock_unpoisoned().pid = pid;
+        self
+    }
+    
+    /// Retry a frame write up to `retries` times, pausing `backoff` (doubling
+    /// per failure) in between, before it counts as lost
+    ///
+    /// Only transient errors (`WouldBlock`, `Interrupted`, `TimedOut`) are
+    /// retried; anything else fails the frame at once. A lost thruster frame
+    /// still marks the link down and disarms.
+    pub fn with_tx_retries(mut self, retries: usize, backoff: Duration) -> Self {
+        self.tx_retry = RetryPolicy::new(retries, backoff);
+        self
+    }
+    
+    /// Allow thrust commands to reach the thrusters
+    pub fn arm(&self) {
+        self.failsafe.store(false, Ordering::SeqCst);
+        if !self.armed.swap(true, Ordering::SeqCst) {
+            *self.armed_at.lock_unpoisoned() = Some(self.clock.now());
+            self.emit(ControllerEvent::Armed);
+        }
+    }
+    
+    /// Force neutral PWM until `arm` is called again
+    pub fn disarm(&self) {
+        if self.armed.swap(false, Ordering::SeqCst) {
+            self.emit(ControllerEvent::Disarmed);
+        }
+    }
+    
+    /// Disarm and send neutral PWM on the loop's very next iteration,
+    /// without waiting for the 50Hz slot or ramping down
+    ///
+    /// Thrust commands keep being stored but have no effect until `arm`.
+    pub fn emergency_stop(&self) {
+        self.disarm();
+        self.estop_pending.store(true, Ordering::SeqCst);
+        eprintln!("[AUV] Emergency stop");
+    }
+    
+    /// Disarm on behalf of a failsafe rather than the operator
+    fn failsafe_disarm(&self, cause: FailsafeCause) {
+        self.failsafe.store(true, Ordering::SeqCst);
+        if self.armed.swap(false, Ordering::SeqCst) {
+            self.emit(ControllerEvent::Failsafe { cause });
+        }
+    }
+    
+    /// Call `cb` on every state transition, in order, on the thread that
+    /// caused it (the control loop for failsafes and link changes)
+    ///
+    /// Any number of callbacks can be registered. A callback may change
+    /// state (e.g. re-arm); the resulting event is delivered once it
+    /// returns. It must not call `on_event` itself.
+    pub fn on_event(&self, cb: impl Fn(ControllerEvent) + Send + 'static) {
+        self.listeners.lock_unpoisoned().push(Box::new(cb));
+    }
+    
+    /// Queue `event` and deliver everything queued, unless another call is
+    /// already delivering (on this thread or another), which then picks it up
+    fn emit(&self, event: ControllerEvent) {
+        self.pending_events.lock_unpoisoned().push_back(event);
+        loop {
+            let listeners = match self.listeners.try_lock() {
+                Ok(listeners) => listeners,
+                Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+                Err(TryLockError::WouldBlock) => return,
+            };
+            loop {
+                let next = self.pending_events.lock_unpoisoned().pop_front();
+                let Some(event) = next else { break };
+                for cb in listeners.iter() {
+                    cb(event);
+                }
+            }
+            drop(listeners);
+            // An event queued while we still held the listeners is ours too
+            if self.pending_events.lock_unpoisoned().is_empty() {
+                return;
+            }
+        }
+    }
+    
+    /// What the status LED shows for the current state
+    pub fn led_status(&self) -> LedStatus {
+        if self.is_armed() {
+            LedStatus::Solid
+        } else if self.failsafe.load(Ordering::SeqCst) {
+            LedStatus::Blink
+        } else {
+            LedStatus::Off
+        }
+    }
+    
+    pub fn is_armed(&self) -> bool {
+        self.armed.load(Ordering::SeqCst)
+    }
+    
+    /// False while the STM32 has been silent past the sensor timeout
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::SeqCst)
+    }
+    
+    /// False once a frame failed to go out, until the next one succeeds
+    pub fn link_ok(&self) -> bool {
+        self.link_ok.load(Ordering::SeqCst)
+    }
+    
+    /// Number of frames that could not be written to the transport
+    pub fn tx_errors(&self) -> u64 {
+        self.tx_errors.load(Ordering::SeqCst)
+    }
+    
+    /// Number of received headers whose length byte exceeded `MAX_MSG_SIZE`;
+    /// a climbing count means the length byte is getting corrupted on the wire
+    pub fn rx_bad_lengths(&self) -> u64 {
+        self.rx_bad_lengths.load(Ordering::SeqCst)
+    }
+    
+    /// Number of queued frames dropped because the TX queue was full
+    pub fn tx_dropped(&self) -> u64 {
+        self.tx_dropped.load(Ordering::SeqCst)
+    }
+    
+    /// Registry holding the raw RX payloads, one topic per message type
+    pub fn registry(&self) -> Arc<TopicRegistry> {
+        Arc::clone(&self.registry)
+    }
+    
+    /// Snapshot of link, arming, sensor freshness, last PWM and topic traffic
+    ///
+    /// Topic rates are measured between consecutive calls, so poll this at a
+    /// steady interval (e.g. 1Hz) for meaningful numbers.
+    pub fn status(&self) -> AuvStatus {
+        let now = self.clock.now();
+        let age_ms = |at: Option<Instant>| at.map(|t| now.duration_since(t).as_millis() as u64);
+        let last_rx = self.last_rx.lock_unpoisoned().clone();
+        
+        let mut marks = self.rate_marks.lock_unpoisoned();
+        let topics = self.registry.iter_byte_metrics().into_iter()
+            .map(|(name, metrics)| {
+                let rate_hz = marks.get(&name).and_then(|&(at, published)| {
+                    let elapsed = now.duration_since(at).as_secs_f64();
+                    (elapsed > 0.0).then(|| (metrics.published - published) as f64 / elapsed)
+                });
+                marks.insert(name.clone(), (now, metrics.published));
+                TopicStatus {
+                    name,
+                    len: metrics.len,
+                    capacity: metrics.capacity,
+                    published: metrics.published,
+                    dropped: metrics.dropped,
+                    rate_hz,
+                }
+            })
+            .collect();
+        
+        AuvStatus {
+            armed: self.is_armed(),
+            failsafe: self.failsafe.load(Ordering::SeqCst),
+            led: self.led_status(),
+            link_ok: self.link_ok(),
+            tx_errors: self.tx_errors(),
+            rx_bad_lengths: self.rx_bad_lengths(),
+            heartbeat_age_ms: age_ms(*self.last_heartbeat.lock_unpoisoned()),
+            imu_age_ms: age_ms(last_rx.get(&MsgType::Imu).copied()),
+            orientation_age_ms: age_ms(last_rx.get(&MsgType::Orientation).copied()),
+            depth_age_ms: age_ms(last_rx.get(&MsgType::Depth).copied()),
+            orientation: self.get_orientation(),
+            depth: self.get_depth(),
+            last_pwm: self.last_pwm.lock_unpoisoned().clone(),
+            topics,
+        }
+    }
+    
+    /// Add an input polled every TX tick
+    ///
+    /// While any source is active, the highest-priority one drives the
+    /// thrusters instead of the `set_*` command; once all go quiet for the
+    /// hold window, control falls back to it.
+    pub fn add_command_source(&self, priority: u8, source: Box<dyn CommandSource>) {
+        self.sources.lock_unpoisoned().add(priority, source);
+    }
+    
+    /// Queue a discrete frame (LED, calibration, ...) for the control loop
+    /// to send on its next iteration
+    ///
+    /// Every queued frame goes out once, in order, and before the shutdown
+    /// PWM. Thrust is not queued: use `set_thrust` and friends, which
+    /// overwrite the command the loop sends every tick. Once the queue
+    /// holds its full depth the drop policy decides what is lost; returns
+    /// false if that was this frame.
+    pub fn queue_frame(&self, msg_type: MsgType, payload: &[u8]) -> bool {
+        let mut queue = self.tx_queue.lock_unpoisoned();
+        if queue.len() >= self.tx_queue_depth {
+            self.tx_dropped.fetch_add(1, Ordering::SeqCst);
+            match self.tx_drop_policy {
+                TxDropPolicy::DropNewest => return false,
+                TxDropPolicy::DropOldest => {
+                    queue.pop_front();
+                }
+            }
+        }
+        queue.push_back((msg_type, payload.to_vec()));
+        true
+    }
+    
     /// Set thrust command (called from Python or other threads)
+    ///
+    /// Latest value wins: the loop sends whatever is set at each tick, so
+    /// commands set in between are overwritten, never queued.
     pub fn set_thrust(&self, cmd: ThrustCommand) {
-        *self.thrust_cmd.write().unwrap() = cmd;
+        *self.thrust_cmd.write_unpoisoned() = cmd;
     }
     
     /// Set individual DoF thrust
     pub fn set_surge(&self, value: f32) {
-        self.thrust_cmd.write().unwrap().surge = value;
+        self.thrust_cmd.write_unpoisoned().surge = value;
     }
     
     pub fn set_sway(&self, value: f32) {
-        self.thrust_cmd.write().unwrap().sway = value;
+        self.thrust_cmd.write_unpoisoned().sway = value;
     }
     
     pub fn set_heave(&self, value: f32) {
-        self.thrust_cmd.write().unwrap().heave = value;
+        self.thrust_cmd.write_unpoisoned().heave = value;
     }
     
     pub fn set_roll(&self, value: f32) {
-        self.thrust_cmd.write().unwrap().roll = value;
+        self.thrust_cmd.write_unpoisoned().roll = value;
     }
     
     pub fn set_pitch(&self, value: f32) {
-        self.thrust_cmd.write().unwrap().pitch = value;
+        self.thrust_cmd.write_unpoisoned().pitch = value;
     }
     
     pub fn set_yaw(&self, value: f32) {
-        self.thrust_cmd.write().unwrap().yaw = value;
+        self.thrust_cmd.write_unpoisoned().yaw = value;
+    }
+    
+    /// Hold this depth in metres, adding a PID correction to heave every
+    /// tick; `None` releases the hold
+    pub fn set_depth_setpoint(&self, depth: Option<f32>) {
+        self.depth_hold.lock_unpoisoned().set(depth);
+    }
+    
+    /// Hold this heading (yaw, degrees), adding a PID correction to yaw
+    /// every tick along the shorter way round; `None` releases the hold
+    pub fn set_heading_setpoint(&self, heading: Option<f32>) {
+        self.heading_hold.lock_unpoisoned().set(heading);
+    }
+    
+    /// Register a decoder for frames of `msg_type`, replacing any existing one.
+    ///
+    /// Decoders run on the controller thread for every valid frame of that
+    /// type, so custom firmware messages can be handled without touching
+    /// the controller. The registry isn't locked while a decoder runs, so a
+    /// decoder may register or replace decoders itself.
+    pub fn register_sensor(&self, msg_type: MsgType, decoder: impl Fn(&[u8]) + Send + Sync + 'static) {
+        self.decoders.lock_unpoisoned().insert(msg_type, Arc::new(decoder));
+    }
+    
+    fn register_default_decoders(&self) {
+        let order = self.codec.endianness();
+        let sensors = Arc::clone(&self.sensors);
+        self.register_sensor(MsgType::Imu, move |payload| {
+            if let Some(imu) = ImuMsg::from_bytes_with(payload, order) {
+                sensors.imu.update(|s| *s = Some(imu));
+            }
+        });
+        
+        let sensors = Arc::clone(&self.sensors);
+        self.register_sensor(MsgType::Orientation, move |payload| {
+            if let Some(orient) = OrientationMsg::from_bytes_with(payload, order) {
+                sensors.orientation.update(|s| *s = Some(orient));
+            }
+        });
+        
+        let sensors = Arc::clone(&self.sensors);
+        self.register_sensor(MsgType::Depth, move |payload| {
+            if let Some(depth) = DepthMsg::from_bytes_with(payload, order) {
+                sensors.depth.update(|s| *s = Some(depth));
+            }
+        });
     }
     
     /// Get latest sensor data
     pub fn get_sensors(&self) -> SensorData {
-        self.sensors.read().unwrap().clone()
+        SensorData {
+            imu: self.sensors.imu.read(),
+            orientation: self.sensors.orientation.read(),
+            depth: self.sensors.depth.read(),
+        }
     }
     
     /// Get current orientation (roll, pitch, yaw in degrees)
     pub fn get_orientation(&self) -> Option<(f32, f32, f32)> {
-        self.sensors.read().unwrap().orientation.as_ref()
-            .map(|o| (o.roll, o.pitch, o.yaw))
+        self.sensors.orientation.read().map(|o| (o.roll, o.pitch, o.yaw))
     }
     
     /// Get current depth in meters
     pub fn get_depth(&self) -> Option<f32> {
-        self.sensors.read().unwrap().depth.as_ref().map(|d| d.depth)
+        self.sensors.depth.read().map(|d| d.depth)
+    }
+    
+    /// Current orientation, or `default` if none has been received yet
+    pub fn get_orientation_or(&self, default: (f32, f32, f32)) -> (f32, f32, f32) {
+        self.get_orientation().unwrap_or(default)
+    }
+    
+    /// Current depth in meters, or `default` if none has been received yet
+    pub fn get_depth_or(&self, default: f32) -> f32 {
+        self.get_depth().unwrap_or(default)
     }
     
     /// Stop all thrusters
@@ -115,52 +811,275 @@ impl AuvController {
     
     /// Start the controller (blocking)
     pub fn run(&self) {
-        self.running.store(true, Ordering::SeqCst);
-        
         println!("[AUV] Opening port {} at {} baud...", self.port_name, self.baud_rate);
         
         let mut port = serialport::new(&self.port_name, self.baud_rate)
             .timeout(Duration::from_millis(100))
             .open()
-            .expect(&format!("Failed to open port {}", self.port_name));
+            .unwrap_or_else(|e| panic!("Failed to open port {}: {}", self.port_name, e));
         
         println!("[AUV] Connected to STM32!");
         
-        let mut rx_buffer = Vec::new();
-        let mut read_buf = [0u8; 256];
-        let mut last_tx = std::time::Instant::now();
+        self.run_with_transport(&mut port);
+    }
+    
+    /// Run the control loop over an already-open transport (blocking)
+    ///
+    /// Calling this again with a new transport is a reconnect: a partial
+    /// frame left by the previous link is dropped, never spliced with the
+    /// new link's bytes.
+    pub fn run_with_transport(&self, port: &mut dyn Transport) {
+        *self.shutdown_done.lock_unpoisoned() = None;
+        self.running.store(true, Ordering::SeqCst);
+        
+        let mut rx_buffer = std::mem::take(&mut *self.rx_buffer.lock_unpoisoned());
+        rx_buffer.clear();
+        let mut last_tx = None;
         
         while self.running.load(Ordering::SeqCst) {
-            // Read incoming sensor data
-            match port.read(&mut read_buf) {
-                Ok(n) if n > 0 => {
-                    rx_buffer.extend_from_slice(&read_buf[..n]);
-                    self.process_rx(&mut rx_buffer);
-                }
-                Ok(_) => {}
-                Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
-                Err(e) => eprintln!("[AUV] Read error: {}", e),
+            self.tick(port, &mut rx_buffer, &mut last_tx);
+        }
+        *self.rx_buffer.lock_unpoisoned() = rx_buffer;
+        
+        // Drain queued frames, then stop thrusters as the final write
+        println!("[AUV] Stopping thrusters...");
+        self.drain_tx_queue(port);
+        let mut pwm = self.neutral_pwm();
+        for (channel, &value) in pwm.iter_mut().zip(self.shutdown_pwm.iter().flatten()) {
+            *channel = value;
+        }
+        let stopped = match self.send_frame(port, MsgType::Thruster, &self.encode_pwm(&pwm)) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("[AUV] Failed to send stop command: {}", e);
+                false
             }
+        };
+        
+        *self.shutdown_done.lock_unpoisoned() = Some(stopped);
+        self.shutdown_cv.notify_all();
+        println!("[AUV] Shutdown complete");
+    }
+    
+    /// Write every queued frame; failures are counted but do not disarm,
+    /// since no thruster command was lost
+    fn drain_tx_queue(&self, port: &mut dyn Transport) {
+        loop {
+            let next = self.tx_queue.lock_unpoisoned().pop_front();
+            let Some((msg_type, payload)) = next else { break };
+            if let Err(e) = self.send_frame(port, msg_type, &payload) {
+                self.tx_errors.fetch_add(1, Ordering::SeqCst);
+                eprintln!("[AUV] Failed to send {:?} frame: {}", msg_type, e);
+            }
+        }
+    }
+    
+    /// One loop iteration: read, supervise the link, send PWM at 50Hz
+    fn tick(&self, port: &mut dyn Transport, rx_buffer: &mut Vec<u8>, last_tx: &mut Option<Instant>) {
+        let mut read_buf = [0u8; 256];
+        
+        // Read incoming sensor data
+        match port.read(&mut read_buf) {
+            Ok(n) if n > 0 => rx_buffer.extend_from_slice(&read_buf[..n]),
+            Ok(_) => {}
+            Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+            Err(e) => eprintln!("[AUV] Read error: {}", e),
+        }
+        // Also runs without new bytes, to work off a backlog left by the budget
+        if !rx_buffer.is_empty() {
+            self.process_rx(rx_buffer);
+        }
+        
+        self.drain_tx_queue(port);
+        
+        // Send thrust commands at 50Hz, or right away after an emergency stop
+        let now = self.clock.now();
+        // `control_step` clears the flag once it has stopped the thrusters
+        let estop = self.estop_pending.load(Ordering::SeqCst);
+        if estop || last_tx.is_none_or(|t| now.duration_since(t) >= TX_PERIOD) {
+            *last_tx = Some(now);
             
-            // Send thrust commands at 50Hz
-            if last_tx.elapsed() >= Duration::from_millis(20) {
-                last_tx = std::time::Instant::now();
-                
-                let cmd = self.thrust_cmd.read().unwrap().clone();
-                let thrusts = self.mixer.mix(&cmd);
-                let pwm = ThrustMixer::to_pwm(&thrusts);
-                
-                let pwm_cmd = ThrusterPwmCmd::new(pwm);
-                self.send_frame(&mut port, MsgType::Thruster, &pwm_cmd.to_bytes());
+            // The holds only need depth and heading; the IMU isn't copied
+            let sensors = SensorData {
+                imu: None,
+                orientation: self.sensors.orientation.read(),
+                depth: self.sensors.depth.read(),
+            };
+            let pwm = self.control_step(&sensors, now);
+            let sent = self.send_frame(port, MsgType::Thruster, &self.encode_pwm(&pwm));
+            if sent.is_ok() {
+                *self.last_pwm.lock_unpoisoned() = pwm;
+            }
+            self.note_tx_result(sent);
+            
+            if self.status_led {
+                self.update_status_led(port);
+            }
+        }
+    }
+    
+    /// Send an `LedCmd` if the status changed since the last one went out
+    fn update_status_led(&self, port: &mut dyn Transport) {
+        let status = self.led_status();
+        let mut last = self.last_led.lock_unpoisoned();
+        if *last == Some(status) {
+            return;
+        }
+        // Retried next tick if the write fails
+        if self.send_frame(port, MsgType::Led, &LedCmd{ indicator: status as i16 }.to_bytes_with(self.codec.endianness())).is_ok() {
+            *last = Some(status);
+        }
+    }
+    
+    /// A thruster command that never reached the STM32 is a lost command:
+    /// mark the link down and disarm, so the operator has to re-arm once
+    /// frames go through again.
+    fn note_tx_result(&self, result: std::io::Result<()>) {
+        match result {
+            Ok(()) => {
+                if !self.link_ok.swap(true, Ordering::SeqCst) {
+                    self.emit(ControllerEvent::LinkUp);
+                }
             }
+            Err(e) => {
+                self.tx_errors.fetch_add(1, Ordering::SeqCst);
+                if self.link_ok.swap(false, Ordering::SeqCst) {
+                    eprintln!("[AUV] Write error: {}", e);
+                    self.emit(ControllerEvent::LinkDown);
+                }
+                if self.is_armed() {
+                    self.failsafe_disarm(FailsafeCause::CommandLost);
+                    eprintln!("[AUV] Thruster command lost, disarming");
+                }
+            }
+        }
+    }
+    
+    /// Compute the PWM to transmit at `now` from the current command and state.
+    ///
+    /// This is the whole control path of the loop (mixing, failsafe, disarm,
+    /// slew), free of any I/O so it can be driven directly with crafted inputs.
+    /// The slew limit ramps from the last PWM that reached the STM32; the loop
+    /// records that once the frame is sent, so calling this alone doesn't
+    /// move it.
+    pub fn control_step(&self, sensors: &SensorData, now: Instant) -> Vec<i32> {
+        let target = self.control_target(sensors, now);
+        // An e-stop jumps straight to neutral, even if `arm` already came in
+        // since, so thrust always ramps back up from there
+        if self.estop_pending.swap(false, Ordering::SeqCst) {
+            return self.neutral_pwm();
         }
+        self.mixer.slew(&self.last_pwm.lock_unpoisoned(), &target)
+    }
+    
+    /// PWM the thrusters should settle at, before the slew limit
+    fn control_target(&self, sensors: &SensorData, now: Instant) -> Vec<i32> {
+        self.check_heartbeat(now);
+        let connected = self.check_sensor_link(now);
         
-        // Stop thrusters on exit
-        println!("[AUV] Stopping thrusters...");
-        let pwm_cmd = ThrusterPwmCmd::new([1500; 6]);
-        self.send_frame(&mut port, MsgType::Thruster, &pwm_cmd.to_bytes());
+        // Sources are polled even while disarmed so their hold windows stay current
+        let sourced = self.sources.lock_unpoisoned().select(now);
+        if !self.is_armed() || !connected {
+            // Nothing accumulates while the thrusters can't act on it
+            self.reset_holds();
+            return self.neutral_pwm();
+        }
+        let mut cmd = sourced.unwrap_or_else(|| *self.thrust_cmd.read_unpoisoned());
+        self.apply_holds(&mut cmd, sensors, now);
+        let mut thrusts = self.mixer.mix(&cmd);
+        let scale = self.arm_ramp_scale(now);
+        for thrust in thrusts.iter_mut() {
+            *thrust *= scale;
+        }
         
-        println!("[AUV] Shutdown complete");
+        // Unused channels stay neutral
+        let mut pwm = self.neutral_pwm();
+        for (channel, value) in pwm.iter_mut().zip(self.mixer.to_pwm(&thrusts)) {
+            *channel = value;
+        }
+        pwm
+    }
+    
+    /// Add the depth and heading hold corrections to `cmd`
+    fn apply_holds(&self, cmd: &mut ThrustCommand, sensors: &SensorData, now: Instant) {
+        let dt = match self.last_hold_step.lock_unpoisoned().replace(now) {
+            Some(prev) => now.saturating_duration_since(prev).as_secs_f32(),
+            None => 0.0,
+        };
+        cmd.heave += self.depth_hold.lock_unpoisoned()
+            .correction(sensors.depth.map(|d| d.depth), dt, |setpoint, depth| setpoint - depth);
+        cmd.yaw += self.heading_hold.lock_unpoisoned()
+            .correction(sensors.orientation.map(|o| o.yaw), dt, heading_error);
+    }
+    
+    fn reset_holds(&self) {
+        self.depth_hold.lock_unpoisoned().pid.reset();
+        self.heading_hold.lock_unpoisoned().pid.reset();
+        *self.last_hold_step.lock_unpoisoned() = None;
+    }
+    
+    /// Zero thrust on every channel, as the mixer's ESCs define it
+    fn neutral_pwm(&self) -> Vec<i32> {
+        vec![self.mixer.pwm_neutral; self.mixer.thruster_count().max(THRUSTER_CHANNELS)]
+    }
+    
+    /// Thruster frame payload, in the codec's byte order
+    fn encode_pwm(&self, pwm: &[i32]) -> Vec<u8> {
+        ThrusterPwmCmd::channels_to_bytes(pwm, self.codec.endianness())
+    }
+    
+    /// Soft-start factor in 0..=1 over the arm ramp window
+    fn arm_ramp_scale(&self, now: Instant) -> f32 {
+        let armed_at = match *self.armed_at.lock_unpoisoned() {
+            Some(t) if !self.arm_ramp.is_zero() => t,
+            _ => return 1.0,
+        };
+        let elapsed = now.duration_since(armed_at).as_secs_f32();
+        (elapsed / self.arm_ramp.as_secs_f32()).min(1.0)
+    }
+    
+    /// Disarm once the heartbeat has been silent for longer than the timeout.
+    ///
+    /// A link that has never sent a heartbeat is not treated as stale, and
+    /// recovery does not re-arm: that needs an explicit `arm()`.
+    fn check_heartbeat(&self, now: Instant) {
+        let last = match *self.last_heartbeat.lock_unpoisoned() {
+            Some(last) => last,
+            None => return,
+        };
+        let silent = now.duration_since(last);
+        if silent <= self.heartbeat_timeout {
+            return;
+        }
+        if !self.heartbeat_lost.swap(true, Ordering::SeqCst) {
+            self.emit(ControllerEvent::HeartbeatLost { last_seen: silent });
+        }
+        if self.is_armed() {
+            self.failsafe_disarm(FailsafeCause::HeartbeatLost);
+            eprintln!("[AUV] Heartbeat lost for {:?}, disarming", silent);
+        }
+    }
+    
+    /// Whether frames are still arriving within the sensor timeout.
+    ///
+    /// Unlike a lost heartbeat this does not disarm: thrust resumes by
+    /// itself with the next frame. A link that has never sent a frame gets
+    /// the startup grace instead of the timeout, so an STM32 that never
+    /// comes up doesn't leave the thrusters live.
+    fn check_sensor_link(&self, now: Instant) -> bool {
+        let last_frame = *self.last_frame.lock_unpoisoned();
+        let (since, limit) = match last_frame {
+            Some(last) => (last, self.sensor_timeout),
+            None => (*self.first_step.lock_unpoisoned().get_or_insert(now), self.sensor_startup_grace),
+        };
+        let silent = now.duration_since(since);
+        if silent <= limit {
+            return true;
+        }
+        if self.connected.swap(false, Ordering::SeqCst) {
+            eprintln!("[AUV] No frames for {:?}, holding thrusters at neutral", silent);
+        }
+        false
     }
     
     /// Start in background thread
@@ -176,95 +1095,1155 @@ impl AuvController {
         self.running.store(false, Ordering::SeqCst);
     }
     
-    fn send_frame(&self, port: &mut Box<dyn serialport::SerialPort>, msg_type: MsgType, payload: &[u8]) {
-        let mut frame = Vec::with_capacity(4 + payload.len());
-        frame.push(SYNC_BYTE);
-        frame.push(msg_type as u8);
-        frame.push(payload.len() as u8);
-        frame.extend_from_slice(payload);
-        
-        let checksum = Self::calculate_checksum(&frame[1..]);
-        frame.push(checksum);
+    /// Stop the loop and wait until it has written queued frames and the
+    /// shutdown PWM, flushed, and exited
+    ///
+    /// Fails with `TimedOut` if the loop does not finish within `timeout`
+    /// (or is not running), and with the write error's outcome if the
+    /// shutdown PWM could not be written.
+    pub fn shutdown_graceful(&self, timeout: Duration) -> std::io::Result<()> {
+        self.shutdown();
         
-        let _ = port.write_all(&frame);
-        let _ = port.flush();
+        let done = self.shutdown_done.lock_unpoisoned();
+        let (done, _) = self.shutdown_cv
+            .wait_timeout_while(done, timeout, |done| done.is_none())
+            .unwrap_or_else(PoisonError::into_inner);
+        match *done {
+            Some(true) => Ok(()),
+            Some(false) => Err(std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "shutdown PWM could not be written",
+            )),
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "control loop did not finish shutting down",
+            )),
+        }
     }
     
-    fn calculate_checksum(data: &[u8]) -> u8 {
-        data.iter().fold(0u8, |acc, &b| acc.wrapping_add(b))
+    fn send_frame(&self, port: &mut dyn Transport, msg_type: MsgType, payload: &[u8]) -> std::io::Result<()> {
+        write_with_retry(port, &self.codec.encode(msg_type, payload), &self.tx_retry)
     }
     
-    fn process_rx(&self, buffer: &mut Vec<u8>) {
-        while let Some((msg_type, payload)) = Self::try_parse_frame(buffer) {
-            match msg_type {
-                MsgType::Imu => {
-                    if let Some(imu) = ImuMsg::from_bytes(&payload) {
-                        self.sensors.write().unwrap().imu = Some(imu);
-                    }
-                }
-                MsgType::Orientation => {
-                    if let Some(orient) = OrientationMsg::from_bytes(&payload) {
-                        self.sensors.write().unwrap().orientation = Some(orient);
-                    }
+    /// Decode up to the per-iteration budget; returns the frames handled
+    fn process_rx(&self, buffer: &mut Vec<u8>) -> usize {
+        let mut handled = 0;
+        while handled < self.max_frames_per_iter {
+            let mut bad_lengths = 0;
+            let frame = self.codec.decode_counting(buffer, &mut bad_lengths);
+            if bad_lengths > 0 {
+                self.rx_bad_lengths.fetch_add(bad_lengths, Ordering::SeqCst);
+            }
+            let Some(frame) = frame else { break };
+            handled += 1;
+            let now = self.clock.now();
+            if frame.msg_type == MsgType::Heartbeat {
+                *self.last_heartbeat.lock_unpoisoned() = Some(now);
+                if self.heartbeat_lost.swap(false, Ordering::SeqCst) {
+                    self.emit(ControllerEvent::HeartbeatRestored);
                 }
-                MsgType::Depth => {
-                    if let Some(depth) = DepthMsg::from_bytes(&payload) {
-                        self.sensors.write().unwrap().depth = Some(depth);
+            }
+            self.last_rx.lock_unpoisoned().insert(frame.msg_type, now);
+            *self.last_frame.lock_unpoisoned() = Some(now);
+            if !self.connected.swap(true, Ordering::SeqCst) {
+                println!("[AUV] Frames resumed");
+            }
+            self.registry
+                .get_or_create_byte(frame.msg_type.to_topic_name(), RX_TOPIC_CAPACITY)
+                .publish(&frame.payload);
+            let decoder = self.decoders.lock_unpoisoned().get(&frame.msg_type).cloned();
+            if let Some(decoder) = decoder {
+                decoder(&frame.payload);
+            }
+        }
+        handled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use crate::auv::ManualClock;
+    use crate::auv::thrust_mixer::VehicleConfig;
+    use crate::pubsub::ByteSubscriber;
+    use crate::uart::LoopbackTransport;
+    
+    fn frame(msg_type: MsgType, payload: &[u8]) -> Vec<u8> {
+        FrameCodec::new().encode(msg_type, payload)
+    }
+    
+    #[test]
+    fn test_default_decoders_update_sensors() {
+        let controller = AuvController::new("/dev/null");
+        let mut buffer = frame(MsgType::Depth, &2.5f32.to_le_bytes());
+        controller.process_rx(&mut buffer);
+        assert_eq!(controller.get_depth(), Some(2.5));
+    }
+    
+    #[test]
+    fn test_impossible_length_counted() {
+        let controller = AuvController::new("/dev/null");
+        let mut buffer = vec![crate::uart::SYNC_BYTE, MsgType::Depth as u8, 0xF5];
+        buffer.extend(frame(MsgType::Depth, &2.5f32.to_le_bytes()));
+        controller.process_rx(&mut buffer);
+        assert_eq!(controller.get_depth(), Some(2.5));
+        assert_eq!(controller.rx_bad_lengths(), 1);
+        assert_eq!(controller.status().rx_bad_lengths, 1);
+    }
+    
+    #[test]
+    fn test_sensor_reads_are_never_torn_by_rx_writes() {
+        let controller = Arc::new(AuvController::new("/dev/null"));
+        let done = Arc::new(AtomicBool::new(false));
+        
+        let readers: Vec<_> = (0..3).map(|_| {
+            let controller = Arc::clone(&controller);
+            let done = Arc::clone(&done);
+            thread::spawn(move || {
+                while !done.load(Ordering::SeqCst) {
+                    if let Some(imu) = controller.get_sensors().imu {
+                        let values = imu.values();
+                        assert!(values.iter().all(|&v| v == values[0]), "torn imu {:?}", values);
                     }
                 }
-                _ => {}
+            })
+        }).collect();
+        
+        // Every IMU frame carries one value in all nine fields
+        for n in 0..20_000 {
+            let mut buffer = frame(MsgType::Imu, &(n as f32).to_le_bytes().repeat(9));
+            controller.process_rx(&mut buffer);
+        }
+        done.store(true, Ordering::SeqCst);
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(controller.get_sensors().imu.unwrap().values(), [19_999.0; 9]);
+    }
+    
+    #[test]
+    fn test_orientation_frames_use_shared_msg_type() {
+        let controller = AuvController::new("/dev/null");
+        let mut payload = Vec::new();
+        for value in [1.0f32, -2.0, 90.0] {
+            payload.extend_from_slice(&value.to_le_bytes());
+        }
+        let mut buffer = frame(MsgType::Orientation, &payload);
+        controller.process_rx(&mut buffer);
+        
+        assert_eq!(controller.get_orientation(), Some((1.0, -2.0, 90.0)));
+        let topic = controller.registry().get_or_create_byte("/stm32/orientation", RX_TOPIC_CAPACITY);
+        assert_eq!(topic.peek_latest().unwrap().0, payload);
+    }
+    
+    #[test]
+    fn test_subscriber_sees_depth_history() {
+        let controller = AuvController::new("/dev/null");
+        let topic = controller.registry().get_or_create_byte("/stm32/depth", RX_TOPIC_CAPACITY);
+        let subscriber = ByteSubscriber::new(topic);
+        
+        let mut buffer = Vec::new();
+        for depth in [1.0f32, 1.5, 2.0] {
+            buffer.extend(frame(MsgType::Depth, &depth.to_le_bytes()));
+        }
+        controller.process_rx(&mut buffer);
+        
+        // Every sample, epoch-stamped, not just the latest snapshot
+        let history: Vec<(f32, u64)> = std::iter::from_fn(|| subscriber.try_next())
+            .map(|(payload, epoch)| (DepthMsg::from_bytes(&payload).unwrap().depth, epoch))
+            .collect();
+        assert_eq!(history, vec![(1.0, 1), (1.5, 2), (2.0, 3)]);
+        assert_eq!(controller.get_depth(), Some(2.0));
+    }
+    
+    #[test]
+    fn test_rx_budget_bounds_frames_per_pass() {
+        let controller = AuvController::new("/dev/null").with_max_frames_per_iter(8);
+        let mut buffer = Vec::new();
+        for i in 0..100 {
+            buffer.extend(frame(MsgType::Depth, &(i as f32).to_le_bytes()));
+        }
+        
+        let mut passes = Vec::new();
+        loop {
+            let handled = controller.process_rx(&mut buffer);
+            if handled == 0 {
+                break;
             }
+            passes.push(handled);
         }
+        assert!(passes.iter().all(|&n| n <= 8));
+        assert_eq!(passes.iter().sum::<usize>(), 100);
+        assert_eq!(controller.get_depth(), Some(99.0));
     }
     
-    fn try_parse_frame(buffer: &mut Vec<u8>) -> Option<(MsgType, Vec<u8>)> {
-        if buffer.len() < 4 {
-            return None;
+    #[test]
+    fn test_big_endian_firmware_both_directions() {
+        let clock = Arc::new(ManualClock::new());
+        let controller = AuvController::new("/dev/null")
+            .with_clock(clock.clone())
+            .with_endianness(Endianness::Big);
+        let link = LoopbackTransport::new();
+        let mut port = link.clone();
+        let mut rx_buffer = Vec::new();
+        let mut last_tx = None;
+        
+        link.feed(&frame(MsgType::Depth, &2.5f32.to_be_bytes()));
+        controller.tick(&mut port, &mut rx_buffer, &mut last_tx);
+        assert_eq!(controller.get_depth(), Some(2.5));
+        
+        let mut written = link.take_written();
+        let sent = FrameCodec::new().decode(&mut written).unwrap();
+        assert_eq!(sent.payload[..4], 1500i32.to_be_bytes());
+        assert_eq!({ThrusterPwmCmd::from_bytes_with(&sent.payload, Endianness::Big).unwrap().pwm}, NEUTRAL_PWM);
+    }
+    
+    #[test]
+    fn test_rx_backlog_drains_across_ticks_without_new_bytes() {
+        let clock = Arc::new(ManualClock::new());
+        let controller = AuvController::new("/dev/null")
+            .with_clock(clock.clone())
+            .with_max_frames_per_iter(4);
+        let link = LoopbackTransport::new();
+        let mut port = link.clone();
+        let mut rx_buffer = Vec::new();
+        let mut last_tx = None;
+        
+        for i in 0..10 {
+            link.feed(&frame(MsgType::Depth, &(i as f32).to_le_bytes()));
+        }
+        for _ in 0..3 {
+            controller.tick(&mut port, &mut rx_buffer, &mut last_tx);
+            // PWM still goes out on schedule during the burst
+            clock.advance(TX_PERIOD);
+            assert_eq!(last_pwm(&link), NEUTRAL_PWM);
         }
+        assert!(rx_buffer.is_empty());
+        assert_eq!(controller.get_depth(), Some(9.0));
+    }
+    
+    #[test]
+    fn test_sensor_defaults_until_first_frame() {
+        let controller = AuvController::new("/dev/null");
+        assert_eq!(controller.get_depth_or(-1.0), -1.0);
+        assert_eq!(controller.get_orientation_or((0.0, 0.0, 0.0)), (0.0, 0.0, 0.0));
         
-        let sync_pos = buffer.iter().position(|&b| b == SYNC_BYTE)?;
-        if sync_pos > 0 {
-            buffer.drain(0..sync_pos);
+        let mut orientation = Vec::new();
+        for value in [10.0f32, -5.0, 90.0] {
+            orientation.extend_from_slice(&value.to_le_bytes());
         }
+        let mut buffer = frame(MsgType::Depth, &3.0f32.to_le_bytes());
+        buffer.extend(frame(MsgType::Orientation, &orientation));
+        controller.process_rx(&mut buffer);
+        
+        assert_eq!(controller.get_depth_or(-1.0), 3.0);
+        assert_eq!(controller.get_orientation_or((0.0, 0.0, 0.0)), (10.0, -5.0, 90.0));
+    }
+    
+    #[test]
+    fn test_registered_decoder_invoked_for_matching_frames() {
+        let controller = AuvController::new("/dev/null");
+        let calls = Arc::new(AtomicUsize::new(0));
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        
+        let calls_in = Arc::clone(&calls);
+        let seen_in = Arc::clone(&seen);
+        controller.register_sensor(MsgType::Heartbeat, move |payload| {
+            calls_in.fetch_add(1, Ordering::SeqCst);
+            seen_in.lock().unwrap().push(payload.to_vec());
+        });
+        
+        let mut buffer = frame(MsgType::Heartbeat, &[0x01, 0x02]);
+        buffer.extend(frame(MsgType::Depth, &1.0f32.to_le_bytes()));
+        buffer.extend(frame(MsgType::Heartbeat, &[0x03]));
+        controller.process_rx(&mut buffer);
         
-        if buffer.len() < 4 {
-            return None;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(*seen.lock().unwrap(), vec![vec![0x01, 0x02], vec![0x03]]);
+        assert_eq!(controller.get_depth(), Some(1.0));
+    }
+    
+    #[test]
+    fn test_decoder_can_register_decoders() {
+        let controller = Arc::new(AuvController::new("/dev/null"));
+        let depth_frames = Arc::new(AtomicUsize::new(0));
+        
+        // The first heartbeat swaps in a depth decoder from inside a decoder
+        let weak = Arc::downgrade(&controller);
+        let depth_frames_in = Arc::clone(&depth_frames);
+        controller.register_sensor(MsgType::Heartbeat, move |_| {
+            let counter = Arc::clone(&depth_frames_in);
+            weak.upgrade().unwrap().register_sensor(MsgType::Depth, move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+        });
+        
+        let mut buffer = frame(MsgType::Depth, &1.0f32.to_le_bytes());
+        buffer.extend(frame(MsgType::Heartbeat, &[]));
+        buffer.extend(frame(MsgType::Depth, &2.0f32.to_le_bytes()));
+        controller.process_rx(&mut buffer);
+        
+        assert_eq!(depth_frames.load(Ordering::SeqCst), 1);
+        assert_eq!(controller.get_depth(), Some(1.0));
+    }
+    
+    fn last_pwm(link: &LoopbackTransport) -> [i32; 6] {
+        let mut written = link.take_written();
+        let mut last = None;
+        while let Some(frame) = FrameCodec::new().decode(&mut written) {
+            assert_eq!(frame.msg_type, MsgType::Thruster);
+            last = Some(ThrusterPwmCmd::from_bytes(&frame.payload).unwrap().pwm);
+        }
+        last.expect("no thruster frame sent")
+    }
+    
+    fn led_frames(link: &LoopbackTransport) -> Vec<LedStatus> {
+        let mut written = link.take_written();
+        let mut statuses = Vec::new();
+        while let Some(frame) = FrameCodec::new().decode(&mut written) {
+            if frame.msg_type == MsgType::Led {
+                statuses.push(match LedCmd::from_bytes(&frame.payload).unwrap().indicator {
+                    0 => LedStatus::Off,
+                    1 => LedStatus::Solid,
+                    2 => LedStatus::Blink,
+                    other => panic!("unexpected indicator {}", other),
+                });
+            }
+        }
+        statuses
+    }
+    
+    #[test]
+    fn test_status_led_follows_state_changes() {
+        let clock = Arc::new(ManualClock::new());
+        let controller = AuvController::new("/dev/null")
+            .with_clock(clock.clone())
+            .with_heartbeat_timeout(Duration::from_millis(100))
+            .with_status_led(true);
+        let link = LoopbackTransport::new();
+        let mut port = link.clone();
+        let mut rx_buffer = Vec::new();
+        let mut last_tx = None;
+        let mut step = |feed_heartbeat: bool| {
+            if feed_heartbeat {
+                link.feed(&frame(MsgType::Heartbeat, &[]));
+            }
+            controller.tick(&mut port, &mut rx_buffer, &mut last_tx);
+            clock.advance(TX_PERIOD);
+            led_frames(&link)
+        };
+        
+        // Initial state is announced once, then only changes
+        assert_eq!(step(true), vec![LedStatus::Solid]);
+        assert_eq!(step(true), vec![]);
+        
+        controller.disarm();
+        assert_eq!(step(true), vec![LedStatus::Off]);
+        controller.arm();
+        assert_eq!(step(true), vec![LedStatus::Solid]);
+        
+        // Heartbeat goes quiet past the timeout: failsafe blink
+        clock.advance(Duration::from_millis(200));
+        assert_eq!(step(false), vec![LedStatus::Blink]);
+        assert_eq!(step(false), vec![]);
+        
+        controller.arm();
+        assert_eq!(step(true), vec![LedStatus::Solid]);
+    }
+    
+    #[test]
+    fn test_status_aggregates_sensors_link_and_topics() {
+        let clock = Arc::new(ManualClock::new());
+        let controller = AuvController::new("/dev/null").with_clock(clock.clone());
+        let link = LoopbackTransport::new();
+        let mut port = link.clone();
+        let mut rx_buffer = Vec::new();
+        let mut last_tx = None;
+        
+        let empty = controller.status();
+        assert_eq!(empty.heartbeat_age_ms, None);
+        assert_eq!(empty.depth_age_ms, None);
+        assert_eq!(empty.last_pwm, NEUTRAL_PWM);
+        assert!(empty.topics.is_empty());
+        
+        let mut orientation = Vec::new();
+        for value in [1.0f32, 2.0, 3.0] {
+            orientation.extend_from_slice(&value.to_le_bytes());
+        }
+        link.feed(&frame(MsgType::Heartbeat, &[]));
+        link.feed(&frame(MsgType::Orientation, &orientation));
+        for i in 0..4 {
+            link.feed(&frame(MsgType::Depth, &(i as f32).to_le_bytes()));
+        }
+        controller.set_surge(50.0);
+        controller.tick(&mut port, &mut rx_buffer, &mut last_tx);
+        controller.status();
+        
+        clock.advance(Duration::from_millis(500));
+        for _ in 0..2 {
+            link.feed(&frame(MsgType::Depth, &4.5f32.to_le_bytes()));
         }
+        controller.tick(&mut port, &mut rx_buffer, &mut last_tx);
         
-        let msg_type_byte = buffer[1];
-        let len = buffer[2] as usize;
+        let status = controller.status();
+        assert!(status.armed);
+        assert!(!status.failsafe);
+        assert_eq!(status.led, LedStatus::Solid);
+        assert!(status.link_ok);
+        assert_eq!(status.tx_errors, 0);
+        assert_eq!(status.heartbeat_age_ms, Some(500));
+        assert_eq!(status.orientation_age_ms, Some(500));
+        assert_eq!(status.depth_age_ms, Some(0));
+        assert_eq!(status.imu_age_ms, None);
+        assert_eq!(status.orientation, Some((1.0, 2.0, 3.0)));
+        assert_eq!(status.depth, Some(4.5));
+        assert_eq!(status.last_pwm, [1700, 1700, 1300, 1300, 1500, 1500]);
         
-        if len > MAX_MSG_SIZE {
-            buffer.remove(0);
-            return None;
+        let names: Vec<&str> = status.topics.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["/stm32/depth", "/stm32/heartbeat", "/stm32/orientation"]);
+        let depth = &status.topics[0];
+        assert_eq!(depth.published, 6);
+        assert_eq!(depth.len, 6);
+        assert_eq!(depth.dropped, 0);
+        assert_eq!(depth.rate_hz, Some(4.0));
+        assert_eq!(status.topics[1].rate_hz, Some(0.0));
+    }
+    
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_status_serializes() {
+        let controller = AuvController::new("/dev/null");
+        controller.process_rx(&mut frame(MsgType::Depth, &2.0f32.to_le_bytes()));
+        
+        let json = serde_json::to_value(controller.status()).unwrap();
+        assert_eq!(json["armed"], true);
+        assert_eq!(json["led"], "Solid");
+        assert_eq!(json["depth"], 2.0);
+        assert_eq!(json["topics"][0]["name"], "/stm32/depth");
+        assert!(json["topics"][0]["rate_hz"].is_null());
+    }
+    
+    #[test]
+    fn test_status_led_uses_configured_endianness() {
+        let controller = AuvController::new("/dev/null")
+            .with_clock(Arc::new(ManualClock::new()))
+            .with_endianness(Endianness::Big)
+            .with_status_led(true);
+        let link = LoopbackTransport::new();
+        let mut port = link.clone();
+        link.feed(&frame(MsgType::Heartbeat, &[]));
+        controller.tick(&mut port, &mut Vec::new(), &mut None);
+        
+        let mut written = link.take_written();
+        let mut led = None;
+        while let Some(frame) = FrameCodec::new().decode(&mut written) {
+            if frame.msg_type == MsgType::Led {
+                led = Some(frame.payload);
+            }
         }
+        assert_eq!(led, Some(vec![0x00, LedStatus::Solid as u8]));
+    }
+    
+    #[test]
+    fn test_status_led_off_by_default() {
+        let controller = AuvController::new("/dev/null");
+        let link = LoopbackTransport::new();
+        let mut port = link.clone();
+        controller.tick(&mut port, &mut Vec::new(), &mut None);
+        assert!(led_frames(&link).is_empty());
+    }
+    
+    #[test]
+    fn test_stale_heartbeat_disarms_until_rearmed() {
+        let clock = Arc::new(ManualClock::new());
+        let controller = AuvController::new("/dev/null")
+            .with_clock(clock.clone())
+            .with_heartbeat_timeout(Duration::from_millis(200));
+        let link = LoopbackTransport::new();
+        let mut port = link.clone();
+        let mut rx_buffer = Vec::new();
+        let mut last_tx = None;
+        controller.set_surge(50.0);
         
-        let frame_len = 4 + len;
-        if buffer.len() < frame_len {
-            return None;
+        // Healthy link: heartbeat every tick, thrust passes through
+        for _ in 0..10 {
+            link.feed(&frame(MsgType::Heartbeat, &[]));
+            controller.tick(&mut port, &mut rx_buffer, &mut last_tx);
+            clock.advance(TX_PERIOD);
         }
+        assert!(controller.is_armed());
+        assert_ne!(last_pwm(&link), NEUTRAL_PWM);
+        
+        // Heartbeats stop: still armed within the timeout
+        clock.advance(Duration::from_millis(150));
+        controller.tick(&mut port, &mut rx_buffer, &mut last_tx);
+        assert!(controller.is_armed());
+        
+        // Past the timeout: disarmed and neutral
+        clock.advance(Duration::from_millis(100));
+        controller.tick(&mut port, &mut rx_buffer, &mut last_tx);
+        assert!(!controller.is_armed());
+        assert_eq!(last_pwm(&link), NEUTRAL_PWM);
+        
+        // Link recovers: no automatic re-arm
+        link.feed(&frame(MsgType::Heartbeat, &[]));
+        clock.advance(TX_PERIOD);
+        controller.tick(&mut port, &mut rx_buffer, &mut last_tx);
+        assert!(!controller.is_armed());
+        assert_eq!(last_pwm(&link), NEUTRAL_PWM);
+        
+        controller.arm();
+        clock.advance(TX_PERIOD);
+        controller.tick(&mut port, &mut rx_buffer, &mut last_tx);
+        assert!(controller.is_armed());
+        assert_ne!(last_pwm(&link), NEUTRAL_PWM);
+    }
+    
+    #[test]
+    fn test_graceful_shutdown_writes_queue_then_stop_frame_last() {
+        let controller = Arc::new(AuvController::new("/dev/null").with_shutdown_pwm([1490; 6]));
+        controller.set_surge(50.0);
+        let link = LoopbackTransport::new();
+        
+        let loop_controller = Arc::clone(&controller);
+        let mut port = link.clone();
+        let handle = thread::spawn(move || loop_controller.run_with_transport(&mut port));
+        while !controller.running.load(Ordering::SeqCst) {
+            thread::yield_now();
+        }
+        
+        controller.queue_frame(MsgType::Led, &3i16.to_le_bytes());
+        controller.queue_frame(MsgType::Calibration, &[1]);
+        controller.shutdown_graceful(Duration::from_secs(5)).unwrap();
+        handle.join().unwrap();
+        
+        let mut written = link.take_written();
+        let mut frames = Vec::new();
+        while let Some(frame) = FrameCodec::new().decode(&mut written) {
+            frames.push(frame);
+        }
+        let types: Vec<MsgType> = frames.iter().map(|f| f.msg_type).collect();
+        assert!(types.contains(&MsgType::Led));
+        assert!(types.contains(&MsgType::Calibration));
+        
+        let last = frames.last().unwrap();
+        assert_eq!(last.msg_type, MsgType::Thruster);
+        let pwm = ThrusterPwmCmd::from_bytes(&last.payload).unwrap().pwm;
+        assert_eq!(pwm, [1490; 6]);
+    }
+    
+    #[test]
+    fn test_tx_queue_bound_and_drop_policy() {
+        let sent_calibrations = |controller: &AuvController| {
+            let link = LoopbackTransport::new();
+            controller.drain_tx_queue(&mut link.clone());
+            let mut written = link.take_written();
+            std::iter::from_fn(|| FrameCodec::new().decode(&mut written))
+                .map(|frame| frame.payload[0])
+                .collect::<Vec<u8>>()
+        };
+        
+        let newest = AuvController::new("/dev/null").with_tx_queue(4, TxDropPolicy::DropNewest);
+        let queued: Vec<bool> = (0..10).map(|i| newest.queue_frame(MsgType::Calibration, &[i])).collect();
+        assert_eq!(queued, [true, true, true, true, false, false, false, false, false, false]);
+        assert_eq!(newest.tx_dropped(), 6);
+        assert_eq!(sent_calibrations(&newest), [0, 1, 2, 3]);
+        
+        let oldest = AuvController::new("/dev/null").with_tx_queue(4, TxDropPolicy::DropOldest);
+        assert!((0..10).all(|i| oldest.queue_frame(MsgType::Calibration, &[i])));
+        assert_eq!(oldest.tx_dropped(), 6);
+        assert_eq!(sent_calibrations(&oldest), [6, 7, 8, 9]);
         
-        let checksum = buffer[3 + len];
-        let calculated = Self::calculate_checksum(&buffer[1..3 + len]);
+        // Draining makes room again
+        assert!(newest.queue_frame(MsgType::Calibration, &[42]));
+        assert_eq!(newest.tx_dropped(), 6);
+    }
+    
+    #[test]
+    fn test_reconnect_clears_partial_rx_frame() {
+        let controller = Arc::new(AuvController::new("/dev/null").with_rx_capacity(1024));
+        let depth = frame(MsgType::Depth, &2.5f32.to_le_bytes());
+        let run = |link: &LoopbackTransport| {
+            let loop_controller = Arc::clone(&controller);
+            let mut port = link.clone();
+            thread::spawn(move || loop_controller.run_with_transport(&mut port))
+        };
+        
+        // The first link drops mid-frame
+        let first = LoopbackTransport::new();
+        first.feed(&depth[..5]);
+        let handle = run(&first);
+        while first.pending_rx() > 0 {
+            thread::yield_now();
+        }
+        controller.shutdown_graceful(Duration::from_secs(5)).unwrap();
+        handle.join().unwrap();
+        assert_eq!(controller.rx_buffer.lock_unpoisoned().len(), 5);
+        let capacity = controller.rx_buffer.lock_unpoisoned().capacity();
         
-        if checksum != calculated {
-            buffer.remove(0);
-            return None;
+        // Its tail arrives first on the new link, then a whole frame
+        let second = LoopbackTransport::new();
+        second.feed(&depth[5..]);
+        second.feed(&frame(MsgType::Depth, &7.0f32.to_le_bytes()));
+        let handle = run(&second);
+        while controller.get_depth() != Some(7.0) {
+            thread::yield_now();
         }
+        controller.shutdown_graceful(Duration::from_secs(5)).unwrap();
+        handle.join().unwrap();
         
-        let msg_type = match msg_type_byte {
-            0x01 => MsgType::Imu,
-            0x02 => MsgType::Depth,
-            0x05 => MsgType::Orientation,
-            _ => {
-                buffer.drain(0..frame_len);
-                return None;
+        let topic = controller.registry().get_or_create_byte(MsgType::Depth.to_topic_name(), RX_TOPIC_CAPACITY);
+        assert_eq!(topic.len(), 1, "stale partial frame spliced with the new link");
+        assert_eq!(controller.rx_buffer.lock_unpoisoned().capacity(), capacity);
+    }
+    
+    #[test]
+    fn test_graceful_shutdown_times_out_without_loop() {
+        let controller = AuvController::new("/dev/null");
+        let err = controller.shutdown_graceful(Duration::from_millis(10)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+    }
+    
+    #[test]
+    fn test_events_report_heartbeat_failsafe() {
+        let clock = Arc::new(ManualClock::new());
+        let controller = Arc::new(AuvController::new("/dev/null")
+            .with_clock(clock.clone())
+            .with_heartbeat_timeout(Duration::from_millis(200)));
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&events);
+        controller.on_event(move |event| seen.lock_unpoisoned().push(event));
+        // A second callback re-arms as soon as the heartbeat is back
+        let weak = Arc::downgrade(&controller);
+        controller.on_event(move |event| {
+            if event == ControllerEvent::HeartbeatRestored {
+                weak.upgrade().unwrap().arm();
             }
+        });
+        
+        let link = LoopbackTransport::new();
+        let mut port = link.clone();
+        let mut rx_buffer = Vec::new();
+        let mut last_tx = None;
+        link.feed(&frame(MsgType::Heartbeat, &[]));
+        controller.tick(&mut port, &mut rx_buffer, &mut last_tx);
+        assert!(events.lock_unpoisoned().is_empty());
+        
+        clock.advance(Duration::from_millis(250));
+        controller.tick(&mut port, &mut rx_buffer, &mut last_tx);
+        clock.advance(TX_PERIOD);
+        controller.tick(&mut port, &mut rx_buffer, &mut last_tx);
+        assert_eq!(*events.lock_unpoisoned(), [
+            ControllerEvent::HeartbeatLost { last_seen: Duration::from_millis(250) },
+            ControllerEvent::Failsafe { cause: FailsafeCause::HeartbeatLost },
+        ]);
+        
+        link.feed(&frame(MsgType::Heartbeat, &[]));
+        controller.tick(&mut port, &mut rx_buffer, &mut last_tx);
+        controller.disarm();
+        assert_eq!(events.lock_unpoisoned()[2..], [
+            ControllerEvent::HeartbeatRestored,
+            ControllerEvent::Armed,
+            ControllerEvent::Disarmed,
+        ]);
+        assert_eq!(controller.led_status(), LedStatus::Off);
+    }
+    
+    #[test]
+    fn test_no_heartbeat_yet_does_not_disarm() {
+        let clock = Arc::new(ManualClock::new());
+        let controller = AuvController::new("/dev/null")
+            .with_clock(clock.clone())
+            .with_heartbeat_timeout(Duration::from_millis(200));
+        let mut port = LoopbackTransport::new();
+        let mut last_tx = None;
+        
+        clock.advance(Duration::from_secs(5));
+        controller.tick(&mut port, &mut Vec::new(), &mut last_tx);
+        assert!(controller.is_armed());
+    }
+    
+    #[test]
+    fn test_control_step_neutral_without_command() {
+        let controller = AuvController::new("/dev/null");
+        let pwm = controller.control_step(&SensorData::default(), Instant::now());
+        assert_eq!(pwm, NEUTRAL_PWM);
+    }
+    
+    #[test]
+    fn test_control_step_mixes_command() {
+        let controller = AuvController::new("/dev/null");
+        controller.set_surge(50.0);
+        controller.set_heave(-25.0);
+        
+        let pwm = controller.control_step(&SensorData::default(), Instant::now());
+        assert_eq!(pwm, [1700, 1700, 1300, 1300, 1400, 1400]);
+    }
+    
+    #[test]
+    fn test_control_step_slews_from_last_sent_pwm() {
+        let controller = AuvController::new("/dev/null")
+            .with_mixer(ThrustMixer::default().with_slew_limit(50));
+        controller.set_surge(50.0);
+        
+        let now = Instant::now();
+        assert_eq!(controller.control_step(&SensorData::default(), now), [1550, 1550, 1450, 1450, 1500, 1500]);
+        // Nothing was sent, so the next step ramps from the same place
+        assert_eq!(controller.control_step(&SensorData::default(), now), [1550, 1550, 1450, 1450, 1500, 1500]);
+        
+        *controller.last_pwm.lock_unpoisoned() = vec![1680, 1680, 1320, 1320, 1500, 1500];
+        assert_eq!(controller.control_step(&SensorData::default(), now), [1700, 1700, 1300, 1300, 1500, 1500]);
+        
+        // Failsafe neutral is slewed too, and an e-stop skips the ramp
+        controller.disarm();
+        assert_eq!(controller.control_step(&SensorData::default(), now), [1630, 1630, 1370, 1370, 1500, 1500]);
+        controller.emergency_stop();
+        assert_eq!(controller.control_step(&SensorData::default(), now), NEUTRAL_PWM);
+    }
+    
+    #[test]
+    fn test_command_source_priority_overrides_direct_command() {
+        use crate::auv::command_source::tests::MockSource;
+        
+        let controller = AuvController::new("/dev/null");
+        let script = MockSource::default();
+        let gamepad = MockSource::default();
+        controller.add_command_source(1, Box::new(script.clone()));
+        controller.add_command_source(5, Box::new(gamepad.clone()));
+        controller.set_surge(10.0);
+        
+        let t0 = Instant::now();
+        script.send(25.0);
+        gamepad.send(50.0);
+        assert_eq!(controller.control_step(&SensorData::default(), t0)[0], 1700);
+        
+        // Both silent past the hold window: back to the direct command
+        let later = t0 + Duration::from_secs(1);
+        assert_eq!(controller.control_step(&SensorData::default(), later)[0], 1540);
+    }
+    
+    #[test]
+    fn test_control_step_clamps_to_max_thrust() {
+        let controller = AuvController::new("/dev/null");
+        controller.set_surge(100.0);
+        controller.set_yaw(100.0);
+        
+        let pwm = controller.control_step(&SensorData::default(), Instant::now());
+        assert_eq!(pwm, [1500, 1900, 1500, 1100, 1500, 1500]);
+    }
+    
+    #[test]
+    fn test_control_step_neutral_when_disarmed() {
+        let controller = AuvController::new("/dev/null");
+        controller.set_surge(50.0);
+        controller.disarm();
+        assert_eq!(controller.control_step(&SensorData::default(), Instant::now()), NEUTRAL_PWM);
+        
+        controller.arm();
+        assert_ne!(controller.control_step(&SensorData::default(), Instant::now()), NEUTRAL_PWM);
+    }
+    
+    #[test]
+    fn test_sensor_silence_neutralizes_until_frames_resume() {
+        let clock = Arc::new(ManualClock::new());
+        let controller = AuvController::new("/dev/null").with_clock(clock.clone());
+        let link = LoopbackTransport::new();
+        let mut port = link.clone();
+        let mut rx_buffer = Vec::new();
+        let mut last_tx = None;
+        controller.set_surge(50.0);
+        
+        link.feed(&frame(MsgType::Depth, &1.5f32.to_le_bytes()));
+        controller.tick(&mut port, &mut rx_buffer, &mut last_tx);
+        assert!(controller.is_connected());
+        assert_ne!(last_pwm(&link), NEUTRAL_PWM);
+        
+        // The port stops feeding: neutral once the default 500ms has passed
+        clock.advance(Duration::from_millis(500));
+        controller.tick(&mut port, &mut rx_buffer, &mut last_tx);
+        assert!(controller.is_connected());
+        assert_ne!(last_pwm(&link), NEUTRAL_PWM);
+        
+        clock.advance(TX_PERIOD);
+        controller.tick(&mut port, &mut rx_buffer, &mut last_tx);
+        assert!(!controller.is_connected());
+        assert_eq!(last_pwm(&link), NEUTRAL_PWM);
+        assert!(controller.is_armed());
+        
+        // Frames resume: thrust comes back without re-arming
+        link.feed(&frame(MsgType::Depth, &1.5f32.to_le_bytes()));
+        clock.advance(TX_PERIOD);
+        controller.tick(&mut port, &mut rx_buffer, &mut last_tx);
+        assert!(controller.is_connected());
+        assert_ne!(last_pwm(&link), NEUTRAL_PWM);
+    }
+    
+    #[test]
+    fn test_sensor_timeout_is_configurable() {
+        let clock = Arc::new(ManualClock::new());
+        let controller = AuvController::new("/dev/null")
+            .with_clock(clock.clone())
+            .with_sensor_timeout(Duration::from_millis(50));
+        controller.set_surge(50.0);
+        controller.process_rx(&mut frame(MsgType::Depth, &1.5f32.to_le_bytes()));
+        
+        let start = clock.now();
+        let sensors = SensorData::default();
+        assert_ne!(controller.control_step(&sensors, start + Duration::from_millis(40)), NEUTRAL_PWM);
+        assert_eq!(controller.control_step(&sensors, start + Duration::from_millis(200)), NEUTRAL_PWM);
+        assert!(!controller.is_connected());
+    }
+    
+    #[test]
+    fn test_never_connected_link_goes_neutral_after_grace() {
+        let controller = AuvController::new("/dev/null")
+            .with_sensor_startup_grace(Duration::from_millis(300));
+        controller.set_surge(50.0);
+        
+        let start = Instant::now();
+        let sensors = SensorData::default();
+        assert_ne!(controller.control_step(&sensors, start), NEUTRAL_PWM);
+        assert_ne!(controller.control_step(&sensors, start + Duration::from_millis(300)), NEUTRAL_PWM);
+        assert_eq!(controller.control_step(&sensors, start + Duration::from_millis(301)), NEUTRAL_PWM);
+        assert!(!controller.is_connected());
+    }
+    
+    #[test]
+    fn test_control_step_failsafe_on_stale_heartbeat() {
+        let clock = Arc::new(ManualClock::new());
+        let controller = AuvController::new("/dev/null")
+            .with_clock(clock.clone())
+            .with_heartbeat_timeout(Duration::from_millis(200));
+        controller.set_surge(50.0);
+        controller.process_rx(&mut frame(MsgType::Heartbeat, &[]));
+        
+        let start = clock.now();
+        let sensors = SensorData::default();
+        assert_ne!(controller.control_step(&sensors, start + Duration::from_millis(200)), NEUTRAL_PWM);
+        assert_eq!(controller.control_step(&sensors, start + Duration::from_millis(201)), NEUTRAL_PWM);
+        assert!(!controller.is_armed());
+    }
+    
+    #[test]
+    fn test_partial_writes_deliver_whole_frame() {
+        let controller = AuvController::new("/dev/null");
+        let link = LoopbackTransport::new();
+        link.set_write_chunk(3);
+        controller.set_surge(50.0);
+        
+        controller.tick(&mut link.clone(), &mut Vec::new(), &mut None);
+        
+        assert_eq!(last_pwm(&link), [1700, 1700, 1300, 1300, 1500, 1500]);
+        assert!(controller.link_ok());
+        assert_eq!(controller.tx_errors(), 0);
+    }
+    
+    #[test]
+    fn test_write_failure_marks_link_down_and_disarms() {
+        let clock = Arc::new(ManualClock::new());
+        let controller = AuvController::new("/dev/null").with_clock(clock.clone());
+        let link = LoopbackTransport::new();
+        let mut port = link.clone();
+        let mut last_tx = None;
+        
+        link.set_broken(true);
+        controller.tick(&mut port, &mut Vec::new(), &mut last_tx);
+        assert!(!controller.link_ok());
+        assert!(!controller.is_armed());
+        assert_eq!(controller.tx_errors(), 1);
+        
+        link.set_broken(false);
+        clock.advance(TX_PERIOD);
+        controller.tick(&mut port, &mut Vec::new(), &mut last_tx);
+        assert!(controller.link_ok());
+        assert!(!controller.is_armed());
+        assert_eq!(last_pwm(&link), NEUTRAL_PWM);
+    }
+    
+    /// Loopback that refuses the next `failures` writes with `WouldBlock`
+    struct FlakyLink {
+        link: LoopbackTransport,
+        failures: Arc<AtomicUsize>,
+    }
+    
+    impl std::io::Read for FlakyLink {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.link.read(buf)
+        }
+    }
+    
+    impl std::io::Write for FlakyLink {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.failures.load(Ordering::SeqCst) > 0 {
+                self.failures.fetch_sub(1, Ordering::SeqCst);
+                return Err(std::io::Error::new(std::io::ErrorKind::WouldBlock, "tx busy"));
+            }
+            self.link.write(buf)
+        }
+        
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    
+    #[test]
+    fn test_tx_retries_land_command_within_budget() {
+        let clock = Arc::new(ManualClock::new());
+        let controller = AuvController::new("/dev/null")
+            .with_clock(clock.clone())
+            .with_tx_retries(2, Duration::from_micros(100));
+        let link = LoopbackTransport::new();
+        let failures = Arc::new(AtomicUsize::new(2));
+        let mut port = FlakyLink { link: link.clone(), failures: failures.clone() };
+        let mut last_tx = None;
+        controller.set_surge(50.0);
+        
+        // Two transient failures fit the budget of two retries
+        controller.tick(&mut port, &mut Vec::new(), &mut last_tx);
+        assert_eq!(failures.load(Ordering::SeqCst), 0);
+        assert_eq!(last_pwm(&link), [1700, 1700, 1300, 1300, 1500, 1500]);
+        assert!(controller.link_ok());
+        assert!(controller.is_armed());
+        assert_eq!(controller.tx_errors(), 0);
+        
+        // A third one exhausts it: the command is lost and the vehicle disarms
+        failures.store(3, Ordering::SeqCst);
+        clock.advance(TX_PERIOD);
+        controller.tick(&mut port, &mut Vec::new(), &mut last_tx);
+        assert!(link.take_written().is_empty());
+        assert!(!controller.link_ok());
+        assert!(!controller.is_armed());
+        assert_eq!(controller.tx_errors(), 1);
+    }
+    
+    #[test]
+    fn test_arm_ramp_eases_thrust_in() {
+        let clock = Arc::new(ManualClock::new());
+        let controller = AuvController::new("/dev/null")
+            .with_clock(clock.clone())
+            .with_arm_ramp(Duration::from_millis(400));
+        let sensors = SensorData::default();
+        
+        controller.disarm();
+        controller.set_surge(50.0);
+        controller.arm();
+        let armed_at = clock.now();
+        
+        let front_pwm = |ms: u64| controller.control_step(&sensors, armed_at + Duration::from_millis(ms))[0];
+        assert_eq!(front_pwm(0), 1500);
+        assert_eq!(front_pwm(100), 1550);
+        assert_eq!(front_pwm(200), 1600);
+        assert_eq!(front_pwm(400), 1700);
+        assert_eq!(front_pwm(1000), 1700);
+        
+        // Re-arming while armed does not restart the ramp
+        clock.advance(Duration::from_millis(500));
+        controller.arm();
+        assert_eq!(controller.control_step(&sensors, clock.now())[0], 1700);
+    }
+    
+    #[test]
+    fn test_slew_limit_ramps_tx_pwm_both_ways() {
+        let clock = Arc::new(ManualClock::new());
+        let controller = AuvController::new("/dev/null")
+            .with_clock(clock.clone())
+            .with_mixer(ThrustMixer::default().with_slew_limit(50));
+        let link = LoopbackTransport::new();
+        let mut port = link.clone();
+        let mut last_tx = None;
+        let mut tick = || {
+            controller.tick(&mut port, &mut Vec::new(), &mut last_tx);
+            clock.advance(TX_PERIOD);
+            last_pwm(&link)
+        };
+        
+        controller.set_surge(50.0);
+        assert_eq!(tick(), [1550, 1550, 1450, 1450, 1500, 1500]);
+        assert_eq!(tick(), [1600, 1600, 1400, 1400, 1500, 1500]);
+        tick();
+        assert_eq!(tick(), [1700, 1700, 1300, 1300, 1500, 1500]);
+        
+        // Disarming ramps back to neutral too
+        controller.disarm();
+        assert_eq!(tick(), [1650, 1650, 1350, 1350, 1500, 1500]);
+        for _ in 0..3 {
+            tick();
+        }
+        assert_eq!(tick(), NEUTRAL_PWM);
+    }
+    
+    #[test]
+    fn test_emergency_stop_sends_neutral_at_once() {
+        let clock = Arc::new(ManualClock::new());
+        let controller = AuvController::new("/dev/null")
+            .with_clock(clock.clone())
+            .with_mixer(ThrustMixer::default().with_slew_limit(50));
+        let link = LoopbackTransport::new();
+        let mut port = link.clone();
+        let mut last_tx = None;
+        controller.set_surge(50.0);
+        for _ in 0..5 {
+            controller.tick(&mut port, &mut Vec::new(), &mut last_tx);
+            clock.advance(TX_PERIOD);
+        }
+        assert_eq!(last_pwm(&link), [1700, 1700, 1300, 1300, 1500, 1500]);
+        
+        // Mid TX period, and past the slew limit
+        clock.advance(TX_PERIOD / 4);
+        controller.emergency_stop();
+        controller.tick(&mut port, &mut Vec::new(), &mut last_tx);
+        assert_eq!(last_pwm(&link), NEUTRAL_PWM);
+        assert!(!controller.is_armed());
+        
+        // Stays neutral through new commands until re-armed
+        controller.set_surge(80.0);
+        clock.advance(TX_PERIOD);
+        controller.tick(&mut port, &mut Vec::new(), &mut last_tx);
+        assert_eq!(last_pwm(&link), NEUTRAL_PWM);
+        controller.arm();
+        clock.advance(TX_PERIOD);
+        controller.tick(&mut port, &mut Vec::new(), &mut last_tx);
+        assert_eq!(last_pwm(&link), [1550, 1550, 1450, 1450, 1500, 1500]);
+    }
+    
+    #[test]
+    fn test_rearm_before_estop_tick_still_ramps() {
+        let clock = Arc::new(ManualClock::new());
+        let controller = AuvController::new("/dev/null")
+            .with_clock(clock.clone())
+            .with_mixer(ThrustMixer::default().with_slew_limit(50));
+        let link = LoopbackTransport::new();
+        let mut port = link.clone();
+        let mut last_tx = None;
+        controller.set_surge(50.0);
+        for _ in 0..5 {
+            controller.tick(&mut port, &mut Vec::new(), &mut last_tx);
+            clock.advance(TX_PERIOD);
+        }
+        
+        // Re-armed before the loop got to the stop
+        controller.emergency_stop();
+        controller.arm();
+        controller.tick(&mut port, &mut Vec::new(), &mut last_tx);
+        assert_eq!(last_pwm(&link), NEUTRAL_PWM);
+        clock.advance(TX_PERIOD);
+        controller.tick(&mut port, &mut Vec::new(), &mut last_tx);
+        assert_eq!(last_pwm(&link), [1550, 1550, 1450, 1450, 1500, 1500]);
+    }
+    
+    #[test]
+    fn test_depth_and_heading_hold_feed_heave_and_yaw() {
+        let controller = AuvController::new("/dev/null")
+            .with_depth_pid(Pid::new(20.0, 0.0, 0.0))
+            .with_heading_pid(Pid::new(1.5, 0.0, 0.0));
+        let reference = AuvController::new("/dev/null");
+        let sensors = SensorData {
+            depth: Some(DepthMsg { depth: 1.5 }),
+            orientation: Some(OrientationMsg { roll: 0.0, pitch: 0.0, yaw: 350.0 }),
+            ..Default::default()
         };
+        let now = Instant::now();
         
-        let payload = buffer[3..3 + len].to_vec();
-        buffer.drain(0..frame_len);
+        // No setpoint: sensors alone don't move anything
+        assert_eq!(controller.control_step(&sensors, now), NEUTRAL_PWM);
         
-        Some((msg_type, payload))
+        // 0.5m too shallow: heave 10. Heading 350 -> 10 is +20 degrees: yaw 30
+        controller.set_depth_setpoint(Some(2.0));
+        controller.set_heading_setpoint(Some(10.0));
+        controller.set_surge(20.0);
+        reference.set_thrust(ThrustCommand { surge: 20.0, heave: 10.0, yaw: 30.0, ..Default::default() });
+        assert_eq!(controller.control_step(&sensors, now), reference.control_step(&sensors, now));
+        
+        // Released holds leave the plain command
+        controller.set_depth_setpoint(None);
+        controller.set_heading_setpoint(None);
+        reference.set_thrust(ThrustCommand { surge: 20.0, ..Default::default() });
+        assert_eq!(controller.control_step(&sensors, now), reference.control_step(&sensors, now));
+    }
+    
+    #[test]
+    fn test_heading_error_takes_shorter_turn() {
+        assert_eq!(heading_error(10.0, 350.0), 20.0);
+        assert_eq!(heading_error(350.0, 10.0), -20.0);
+        assert_eq!(heading_error(90.0, 90.0), 0.0);
+        assert_eq!(heading_error(-90.0, 180.0), 90.0);
+    }
+    
+    #[test]
+    fn test_smaller_mixer_leaves_spare_channels_neutral() {
+        let controller = AuvController::new("/dev/null")
+            .with_mixer(ThrustMixer::preset(VehicleConfig::Planar4));
+        controller.set_surge(50.0);
+        controller.set_heave(50.0);
+        
+        let pwm = controller.control_step(&SensorData::default(), Instant::now());
+        assert_eq!(pwm, [1700, 1700, 1300, 1300, 1500, 1500]);
+    }
+    
+    #[test]
+    fn test_mixer_pwm_range_sets_neutral_and_shutdown() {
+        let controller = Arc::new(AuvController::new("/dev/null")
+            .with_mixer(ThrustMixer::default().with_pwm_range(1000, 1520, 2000).with_reversed(0)));
+        controller.set_surge(50.0);
+        
+        // Front-left is reversed; rear horizontals already mix negative
+        let pwm = controller.control_step(&SensorData::default(), Instant::now());
+        assert_eq!(pwm, [1260, 1760, 1260, 1260, 1520, 1520]);
+        controller.disarm();
+        assert_eq!(controller.control_step(&SensorData::default(), Instant::now()), [1520; 6]);
+        
+        let link = LoopbackTransport::new();
+        let loop_controller = Arc::clone(&controller);
+        let mut port = link.clone();
+        let handle = thread::spawn(move || loop_controller.run_with_transport(&mut port));
+        while !controller.running.load(Ordering::SeqCst) {
+            thread::yield_now();
+        }
+        controller.shutdown_graceful(Duration::from_secs(5)).unwrap();
+        handle.join().unwrap();
+        assert_eq!(last_pwm(&link), [1520; 6]);
+    }
+    
+    #[test]
+    fn test_first_ramp_starts_from_mixer_neutral() {
+        let clock = Arc::new(ManualClock::new());
+        let controller = AuvController::new("/dev/null")
+            .with_clock(clock.clone())
+            .with_mixer(ThrustMixer::default().with_pwm_range(1000, 1520, 2000).with_slew_limit(50));
+        assert_eq!(controller.status().last_pwm, [1520; 6]);
+        
+        let link = LoopbackTransport::new();
+        let mut port = link.clone();
+        controller.set_heave(50.0);
+        controller.tick(&mut port, &mut Vec::new(), &mut None);
+        assert_eq!(last_pwm(&link), [1520, 1520, 1520, 1520, 1570, 1570]);
+    }
+    
+    #[test]
+    fn test_eight_thruster_mixer_extends_the_frame() {
+        let clock = Arc::new(ManualClock::new());
+        let controller = AuvController::new("/dev/null")
+            .with_clock(clock.clone())
+            .with_mixer(ThrustMixer::preset(VehicleConfig::BlueROV2Heavy));
+        let link = LoopbackTransport::new();
+        let mut port = link.clone();
+        controller.set_surge(50.0);
+        controller.set_heave(50.0);
+        
+        controller.tick(&mut port, &mut Vec::new(), &mut None);
+        let sent = FrameCodec::new().decode(&mut link.take_written()).unwrap();
+        assert_eq!(sent.payload.len(), 32);
+        assert_eq!(
+            ThrusterPwmCmd::channels_from_bytes(&sent.payload, Endianness::Little).unwrap(),
+            [1700, 1700, 1300, 1300, 1700, 1700, 1700, 1700]
+        );
+        assert_eq!(controller.status().last_pwm.len(), 8);
+    }
+    
+    #[test]
+    #[should_panic(expected = "mixer drives 9 thrusters")]
+    fn test_oversized_mixer_rejected() {
+        let _ = AuvController::new("/dev/null").with_mixer(ThrustMixer::from_matrix(vec![[1.0; 6]; 9], 100.0));
     }
 }
@@ -1,4 +1,4 @@
-/**
+/*!
  * AUV Controller Module
  * 
  * Unified controller that combines:
@@ -9,8 +9,15 @@
  * All in one process with shared BiBi-Sync ring buffers.
  */
 
+pub mod clock;
+pub mod command_source;
 pub mod controller;
+pub mod pid;
+mod seqlock;
 pub mod thrust_mixer;
 
-pub use controller::AuvController;
-pub use thrust_mixer::ThrustMixer;
+pub use clock::{Clock, SystemClock, ManualClock};
+pub use command_source::{CommandSource, CommandArbiter};
+pub use controller::{AuvController, AuvStatus, TopicStatus, LedStatus, ControllerEvent, FailsafeCause, TxDropPolicy};
+pub use pid::Pid;
+pub use thrust_mixer::{ThrustMixer, VehicleConfig, SaturationMode};
@@ -0,0 +1,110 @@
+/*!
+ * PID
+ *
+ * Discrete PID loop for the controller's depth and heading hold, stepped
+ * once per control tick with the time since the previous step.
+ */
+
+/// PID controller with a clamped integral against windup
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pid {
+    pub kp: f32,
+    pub ki: f32,
+    pub kd: f32,
+    /// Bound on `integral` in either direction
+    pub integral_limit: f32,
+    integral: f32,
+    prev_error: Option<f32>,
+}
+
+impl Pid {
+    /// Loop with the given gains and an unbounded integral
+    pub fn new(kp: f32, ki: f32, kd: f32) -> Self {
+        Self { kp, ki, kd, integral_limit: f32::INFINITY, integral: 0.0, prev_error: None }
+    }
+
+    /// Keep the accumulated error within `±limit`, so a long saturation
+    /// doesn't wind the integral up into a large overshoot
+    pub fn with_integral_limit(mut self, limit: f32) -> Self {
+        self.integral_limit = limit.abs();
+        self
+    }
+
+    /// Accumulated error so far
+    pub fn integral(&self) -> f32 {
+        self.integral
+    }
+
+    /// Forget the accumulated error and the previous step
+    pub fn reset(&mut self) {
+        self.integral = 0.0;
+        self.prev_error = None;
+    }
+
+    /// Output for one step of `dt` seconds
+    ///
+    /// The first step after `new` or `reset` has no derivative term, so a
+    /// fresh setpoint doesn't kick. A non-positive `dt` only applies the
+    /// proportional term.
+    pub fn update(&mut self, setpoint: f32, measured: f32, dt: f32) -> f32 {
+        let error = setpoint - measured;
+        if dt <= 0.0 {
+            return self.kp * error;
+        }
+
+        self.integral = (self.integral + error * dt).clamp(-self.integral_limit, self.integral_limit);
+        let derivative = self.prev_error.map_or(0.0, |prev| (error - prev) / dt);
+        self.prev_error = Some(error);
+
+        self.kp * error + self.ki * self.integral + self.kd * derivative
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_response_settles_on_setpoint() {
+        let mut pid = Pid::new(2.0, 0.5, 0.1).with_integral_limit(10.0);
+        let dt = 0.02;
+
+        // First-order plant with a constant disturbance only the integral can cancel
+        let mut position = 0.0f32;
+        let mut first = None;
+        for _ in 0..2000 {
+            let output = pid.update(1.0, position, dt);
+            first.get_or_insert(output);
+            position += (output - 0.3) * dt;
+        }
+        // Proportional plus one step of integral, no derivative kick
+        assert!((first.unwrap() - (2.0 + 0.5 * 0.02)).abs() < 1e-6);
+        assert!((position - 1.0).abs() < 1e-3, "settled at {}", position);
+        assert!((pid.integral() * 0.5 - 0.3).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_derivative_opposes_fast_error_change() {
+        let mut pid = Pid::new(0.0, 0.0, 1.0);
+        assert_eq!(pid.update(0.0, 0.0, 0.1), 0.0);
+        // Measurement rising by 0.5 in 0.1s: error falls at 5/s
+        assert!((pid.update(0.0, 0.5, 0.1) + 5.0).abs() < 1e-5);
+
+        pid.reset();
+        assert_eq!(pid.update(0.0, 2.0, 0.1), 0.0);
+    }
+
+    #[test]
+    fn test_integral_is_clamped() {
+        let mut pid = Pid::new(0.0, 1.0, 0.0).with_integral_limit(0.5);
+        for _ in 0..100 {
+            pid.update(10.0, 0.0, 0.1);
+        }
+        assert_eq!(pid.integral(), 0.5);
+        assert_eq!(pid.update(10.0, 0.0, 0.1), 0.5);
+
+        // Unwinds as soon as the error turns
+        pid.update(0.0, 10.0, 0.01);
+        assert!((pid.integral() - 0.4).abs() < 1e-6);
+    }
+}
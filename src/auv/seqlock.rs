@@ -0,0 +1,119 @@
+/*!
+ * Sequence Lock
+ *
+ * Single-value cache for data written at sensor rate and read by the
+ * control loop. Readers never block the writer: they copy the value and
+ * retry if a write was in progress meanwhile, so every read is a
+ * consistent snapshot.
+ */
+
+use std::cell::UnsafeCell;
+use std::hint;
+use std::ptr;
+use std::sync::atomic::{self, AtomicU64, Ordering};
+
+/// Copyable value behind a sequence counter, odd while a write is underway
+pub(crate) struct SeqLock<T: Copy> {
+    seq: AtomicU64,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Copy + Send> Send for SeqLock<T> {}
+unsafe impl<T: Copy + Send> Sync for SeqLock<T> {}
+
+impl<T: Copy> SeqLock<T> {
+    pub(crate) fn new(value: T) -> Self {
+        Self { seq: AtomicU64::new(0), data: UnsafeCell::new(value) }
+    }
+
+    /// Consistent copy of the value, retrying while a write overlaps it
+    pub(crate) fn read(&self) -> T {
+        loop {
+            let before = self.seq.load(Ordering::Acquire);
+            if before % 2 == 1 {
+                hint::spin_loop();
+                continue;
+            }
+            // A torn copy is possible here, but it is thrown away below
+            let value = unsafe { ptr::read_volatile(self.data.get()) };
+            atomic::fence(Ordering::Acquire);
+            if self.seq.load(Ordering::Relaxed) == before {
+                return value;
+            }
+        }
+    }
+
+    /// Modify the value in place. Concurrent writers take turns; readers
+    /// are never waited for
+    pub(crate) fn update(&self, f: impl FnOnce(&mut T)) {
+        let mut seq = self.seq.load(Ordering::Relaxed);
+        loop {
+            if seq % 2 == 1 {
+                hint::spin_loop();
+                seq = self.seq.load(Ordering::Relaxed);
+                continue;
+            }
+            match self.seq.compare_exchange_weak(seq, seq + 1, Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_) => break,
+                Err(current) => seq = current,
+            }
+        }
+        atomic::fence(Ordering::Release);
+
+        unsafe {
+            let mut value = ptr::read_volatile(self.data.get());
+            f(&mut value);
+            ptr::write_volatile(self.data.get(), value);
+        }
+        self.seq.store(seq + 2, Ordering::Release);
+    }
+}
+
+impl<T: Copy + Default> Default for SeqLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, AtomicUsize};
+    use std::thread;
+
+    #[test]
+    fn test_readers_never_see_a_torn_value() {
+        const READERS: usize = 3;
+        const MIN_READS: usize = 1_000;
+        let lock = Arc::new(SeqLock::new([0u64; 16]));
+        let done = Arc::new(AtomicBool::new(false));
+        let reads: Arc<Vec<AtomicUsize>> = Arc::new((0..READERS).map(|_| AtomicUsize::new(0)).collect());
+
+        let readers: Vec<_> = (0..READERS).map(|id| {
+            let lock = Arc::clone(&lock);
+            let done = Arc::clone(&done);
+            let reads = Arc::clone(&reads);
+            thread::spawn(move || {
+                while !done.load(Ordering::SeqCst) {
+                    let value = lock.read();
+                    assert!(value.iter().all(|&v| v == value[0]), "torn read {:?}", value);
+                    reads[id].fetch_add(1, Ordering::Relaxed);
+                }
+            })
+        }).collect();
+
+        // Keep writing until every reader has raced the writer for a while,
+        // however the threads happen to be scheduled
+        let mut n = 0u64;
+        while n < 100_000 || reads.iter().any(|r| r.load(Ordering::Relaxed) < MIN_READS) {
+            n += 1;
+            lock.update(|value| *value = [n; 16]);
+        }
+        done.store(true, Ordering::SeqCst);
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(lock.read(), [n; 16]);
+    }
+}
@@ -1,4 +1,4 @@
-/**
+/*!
  * Thrust Mixer
  * 
  * Converts 6-DoF thrust commands (surge, sway, heave, roll, pitch, yaw)
@@ -16,73 +16,235 @@ pub struct ThrustCommand {
     pub yaw: f32,
 }
 
+/// Known thruster layouts with ready-made mix matrices
+///
+/// Columns are always `[surge, sway, heave, roll, pitch, yaw]`; a positive
+/// entry means positive thrust on that thruster drives the vehicle in the
+/// positive direction of that DoF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VehicleConfig {
+    /// 4 vectored horizontal + 2 vertical thrusters (the original default).
+    /// Rows: front-left, front-right, rear-left, rear-right, left vertical,
+    /// right vertical. Rear horizontals are mounted reversed.
+    Vectored6,
+    /// BlueROV2 Heavy: 4 vectored horizontal + 4 corner verticals.
+    /// Rows: front-left, front-right, rear-left, rear-right (horizontal, as
+    /// in `Vectored6`), then front-left, front-right, rear-left, rear-right
+    /// verticals.
+    BlueROV2Heavy,
+    /// 4 vectored horizontal thrusters only, for surface vehicles.
+    /// Rows: front-left, front-right, rear-left, rear-right.
+    Planar4,
+}
+
+/// How `ThrustMixer::mix` keeps thrusters within `max_thrust`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SaturationMode {
+    /// Clamp each thruster on its own; a saturated thruster bends the
+    /// resulting direction
+    #[default]
+    Clamp,
+    /// Scale all thrusters by the same factor until the largest one fits,
+    /// keeping the commanded direction
+    Scale,
+}
+
+/// Horizontal rows shared by every vectored preset
+const VECTORED_HORIZONTAL: [[f32; 6]; 4] = [
+    // Front-left
+    [1.0, -1.0, 0.0, 0.0, 0.0, -1.0],
+    // Front-right
+    [1.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+    // Rear-left
+    [-1.0, -1.0, 0.0, 0.0, 0.0, 1.0],
+    // Rear-right
+    [-1.0, 1.0, 0.0, 0.0, 0.0, -1.0],
+];
+
 /// Thrust mixer configuration (matches your thruster layout)
-/// Default values for 6-thruster vectored configuration
+/// Defaults to the `Vectored6` preset
 #[derive(Debug, Clone)]
 pub struct ThrustMixer {
-    /// Contribution of each DoF to each thruster [6 thrusters x 6 DoFs]
-    pub mix_matrix: [[f32; 6]; 6],
+    /// Contribution of each DoF to each thruster, one row per thruster
+    pub mix_matrix: Vec<[f32; 6]>,
     /// Maximum thrust per thruster
     pub max_thrust: f32,
+    /// What happens to outputs beyond `max_thrust`
+    pub saturation: SaturationMode,
+    /// PWM at full reverse thrust
+    pub pwm_min: i32,
+    /// PWM at zero thrust
+    pub pwm_neutral: i32,
+    /// PWM at full forward thrust
+    pub pwm_max: i32,
+    /// Thrusters whose ESC runs the other way round, one flag per row
+    pub reversed: Vec<bool>,
+    /// Largest PWM change any one thruster may make per TX tick, `None` for
+    /// no limit
+    pub max_pwm_delta_per_tick: Option<i32>,
 }
 
 impl Default for ThrustMixer {
     fn default() -> Self {
-        // Standard vectored 6-thruster configuration
-        // Rows: thrusters, Columns: [surge, sway, heave, roll, pitch, yaw]
-        Self {
-            mix_matrix: [
-                // Thruster 0 (front-left horizontal)
-                [1.0, -1.0, 0.0, 0.0, 0.0, -1.0],
-                // Thruster 1 (front-right horizontal)  
-                [1.0, 1.0, 0.0, 0.0, 0.0, 1.0],
-                // Thruster 2 (rear-left horizontal)
-                [-1.0, -1.0, 0.0, 0.0, 0.0, 1.0],
-                // Thruster 3 (rear-right horizontal)
-                [-1.0, 1.0, 0.0, 0.0, 0.0, -1.0],
-                // Thruster 4 (left vertical)
-                [0.0, 0.0, 1.0, -1.0, 1.0, 0.0],
-                // Thruster 5 (right vertical)
-                [0.0, 0.0, 1.0, 1.0, 1.0, 0.0],
-            ],
-            max_thrust: 100.0,
-        }
+        Self::preset(VehicleConfig::Vectored6)
     }
 }
 
 impl ThrustMixer {
+    /// Mixer for a custom layout, one `[surge, sway, heave, roll, pitch, yaw]`
+    /// row per thruster
+    ///
+    /// Panics if there are no rows or `max_thrust` is not positive.
+    pub fn from_matrix(rows: Vec<[f32; 6]>, max_thrust: f32) -> Self {
+        assert!(!rows.is_empty(), "mix matrix needs at least one thruster");
+        assert!(max_thrust > 0.0, "max_thrust must be positive, got {}", max_thrust);
+        let reversed = vec![false; rows.len()];
+        Self { mix_matrix: rows, max_thrust, reversed, ..Self::default() }
+    }
+    
+    /// The original 6-thruster layout, same as `Default`
+    pub fn default_6dof() -> Self {
+        Self::preset(VehicleConfig::Vectored6)
+    }
+    
+    /// Mixer for a known vehicle layout
+    pub fn preset(config: VehicleConfig) -> Self {
+        let mut mix_matrix = VECTORED_HORIZONTAL.to_vec();
+        match config {
+            VehicleConfig::Vectored6 => {
+                mix_matrix.extend([
+                    // Left vertical
+                    [0.0, 0.0, 1.0, -1.0, 1.0, 0.0],
+                    // Right vertical
+                    [0.0, 0.0, 1.0, 1.0, 1.0, 0.0],
+                ]);
+            }
+            VehicleConfig::BlueROV2Heavy => {
+                mix_matrix.extend([
+                    // Front-left vertical
+                    [0.0, 0.0, 1.0, -1.0, 1.0, 0.0],
+                    // Front-right vertical
+                    [0.0, 0.0, 1.0, 1.0, 1.0, 0.0],
+                    // Rear-left vertical
+                    [0.0, 0.0, 1.0, -1.0, -1.0, 0.0],
+                    // Rear-right vertical
+                    [0.0, 0.0, 1.0, 1.0, -1.0, 0.0],
+                ]);
+            }
+            VehicleConfig::Planar4 => {}
+        }
+        Self {
+            reversed: vec![false; mix_matrix.len()],
+            mix_matrix,
+            max_thrust: 100.0,
+            saturation: SaturationMode::Clamp,
+            pwm_min: 1100,
+            pwm_neutral: 1500,
+            pwm_max: 1900,
+            max_pwm_delta_per_tick: None,
+        }
+    }
+    
+    /// Map thrust onto `min..=max` PWM with zero thrust at `neutral`
+    /// (1100/1500/1900 by default)
+    ///
+    /// Panics unless `min < neutral < max`.
+    pub fn with_pwm_range(mut self, min: i32, neutral: i32, max: i32) -> Self {
+        assert!(min < neutral && neutral < max, "PWM range {}/{}/{} is not min < neutral < max", min, neutral, max);
+        self.pwm_min = min;
+        self.pwm_neutral = neutral;
+        self.pwm_max = max;
+        self
+    }
+    
+    /// Invert the PWM of `thruster` (a mix matrix row), for ESCs or
+    /// propellers mounted the other way round
+    ///
+    /// Panics if the mixer has no such thruster.
+    pub fn with_reversed(mut self, thruster: usize) -> Self {
+        assert!(thruster < self.thruster_count(), "no thruster {} in a {}-thruster mixer", thruster, self.thruster_count());
+        self.reversed.resize(self.thruster_count(), false);
+        self.reversed[thruster] = true;
+        self
+    }
+    
+    /// Handle saturated outputs with `mode` instead of clamping
+    pub fn with_saturation_mode(mut self, mode: SaturationMode) -> Self {
+        self.saturation = mode;
+        self
+    }
+    
+    /// Limit every thruster to `delta` PWM steps per tick, so a reversal
+    /// ramps through neutral instead of hitting the ESCs in one step
+    ///
+    /// Panics if `delta` is not positive.
+    pub fn with_slew_limit(mut self, delta: i32) -> Self {
+        assert!(delta > 0, "slew limit must be positive, got {}", delta);
+        self.max_pwm_delta_per_tick = Some(delta);
+        self
+    }
+    
+    /// Number of thrusters (rows in the mix matrix)
+    pub fn thruster_count(&self) -> usize {
+        self.mix_matrix.len()
+    }
+    
     /// Mix 6-DoF command into individual thruster values
-    pub fn mix(&self, cmd: &ThrustCommand) -> [f32; 6] {
+    pub fn mix(&self, cmd: &ThrustCommand) -> Vec<f32> {
         let dof = [cmd.surge, cmd.sway, cmd.heave, cmd.roll, cmd.pitch, cmd.yaw];
-        let mut output = [0.0f32; 6];
         
-        for (i, row) in self.mix_matrix.iter().enumerate() {
-            let mut sum = 0.0;
-            for (j, &coeff) in row.iter().enumerate() {
-                sum += coeff * dof[j];
+        let mut thrusts: Vec<f32> = self.mix_matrix.iter()
+            .map(|row| row.iter().zip(dof.iter()).map(|(coeff, value)| coeff * value).sum())
+            .collect();
+        
+        if self.saturation == SaturationMode::Scale {
+            let peak = thrusts.iter().fold(0.0f32, |peak, thrust| peak.max(thrust.abs()));
+            if peak > self.max_thrust {
+                let factor = self.max_thrust / peak;
+                for thrust in thrusts.iter_mut() {
+                    *thrust *= factor;
+                }
             }
-            output[i] = sum.clamp(-self.max_thrust, self.max_thrust);
         }
-        
-        output
+        // Scaling can land a hair past the limit through rounding
+        for thrust in thrusts.iter_mut() {
+            *thrust = thrust.clamp(-self.max_thrust, self.max_thrust);
+        }
+        thrusts
     }
     
-    /// Convert thrust values (-100 to 100) to PWM (1100 to 1900)
-    pub fn thrust_to_pwm(thrust: f32) -> i32 {
-        // Linear mapping: -100 -> 1100, 0 -> 1500, 100 -> 1900
-        (1500.0 + thrust * 4.0) as i32
+    /// Convert the thrust (-100 to 100) of `thruster` to PWM
+    ///
+    /// Piecewise linear: 0 maps to `pwm_neutral`, 100 to `pwm_max` and -100
+    /// to `pwm_min`, with the sign flipped first on reversed thrusters.
+    pub fn thrust_to_pwm(&self, thruster: usize, thrust: f32) -> i32 {
+        let thrust = thrust.clamp(-100.0, 100.0);
+        let thrust = if self.reversed.get(thruster).copied().unwrap_or(false) { -thrust } else { thrust };
+        let span = if thrust >= 0.0 { self.pwm_max - self.pwm_neutral } else { self.pwm_neutral - self.pwm_min };
+        self.pwm_neutral + (thrust * span as f32 / 100.0).round() as i32
     }
     
-    /// Convert thrust array to PWM array
-    pub fn to_pwm(thrusts: &[f32; 6]) -> [i32; 6] {
-        [
-            Self::thrust_to_pwm(thrusts[0]),
-            Self::thrust_to_pwm(thrusts[1]),
-            Self::thrust_to_pwm(thrusts[2]),
-            Self::thrust_to_pwm(thrusts[3]),
-            Self::thrust_to_pwm(thrusts[4]),
-            Self::thrust_to_pwm(thrusts[5]),
-        ]
+    /// Convert thrust array (one value per thruster) to PWM array
+    pub fn to_pwm(&self, thrusts: &[f32]) -> Vec<i32> {
+        thrusts.iter().enumerate().map(|(i, &thrust)| self.thrust_to_pwm(i, thrust)).collect()
+    }
+    
+    /// Convert thrust array to PWM, ramping from the `previous` tick's PWM
+    pub fn to_pwm_from(&self, thrusts: &[f32], previous: &[i32]) -> Vec<i32> {
+        self.slew(previous, &self.to_pwm(thrusts))
+    }
+    
+    /// Move each thruster from `previous` toward `target` by at most the
+    /// slew limit, independently of the others
+    ///
+    /// Thrusters without a previous value go straight to their target.
+    pub fn slew(&self, previous: &[i32], target: &[i32]) -> Vec<i32> {
+        target.iter().enumerate()
+            .map(|(i, &pwm)| match (self.max_pwm_delta_per_tick, previous.get(i)) {
+                (Some(delta), Some(&from)) => pwm.clamp(from - delta, from + delta),
+                _ => pwm,
+            })
+            .collect()
     }
 }
 
@@ -109,4 +271,173 @@ mod tests {
         assert!(output[2] < 0.0);
         assert!(output[3] < 0.0);
     }
+    
+    fn signs(values: &[f32]) -> Vec<i32> {
+        values.iter().map(|&v| if v > 0.0 { 1 } else if v < 0.0 { -1 } else { 0 }).collect()
+    }
+    
+    #[test]
+    fn test_default_is_vectored6() {
+        let default = ThrustMixer::default();
+        let preset = ThrustMixer::preset(VehicleConfig::Vectored6);
+        assert_eq!(default.mix_matrix, preset.mix_matrix);
+        assert_eq!(default.thruster_count(), 6);
+    }
+    
+    #[test]
+    fn test_preset_surge_and_yaw_signs() {
+        let surge = ThrustCommand { surge: 50.0, ..Default::default() };
+        let yaw = ThrustCommand { yaw: 50.0, ..Default::default() };
+        
+        let cases = [
+            (VehicleConfig::Vectored6, vec![1, 1, -1, -1, 0, 0], vec![-1, 1, 1, -1, 0, 0]),
+            (VehicleConfig::BlueROV2Heavy, vec![1, 1, -1, -1, 0, 0, 0, 0], vec![-1, 1, 1, -1, 0, 0, 0, 0]),
+            (VehicleConfig::Planar4, vec![1, 1, -1, -1], vec![-1, 1, 1, -1]),
+        ];
+        
+        for (config, surge_signs, yaw_signs) in cases {
+            let mixer = ThrustMixer::preset(config);
+            assert_eq!(signs(&mixer.mix(&surge)), surge_signs, "{:?} surge", config);
+            assert_eq!(signs(&mixer.mix(&yaw)), yaw_signs, "{:?} yaw", config);
+        }
+    }
+    
+    #[test]
+    fn test_bluerov2_heavy_pitch_uses_corner_verticals() {
+        let mixer = ThrustMixer::preset(VehicleConfig::BlueROV2Heavy);
+        let pitch = ThrustCommand { pitch: 50.0, ..Default::default() };
+        assert_eq!(signs(&mixer.mix(&pitch)), vec![0, 0, 0, 0, 1, 1, -1, -1]);
+    }
+    
+    #[test]
+    fn test_from_matrix_four_thrusters() {
+        // Two surge thrusters that also yaw, two heave thrusters that also roll
+        let mixer = ThrustMixer::from_matrix(vec![
+            [1.0, 0.0, 0.0, 0.0, 0.0, -1.0],
+            [1.0, 0.0, 0.0, 0.0, 0.0, 1.0],
+            [0.0, 0.0, 1.0, -0.5, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.5, 0.0, 0.0],
+        ], 50.0);
+        assert_eq!(mixer.thruster_count(), 4);
+        
+        let cmd = ThrustCommand { surge: 20.0, yaw: 10.0, heave: 30.0, roll: 20.0, ..Default::default() };
+        assert_eq!(mixer.mix(&cmd), vec![10.0, 30.0, 20.0, 40.0]);
+        // Clamped to this mixer's own limit
+        assert_eq!(mixer.mix(&ThrustCommand { surge: 80.0, ..Default::default() }), vec![50.0, 50.0, 0.0, 0.0]);
+        assert_eq!(mixer.to_pwm(&mixer.mix(&cmd)), vec![1540, 1620, 1580, 1660]);
+    }
+    
+    #[test]
+    fn test_from_matrix_eight_thrusters() {
+        let rows = ThrustMixer::preset(VehicleConfig::BlueROV2Heavy).mix_matrix;
+        let mixer = ThrustMixer::from_matrix(rows, 100.0);
+        assert_eq!(mixer.thruster_count(), 8);
+        
+        let heave = mixer.mix(&ThrustCommand { heave: 40.0, ..Default::default() });
+        assert_eq!(heave, vec![0.0, 0.0, 0.0, 0.0, 40.0, 40.0, 40.0, 40.0]);
+        let sway = mixer.mix(&ThrustCommand { sway: 25.0, ..Default::default() });
+        assert_eq!(sway.len(), 8);
+        assert_eq!(sway[4..], [0.0; 4]);
+        assert_eq!(mixer.to_pwm(&heave).len(), 8);
+    }
+    
+    #[test]
+    #[should_panic(expected = "at least one thruster")]
+    fn test_from_matrix_rejects_empty() {
+        let _ = ThrustMixer::from_matrix(Vec::new(), 100.0);
+    }
+    
+    #[test]
+    fn test_default_6dof_matches_default() {
+        assert_eq!(ThrustMixer::default_6dof().mix_matrix, ThrustMixer::default().mix_matrix);
+        assert_eq!(ThrustMixer::default_6dof().thruster_count(), 6);
+    }
+    
+    #[test]
+    fn test_presets_have_no_dead_thrusters() {
+        for config in [VehicleConfig::Vectored6, VehicleConfig::BlueROV2Heavy, VehicleConfig::Planar4] {
+            let mixer = ThrustMixer::preset(config);
+            assert!(mixer.mix_matrix.iter().all(|row| row.iter().any(|&c| c != 0.0)), "{:?}", config);
+        }
+    }
+    
+    #[test]
+    fn test_scale_saturation_keeps_direction() {
+        let cmd = ThrustCommand { surge: 80.0, yaw: 60.0, ..Default::default() };
+        let raw = ThrustMixer { max_thrust: f32::MAX, ..ThrustMixer::default() }.mix(&cmd);
+        let peak = raw.iter().fold(0.0f32, |peak, t| peak.max(t.abs()));
+        assert!(peak > 100.0);
+        
+        let scaled = ThrustMixer::default().with_saturation_mode(SaturationMode::Scale).mix(&cmd);
+        assert_eq!(scaled.iter().fold(0.0f32, |peak, t| peak.max(t.abs())), 100.0);
+        for (scaled, raw) in scaled.iter().zip(&raw) {
+            assert!((scaled - raw * 100.0 / peak).abs() < 1e-4, "{} vs {}", scaled, raw);
+        }
+        
+        // Clamp stays the default and flattens only the saturated thrusters
+        let clamped = ThrustMixer::default().mix(&cmd);
+        for (clamped, raw) in clamped.iter().zip(&raw) {
+            assert_eq!(*clamped, raw.clamp(-100.0, 100.0));
+        }
+        
+        // Unsaturated commands are left alone
+        let gentle = ThrustCommand { surge: 30.0, yaw: 20.0, ..Default::default() };
+        assert_eq!(
+            ThrustMixer::default().with_saturation_mode(SaturationMode::Scale).mix(&gentle),
+            ThrustMixer::default().mix(&gentle)
+        );
+    }
+    
+    #[test]
+    fn test_to_pwm() {
+        assert_eq!(ThrustMixer::default().to_pwm(&[0.0, 100.0, -100.0, 25.0]), vec![1500, 1900, 1100, 1600]);
+    }
+    
+    #[test]
+    fn test_custom_pwm_range_and_reversed_channel() {
+        let mixer = ThrustMixer::default().with_pwm_range(1000, 1480, 2000).with_reversed(1);
+        assert_eq!(mixer.thrust_to_pwm(0, 0.0), 1480);
+        assert_eq!(mixer.thrust_to_pwm(0, 100.0), 2000);
+        assert_eq!(mixer.thrust_to_pwm(0, -100.0), 1000);
+        // Each side of neutral scales on its own span
+        assert_eq!(mixer.thrust_to_pwm(0, 50.0), 1740);
+        assert_eq!(mixer.thrust_to_pwm(0, -50.0), 1240);
+        
+        // The reversed thruster mirrors the same thrust
+        assert_eq!(mixer.to_pwm(&[100.0, 100.0, -50.0]), vec![2000, 1000, 1240]);
+        assert_eq!(mixer.to_pwm(&[0.0, 0.0]), vec![1480, 1480]);
+        assert_eq!(mixer.to_pwm(&[0.0, -50.0]), vec![1480, 1740]);
+    }
+    
+    #[test]
+    #[should_panic(expected = "not min < neutral < max")]
+    fn test_pwm_range_must_be_ordered() {
+        let _ = ThrustMixer::default().with_pwm_range(1500, 1500, 1900);
+    }
+    
+    #[test]
+    fn test_slew_limit_ramps_each_thruster_independently() {
+        let mixer = ThrustMixer::default().with_slew_limit(60);
+        
+        let mut pwm = vec![1500, 1500];
+        let mut history = vec![pwm.clone()];
+        while pwm[0] != 1900 {
+            pwm = mixer.to_pwm_from(&[100.0, 5.0], &pwm);
+            history.push(pwm.clone());
+            assert!(history.len() <= 10, "never reached full thrust: {:?}", history);
+        }
+        for step in history.windows(2) {
+            let rise = step[1][0] - step[0][0];
+            assert!(rise > 0 && rise <= 60, "step {:?}", step);
+        }
+        // 400 steps at 60 per tick, the last one short
+        assert_eq!(history.len(), 8);
+        // The small command is not held back by the big one
+        assert_eq!(history[1][1], 1520);
+        
+        // Ramping down to neutral is limited the same way
+        assert_eq!(mixer.slew(&[1900, 1100], &[1500, 1500]), vec![1840, 1160]);
+        // No limit by default
+        assert_eq!(ThrustMixer::default().slew(&[1100], &[1900]), vec![1900]);
+    }
 }
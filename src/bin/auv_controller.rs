@@ -1,4 +1,4 @@
-/**
+/*!
  * AUV Controller Binary
  * 
  * Runs the unified AUV controller that:
@@ -89,7 +89,6 @@ fn main() {
                 println!("[STOP]");
             }
             "sensors" | "r" => {
-                let sensors = controller.get_sensors();
                 if let Some((r, p, y)) = controller.get_orientation() {
                     println!("[ORIENT] roll={:.1}° pitch={:.1}° yaw={:.1}°", r, p, y);
                 }
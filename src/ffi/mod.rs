@@ -1,7 +1,10 @@
+#![allow(clippy::missing_safety_doc)]
+
 use std::ffi::{c_char, CStr};
 use std::sync::Arc;
 use std::ptr;
 use crate::pubsub::{TopicRegistry, ByteTopic};
+use crate::uart::MsgType;
 
 pub struct BibiRegistry{
     inner: TopicRegistry,
@@ -43,7 +46,11 @@ pub unsafe extern "C" fn bibi_registry_get_byte_topic(
             Err(_) => return ptr::null_mut(),
         };
 
-        let topic = reg.inner.get_or_create_byte(name_str, capacity);
+        //null if the name is already a typed topic
+        let topic = match reg.inner.try_get_or_create_byte(name_str, capacity){
+            Ok(t) => t,
+            Err(_) => return ptr::null_mut(),
+        };
         let handle = Box::new(BibiByteTopic{ inner: topic });
         Box::into_raw(handle)
     }
@@ -70,10 +77,7 @@ pub unsafe extern "C" fn bibi_byte_topic_publish(
         let t = &*topic;
         let slice = std::slice::from_raw_parts(data, len);
         
-        match t.inner.publish(slice){
-            Some(epoch) => epoch,
-            None => 0,
-        }
+        t.inner.publish(slice).unwrap_or_default()
     }
 }
 
@@ -83,6 +87,18 @@ pub unsafe extern "C" fn bibi_byte_topic_try_receive(
     out_data: *mut u8,
     out_len: *mut usize,
     max_len: usize,
+) -> i32{
+    unsafe{ bibi_byte_topic_try_receive_epoch(topic, out_data, out_len, ptr::null_mut(), max_len) }
+}
+
+//try_receive that also reports the message epoch, e.g. for bibi_byte_topic_ack
+#[no_mangle]
+pub unsafe extern "C" fn bibi_byte_topic_try_receive_epoch(
+    topic: *mut BibiByteTopic,
+    out_data: *mut u8,
+    out_len: *mut usize,
+    out_epoch: *mut u64,
+    max_len: usize,
 ) -> i32{
     if topic.is_null() || out_data.is_null() || out_len.is_null(){
         return -1;
@@ -92,12 +108,15 @@ pub unsafe extern "C" fn bibi_byte_topic_try_receive(
         let t = &*topic;
         
         match t.inner.try_receive(){
-            Some((data, _epoch)) =>{
+            Some((data, epoch)) =>{
                 if data.len() > max_len{
                     return -2;
                 }
                 ptr::copy_nonoverlapping(data.as_ptr(), out_data, data.len());
                 *out_len = data.len();
+                if !out_epoch.is_null(){
+                    *out_epoch = epoch;
+                }
                 1
             }
             None => 0,
@@ -170,17 +189,97 @@ pub unsafe extern "C" fn bibi_byte_topic_latest_epoch(topic: *mut BibiByteTopic)
     }
 }
 
+//consumer confirms it is done with every message up to and including `epoch`
+#[no_mangle]
+pub unsafe extern "C" fn bibi_byte_topic_ack(topic: *mut BibiByteTopic, epoch: u64){
+    if topic.is_null(){
+        return;
+    }
+    unsafe{
+        let t = &*topic;
+        t.inner.ack(epoch);
+    }
+}
+
+//highest epoch any consumer has acked, 0 if none; never decreases
+#[no_mangle]
+pub unsafe extern "C" fn bibi_byte_topic_acked_epoch(topic: *mut BibiByteTopic) -> u64{
+    if topic.is_null(){
+        return 0;
+    }
+    unsafe{
+        let t = &*topic;
+        t.inner.acked_epoch()
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn bibi_byte_topic_capacity(topic: *mut BibiByteTopic) -> usize{
+    if topic.is_null(){
+        return 0;
+    }
+    unsafe{
+        let t = &*topic;
+        t.inner.capacity()
+    }
+}
+
+//unread messages overwritten by the producer since the topic was created
+#[no_mangle]
+pub unsafe extern "C" fn bibi_byte_topic_dropped(topic: *mut BibiByteTopic) -> u64{
+    if topic.is_null(){
+        return 0;
+    }
+    unsafe{
+        let t = &*topic;
+        t.inner.dropped_count()
+    }
+}
+
+//true when the next publish overwrites an unread message
+#[no_mangle]
+pub unsafe extern "C" fn bibi_byte_topic_is_full(topic: *mut BibiByteTopic) -> bool{
+    if topic.is_null(){
+        return false;
+    }
+    unsafe{
+        let t = &*topic;
+        t.inner.is_full()
+    }
+}
+
 pub struct BibiTypedTopic{
     inner: Arc<ByteTopic>,
     msg_size: usize,
+    //set for protocol topics, whose messages can be validated
+    msg_type: Option<MsgType>,
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn bibi_registry_get_typed_topic(
+impl BibiTypedTopic{
+    //protocol topics hold the longest frame of their type, so shorter ones
+    //(a 6 channel thruster frame in an 8 channel slot) fit as well
+    fn fits(&self, len: usize) -> bool{
+        match self.msg_type{
+            Some(msg_type) => msg_type.payload_size().is_some_and(|min| (min..=self.msg_size).contains(&len)),
+            None => len == self.msg_size,
+        }
+    }
+
+    //fill the caller's msg_size buffer, zeroing what a short frame leaves
+    unsafe fn copy_out(&self, data: &[u8], out_data: *mut u8){
+        unsafe{
+            ptr::copy_nonoverlapping(data.as_ptr(), out_data, data.len());
+            ptr::write_bytes(out_data.add(data.len()), 0, self.msg_size - data.len());
+        }
+    }
+}
+
+unsafe fn typed_topic_handle(
     registry: *mut BibiRegistry,
     name: *const c_char,
     capacity: usize,
     msg_size: usize,
+    msg_type: Option<MsgType>,
 ) -> *mut BibiTypedTopic{
     if registry.is_null() || name.is_null(){
         return ptr::null_mut();
@@ -193,12 +292,47 @@ pub unsafe extern "C" fn bibi_registry_get_typed_topic(
             Err(_) => return ptr::null_mut(),
         };
 
-        let topic = reg.inner.get_or_create_byte(name_str, capacity);
-        let handle = Box::new(BibiTypedTopic{ inner: topic, msg_size });
+        let topic = match reg.inner.try_get_or_create_byte(name_str, capacity){
+            Ok(t) => t,
+            Err(_) => return ptr::null_mut(),
+        };
+        let handle = Box::new(BibiTypedTopic{ inner: topic, msg_size, msg_type });
         Box::into_raw(handle)
     }
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn bibi_registry_get_typed_topic(
+    registry: *mut BibiRegistry,
+    name: *const c_char,
+    capacity: usize,
+    msg_size: usize,
+) -> *mut BibiTypedTopic{
+    unsafe{ typed_topic_handle(registry, name, capacity, msg_size, None) }
+}
+
+//typed topic for an STM32 message type (the frame type byte, e.g. 0x03 PWM):
+//the size comes from the protocol, and try_receive_validated can check
+//values. null for unknown types or types without a fixed layout.
+//messages are the longest frame the type can carry (32 bytes for 8 PWM
+//channels): publish reads that many, and shorter frames from other
+//publishers are received zero-padded
+#[no_mangle]
+pub unsafe extern "C" fn bibi_registry_get_protocol_topic(
+    registry: *mut BibiRegistry,
+    name: *const c_char,
+    capacity: usize,
+    msg_type: u8,
+) -> *mut BibiTypedTopic{
+    let Some(msg_type) = MsgType::from_u8(msg_type) else{
+        return ptr::null_mut();
+    };
+    let Some(msg_size) = msg_type.max_payload_size() else{
+        return ptr::null_mut();
+    };
+    unsafe{ typed_topic_handle(registry, name, capacity, msg_size, Some(msg_type)) }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn bibi_typed_topic_free(topic: *mut BibiTypedTopic){
     if !topic.is_null(){
@@ -219,10 +353,7 @@ pub unsafe extern "C" fn bibi_typed_topic_publish(
         let t = &*topic;
         let slice = std::slice::from_raw_parts(data, t.msg_size);
         
-        match t.inner.publish(slice){
-            Some(epoch) => epoch,
-            None => 0,
-        }
+        t.inner.publish(slice).unwrap_or_default()
     }
 }
 
@@ -240,10 +371,10 @@ pub unsafe extern "C" fn bibi_typed_topic_try_receive(
         
         match t.inner.try_receive(){
             Some((data, _epoch)) =>{
-                if data.len() != t.msg_size{
+                if !t.fits(data.len()){
                     return -2;
                 }
-                ptr::copy_nonoverlapping(data.as_ptr(), out_data, t.msg_size);
+                t.copy_out(&data, out_data);
                 1
             }
             None => 0,
@@ -251,6 +382,40 @@ pub unsafe extern "C" fn bibi_typed_topic_try_receive(
     }
 }
 
+//like try_receive, plus the protocol's value checks on protocol topics.
+//returns 1 ok, 0 empty, -1 null argument, -2 wrong size, -3 failed
+//validation; on -2/-3 the message is consumed, out_data left untouched and
+//out_epoch (if given) names the bad message
+#[no_mangle]
+pub unsafe extern "C" fn bibi_typed_topic_try_receive_validated(
+    topic: *mut BibiTypedTopic,
+    out_data: *mut u8,
+    out_epoch: *mut u64,
+) -> i32{
+    if topic.is_null() || out_data.is_null(){
+        return -1;
+    }
+
+    unsafe{
+        let t = &*topic;
+
+        let Some((data, epoch)) = t.inner.try_receive() else{
+            return 0;
+        };
+        if !out_epoch.is_null(){
+            *out_epoch = epoch;
+        }
+        if !t.fits(data.len()){
+            return -2;
+        }
+        if t.msg_type.is_some_and(|msg_type| !msg_type.validate_payload(&data)){
+            return -3;
+        }
+        t.copy_out(&data, out_data);
+        1
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn bibi_typed_topic_peek_latest(
     topic: *mut BibiTypedTopic,
@@ -266,10 +431,10 @@ pub unsafe extern "C" fn bibi_typed_topic_peek_latest(
         
         match t.inner.peek_latest(){
             Some((data, epoch)) =>{
-                if data.len() != t.msg_size{
+                if !t.fits(data.len()){
                     return -2;
                 }
-                ptr::copy_nonoverlapping(data.as_ptr(), out_data, t.msg_size);
+                t.copy_out(&data, out_data);
                 if !out_epoch.is_null(){
                     *out_epoch = epoch;
                 }
@@ -362,6 +527,74 @@ mod tests{
         }
     }
 
+    #[test]
+    fn test_ffi_validated_receive_rejects_out_of_range_pwm(){
+        use crate::uart::{ThrusterPwmCmd, MsgType, Endianness, PWM_MAX};
+
+        let registry = bibi_registry_new();
+        let name = CString::new("/thrusters").unwrap();
+
+        unsafe{
+            assert!(bibi_registry_get_protocol_topic(registry, name.as_ptr(), 8, 0x7F).is_null());
+            let topic = bibi_registry_get_protocol_topic(registry, name.as_ptr(), 8, MsgType::Thruster as u8);
+            assert!(!topic.is_null());
+
+            let good = ThrusterPwmCmd::channels_to_bytes(&[1500, 1600, 1400, 1500, 1000, 2000, 1550, 1450], Endianness::Little);
+            let bad = ThrusterPwmCmd::channels_to_bytes(&[1500, 1500, 1500, 1500, 1500, 1500, 1500, PWM_MAX + 500], Endianness::Little);
+            bibi_typed_topic_publish(topic, good.as_ptr());
+            bibi_typed_topic_publish(topic, bad.as_ptr());
+
+            let mut out = [0u8; 32];
+            let mut epoch = 0u64;
+            assert_eq!(bibi_typed_topic_try_receive_validated(topic, out.as_mut_ptr(), &mut epoch), 1);
+            assert_eq!(epoch, 1);
+            assert_eq!(out.as_slice(), good.as_slice());
+
+            let mut untouched = [0u8; 32];
+            assert_eq!(bibi_typed_topic_try_receive_validated(topic, untouched.as_mut_ptr(), &mut epoch), -3);
+            assert_eq!(epoch, 2);
+            assert_eq!(untouched, [0u8; 32]);
+
+            assert_eq!(bibi_typed_topic_try_receive_validated(topic, out.as_mut_ptr(), ptr::null_mut()), 0);
+            assert_eq!(bibi_typed_topic_try_receive_validated(ptr::null_mut(), out.as_mut_ptr(), ptr::null_mut()), -1);
+
+            bibi_typed_topic_free(topic);
+            bibi_registry_free(registry);
+        }
+    }
+
+    #[test]
+    fn test_ffi_protocol_topic_takes_every_thruster_frame_size(){
+        use crate::uart::{ThrusterPwmCmd, MsgType, Endianness};
+
+        let registry = bibi_registry_new();
+        let name = CString::new("/thrusters").unwrap();
+
+        unsafe{
+            let topic = bibi_registry_get_protocol_topic(registry, name.as_ptr(), 8, MsgType::Thruster as u8);
+            let bytes = bibi_registry_get_byte_topic(registry, name.as_ptr(), 8);
+
+            //6 and 8 channel frames as the bridge publishes them
+            let six = ThrusterPwmCmd::new([1500, 1600, 1400, 1500, 1000, 2000]).to_bytes();
+            let eight = ThrusterPwmCmd::channels_to_bytes(&[1700; 8], Endianness::Little);
+            bibi_byte_topic_publish(bytes, six.as_ptr(), six.len());
+            bibi_byte_topic_publish(bytes, eight.as_ptr(), eight.len());
+            bibi_byte_topic_publish(bytes, [0u8; 36].as_ptr(), 36);
+
+            let mut out = [0xFFu8; 32];
+            assert_eq!(bibi_typed_topic_try_receive_validated(topic, out.as_mut_ptr(), ptr::null_mut()), 1);
+            assert_eq!(&out[..24], six.as_slice());
+            assert_eq!(out[24..], [0u8; 8]);
+            assert_eq!(bibi_typed_topic_try_receive(topic, out.as_mut_ptr()), 1);
+            assert_eq!(out.as_slice(), eight.as_slice());
+            assert_eq!(bibi_typed_topic_try_receive(topic, out.as_mut_ptr()), -2);
+
+            bibi_byte_topic_free(bytes);
+            bibi_typed_topic_free(topic);
+            bibi_registry_free(registry);
+        }
+    }
+
     #[test]
     fn test_ffi_shared_topic(){
         let registry = bibi_registry_new();
@@ -386,4 +619,73 @@ mod tests{
             bibi_registry_free(registry);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_ffi_ack_watermark(){
+        let registry = bibi_registry_new();
+        let name = CString::new("/acked").unwrap();
+
+        unsafe{
+            let producer = bibi_registry_get_byte_topic(registry, name.as_ptr(), 8);
+            let consumer = bibi_registry_get_byte_topic(registry, name.as_ptr(), 8);
+            assert_eq!(bibi_byte_topic_acked_epoch(producer), 0);
+
+            let data: [u8; 1] = [7];
+            bibi_byte_topic_publish(producer, data.as_ptr(), 1);
+            bibi_byte_topic_publish(producer, data.as_ptr(), 1);
+
+            let mut out_data: [u8; 256] = [0; 256];
+            let mut out_len: usize = 0;
+            let mut epoch: u64 = 0;
+            let result = bibi_byte_topic_try_receive_epoch(consumer, out_data.as_mut_ptr(), &mut out_len, &mut epoch, 256);
+            assert_eq!((result, epoch), (1, 1));
+            bibi_byte_topic_ack(consumer, epoch);
+            assert_eq!(bibi_byte_topic_acked_epoch(producer), 1);
+
+            bibi_byte_topic_try_receive_epoch(consumer, out_data.as_mut_ptr(), &mut out_len, &mut epoch, 256);
+            bibi_byte_topic_ack(consumer, epoch);
+            bibi_byte_topic_ack(consumer, 1);
+            assert_eq!(bibi_byte_topic_acked_epoch(producer), 2);
+
+            assert_eq!(bibi_byte_topic_acked_epoch(ptr::null_mut()), 0);
+            bibi_byte_topic_ack(ptr::null_mut(), 5);
+
+            bibi_byte_topic_free(producer);
+            bibi_byte_topic_free(consumer);
+            bibi_registry_free(registry);
+        }
+    }
+
+    #[test]
+    fn test_ffi_overflow_metrics(){
+        let registry = bibi_registry_new();
+        let name = CString::new("/small").unwrap();
+
+        unsafe{
+            let topic = bibi_registry_get_byte_topic(registry, name.as_ptr(), 4);
+            assert_eq!(bibi_byte_topic_capacity(topic), 4);
+            assert!(!bibi_byte_topic_is_full(topic));
+
+            let data: [u8; 2] = [1, 2];
+            for _ in 0..4{
+                bibi_byte_topic_publish(topic, data.as_ptr(), 2);
+            }
+            assert!(bibi_byte_topic_is_full(topic));
+            assert_eq!(bibi_byte_topic_dropped(topic), 0);
+
+            for _ in 0..3{
+                bibi_byte_topic_publish(topic, data.as_ptr(), 2);
+            }
+            assert!(bibi_byte_topic_is_full(topic));
+            assert_eq!(bibi_byte_topic_dropped(topic), 3);
+            assert_eq!(bibi_byte_topic_len(topic), 4);
+
+            assert_eq!(bibi_byte_topic_capacity(ptr::null_mut()), 0);
+            assert_eq!(bibi_byte_topic_dropped(ptr::null_mut()), 0);
+            assert!(!bibi_byte_topic_is_full(ptr::null_mut()));
+
+            bibi_byte_topic_free(topic);
+            bibi_registry_free(registry);
+        }
+    }
+}
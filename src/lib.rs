@@ -3,22 +3,25 @@ pub mod pubsub;
 pub mod ffi;
 pub mod uart;
 pub mod auv;
+mod poison;
 
 #[cfg(feature = "python")]
 pub mod python;
 
-pub use ring_buffer::RingBuffer;
-pub use ring_buffer::byte_buffer::{ByteRingBuffer, ByteSlot, SLOT_SIZE, MAX_PAYLOAD_SIZE};
+pub use ring_buffer::{RingBuffer, ArenaByteBuffer, BufferMetrics, WaitStrategy};
+pub use ring_buffer::byte_buffer::{ByteRingBuffer, ByteSlot, ReadGuard, SlotError, SLOT_SIZE, MAX_PAYLOAD_SIZE, PRIORITY_LANES};
 
 pub use pubsub::{
-    Message, Topic, ByteTopic,
-    Publisher, BytePublisher,
-    Subscriber, ByteSubscriber,
-    TopicRegistry,
+    Message, Topic, ByteTopic, PublishError, PublishSummary,
+    Publisher, BytePublisher, FixedSizePublisher, FixedSizeError,
+    Subscriber, ByteSubscriber, TypedSubscriber, LatestSubscriber,
+    TopicRegistry, TopicKind, RegistryError, Selector, SelectEvent,
+    LogWriter, LogReader, LogRecord, MergeReader, Combiner, TypedView,
 };
 
 pub use uart::{
-    UartBridge, MsgType, 
+    UartBridge, UartFrame, FrameCodec, FrameDecoder, ChecksumCoverage, ChecksumKind, Endianness, Preamble, MsgType, UnknownMsgType, Transport, LoopbackTransport, RetryPolicy, FromFrame, ToFrame,
+    encode_frame, decode_frame, find_frame_start, frame_is_complete,
     ImuMsg, OrientationMsg, DepthMsg, 
-    ThrusterPwmCmd, LedCmd, CalibrationCmd,
+    ThrusterPwmCmd, ThrusterPwmBuilder, Thruster, ChannelLayout, LedCmd, CalibrationCmd,
 };
\ No newline at end of file
@@ -0,0 +1,31 @@
+use std::sync::{Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+//a thread that panics while holding one of our locks poisons it, and with
+//plain unwrap() every later access would panic too: one bad decoder or gap
+//callback would take down the whole controller. the state behind these locks
+//is only changed by single assignments or map inserts, so it is consistent
+//even if the holder died, and the guard is simply taken back
+pub(crate) trait MutexExt<T>{
+    fn lock_unpoisoned(&self) -> MutexGuard<'_, T>;
+}
+
+impl<T> MutexExt<T> for Mutex<T>{
+    fn lock_unpoisoned(&self) -> MutexGuard<'_, T>{
+        self.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+pub(crate) trait RwLockExt<T>{
+    fn read_unpoisoned(&self) -> RwLockReadGuard<'_, T>;
+    fn write_unpoisoned(&self) -> RwLockWriteGuard<'_, T>;
+}
+
+impl<T> RwLockExt<T> for RwLock<T>{
+    fn read_unpoisoned(&self) -> RwLockReadGuard<'_, T>{
+        self.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write_unpoisoned(&self) -> RwLockWriteGuard<'_, T>{
+        self.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -0,0 +1,447 @@
+//binary topic log, readable without this crate. all integers little-endian.
+//
+//header:
+//  magic       8 bytes  "BIBILOG\0"
+//  version     u16      LOG_VERSION
+//  topic_count u16
+//  topic_count times:
+//    topic_id  u16      index used by records
+//    name_len  u16
+//    name      name_len bytes, utf-8
+//
+//records, until end of file:
+//  topic_id    u16
+//  timestamp   u64      publish time, microseconds since the unix epoch
+//  epoch       u64      topic epoch of the message
+//  len         u32      at most MAX_RECORD_PAYLOAD
+//  payload     len bytes
+//
+//readers must reject versions newer than they know.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::ring_buffer::byte_buffer::MAX_PAYLOAD_SIZE;
+use super::subscriber::ByteSubscriber;
+use super::topic::ByteTopic;
+
+pub const LOG_MAGIC: [u8; 8] = *b"BIBILOG\0";
+pub const LOG_VERSION: u16 = 1;
+//records hold one topic message, so nothing longer than a slot is legitimate
+pub const MAX_RECORD_PAYLOAD: usize = MAX_PAYLOAD_SIZE;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogRecord{
+    pub topic: String,
+    pub timestamp_us: u64,
+    pub epoch: u64,
+    pub payload: Vec<u8>,
+}
+
+pub struct LogWriter<W: Write>{
+    out: W,
+    subscribers: Vec<ByteSubscriber>,
+}
+
+impl<W: Write> LogWriter<W>{
+    pub fn new(mut out: W, topics: &[Arc<ByteTopic>]) -> io::Result<Self>{
+        if topics.len() > u16::MAX as usize{
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "too many topics for one log"));
+        }
+
+        out.write_all(&LOG_MAGIC)?;
+        out.write_all(&LOG_VERSION.to_le_bytes())?;
+        out.write_all(&(topics.len() as u16).to_le_bytes())?;
+        for (id, topic) in topics.iter().enumerate(){
+            let name = topic.name().as_bytes();
+            if name.len() > u16::MAX as usize{
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "topic name too long"));
+            }
+            out.write_all(&(id as u16).to_le_bytes())?;
+            out.write_all(&(name.len() as u16).to_le_bytes())?;
+            out.write_all(name)?;
+        }
+
+        //stamping starts before the cursors, so everything logged has a time
+        for topic in topics{
+            topic.record_publish_times();
+        }
+        Ok(LogWriter{
+            out,
+            subscribers: topics.iter().map(|t| ByteSubscriber::new(Arc::clone(t))).collect(),
+        })
+    }
+
+    //write everything published since the last poll, with its publish time,
+    //interleaved across topics in timestamp order. reads through the log's
+    //own cursors, so other consumers lose nothing
+    pub fn poll(&mut self) -> io::Result<usize>{
+        let mut pending = Vec::new();
+        for (id, subscriber) in self.subscribers.iter().enumerate(){
+            while let Some((payload, epoch, published)) = subscriber.try_next_stamped(){
+                //a push that raced record_publish_times has no stamp
+                let timestamp_us = match published{
+                    0 => SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_micros() as u64),
+                    t => t,
+                };
+                pending.push((timestamp_us, id as u16, epoch, payload));
+            }
+        }
+
+        //stable, so equal stamps keep topic order and each topic its epoch order
+        pending.sort_by_key(|&(timestamp_us, id, _, _)| (timestamp_us, id));
+        for (timestamp_us, id, epoch, payload) in &pending{
+            write_record(&mut self.out, *id, *timestamp_us, *epoch, payload)?;
+        }
+        Ok(pending.len())
+    }
+
+    pub fn finish(mut self) -> io::Result<W>{
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+fn write_record<W: Write>(out: &mut W, topic_id: u16, timestamp_us: u64, epoch: u64, payload: &[u8]) -> io::Result<()>{
+    out.write_all(&topic_id.to_le_bytes())?;
+    out.write_all(&timestamp_us.to_le_bytes())?;
+    out.write_all(&epoch.to_le_bytes())?;
+    out.write_all(&(payload.len() as u32).to_le_bytes())?;
+    out.write_all(payload)
+}
+
+pub struct LogReader<R: Read>{
+    input: R,
+    version: u16,
+    topics: Vec<String>,
+}
+
+fn invalid(msg: &str) -> io::Error{
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn read_u16<R: Read>(r: &mut R) -> io::Result<u16>{
+    let mut b = [0u8; 2];
+    r.read_exact(&mut b)?;
+    Ok(u16::from_le_bytes(b))
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32>{
+    let mut b = [0u8; 4];
+    r.read_exact(&mut b)?;
+    Ok(u32::from_le_bytes(b))
+}
+
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64>{
+    let mut b = [0u8; 8];
+    r.read_exact(&mut b)?;
+    Ok(u64::from_le_bytes(b))
+}
+
+impl<R: Read> LogReader<R>{
+    pub fn new(mut input: R) -> io::Result<Self>{
+        let mut magic = [0u8; 8];
+        input.read_exact(&mut magic)?;
+        if magic != LOG_MAGIC{
+            return Err(invalid("not a bibi-sync log"));
+        }
+
+        let version = read_u16(&mut input)?;
+        if version == 0 || version > LOG_VERSION{
+            return Err(invalid(&format!("unsupported log version {}", version)));
+        }
+
+        let count = read_u16(&mut input)? as usize;
+        let mut topics = vec![String::new(); count];
+        for _ in 0..count{
+            let id = read_u16(&mut input)? as usize;
+            let len = read_u16(&mut input)? as usize;
+            let mut name = vec![0u8; len];
+            input.read_exact(&mut name)?;
+            let name = String::from_utf8(name).map_err(|_| invalid("topic name is not utf-8"))?;
+            match topics.get_mut(id){
+                Some(slot) => *slot = name,
+                None => return Err(invalid("topic id out of range")),
+            }
+        }
+
+        Ok(LogReader{ input, version, topics })
+    }
+
+    pub fn version(&self) -> u16{
+        self.version
+    }
+
+    pub fn topics(&self) -> &[String]{
+        &self.topics
+    }
+
+    fn read_record(&mut self) -> io::Result<Option<LogRecord>>{
+        //a clean end of file is only allowed between records
+        let mut id = [0u8; 2];
+        match self.input.read(&mut id[..1])?{
+            0 => return Ok(None),
+            _ => self.input.read_exact(&mut id[1..])?,
+        }
+        let id = u16::from_le_bytes(id) as usize;
+
+        let timestamp_us = read_u64(&mut self.input)?;
+        let epoch = read_u64(&mut self.input)?;
+        let len = read_u32(&mut self.input)? as usize;
+        //checked before allocating, so a corrupt length can't ask for gigabytes
+        if len > MAX_RECORD_PAYLOAD{
+            return Err(invalid(&format!("record of {} bytes is over the {} byte limit", len, MAX_RECORD_PAYLOAD)));
+        }
+        let mut payload = vec![0u8; len];
+        self.input.read_exact(&mut payload)?;
+
+        let topic = self.topics.get(id).cloned().ok_or_else(|| invalid("record for unknown topic id"))?;
+        Ok(Some(LogRecord{ topic, timestamp_us, epoch, payload }))
+    }
+}
+
+impl<R: Read> Iterator for LogReader<R>{
+    type Item = io::Result<LogRecord>;
+
+    fn next(&mut self) -> Option<Self::Item>{
+        self.read_record().transpose()
+    }
+}
+
+//k-way merge of several logs by timestamp. each reader keeps at most one record
+//in the heap, so equal timestamps come out in reader order and records from the
+//same log keep their file order.
+pub struct MergeReader<R: Read>{
+    readers: Vec<LogReader<R>>,
+    heads: Vec<Option<LogRecord>>,
+    heap: BinaryHeap<Reverse<(u64, usize)>>,
+    //a refill that failed after its reader's record was taken, reported on
+    //the following call so that record still comes out
+    pending_err: Option<io::Error>,
+}
+
+impl<R: Read> MergeReader<R>{
+    pub fn new(readers: Vec<LogReader<R>>) -> io::Result<Self>{
+        let mut merge = MergeReader{
+            heads: (0..readers.len()).map(|_| None).collect(),
+            heap: BinaryHeap::with_capacity(readers.len()),
+            pending_err: None,
+            readers,
+        };
+        for idx in 0..merge.readers.len(){
+            merge.refill(idx)?;
+        }
+        Ok(merge)
+    }
+
+    pub fn len(&self) -> usize{
+        self.readers.len()
+    }
+
+    pub fn is_empty(&self) -> bool{
+        self.readers.is_empty()
+    }
+
+    fn refill(&mut self, idx: usize) -> io::Result<()>{
+        if let Some(record) = self.readers[idx].read_record()?{
+            self.heap.push(Reverse((record.timestamp_us, idx)));
+            self.heads[idx] = Some(record);
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for MergeReader<R>{
+    type Item = io::Result<LogRecord>;
+
+    fn next(&mut self) -> Option<Self::Item>{
+        if let Some(e) = self.pending_err.take(){
+            return Some(Err(e));
+        }
+        let Reverse((_, idx)) = self.heap.pop()?;
+        let record = self.heads[idx].take().expect("heap entry without a head record");
+        //a broken log stops contributing but the others keep merging
+        if let Err(e) = self.refill(idx){
+            self.pending_err = Some(e);
+        }
+        Some(Ok(record))
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use std::time::Duration;
+
+    fn now_us() -> u64{
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_micros() as u64
+    }
+
+    #[test]
+    fn test_log_round_trip_two_topics(){
+        let imu = Arc::new(ByteTopic::new("/imu", 16));
+        let depth = Arc::new(ByteTopic::new("/depth", 16));
+        let mut writer = LogWriter::new(Vec::new(), &[Arc::clone(&imu), Arc::clone(&depth)]).unwrap();
+        let start = now_us();
+
+        imu.publish(&[1, 1]);
+        depth.publish(&[9]);
+        assert_eq!(writer.poll().unwrap(), 2);
+
+        depth.publish(&[8]);
+        assert_eq!(writer.poll().unwrap(), 1);
+
+        imu.publish(&[2, 2]);
+        imu.publish(&[3, 3]);
+        assert_eq!(writer.poll().unwrap(), 2);
+        assert_eq!(writer.poll().unwrap(), 0);
+        let end = now_us();
+
+        let bytes = writer.finish().unwrap();
+        let reader = LogReader::new(&bytes[..]).unwrap();
+        assert_eq!(reader.version(), LOG_VERSION);
+        assert_eq!(reader.topics(), &["/imu".to_string(), "/depth".to_string()]);
+
+        let records: Vec<LogRecord> = reader.map(|r| r.unwrap()).collect();
+        let summary: Vec<(&str, u64, &[u8])> = records.iter()
+            .map(|r| (r.topic.as_str(), r.epoch, r.payload.as_slice()))
+            .collect();
+        assert_eq!(summary, vec![
+            ("/imu", 1, &[1, 1][..]),
+            ("/depth", 1, &[9][..]),
+            ("/depth", 2, &[8][..]),
+            ("/imu", 2, &[2, 2][..]),
+            ("/imu", 3, &[3, 3][..]),
+        ]);
+        //published in file order here, so the stamps never go backwards
+        assert!(records.windows(2).all(|w| w[0].timestamp_us <= w[1].timestamp_us));
+        assert!(records.iter().all(|r| (start..=end).contains(&r.timestamp_us)));
+    }
+
+    #[test]
+    fn test_single_poll_interleaves_topics_by_publish_time(){
+        let imu = Arc::new(ByteTopic::new("/imu", 16));
+        let depth = Arc::new(ByteTopic::new("/depth", 16));
+        let mut writer = LogWriter::new(Vec::new(), &[Arc::clone(&imu), Arc::clone(&depth)]).unwrap();
+
+        //all published before the one poll; the sleeps keep the stamps apart
+        for (topic, byte) in [(&imu, 1), (&depth, 9), (&depth, 8), (&imu, 2), (&depth, 7)]{
+            topic.publish(&[byte]);
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        assert_eq!(writer.poll().unwrap(), 5);
+
+        let bytes = writer.finish().unwrap();
+        let records: Vec<LogRecord> = LogReader::new(&bytes[..]).unwrap().map(|r| r.unwrap()).collect();
+        let order: Vec<(&str, u8)> = records.iter().map(|r| (r.topic.as_str(), r.payload[0])).collect();
+        assert_eq!(order, vec![("/imu", 1), ("/depth", 9), ("/depth", 8), ("/imu", 2), ("/depth", 7)]);
+        assert!(records.windows(2).all(|w| w[0].timestamp_us < w[1].timestamp_us));
+    }
+
+    #[test]
+    fn test_log_stamps_publish_time_and_leaves_messages_for_others(){
+        let depth = Arc::new(ByteTopic::new("/depth", 8));
+        let mut writer = LogWriter::new(Vec::new(), &[Arc::clone(&depth)]).unwrap();
+
+        depth.publish(&[7]);
+        let published_by = now_us();
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(writer.poll().unwrap(), 1);
+        assert_eq!(writer.poll().unwrap(), 0);
+
+        //the controller reading the same topic still gets it
+        assert_eq!(depth.try_receive(), Some((vec![7], 1)));
+
+        let bytes = writer.finish().unwrap();
+        let record = LogReader::new(&bytes[..]).unwrap().next().unwrap().unwrap();
+        assert_eq!(record.payload, vec![7]);
+        assert!(record.timestamp_us <= published_by, "stamped {} after publish {}", record.timestamp_us, published_by);
+    }
+
+    #[test]
+    fn test_log_reader_rejects_bad_input(){
+        assert_eq!(LogReader::new(&b"NOTALOG\0\x01\x00\x00\x00"[..]).err().unwrap().kind(), io::ErrorKind::InvalidData);
+
+        let mut future = LOG_MAGIC.to_vec();
+        future.extend((LOG_VERSION + 1).to_le_bytes());
+        future.extend(0u16.to_le_bytes());
+        assert_eq!(LogReader::new(&future[..]).err().unwrap().kind(), io::ErrorKind::InvalidData);
+
+        //truncated mid-record
+        let topic = Arc::new(ByteTopic::new("/t", 4));
+        let mut writer = LogWriter::new(Vec::new(), &[Arc::clone(&topic)]).unwrap();
+        topic.publish(&[1, 2, 3]);
+        writer.poll().unwrap();
+        let bytes = writer.finish().unwrap();
+        let mut reader = LogReader::new(&bytes[..bytes.len() - 1]).unwrap();
+        assert_eq!(reader.next().unwrap().unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+
+        //a corrupt length is refused instead of allocated
+        let mut huge = LogWriter::new(Vec::new(), &[topic]).unwrap().finish().unwrap();
+        huge.extend(0u16.to_le_bytes());
+        huge.extend(1u64.to_le_bytes());
+        huge.extend(1u64.to_le_bytes());
+        huge.extend(u32::MAX.to_le_bytes());
+        let mut reader = LogReader::new(&huge[..]).unwrap();
+        assert_eq!(reader.next().unwrap().unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    //one-topic log with records at the given timestamps
+    fn log_at(name: &str, timestamps: &[u64]) -> Vec<u8>{
+        let topic = Arc::new(ByteTopic::new(name, 16));
+        let mut bytes = LogWriter::new(Vec::new(), &[topic]).unwrap().finish().unwrap();
+        for (i, &ts) in timestamps.iter().enumerate(){
+            write_record(&mut bytes, 0, ts, i as u64 + 1, &[ts as u8]).unwrap();
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_merge_reader_interleaves_by_timestamp(){
+        let imu_bytes = log_at("/imu", &[100, 300, 500, 700]);
+        let depth_bytes = log_at("/depth", &[50, 300, 400, 900]);
+        let merge = MergeReader::new(vec![
+            LogReader::new(&imu_bytes[..]).unwrap(),
+            LogReader::new(&depth_bytes[..]).unwrap(),
+        ]).unwrap();
+        assert_eq!(merge.len(), 2);
+
+        let order: Vec<(String, u64)> = merge.map(|r| r.unwrap()).map(|r| (r.topic, r.timestamp_us)).collect();
+        assert_eq!(order, vec![
+            ("/depth".to_string(), 50),
+            ("/imu".to_string(), 100),
+            //tie goes to the first reader
+            ("/imu".to_string(), 300),
+            ("/depth".to_string(), 300),
+            ("/depth".to_string(), 400),
+            ("/imu".to_string(), 500),
+            ("/imu".to_string(), 700),
+            ("/depth".to_string(), 900),
+        ]);
+    }
+
+    #[test]
+    fn test_merge_reader_yields_the_record_before_a_truncated_tail(){
+        let imu_bytes = log_at("/imu", &[100, 300, 500]);
+        let depth_bytes = log_at("/depth", &[50, 300, 400]);
+        let merge = MergeReader::new(vec![
+            LogReader::new(&imu_bytes[..]).unwrap(),
+            LogReader::new(&depth_bytes[..depth_bytes.len() - 1]).unwrap(),
+        ]).unwrap();
+
+        let results: Vec<Result<(String, u64), io::ErrorKind>> = merge
+            .map(|r| r.map(|r| (r.topic, r.timestamp_us)).map_err(|e| e.kind()))
+            .collect();
+        assert_eq!(results, vec![
+            Ok(("/depth".to_string(), 50)),
+            Ok(("/imu".to_string(), 100)),
+            Ok(("/imu".to_string(), 300)),
+            //taken before the refill hit the cut, still delivered
+            Ok(("/depth".to_string(), 300)),
+            Err(io::ErrorKind::UnexpectedEof),
+            Ok(("/imu".to_string(), 500)),
+        ]);
+    }
+}
@@ -0,0 +1,144 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use super::registry::TopicRegistry;
+use super::selector::Selector;
+use super::subscriber::ByteSubscriber;
+use super::topic::ByteTopic;
+
+//how often a started combiner rechecks its running flag while inputs are quiet
+const IDLE_POLL: Duration = Duration::from_millis(50);
+
+//fuse gets the latest payload of every input, in add_input order, None for
+//inputs that have not published yet. returning None publishes nothing.
+pub type FuseFn = Box<dyn Fn(&[Option<&[u8]>]) -> Option<Vec<u8>> + Send>;
+
+//fan-in: several byte topics of one registry fused into one output topic.
+//fuse runs once per input message, so the output follows every update.
+//the combiner consumes its inputs like any other ByteSubscriber.
+pub struct Combiner{
+    selector: Selector,
+    latest: Vec<Option<Vec<u8>>>,
+    output: Arc<ByteTopic>,
+    fuse: FuseFn,
+    running: Arc<AtomicBool>,
+}
+
+impl Combiner{
+    pub fn new<F>(registry: &TopicRegistry, output: Arc<ByteTopic>, fuse: F) -> Self
+    where F: Fn(&[Option<&[u8]>]) -> Option<Vec<u8>> + Send + 'static{
+        Combiner{
+            selector: registry.selector(),
+            latest: Vec::new(),
+            output,
+            fuse: Box::new(fuse),
+            running: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    //panics like Selector::add if the topic is from another registry
+    pub fn add_input(&mut self, topic: Arc<ByteTopic>) -> usize{
+        assert!(!Arc::ptr_eq(&topic, &self.output), "combiner output '{}' can't also be an input bruddaa!!", topic.name());
+        self.latest.push(None);
+        self.selector.add(ByteSubscriber::new(topic))
+    }
+
+    pub fn output(&self) -> &Arc<ByteTopic>{
+        &self.output
+    }
+
+    //wait up to `timeout` for input, then fuse everything pending;
+    //returns how many fused messages were published
+    pub fn spin_once(&mut self, timeout: Duration) -> usize{
+        let mut published = 0;
+        let mut event = self.selector.select_timeout(timeout);
+        while let Some(ready) = event{
+            while let Some((payload, _)) = self.selector.subscriber(ready.index).try_recv(){
+                self.latest[ready.index] = Some(payload);
+                let inputs: Vec<Option<&[u8]>> = self.latest.iter().map(|p| p.as_deref()).collect();
+                if let Some(fused) = (self.fuse)(&inputs){
+                    self.output.publish(&fused);
+                    published += 1;
+                }
+            }
+            event = self.selector.try_select();
+        }
+        published
+    }
+
+    //run on its own thread until the returned flag is cleared
+    pub fn start(mut self) -> (JoinHandle<()>, Arc<AtomicBool>){
+        let running = Arc::clone(&self.running);
+        self.running.store(true, Ordering::SeqCst);
+
+        let handle = thread::spawn(move ||{
+            while self.running.load(Ordering::SeqCst){
+                self.spin_once(IDLE_POLL);
+            }
+        });
+
+        (handle, running)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::ring_buffer::WaitStrategy;
+
+    fn sum(inputs: &[Option<&[u8]>]) -> Option<Vec<u8>>{
+        let total: u32 = inputs.iter().flatten().map(|p| p[0] as u32).sum();
+        Some(vec![total as u8])
+    }
+
+    #[test]
+    fn test_combiner_sums_inputs_on_each_update(){
+        let registry = TopicRegistry::new();
+        let a = registry.get_or_create_byte("/a", 8);
+        let b = registry.get_or_create_byte("/b", 8);
+        let fused = registry.get_or_create_byte("/fused", 8);
+
+        let mut combiner = Combiner::new(&registry, Arc::clone(&fused), sum);
+        combiner.add_input(Arc::clone(&a));
+        combiner.add_input(Arc::clone(&b));
+        let (handle, running) = combiner.start();
+
+        let next = ||{
+            let (data, _) = fused.recv_timeout(Duration::from_secs(5), WaitStrategy::Park).expect("no fused output");
+            data[0]
+        };
+        a.publish(&[3]);
+        assert_eq!(next(), 3);
+        b.publish(&[4]);
+        assert_eq!(next(), 7);
+        a.publish(&[10]);
+        assert_eq!(next(), 14);
+
+        running.store(false, Ordering::SeqCst);
+        handle.join().unwrap();
+        assert_eq!(fused.len(), 0);
+    }
+
+    #[test]
+    fn test_fuse_can_hold_back_output(){
+        let registry = TopicRegistry::new();
+        let a = registry.get_or_create_byte("/a", 8);
+        let b = registry.get_or_create_byte("/b", 8);
+        let fused = registry.get_or_create_byte("/fused", 8);
+
+        //only publish once both inputs are known
+        let mut combiner = Combiner::new(&registry, Arc::clone(&fused), |inputs|{
+            if inputs.iter().all(Option::is_some){ sum(inputs) }else{ None }
+        });
+        combiner.add_input(Arc::clone(&a));
+        combiner.add_input(Arc::clone(&b));
+
+        a.publish(&[1]);
+        a.publish(&[2]);
+        assert_eq!(combiner.spin_once(Duration::from_millis(10)), 0);
+        b.publish(&[5]);
+        assert_eq!(combiner.spin_once(Duration::from_millis(10)), 1);
+        assert_eq!(fused.peek_latest().unwrap().0, vec![7]);
+    }
+}
@@ -1,7 +1,8 @@
-pub trait Message: Clone + Default + Send + 'static{}
+//Sync because topics hand out &T to any thread (peek_latest_ref, with_latest)
+pub trait Message: Clone + Default + Send + Sync + 'static{}
 
 //blanket impl for all types that meet constraints
-impl<T: Clone + Default + Send + 'static> Message for T{}
+impl<T: Clone + Default + Send + Sync + 'static> Message for T{}
 
 #[cfg(test)]
 mod tests{
@@ -20,6 +21,8 @@ mod tests{
         accepts_message(0i32);
         accepts_message(0.0f64);
         accepts_message(true);
-        accepts_message(TestMsg{ x: 1.0, y: 2.0 });
+        let msg = TestMsg{ x: 1.0, y: 2.0 };
+        assert_eq!((msg.x, msg.y), (1.0, 2.0));
+        accepts_message(msg);
     }
 }
\ No newline at end of file
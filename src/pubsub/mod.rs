@@ -1,14 +1,22 @@
+pub mod binlog;
+pub mod combiner;
 pub mod message;
 pub mod topic;
 pub mod publisher;
 pub mod subscriber;
 pub mod registry;
+pub mod selector;
+pub mod typed_view;
 
+pub use binlog::{LogWriter, LogReader, LogRecord, MergeReader};
+pub use combiner::{Combiner, FuseFn};
 pub use message::Message;
-pub use topic::{Topic, ByteTopic};
-pub use publisher::{Publisher, BytePublisher};
-pub use subscriber::{Subscriber, ByteSubscriber};
-pub use registry::TopicRegistry;
+pub use topic::{Topic, ByteTopic, PublishError, PublishSummary};
+pub use publisher::{Publisher, BytePublisher, FixedSizePublisher, FixedSizeError};
+pub use subscriber::{Subscriber, ByteSubscriber, TypedSubscriber, LatestSubscriber, GapCallback};
+pub use registry::{TopicRegistry, TopicKind, RegistryError};
+pub use selector::{Selector, SelectEvent};
+pub use typed_view::TypedView;
 
 #[cfg(test)]
 mod tests{
@@ -64,4 +72,93 @@ mod tests{
             assert!(received[i] > received[i - 1]);
         }
     }
-}
\ No newline at end of file
+
+    //drains one typed and one byte topic until both producers are done
+    fn drain_pair(typed: Arc<Topic<u64>>, bytes: Arc<ByteTopic>, done: Arc<AtomicBool>) -> (Vec<u64>, Vec<u64>){
+        let mut typed_seq = Vec::new();
+        let mut byte_seq = Vec::new();
+        loop{
+            let finished = done.load(Ordering::SeqCst);
+            let mut idle = true;
+            while let Some(val) = typed.try_receive(){
+                typed_seq.push(val);
+                idle = false;
+            }
+            while let Some((data, _)) = bytes.try_receive(){
+                byte_seq.push(u64::from_le_bytes(data[..8].try_into().unwrap()));
+                idle = false;
+            }
+            //one full pass after the producers finished picks up the tail
+            if finished && idle{
+                return (typed_seq, byte_seq);
+            }
+            if idle{
+                thread::yield_now();
+            }
+        }
+    }
+
+    fn run_registry_stress(capacity: usize, per_topic: u64) -> Vec<Vec<u64>>{
+        const PAIRS: usize = 3;
+        let registry = Arc::new(TopicRegistry::new());
+        let done = Arc::new(AtomicBool::new(false));
+
+        let mut producers = Vec::new();
+        for pair in 0..PAIRS{
+            let typed: Arc<Topic<u64>> = registry.get_or_create(&format!("/stress/typed/{}", pair), capacity);
+            producers.push(thread::spawn(move ||{
+                for i in 0..per_topic{
+                    typed.publish(i);
+                }
+            }));
+            let bytes = registry.get_or_create_byte(&format!("/stress/bytes/{}", pair), capacity);
+            producers.push(thread::spawn(move ||{
+                for i in 0..per_topic{
+                    bytes.publish(&i.to_le_bytes());
+                }
+            }));
+        }
+
+        let consumers: Vec<_> = (0..PAIRS).map(|pair|{
+            let typed: Arc<Topic<u64>> = registry.get_or_create(&format!("/stress/typed/{}", pair), capacity);
+            let bytes = registry.get_or_create_byte(&format!("/stress/bytes/{}", pair), capacity);
+            let done = Arc::clone(&done);
+            thread::spawn(move || drain_pair(typed, bytes, done))
+        }).collect();
+
+        for producer in producers{
+            producer.join().unwrap();
+        }
+        done.store(true, Ordering::SeqCst);
+
+        let mut sequences = Vec::new();
+        for consumer in consumers{
+            let (typed_seq, byte_seq) = consumer.join().unwrap();
+            sequences.push(typed_seq);
+            sequences.push(byte_seq);
+        }
+        assert_eq!(registry.topic_count(), 2 * PAIRS);
+        sequences
+    }
+
+    #[test]
+    fn test_registry_multi_topic_fifo_complete(){
+        let per_topic = 5000;
+        for seq in run_registry_stress(8192, per_topic){
+            assert_eq!(seq, (0..per_topic).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_registry_multi_topic_fifo_under_overflow(){
+        //buffers far smaller than the burst: messages may be dropped
+        //(overwrite-oldest), but never reordered or duplicated, and the
+        //newest always arrives
+        let per_topic = 20_000;
+        for seq in run_registry_stress(64, per_topic){
+            assert!(!seq.is_empty());
+            assert!(seq.windows(2).all(|w| w[0] < w[1]), "out of order or duplicated");
+            assert_eq!(*seq.last().unwrap(), per_topic - 1);
+        }
+    }
+}
@@ -1,5 +1,7 @@
+use std::fmt;
 use std::sync::Arc;
-use super::topic::{Topic, ByteTopic};
+use super::topic::{Topic, ByteTopic, PublishError};
+use crate::ring_buffer::byte_buffer::MAX_PAYLOAD_SIZE;
 use super::message::Message;
 
 pub struct Publisher<T: Message>{
@@ -28,15 +30,37 @@ impl<T: Message> Clone for Publisher<T>{
 
 pub struct BytePublisher{
     topic: Arc<ByteTopic>,
+    //prepended to every message on a tagged topic
+    tag: Option<u8>,
 }
 
 impl BytePublisher{
     pub fn new(topic: Arc<ByteTopic>) -> Self{
-        BytePublisher{ topic }
+        BytePublisher{ topic, tag: None }
     }
 
+    //publisher that identifies itself as `id` to consumers of a tagged topic
+    pub fn new_tagged(topic: Arc<ByteTopic>, id: u8) -> Self{
+        assert!(topic.is_tagged(), "topic '{}' was not created with_tagged_publishers", topic.name());
+        BytePublisher{ topic, tag: Some(id) }
+    }
+
+    pub fn tag(&self) -> Option<u8>{
+        self.tag
+    }
+
+    //None if the payload (plus the tag byte, if any) does not fit a slot
     pub fn publish(&self, data: &[u8]) -> Option<u64>{
-        self.topic.publish(data)
+        let Some(tag) = self.tag else{
+            return self.topic.publish(data);
+        };
+        if data.len() >= MAX_PAYLOAD_SIZE{
+            return None;
+        }
+        let mut tagged = [0u8; MAX_PAYLOAD_SIZE];
+        tagged[0] = tag;
+        tagged[1..=data.len()].copy_from_slice(data);
+        self.topic.publish(&tagged[..=data.len()])
     }
 
     pub fn topic_name(&self) -> &str{
@@ -46,7 +70,74 @@ impl BytePublisher{
 
 impl Clone for BytePublisher{
     fn clone(&self) -> Self{
-        BytePublisher{ topic: Arc::clone(&self.topic) }
+        BytePublisher{ topic: Arc::clone(&self.topic), tag: self.tag }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FixedSizeError{
+    //refused before it reached the topic
+    WrongSize{ len: usize, expected: usize },
+    //right size, but the topic turned it down
+    Publish(PublishError),
+}
+
+impl fmt::Display for FixedSizeError{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        match self{
+            FixedSizeError::WrongSize{ len, expected } => write!(f, "expected {} bytes, got {}", expected, len),
+            FixedSizeError::Publish(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for FixedSizeError{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)>{
+        match self{
+            FixedSizeError::WrongSize{ .. } => None,
+            FixedSizeError::Publish(e) => Some(e),
+        }
+    }
+}
+
+impl From<PublishError> for FixedSizeError{
+    fn from(e: PublishError) -> Self{
+        FixedSizeError::Publish(e)
+    }
+}
+
+//byte publisher for a fixed-schema stream: anything but exactly `size`
+//bytes is a serialization bug and is refused rather than published
+pub struct FixedSizePublisher{
+    topic: Arc<ByteTopic>,
+    size: usize,
+}
+
+impl FixedSizePublisher{
+    pub fn new(topic: Arc<ByteTopic>, size: usize) -> Self{
+        assert!(size <= MAX_PAYLOAD_SIZE, "fixed size {} exceeds MAX_PAYLOAD_SIZE bruddaa!!", size);
+        FixedSizePublisher{ topic, size }
+    }
+
+    pub fn size(&self) -> usize{
+        self.size
+    }
+
+    pub fn publish(&self, data: &[u8]) -> Result<u64, FixedSizeError>{
+        if data.len() != self.size{
+            return Err(FixedSizeError::WrongSize{ len: data.len(), expected: self.size });
+        }
+        Ok(self.topic.publish_checked(data)?)
+    }
+
+    pub fn topic_name(&self) -> &str{
+        self.topic.name()
+    }
+}
+
+impl Clone for FixedSizePublisher{
+    fn clone(&self) -> Self{
+        FixedSizePublisher{ topic: Arc::clone(&self.topic), size: self.size }
     }
 }
 
@@ -74,6 +165,79 @@ mod tests{
         assert_eq!(e1, 1);
         assert_eq!(topic.len(), 1);
     }
+
+    #[test]
+    fn test_fixed_size_publisher_rejects_wrong_sizes(){
+        let topic = Arc::new(ByteTopic::new("/imu", 8));
+        let publisher = FixedSizePublisher::new(Arc::clone(&topic), 36);
+
+        assert_eq!(publisher.publish(&[0; 36]), Ok(1));
+        assert_eq!(publisher.publish(&[0; 35]), Err(FixedSizeError::WrongSize{ len: 35, expected: 36 }));
+        assert_eq!(publisher.publish(&[]), Err(FixedSizeError::WrongSize{ len: 0, expected: 36 }));
+        assert_eq!(publisher.clone().publish(&[1; 37]).unwrap_err().to_string(), "expected 36 bytes, got 37");
+        assert_eq!(topic.len(), 1);
+
+        //the topic's own refusals come through wrapped
+        let limited = Arc::new(ByteTopic::new("/imu", 8).with_max_rate(1.0));
+        let publisher = FixedSizePublisher::new(Arc::clone(&limited), 4);
+        assert_eq!(publisher.publish(&[0; 4]), Ok(1));
+        assert_eq!(publisher.publish(&[0; 4]), Err(FixedSizeError::Publish(PublishError::RateLimited)));
+    }
+
+    #[test]
+    fn test_tagged_publishers_identify_the_writer(){
+        let topic = Arc::new(ByteTopic::new("/shared", 8).with_tagged_publishers());
+        let nav = BytePublisher::new_tagged(Arc::clone(&topic), 1);
+        let sonar = BytePublisher::new_tagged(Arc::clone(&topic), 2);
+
+        nav.publish(&[0xAB, 0xCD]).unwrap();
+        sonar.publish(&[]).unwrap();
+        nav.clone().publish(&[0xEF]).unwrap();
+        assert!(sonar.publish(&[0; MAX_PAYLOAD_SIZE]).is_none());
+
+        assert_eq!(topic.try_receive_tagged(), Some((1, vec![0xAB, 0xCD], 1)));
+        assert_eq!(topic.try_receive_tagged(), Some((2, vec![], 2)));
+        assert_eq!(topic.try_receive_tagged(), Some((1, vec![0xEF], 3)));
+        assert_eq!(topic.try_receive_tagged(), None);
+    }
+
+    #[test]
+    fn test_tagged_publishers_on_separate_threads(){
+        const PER_WRITER: u32 = 5000;
+        let topic = Arc::new(ByteTopic::new("/shared", 4 * PER_WRITER as usize).with_tagged_publishers());
+        let start = Arc::new(std::sync::Barrier::new(4));
+
+        let writers: Vec<_> = (0..4u8).map(|id|{
+            let publisher = BytePublisher::new_tagged(Arc::clone(&topic), id);
+            let start = Arc::clone(&start);
+            std::thread::spawn(move ||{
+                start.wait();
+                for seq in 0..PER_WRITER{
+                    publisher.publish(&seq.to_le_bytes()).unwrap();
+                }
+            })
+        }).collect();
+        for writer in writers{
+            writer.join().unwrap();
+        }
+
+        //a slot claimed by two writers would lose one message and mix up the other
+        let mut next = [0u32; 4];
+        let mut epochs = Vec::new();
+        while let Some((tag, data, epoch)) = topic.try_receive_tagged(){
+            assert_eq!(u32::from_le_bytes(data.try_into().unwrap()), next[tag as usize]);
+            next[tag as usize] += 1;
+            epochs.push(epoch);
+        }
+        assert_eq!(next, [PER_WRITER; 4]);
+        assert_eq!(epochs, (1..=4 * PER_WRITER as u64).collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[should_panic(expected = "not created with_tagged_publishers")]
+    fn test_tagged_publisher_needs_tagged_topic(){
+        BytePublisher::new_tagged(Arc::new(ByteTopic::new("/plain", 8)), 1);
+    }
 }
 
 
@@ -1,48 +1,182 @@
 use std::sync::{Arc, RwLock};
 use std::collections::HashMap;
 use std::any::Any;
+use std::time::Duration;
 use super::topic::{Topic, ByteTopic};
 use super::message::Message;
+use super::selector::Selector;
+use crate::ring_buffer::BufferMetrics;
+use crate::ring_buffer::wait::Notifier;
+use crate::poison::RwLockExt;
 
+//upper bound for auto-sized topics, 4096 byte slots is ~1 MiB
+pub const MAX_AUTO_CAPACITY: usize = 4096;
+
+//slots needed to hold `retention` worth of messages at `rate_hz`
+pub fn auto_capacity(rate_hz: f64, retention: Duration) -> usize{
+    let slots = (rate_hz * retention.as_secs_f64()).ceil();
+    if slots.is_nan() || slots < 1.0{
+        return 1;
+    }
+    (slots as usize).min(MAX_AUTO_CAPACITY)
+}
+
+//what a name is bound to; one registry name is exactly one buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopicKind{
+    Bytes,
+    Typed(&'static str),   //message type name
+}
+
+impl std::fmt::Display for TopicKind{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        match self{
+            TopicKind::Bytes => write!(f, "byte topic"),
+            TopicKind::Typed(ty) => write!(f, "typed topic of {}", ty),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryError{
+    //name already taken by a topic of another kind or message type
+    KindMismatch{ name: String, existing: TopicKind, requested: TopicKind },
+}
+
+impl std::fmt::Display for RegistryError{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        match self{
+            RegistryError::KindMismatch{ name, existing, requested } =>
+                write!(f, "topic '{}' is a {}, requested as a {}", name, existing, requested),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError{}
+
+enum Entry{
+    Typed{
+        topic: Arc<dyn Any + Send + Sync>,
+        kind: TopicKind,
+        memory_footprint: usize,
+    },
+    Bytes(Arc<ByteTopic>),
+}
+
+impl Entry{
+    fn kind(&self) -> TopicKind{
+        match self{
+            Entry::Typed{ kind, .. } => *kind,
+            Entry::Bytes(_) => TopicKind::Bytes,
+        }
+    }
+
+    fn memory_footprint(&self) -> usize{
+        match self{
+            Entry::Typed{ memory_footprint, .. } => *memory_footprint,
+            Entry::Bytes(topic) => topic.memory_footprint(),
+        }
+    }
+}
+
+//typed and byte topics share one namespace: asking for a name as a kind
+//(or message type) other than the one it was created with is an error,
+//never a second buffer under the same name. the try_ variants report it,
+//the plain ones panic with the same message.
 pub struct TopicRegistry{
-    typed_topics: RwLock<HashMap<String, Arc<dyn Any + Send + Sync>>>,
-    byte_topics: RwLock<HashMap<String, Arc<ByteTopic>>>,
+    topics: RwLock<HashMap<String, Entry>>,
+    //shared publish signal of every byte topic below, what a Selector waits on
+    signal: Arc<Notifier>,
 }
 
 impl TopicRegistry{
     pub fn new() -> Self{
         TopicRegistry{
-            typed_topics: RwLock::new(HashMap::new()),
-            byte_topics: RwLock::new(HashMap::new()),
+            topics: RwLock::new(HashMap::new()),
+            signal: Arc::new(Notifier::default()),
         }
     }
 
     pub fn get_or_create<T: Message>(&self, name: &str, capacity: usize) -> Arc<Topic<T>>{
-        let mut topics = self.typed_topics.write().unwrap();
+        self.try_get_or_create(name, capacity).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_get_or_create<T: Message>(&self, name: &str, capacity: usize) -> Result<Arc<Topic<T>>, RegistryError>{
+        let requested = TopicKind::Typed(std::any::type_name::<T>());
+        let mut topics = self.topics.write_unpoisoned();
         if let Some(existing) = topics.get(name){
-            if let Some(topic) = existing.clone().downcast::<Topic<T>>().ok(){
-                return topic;
+            if let Entry::Typed{ topic, .. } = existing{
+                if let Ok(topic) = topic.clone().downcast::<Topic<T>>(){
+                    return Ok(topic);
+                }
             }
+            return Err(RegistryError::KindMismatch{ name: name.to_string(), existing: existing.kind(), requested });
         }
         let topic = Arc::new(Topic::<T>::new(name, capacity));
-        topics.insert(name.to_string(), topic.clone() as Arc<dyn Any + Send + Sync>);
-        topic
+        topics.insert(name.to_string(), Entry::Typed{
+            topic: topic.clone() as Arc<dyn Any + Send + Sync>,
+            kind: requested,
+            memory_footprint: topic.memory_footprint(),
+        });
+        Ok(topic)
     }
 
     pub fn get_or_create_byte(&self, name: &str, capacity: usize) -> Arc<ByteTopic>{
-        let mut topics = self.byte_topics.write().unwrap();
-        if let Some(existing) = topics.get(name){
-            return Arc::clone(existing);
+        self.try_get_or_create_byte(name, capacity).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_get_or_create_byte(&self, name: &str, capacity: usize) -> Result<Arc<ByteTopic>, RegistryError>{
+        let mut topics = self.topics.write_unpoisoned();
+        match topics.get(name){
+            Some(Entry::Bytes(existing)) => return Ok(Arc::clone(existing)),
+            Some(existing) =>{
+                return Err(RegistryError::KindMismatch{
+                    name: name.to_string(),
+                    existing: existing.kind(),
+                    requested: TopicKind::Bytes,
+                });
+            }
+            None => {}
         }
-        let topic = Arc::new(ByteTopic::new(name, capacity));
-        topics.insert(name.to_string(), Arc::clone(&topic));
-        topic
+        let topic = Arc::new(ByteTopic::new(name, capacity).with_signal(Arc::clone(&self.signal)));
+        topics.insert(name.to_string(), Entry::Bytes(Arc::clone(&topic)));
+        Ok(topic)
+    }
+
+    //"hold 2s of 100 Hz" instead of a raw slot count; an existing topic keeps its capacity
+    pub fn get_or_create_byte_auto(&self, name: &str, target_rate_hz: f64, retention: Duration) -> Arc<ByteTopic>{
+        self.get_or_create_byte(name, auto_capacity(target_rate_hz, retention))
+    }
+
+    //wait on several of this registry's byte topics at once
+    pub fn selector(&self) -> Selector{
+        Selector::new(Arc::clone(&self.signal))
+    }
+
+    pub fn kind_of(&self, name: &str) -> Option<TopicKind>{
+        self.topics.read_unpoisoned().get(name).map(Entry::kind)
     }
 
     pub fn topic_count(&self) -> usize{
-        let typed = self.typed_topics.read().unwrap().len();
-        let bytes = self.byte_topics.read().unwrap().len();
-        typed + bytes
+        self.topics.read_unpoisoned().len()
+    }
+
+    pub fn total_memory(&self) -> usize{
+        self.topics.read_unpoisoned().values().map(Entry::memory_footprint).sum()
+    }
+
+    //name -> metrics for every byte topic, sorted by name, under a single read lock
+    pub fn iter_byte_metrics(&self) -> Vec<(String, BufferMetrics)>{
+        let topics = self.topics.read_unpoisoned();
+        let mut snapshot: Vec<_> = topics.iter()
+            .filter_map(|(name, entry)| match entry{
+                Entry::Bytes(topic) => Some((name.clone(), topic.metrics())),
+                Entry::Typed{ .. } => None,
+            })
+            .collect();
+        drop(topics);
+        snapshot.sort_by(|a, b| a.0.cmp(&b.0));
+        snapshot
     }
 }
 
@@ -56,6 +190,24 @@ impl Default for TopicRegistry{
 mod tests{
     use super::*;
     
+    #[test]
+    fn test_registry_survives_panic_under_its_lock(){
+        let registry = Arc::new(TopicRegistry::new());
+        registry.get_or_create_byte("/depth", 8).publish(&[1]);
+
+        //capacity 0 panics inside ByteTopic::new, with the write lock held
+        let doomed = Arc::clone(&registry);
+        assert!(std::thread::spawn(move || doomed.get_or_create_byte("/bad", 0)).join().is_err());
+        assert!(registry.topics.is_poisoned());
+
+        let depth = registry.get_or_create_byte("/depth", 8);
+        assert_eq!(depth.try_receive(), Some((vec![1], 1)));
+        registry.get_or_create_byte("/imu", 8).publish(&[2]);
+        assert_eq!(registry.topic_count(), 2);
+        assert_eq!(registry.kind_of("/bad"), None);
+        assert_eq!(registry.iter_byte_metrics().len(), 2);
+    }
+
     #[test]
     fn test_registry_get_or_create(){
         let registry = TopicRegistry::new();
@@ -86,4 +238,100 @@ mod tests{
         let (data, _) = topic2.try_receive().unwrap();
         assert_eq!(data, vec![1, 2, 3]);
     }
+
+    #[test]
+    fn test_registry_total_memory(){
+        let registry = TopicRegistry::new();
+        assert_eq!(registry.total_memory(), 0);
+
+        let imu = registry.get_or_create_byte("/imu", 32);
+        let temp: Arc<Topic<f64>> = registry.get_or_create("/temp", 8);
+        assert_eq!(registry.total_memory(), imu.memory_footprint() + temp.memory_footprint());
+
+        //looking a topic up again must not count it twice
+        registry.get_or_create_byte("/imu", 32);
+        assert_eq!(registry.total_memory(), imu.memory_footprint() + temp.memory_footprint());
+    }
+
+    #[test]
+    fn test_registry_iter_byte_metrics(){
+        let registry = TopicRegistry::new();
+        let idle = registry.get_or_create_byte("/idle", 8);
+        let imu = registry.get_or_create_byte("/imu", 8);
+        let depth = registry.get_or_create_byte("/depth", 4);
+        let _typed: Arc<Topic<i32>> = registry.get_or_create("/typed", 8);
+
+        for i in 0..3u8{
+            imu.publish(&[i]);
+        }
+        for i in 0..6u8{
+            depth.publish(&[i]);
+        }
+
+        let metrics = registry.iter_byte_metrics();
+        let names: Vec<_> = metrics.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["/depth", "/idle", "/imu"]);
+
+        let (_, m) = &metrics[0];
+        assert_eq!((m.len, m.capacity, m.published, m.dropped), (4, 4, 6, 2));
+        let (_, m) = &metrics[1];
+        assert_eq!((m.len, m.capacity, m.published, m.dropped), (0, 8, 0, 0));
+        let (_, m) = &metrics[2];
+        assert_eq!((m.len, m.capacity, m.published, m.dropped), (3, 8, 3, 0));
+        assert!(idle.is_empty());
+    }
+
+    #[test]
+    fn test_auto_capacity(){
+        assert_eq!(auto_capacity(100.0, Duration::from_secs(2)), 200);
+        assert_eq!(auto_capacity(50.0, Duration::from_millis(100)), 5);
+        assert_eq!(auto_capacity(30.0, Duration::from_millis(110)), 4);
+        assert_eq!(auto_capacity(0.5, Duration::from_secs(1)), 1);
+        assert_eq!(auto_capacity(0.0, Duration::from_secs(10)), 1);
+        assert_eq!(auto_capacity(1000.0, Duration::from_secs(60)), MAX_AUTO_CAPACITY);
+    }
+
+    #[test]
+    fn test_registry_get_or_create_byte_auto(){
+        let registry = TopicRegistry::new();
+        let imu = registry.get_or_create_byte_auto("/imu", 100.0, Duration::from_secs(2));
+        assert_eq!(imu.capacity(), 200);
+
+        //existing topics are returned as-is
+        let again = registry.get_or_create_byte_auto("/imu", 10.0, Duration::from_secs(1));
+        assert!(Arc::ptr_eq(&imu, &again));
+        assert_eq!(again.capacity(), 200);
+    }
+
+    #[test]
+    fn test_registry_unified_namespace(){
+        let registry = TopicRegistry::new();
+        let bytes = registry.get_or_create_byte("/x", 8);
+
+        let err = registry.try_get_or_create::<i32>("/x", 8).err().unwrap();
+        assert_eq!(err, RegistryError::KindMismatch{
+            name: "/x".to_string(),
+            existing: TopicKind::Bytes,
+            requested: TopicKind::Typed("i32"),
+        });
+        assert_eq!(err.to_string(), "topic '/x' is a byte topic, requested as a typed topic of i32");
+
+        //still one buffer under the name
+        assert_eq!(registry.topic_count(), 1);
+        assert!(Arc::ptr_eq(&bytes, &registry.get_or_create_byte("/x", 8)));
+
+        let _typed: Arc<Topic<i32>> = registry.get_or_create("/y", 8);
+        assert_eq!(registry.kind_of("/y"), Some(TopicKind::Typed("i32")));
+        assert!(registry.try_get_or_create_byte("/y", 8).is_err());
+        assert!(registry.try_get_or_create::<f64>("/y", 8).is_err());
+        assert_eq!(registry.kind_of("/z"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "topic '/x' is a byte topic")]
+    fn test_registry_get_or_create_panics_on_kind_mismatch(){
+        let registry = TopicRegistry::new();
+        registry.get_or_create_byte("/x", 8);
+        let _: Arc<Topic<i32>> = registry.get_or_create("/x", 8);
+    }
 }
@@ -0,0 +1,166 @@
+use std::sync::Arc;
+use std::time::Duration;
+use crate::ring_buffer::WaitStrategy;
+use crate::ring_buffer::wait::Notifier;
+use super::subscriber::ByteSubscriber;
+
+//which subscriber has data: `index` as returned by Selector::add
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectEvent{
+    pub index: usize,
+    pub topic: String,
+}
+
+//select! over byte topics without an async runtime. every byte topic a
+//registry creates fires the registry's shared signal on publish, so one wait
+//covers all of them. the event only says who is ready; read it through
+//subscriber(index), with try_recv for subscribers added with add and
+//try_next for ones added with add_fanout.
+pub struct Selector{
+    signal: Arc<Notifier>,
+    subscribers: Vec<ByteSubscriber>,
+    //per subscriber: ready means unread past its own cursor, not in the topic
+    fanout: Vec<bool>,
+    //where the next scan starts, so one busy topic can't starve the rest
+    next: usize,
+}
+
+impl Selector{
+    pub(crate) fn new(signal: Arc<Notifier>) -> Self{
+        Selector{
+            signal,
+            subscribers: Vec::new(),
+            fanout: Vec::new(),
+            next: 0,
+        }
+    }
+
+    //ready while the topic's shared queue has unread data. panics if the
+    //topic is not from the registry that made this selector, since its
+    //publishes would never wake us
+    pub fn add(&mut self, subscriber: ByteSubscriber) -> usize{
+        self.register(subscriber, false)
+    }
+
+    //ready while the subscriber's own cursor is behind, whatever other
+    //consumers took from the shared queue; for readers using try_next
+    pub fn add_fanout(&mut self, subscriber: ByteSubscriber) -> usize{
+        self.register(subscriber, true)
+    }
+
+    fn register(&mut self, subscriber: ByteSubscriber, fanout: bool) -> usize{
+        let shared = subscriber.topic().signal().is_some_and(|s| Arc::ptr_eq(s, &self.signal));
+        assert!(shared, "topic '{}' is not from this selector's registry bruddaa!!", subscriber.topic_name());
+        self.subscribers.push(subscriber);
+        self.fanout.push(fanout);
+        self.subscribers.len() - 1
+    }
+
+    pub fn subscriber(&self, index: usize) -> &ByteSubscriber{
+        &self.subscribers[index]
+    }
+
+    pub fn len(&self) -> usize{
+        self.subscribers.len()
+    }
+
+    pub fn is_empty(&self) -> bool{
+        self.subscribers.is_empty()
+    }
+
+    //non-blocking: the first subscriber with unread data, if any
+    pub fn try_select(&mut self) -> Option<SelectEvent>{
+        let count = self.subscribers.len();
+        for offset in 0..count{
+            let index = (self.next + offset) % count;
+            let subscriber = &self.subscribers[index];
+            let ready = if self.fanout[index]{ subscriber.has_next() }else{ !subscriber.topic().is_empty() };
+            if ready{
+                self.next = (index + 1) % count;
+                return Some(SelectEvent{ index, topic: subscriber.topic_name().to_string() });
+            }
+        }
+        None
+    }
+
+    //blocks until any subscriber has unread data, None after `timeout`
+    pub fn select_timeout(&mut self, timeout: Duration) -> Option<SelectEvent>{
+        let signal = Arc::clone(&self.signal);
+        signal.wait_for(timeout, WaitStrategy::Park, || self.try_select())
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use std::thread;
+    use crate::pubsub::TopicRegistry;
+
+    #[test]
+    fn test_select_reports_the_topic_that_woke_it(){
+        let registry = TopicRegistry::new();
+        let imu = registry.get_or_create_byte("/imu", 8);
+        let depth = registry.get_or_create_byte("/depth", 8);
+
+        let mut selector = registry.selector();
+        let imu_idx = selector.add(ByteSubscriber::new(imu));
+        let depth_idx = selector.add(ByteSubscriber::new(Arc::clone(&depth)));
+        assert_eq!(selector.select_timeout(Duration::from_millis(10)), None);
+
+        let producer = thread::spawn(move ||{
+            thread::sleep(Duration::from_millis(20));
+            depth.publish(&[7]);
+        });
+        let event = selector.select_timeout(Duration::from_secs(5)).unwrap();
+        producer.join().unwrap();
+
+        assert_eq!(event, SelectEvent{ index: depth_idx, topic: "/depth".to_string() });
+        assert_ne!(event.index, imu_idx);
+        assert_eq!(selector.subscriber(event.index).try_recv(), Some((vec![7], 1)));
+        assert_eq!(selector.try_select(), None);
+    }
+
+    #[test]
+    fn test_select_takes_turns_between_ready_topics(){
+        let registry = TopicRegistry::new();
+        let a = registry.get_or_create_byte("/a", 8);
+        let b = registry.get_or_create_byte("/b", 8);
+        let mut selector = registry.selector();
+        selector.add(ByteSubscriber::new(Arc::clone(&a)));
+        selector.add(ByteSubscriber::new(Arc::clone(&b)));
+
+        for i in 0..4u8{
+            a.publish(&[i]);
+            b.publish(&[i]);
+        }
+        let order: Vec<usize> = (0..4).map(|_| selector.try_select().unwrap().index).collect();
+        assert_eq!(order, vec![0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn test_fanout_subscriber_ready_by_its_own_cursor(){
+        let registry = TopicRegistry::new();
+        let topic = registry.get_or_create_byte("/fanout", 8);
+        let mut selector = registry.selector();
+        let idx = selector.add_fanout(ByteSubscriber::new(Arc::clone(&topic)));
+
+        topic.publish(&[1]);
+        topic.publish(&[2]);
+        //another consumer emptying the shared queue doesn't hide them
+        while topic.try_receive().is_some(){}
+        assert_eq!(selector.try_select().map(|e| e.index), Some(idx));
+
+        assert_eq!(selector.subscriber(idx).try_next(), Some((vec![1], 1)));
+        assert!(selector.try_select().is_some());
+        assert_eq!(selector.subscriber(idx).try_next(), Some((vec![2], 2)));
+        assert_eq!(selector.try_select(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "not from this selector's registry")]
+    fn test_foreign_topic_rejected(){
+        let registry = TopicRegistry::new();
+        let loose = Arc::new(crate::pubsub::ByteTopic::new("/loose", 4));
+        registry.selector().add(ByteSubscriber::new(loose));
+    }
+}
@@ -1,18 +1,32 @@
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use crate::ring_buffer::WaitStrategy;
+use crate::uart::FromFrame;
 use super::topic::{Topic, ByteTopic};
 use super::message::Message;
+use crate::poison::MutexExt;
 
+//try_recv and recv_timeout consume from the topic, shared with every other
+//consumer. try_next instead reads through this subscriber's own cursor, so
+//each subscriber sees every message (fan-out) and nobody else's reads matter
 pub struct Subscriber<T: Message>{
     topic: Arc<Topic<T>>,
     last_seen_epoch: AtomicU64,
+    //epoch of the last message try_next returned
+    last_read_epoch: AtomicU64,
 }
 
 impl<T: Message> Subscriber<T>{
     pub fn new(topic: Arc<Topic<T>>) -> Self{
+        //the cursor starts at subscribe time, like a late joiner should
+        let start = topic.latest_epoch();
         Subscriber{
             topic,
             last_seen_epoch: AtomicU64::new(0),
+            last_read_epoch: AtomicU64::new(start),
         }
     }
 
@@ -20,6 +34,19 @@ impl<T: Message> Subscriber<T>{
         self.topic.try_receive()
     }
 
+    //next message after this subscriber's cursor, leaving it for the others
+    pub fn try_next(&self) -> Option<(T, u64)>{
+        next_after(&self.last_read_epoch, |cursor| self.topic.pop_from(cursor))
+    }
+
+    pub fn cursor(&self) -> u64{
+        self.last_read_epoch.load(Ordering::SeqCst)
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration, strategy: WaitStrategy) -> Option<T>{
+        self.topic.recv_timeout(timeout, strategy)
+    }
+
     pub fn peek_latest(&self) -> Option<(T, u64)>{
         self.topic.peek_latest()
     }
@@ -40,29 +67,142 @@ impl<T: Message> Subscriber<T>{
     }
 }
 
+//advance `cursor` to the message read after it; a racing call on the same
+//subscriber that got there first makes us read again
+fn next_after<M>(cursor: &AtomicU64, read: impl Fn(u64) -> Option<(M, u64)>) -> Option<(M, u64)>{
+    loop{
+        let last = cursor.load(Ordering::SeqCst);
+        let (msg, epoch) = read(last)?;
+        if cursor.compare_exchange(last, epoch, Ordering::SeqCst, Ordering::SeqCst).is_ok(){
+            return Some((msg, epoch));
+        }
+    }
+}
+
+pub type GapCallback = Box<dyn Fn(u64) + Send>;
+
+//same two ways to read as Subscriber: shared try_recv, or own-cursor try_next
 pub struct ByteSubscriber{
     topic: Arc<ByteTopic>,
     last_seen_epoch: AtomicU64,
+    //newest epoch handed out by recv, for gap detection
+    last_recv_epoch: AtomicU64,
+    //epoch of the last message try_next returned
+    last_read_epoch: AtomicU64,
+    on_gap: Mutex<Option<GapCallback>>,
 }
 
 impl ByteSubscriber{
     pub fn new(topic: Arc<ByteTopic>) -> Self{
+        //anything published before we subscribed is not a gap
+        let start = topic.latest_epoch();
         ByteSubscriber{
             topic,
             last_seen_epoch: AtomicU64::new(0),
+            last_recv_epoch: AtomicU64::new(start),
+            last_read_epoch: AtomicU64::new(start),
+            on_gap: Mutex::new(None),
         }
     }
 
     pub fn try_recv(&self) -> Option<(Vec<u8>, u64)>{
-        self.topic.try_receive()
+        self.recv_with_gap().map(|(data, epoch, _)| (data, epoch))
+    }
+
+    //allocation-free try_recv: `out` is reused and only touched on Some
+    pub fn try_recv_into(&self, out: &mut Vec<u8>) -> Option<u64>{
+        let epoch = self.topic.try_receive_into(out)?;
+        self.note_epoch(epoch);
+        Some(epoch)
+    }
+
+    //next message after this subscriber's cursor, leaving it for the others;
+    //a lap past the cursor is reported to the gap callback like for try_recv
+    pub fn try_next(&self) -> Option<(Vec<u8>, u64)>{
+        let (data, epoch) = next_after(&self.last_read_epoch, |cursor| self.topic.pop_from(cursor))?;
+        self.note_epoch(epoch);
+        Some((data, epoch))
+    }
+
+    //try_next plus the publish time, see ByteTopic::record_publish_times
+    pub fn try_next_stamped(&self) -> Option<(Vec<u8>, u64, u64)>{
+        let ((data, stamp), epoch) = next_after(&self.last_read_epoch, |cursor|{
+            self.topic.pop_from_stamped(cursor).map(|(data, epoch, stamp)| ((data, stamp), epoch))
+        })?;
+        self.note_epoch(epoch);
+        Some((data, epoch, stamp))
+    }
+
+    pub fn cursor(&self) -> u64{
+        self.last_read_epoch.load(Ordering::SeqCst)
+    }
+
+    //messages were published past this subscriber's cursor; try_next may
+    //still come back empty while the newest write is in flight
+    pub fn has_next(&self) -> bool{
+        self.cursor() < self.topic.latest_epoch()
+    }
+
+    //like try_recv, also reporting how many epochs were skipped right before this one
+    pub fn recv_with_gap(&self) -> Option<(Vec<u8>, u64, u64)>{
+        let (data, epoch) = self.topic.try_receive()?;
+        let gap = self.note_epoch(epoch);
+        Some((data, epoch, gap))
+    }
+
+    //messages published since the last one this subscriber received
+    pub fn lag(&self) -> u64{
+        self.topic.latest_epoch().saturating_sub(self.last_recv_epoch.load(Ordering::SeqCst))
+    }
+
+    //true once some of those messages are no longer resident in the buffer
+    pub fn is_lapped(&self) -> bool{
+        self.lag() > self.topic.capacity() as u64
+    }
+
+    //called once per detected gap with the number of lost messages; replaces any previous callback
+    pub fn on_gap(&self, cb: impl Fn(u64) + Send + 'static){
+        *self.on_gap.lock_unpoisoned() = Some(Box::new(cb));
+    }
+
+    fn note_epoch(&self, epoch: u64) -> u64{
+        let last = self.last_recv_epoch.fetch_max(epoch, Ordering::SeqCst);
+        let gap = epoch.saturating_sub(last + 1);
+        if gap > 0{
+            if let Some(cb) = self.on_gap.lock_unpoisoned().as_ref(){
+                cb(gap);
+            }
+        }
+        gap
+    }
+
+    //blocking try_recv, with the same gap accounting
+    pub fn recv_timeout(&self, timeout: Duration, strategy: WaitStrategy) -> Option<(Vec<u8>, u64)>{
+        let (data, epoch) = self.topic.recv_timeout(timeout, strategy)?;
+        self.note_epoch(epoch);
+        Some((data, epoch))
+    }
+
+    //waits as long as it takes; for several topics at once use a Selector
+    pub fn recv_blocking(&self) -> (Vec<u8>, u64){
+        self.recv_timeout(Duration::MAX, WaitStrategy::default())
+            .expect("an unbounded wait only returns with a message")
     }
 
     pub fn peek_latest(&self) -> Option<(Vec<u8>, u64)>{
         self.topic.peek_latest()
     }
 
-    pub fn peek_latest_ref(&self) -> Option<(&[u8], u64)>{
-        self.topic.peek_latest_ref()
+    //drains everything available and keeps the newest message per key byte;
+    //messages too short to hold the key are dropped
+    pub fn recv_coalesced_by(&self, key_offset: usize) -> HashMap<u8, (Vec<u8>, u64)>{
+        let mut latest = HashMap::new();
+        while let Some((data, epoch)) = self.topic.try_receive(){
+            if let Some(&key) = data.get(key_offset){
+                latest.insert(key, (data, epoch));
+            }
+        }
+        latest
     }
 
     pub fn has_new(&self) -> bool{
@@ -79,6 +219,103 @@ impl ByteSubscriber{
     pub fn topic_name(&self) -> &str{
         self.topic.name()
     }
+
+    pub(crate) fn topic(&self) -> &Arc<ByteTopic>{
+        &self.topic
+    }
+}
+
+//ByteSubscriber that hands out decoded messages (ImuMsg from a raw frame
+//topic, say). payloads that don't decode as T are consumed, counted in
+//undecodable() and skipped, so one bad frame never stalls the reader
+pub struct TypedSubscriber<T>{
+    inner: ByteSubscriber,
+    undecodable: AtomicU64,
+    _msg: PhantomData<fn() -> T>,
+}
+
+impl<T: FromFrame> TypedSubscriber<T>{
+    pub fn new(topic: Arc<ByteTopic>) -> Self{
+        TypedSubscriber{
+            inner: ByteSubscriber::new(topic),
+            undecodable: AtomicU64::new(0),
+            _msg: PhantomData,
+        }
+    }
+
+    //shared consumption, like ByteSubscriber::try_recv
+    pub fn try_recv(&self) -> Option<(T, u64)>{
+        self.decode_next(|| self.inner.try_recv())
+    }
+
+    //through this subscriber's own cursor, like ByteSubscriber::try_next
+    pub fn try_next(&self) -> Option<(T, u64)>{
+        self.decode_next(|| self.inner.try_next())
+    }
+
+    fn decode_next(&self, mut next: impl FnMut() -> Option<(Vec<u8>, u64)>) -> Option<(T, u64)>{
+        loop{
+            let (data, epoch) = next()?;
+            match T::from_frame(&data){
+                Some(msg) => return Some((msg, epoch)),
+                None =>{
+                    self.undecodable.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    pub fn undecodable(&self) -> u64{
+        self.undecodable.load(Ordering::Relaxed)
+    }
+
+    pub fn cursor(&self) -> u64{
+        self.inner.cursor()
+    }
+
+    pub fn lag(&self) -> u64{
+        self.inner.lag()
+    }
+
+    pub fn topic_name(&self) -> &str{
+        self.inner.topic_name()
+    }
+}
+
+//keep-last view for dashboards: no cursor, just the last epoch handed out.
+//poll() yields the newest message only when it is newer than that
+pub struct LatestSubscriber{
+    topic: Arc<ByteTopic>,
+    last_rendered: AtomicU64,
+}
+
+impl LatestSubscriber{
+    pub fn new(topic: Arc<ByteTopic>) -> Self{
+        LatestSubscriber{
+            topic,
+            last_rendered: AtomicU64::new(0),
+        }
+    }
+
+    pub fn poll(&self) -> Option<(Vec<u8>, u64)>{
+        if self.topic.latest_epoch() <= self.last_rendered.load(Ordering::SeqCst){
+            return None;
+        }
+        let (data, epoch) = self.topic.peek_latest()?;
+        //only move forward, even if a racing poll already rendered newer
+        if self.last_rendered.fetch_max(epoch, Ordering::SeqCst) >= epoch{
+            return None;
+        }
+        Some((data, epoch))
+    }
+
+    pub fn last_rendered(&self) -> u64{
+        self.last_rendered.load(Ordering::SeqCst)
+    }
+
+    pub fn topic_name(&self) -> &str{
+        self.topic.name()
+    }
 }
 
 #[cfg(test)]
@@ -98,6 +335,73 @@ mod tests{
         assert_eq!(subscriber.try_recv(), None);
     }
 
+    #[test]
+    fn test_subscribers_each_walk_the_stream_in_order(){
+        //far fewer slots than messages, so slow readers get lapped and every
+        //pop_from has to cope with the producer rewriting the slot it reads
+        let topic = Arc::new(Topic::<Vec<u64>>::new("/fanout", 16));
+        let subscribers: Vec<_> = (0..3).map(|_| Subscriber::new(Arc::clone(&topic))).collect();
+
+        let readers: Vec<_> = subscribers.into_iter().map(|sub| std::thread::spawn(move ||{
+            let (mut read, mut skipped, mut last) = (0u64, 0u64, 0u64);
+            while last < 1000{
+                match sub.try_next(){
+                    Some((msg, epoch)) =>{
+                        assert!(epoch > last);
+                        assert!(msg.iter().all(|&v| v == epoch - 1), "epoch {} carried {:?}", epoch, msg);
+                        read += 1;
+                        skipped += epoch - last - 1;
+                        last = epoch;
+                    }
+                    None => std::thread::yield_now(),
+                }
+            }
+            (read, skipped)
+        })).collect();
+
+        let publisher ={
+            let topic = Arc::clone(&topic);
+            std::thread::spawn(move ||{
+                for i in 0..1000{
+                    topic.publish(vec![i; 8]);
+                }
+            })
+        };
+        publisher.join().unwrap();
+
+        for reader in readers{
+            let (read, skipped) = reader.join().unwrap();
+            assert_eq!(read + skipped, 1000);
+        }
+        //cursor reads leave the shared queue alone: the oldest resident is still there
+        assert_eq!(topic.try_receive(), Some(vec![984; 8]));
+    }
+
+    #[test]
+    fn test_byte_subscriber_cursor_reports_laps(){
+        let topic = Arc::new(ByteTopic::new("/fanout", 4));
+        topic.publish(&[0]);
+        let sub = ByteSubscriber::new(Arc::clone(&topic));
+        let gaps = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&gaps);
+        sub.on_gap(move |skipped|{ counter.fetch_add(skipped, Ordering::SeqCst); });
+
+        for i in 1..=6u8{
+            topic.publish(&[i]);
+        }
+        assert_eq!(sub.try_next(), Some((vec![3], 4)));
+        assert_eq!(gaps.load(Ordering::SeqCst), 2);
+        assert_eq!(sub.cursor(), 4);
+        assert_eq!(sub.try_next().unwrap().0, vec![4]);
+
+        //a second subscriber is unaffected by the first one's reads
+        let other = ByteSubscriber::new(Arc::clone(&topic));
+        assert_eq!(other.try_next(), None);
+        topic.publish(&[7]);
+        assert_eq!(other.try_next().unwrap().0, vec![7]);
+        assert_eq!(sub.try_next().unwrap().0, vec![5]);
+    }
+
     #[test]
     fn test_subscriber_has_new(){
         let topic = Arc::new(Topic::<i32>::new("/test", 8));
@@ -131,4 +435,206 @@ mod tests{
         //peek doesn't consume
         assert_eq!(topic.len(), 3);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_byte_subscriber_on_gap(){
+        let topic = Arc::new(ByteTopic::new("/depth", 4));
+        let subscriber = ByteSubscriber::new(Arc::clone(&topic));
+
+        let gaps = Arc::new(Mutex::new(Vec::new()));
+        let gaps_in = Arc::clone(&gaps);
+        subscriber.on_gap(move |gap| gaps_in.lock().unwrap().push(gap));
+
+        topic.publish(&[1]);
+        assert_eq!(subscriber.recv_with_gap().unwrap().2, 0);
+
+        //overflow a 4-slot buffer between reads
+        for i in 2..=9u8{
+            topic.publish(&[i]);
+        }
+
+        //2..=5 were overwritten, 6 is the oldest still resident
+        assert_eq!(subscriber.recv_with_gap(), Some((vec![6], 6, 4)));
+
+        //the rest arrive in order without further gaps
+        while let Some((_, _, gap)) = subscriber.recv_with_gap(){
+            assert_eq!(gap, 0);
+        }
+        assert_eq!(*gaps.lock().unwrap(), vec![4]);
+    }
+
+    #[test]
+    fn test_byte_subscriber_recv_timeout_reports_gaps(){
+        let topic = Arc::new(ByteTopic::new("/depth", 4));
+        let subscriber = ByteSubscriber::new(Arc::clone(&topic));
+        let gaps = Arc::new(Mutex::new(Vec::new()));
+        let gaps_in = Arc::clone(&gaps);
+        subscriber.on_gap(move |gap| gaps_in.lock().unwrap().push(gap));
+
+        for i in 1..=7u8{
+            topic.publish(&[i]);
+        }
+        let wait = Duration::from_millis(10);
+        assert_eq!(subscriber.recv_timeout(wait, WaitStrategy::Spin), Some((vec![4], 4)));
+        assert_eq!(subscriber.lag(), 3);
+        assert_eq!(subscriber.recv_blocking(), (vec![5], 5));
+        assert_eq!(*gaps.lock().unwrap(), vec![3]);
+    }
+
+    #[test]
+    fn test_byte_subscriber_survives_panicking_gap_callback(){
+        let topic = Arc::new(ByteTopic::new("/depth", 2));
+        let subscriber = Arc::new(ByteSubscriber::new(Arc::clone(&topic)));
+        subscriber.on_gap(|_| panic!("bad callback"));
+
+        for i in 0..5u8{
+            topic.publish(&[i]);
+        }
+        let reader = Arc::clone(&subscriber);
+        assert!(std::thread::spawn(move || reader.try_recv()).join().is_err());
+
+        //the callback lock was poisoned mid-call; the subscriber keeps working
+        let gaps = Arc::new(Mutex::new(Vec::new()));
+        let gaps_in = Arc::clone(&gaps);
+        subscriber.on_gap(move |gap| gaps_in.lock().unwrap().push(gap));
+        assert_eq!(subscriber.try_recv(), Some((vec![4], 5)));
+
+        for i in 5..9u8{
+            topic.publish(&[i]);
+        }
+        assert_eq!(subscriber.try_recv(), Some((vec![7], 8)));
+        assert_eq!(*gaps.lock().unwrap(), vec![2]);
+    }
+
+    #[test]
+    fn test_byte_subscriber_try_recv_into(){
+        let topic = Arc::new(ByteTopic::new("/imu", 2));
+        let sub = ByteSubscriber::new(Arc::clone(&topic));
+        let gaps = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&gaps);
+        sub.on_gap(move |skipped|{ counter.fetch_add(skipped, Ordering::SeqCst); });
+
+        let mut out = Vec::new();
+        for i in 1..=3u8{
+            topic.publish(&[i; 4]);
+        }
+        assert_eq!(sub.try_recv_into(&mut out), Some(2));
+        assert_eq!(out, vec![2; 4]);
+        assert_eq!(gaps.load(Ordering::SeqCst), 1);
+        assert_eq!(sub.try_recv_into(&mut out), Some(3));
+        assert_eq!(sub.try_recv_into(&mut out), None);
+        assert_eq!(out, vec![3; 4]);
+    }
+
+    #[test]
+    fn test_byte_subscriber_lag(){
+        let topic = Arc::new(ByteTopic::new("/imu", 4));
+        let subscriber = ByteSubscriber::new(Arc::clone(&topic));
+        assert_eq!(subscriber.lag(), 0);
+
+        topic.publish(&[1]);
+        topic.publish(&[2]);
+        topic.publish(&[3]);
+        assert_eq!(subscriber.lag(), 3);
+        assert!(!subscriber.is_lapped());
+
+        subscriber.try_recv();
+        assert_eq!(subscriber.lag(), 2);
+
+        for i in 4..=7u8{
+            topic.publish(&[i]);
+        }
+        assert_eq!(subscriber.lag(), 6);
+        assert!(subscriber.is_lapped());
+
+        while subscriber.try_recv().is_some(){}
+        assert_eq!(subscriber.lag(), 0);
+        assert!(!subscriber.is_lapped());
+    }
+
+    #[test]
+    fn test_byte_subscriber_late_join_is_not_a_gap(){
+        let topic = Arc::new(ByteTopic::new("/depth", 4));
+        topic.publish(&[1]);
+        topic.publish(&[2]);
+
+        let subscriber = ByteSubscriber::new(Arc::clone(&topic));
+        topic.publish(&[3]);
+
+        while let Some((_, _, gap)) = subscriber.recv_with_gap(){
+            assert_eq!(gap, 0);
+        }
+    }
+
+    #[test]
+    fn test_byte_subscriber_recv_coalesced_by(){
+        let topic = Arc::new(ByteTopic::new("/thrusters", 16));
+        let subscriber = ByteSubscriber::new(Arc::clone(&topic));
+
+        //[tag, thruster_id, value]
+        topic.publish(&[0xF0, 1, 10]);
+        topic.publish(&[0xF0, 2, 20]);
+        topic.publish(&[0xF0, 1, 11]);
+        topic.publish(&[0xF0]);
+        topic.publish(&[0xF0, 3, 30]);
+        topic.publish(&[0xF0, 2, 21]);
+
+        let latest = subscriber.recv_coalesced_by(1);
+        assert_eq!(latest.len(), 3);
+        assert_eq!(latest[&1], (vec![0xF0, 1, 11], 3));
+        assert_eq!(latest[&2], (vec![0xF0, 2, 21], 6));
+        assert_eq!(latest[&3], (vec![0xF0, 3, 30], 5));
+
+        assert!(topic.is_empty());
+        assert!(subscriber.recv_coalesced_by(1).is_empty());
+    }
+
+    #[test]
+    fn test_latest_subscriber_yields_only_new_data(){
+        let topic = Arc::new(ByteTopic::new("/display", 4));
+        let latest = topic.subscribe_latest_only();
+        assert!(latest.poll().is_none());
+
+        topic.publish(&[1]);
+        assert_eq!(latest.poll(), Some((vec![1], 1)));
+        assert!(latest.poll().is_none());
+
+        //many publishes in between, lapping the buffer: only the newest shows
+        for i in 2..=50u8{
+            topic.publish(&[i]);
+        }
+        assert_eq!(latest.poll(), Some((vec![50], 50)));
+        assert!(latest.poll().is_none());
+        assert_eq!(latest.last_rendered(), 50);
+
+        //peeking leaves the queue for regular consumers
+        assert!(topic.try_receive().is_some());
+    }
+
+    #[test]
+    fn test_typed_subscriber_decodes_and_skips_malformed(){
+        use crate::uart::{ImuMsg, ToFrame};
+        let imu = |seed: f32| ImuMsg{ accel_x: seed, accel_z: 9.81, gyro_z: -seed, ..Default::default() };
+
+        let topic = Arc::new(ByteTopic::new("/imu", 8));
+        let shared = TypedSubscriber::<ImuMsg>::new(Arc::clone(&topic));
+        let own = TypedSubscriber::<ImuMsg>::new(Arc::clone(&topic));
+
+        topic.publish(&imu(1.0).to_frame());
+        //a truncated frame, as a flaky link would leave it
+        topic.publish(&imu(2.0).to_frame()[..10]);
+        topic.publish(&imu(3.0).to_frame());
+
+        assert_eq!(shared.try_recv(), Some((imu(1.0), 1)));
+        assert_eq!(shared.try_recv(), Some((imu(3.0), 3)));
+        assert_eq!(shared.try_recv(), None);
+        assert_eq!(shared.undecodable(), 1);
+
+        //the cursor reader still sees everything the shared one consumed
+        let seen: Vec<_> = std::iter::from_fn(|| own.try_next()).collect();
+        assert_eq!(seen, vec![(imu(1.0), 1), (imu(3.0), 3)]);
+        assert_eq!(own.undecodable(), 1);
+        assert_eq!(own.cursor(), 3);
+        assert_eq!(own.topic_name(), "/imu");
+    }
+}
@@ -1,7 +1,52 @@
-use std::sync::Arc;
-use crate::ring_buffer::RingBuffer;
-use crate::ring_buffer::byte_buffer::ByteRingBuffer;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use crate::ring_buffer::{RingBuffer, BufferMetrics, WaitStrategy};
+use crate::ring_buffer::wait::Notifier;
+use crate::ring_buffer::byte_buffer::{ByteRingBuffer, SlotError, MAX_PAYLOAD_SIZE};
 use super::message::Message;
+use super::subscriber::LatestSubscriber;
+use super::typed_view::TypedView;
+use crate::uart::{FromFrame, ToFrame};
+use crate::poison::MutexExt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PublishError{
+    TooLarge{ len: usize, max: usize },
+    RateLimited,
+}
+
+impl fmt::Display for PublishError{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        match self{
+            PublishError::TooLarge{ len, max } =>{
+                write!(f, "payload of {} bytes exceeds the {} byte slot limit", len, max)
+            }
+            PublishError::RateLimited => write!(f, "publish arrived faster than the topic's max rate"),
+        }
+    }
+}
+
+impl std::error::Error for PublishError{}
+
+//what ByteTopic::publish_all did with a batch
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PublishSummary{
+    pub published: usize,
+    pub dropped_oversize: usize,
+    pub rate_limited: usize,
+    //published by this batch but already overwritten by its own later
+    //messages; an estimate, a consumer reading meanwhile can save some
+    pub lapped: usize,
+}
+
+impl PublishSummary{
+    //messages of the batch still in the buffer right after it
+    pub fn retained(&self) -> usize{
+        self.published - self.lapped
+    }
+}
 
 pub struct Topic<T: Message>{
     name: String,
@@ -28,12 +73,30 @@ impl<T: Message> Topic<T>{
         self.buffer.pop()
     }
 
+    pub fn recv_timeout(&self, timeout: Duration, strategy: WaitStrategy) -> Option<T>{
+        self.buffer.pop_timeout(timeout, strategy)
+    }
+
+    //next message after `cursor` without consuming it, see RingBuffer::pop_from
+    pub fn pop_from(&self, cursor: u64) -> Option<(T, u64)>{
+        self.buffer.pop_from(cursor)
+    }
+
     pub fn peek_latest(&self) -> Option<(T, u64)>{
         self.buffer.peek_latest()
     }
     
-    pub fn peek_latest_ref(&self) -> Option<(&T, u64)>{
-        self.buffer.peek_latest_ref()
+    /// # Safety
+    /// See RingBuffer::peek_latest_ref: no publish may reach the slot while
+    /// the reference is alive. with_latest is the safe form
+    pub unsafe fn peek_latest_ref(&self) -> Option<(&T, u64)>{
+        unsafe{ self.buffer.peek_latest_ref() }
+    }
+
+    //borrow the latest message without cloning it; None only when empty.
+    //publishes that lap onto it wait for f, see RingBuffer::with_latest
+    pub fn with_latest<R>(&self, f: impl FnOnce(&T, u64) -> R) -> Option<R>{
+        self.buffer.with_latest(f)
     }
 
     pub fn latest_epoch(&self) -> u64{
@@ -52,6 +115,10 @@ impl<T: Message> Topic<T>{
         self.buffer.capacity()
     }
 
+    pub fn memory_footprint(&self) -> usize{
+        self.buffer.memory_footprint()
+    }
+
     pub fn buffer(&self) -> Arc<RingBuffer<T>>{
         Arc::clone(&self.buffer)
     }
@@ -66,42 +133,218 @@ impl<T: Message> Clone for Topic<T>{
     }
 }
 
+//minimum spacing between accepted publishes, shared by clones of the topic
+struct RateLimit{
+    min_interval: Duration,
+    last_accepted: Mutex<Option<Instant>>,
+    rejected: AtomicU64,
+}
+
+impl RateLimit{
+    fn admit(&self, now: Instant) -> bool{
+        let mut last = self.last_accepted.lock_unpoisoned();
+        if last.is_some_and(|t| now.duration_since(t) < self.min_interval){
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        *last = Some(now);
+        true
+    }
+}
+
 pub struct ByteTopic{
     name: String,
     buffer: Arc<ByteRingBuffer>,
+    rate_limit: Option<Arc<RateLimit>>,
+    //highest epoch a consumer confirmed it has handled
+    acked: Arc<AtomicU64>,
+    //fired after every publish, shared by all topics of one registry
+    signal: Option<Arc<Notifier>>,
+    //every message starts with the id byte of the BytePublisher that sent it
+    tagged: bool,
 }
 
 impl ByteTopic{
     pub fn new(name: &str, capacity: usize) -> Self{
+        Self::with_buffer(name, ByteRingBuffer::new(capacity))
+    }
+
+    //receivers get urgent messages (e-stop) ahead of routine ones still
+    //queued. routine messages starve while urgent ones keep coming: they
+    //wait, and any that age out of the window are overwritten and counted
+    //as dropped, so size capacity for the longest urgent burst. safe to
+    //share between subscribers, each message goes to one of them
+    pub fn new_prioritized(name: &str, capacity: usize) -> Self{
+        Self::with_buffer(name, ByteRingBuffer::new_prioritized(capacity))
+    }
+
+    fn with_buffer(name: &str, buffer: ByteRingBuffer) -> Self{
         ByteTopic{
             name: name.to_string(),
-            buffer: Arc::new(ByteRingBuffer::new(capacity)),
+            buffer: Arc::new(buffer),
+            rate_limit: None,
+            acked: Arc::new(AtomicU64::new(0)),
+            signal: None,
+            tagged: false,
         }
     }
 
+    //opt in to publisher ids: writers must use BytePublisher::new_tagged,
+    //readers get the id back from try_receive_tagged. costs one payload byte
+    pub fn with_tagged_publishers(mut self) -> Self{
+        self.tagged = true;
+        self
+    }
+
+    pub fn is_tagged(&self) -> bool{
+        self.tagged
+    }
+
+    pub(crate) fn with_signal(mut self, signal: Arc<Notifier>) -> Self{
+        self.signal = Some(signal);
+        self
+    }
+
+    pub(crate) fn signal(&self) -> Option<&Arc<Notifier>>{
+        self.signal.as_ref()
+    }
+
+    //reject publishes arriving less than 1/hz after the last accepted one
+    pub fn with_max_rate(mut self, hz: f64) -> Self{
+        assert!(hz > 0.0, "max rate must be positive");
+        self.rate_limit = Some(Arc::new(RateLimit{
+            min_interval: Duration::from_secs_f64(1.0 / hz),
+            last_accepted: Mutex::new(None),
+            rejected: AtomicU64::new(0),
+        }));
+        self
+    }
+
     pub fn name(&self) -> &str{
         &self.name
     }
 
     pub fn publish(&self, data: &[u8]) -> Option<u64>{
-        self.buffer.push(data)
+        self.publish_checked(data).ok()
+    }
+
+    pub fn publish_checked(&self, data: &[u8]) -> Result<u64, PublishError>{
+        self.publish_with_priority(data, 0)
+    }
+
+    //priority only matters on a topic made with new_prioritized
+    pub fn publish_with_priority(&self, data: &[u8], priority: u8) -> Result<u64, PublishError>{
+        match self.rate_limit{
+            Some(_) => self.publish_at(data, priority, Instant::now()),
+            None => self.push(data, priority),
+        }
+    }
+
+    //publish a batch (log replay, fixtures) and say how much of it stuck
+    pub fn publish_all(&self, msgs: &[&[u8]]) -> PublishSummary{
+        let mut summary = PublishSummary::default();
+        for msg in msgs{
+            match self.publish_checked(msg){
+                Ok(_) => summary.published += 1,
+                Err(PublishError::TooLarge{ .. }) => summary.dropped_oversize += 1,
+                Err(PublishError::RateLimited) => summary.rate_limited += 1,
+            }
+        }
+        summary.lapped = summary.published.saturating_sub(self.capacity());
+        summary
+    }
+
+    fn publish_at(&self, data: &[u8], priority: u8, now: Instant) -> Result<u64, PublishError>{
+        if data.len() > MAX_PAYLOAD_SIZE{
+            return Err(PublishError::TooLarge{ len: data.len(), max: MAX_PAYLOAD_SIZE });
+        }
+        if let Some(limit) = &self.rate_limit{
+            if !limit.admit(now){
+                return Err(PublishError::RateLimited);
+            }
+        }
+        self.push(data, priority)
+    }
+
+    fn push(&self, data: &[u8], priority: u8) -> Result<u64, PublishError>{
+        let epoch = self.buffer.push_with_priority(data, priority)
+            .map_err(|SlotError::TooLarge{ len, max_payload }| PublishError::TooLarge{ len, max: max_payload })?;
+        if let Some(signal) = &self.signal{
+            signal.notify();
+        }
+        Ok(epoch)
+    }
+
+    pub fn rate_limited_count(&self) -> u64{
+        self.rate_limit.as_ref().map_or(0, |limit| limit.rejected.load(Ordering::Relaxed))
     }
 
     pub fn try_receive(&self) -> Option<(Vec<u8>, u64)>{
         self.buffer.pop()
     }
+
+    //try_receive into a caller-owned buffer, see ByteRingBuffer::pop_into
+    pub fn try_receive_into(&self, out: &mut Vec<u8>) -> Option<u64>{
+        self.buffer.pop_into(out)
+    }
+
+    //(publisher id, payload, epoch); an empty message has no id and is skipped
+    pub fn try_receive_tagged(&self) -> Option<(u8, Vec<u8>, u64)>{
+        assert!(self.tagged, "topic '{}' does not carry publisher ids", self.name);
+        loop{
+            let (mut data, epoch) = self.buffer.pop()?;
+            if !data.is_empty(){
+                let id = data.remove(0);
+                return Some((id, data, epoch));
+            }
+        }
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration, strategy: WaitStrategy) -> Option<(Vec<u8>, u64)>{
+        self.buffer.pop_timeout(timeout, strategy)
+    }
+
+    pub fn pop_from(&self, cursor: u64) -> Option<(Vec<u8>, u64)>{
+        self.buffer.pop_from(cursor)
+    }
+
+    //see ByteRingBuffer::record_publish_times
+    pub fn record_publish_times(&self){
+        self.buffer.record_publish_times();
+    }
+
+    pub fn pop_from_stamped(&self, cursor: u64) -> Option<(Vec<u8>, u64, u64)>{
+        self.buffer.pop_from_stamped(cursor)
+    }
     
     pub fn peek_latest(&self) -> Option<(Vec<u8>, u64)>{
         self.buffer.peek_latest()
     }
     
-    pub fn peek_latest_ref(&self) -> Option<(&[u8], u64)>{
-        self.buffer.peek_latest_ref()
+    /// # Safety
+    /// See ByteRingBuffer::peek_latest_ref: no publish may reach the slot
+    /// while the slice is alive. read_guard on the buffer is the safe form
+    pub unsafe fn peek_latest_ref(&self) -> Option<(&[u8], u64)>{
+        unsafe{ self.buffer.peek_latest_ref() }
     }
     
     pub fn latest_epoch(&self) -> u64{
         self.buffer.latest_epoch()
     }
+
+    //for check-then-act: note the epoch of what a computation used, then ask
+    //afterwards whether anything newer arrived
+    pub fn changed_since(&self, epoch: u64) -> bool{
+        self.latest_epoch() > epoch
+    }
+
+    //latest message, but only if it is newer than `since`
+    pub fn peek_latest_if_changed(&self, since: u64) -> Option<(Vec<u8>, u64)>{
+        if !self.changed_since(since){
+            return None;
+        }
+        self.peek_latest().filter(|&(_, epoch)| epoch > since)
+    }
     
     pub fn len(&self) -> usize{
         self.buffer.len()
@@ -115,15 +358,63 @@ impl ByteTopic{
         self.buffer.capacity()
     }
     
+    pub fn memory_footprint(&self) -> usize{
+        self.buffer.memory_footprint()
+    }
+    
+    pub fn is_full(&self) -> bool{
+        self.buffer.is_full()
+    }
+
+    //publishes that overwrote a message nobody had read
+    pub fn dropped_count(&self) -> u64{
+        self.buffer.dropped_count()
+    }
+
+    pub fn metrics(&self) -> BufferMetrics{
+        self.buffer.metrics()
+    }
+
     pub fn buffer(&self) -> Arc<ByteRingBuffer>{
         Arc::clone(&self.buffer)
     }
+
+    //true if both handles publish into and read from the same ring buffer
+    pub fn same_buffer(&self, other: &ByteTopic) -> bool{
+        Arc::ptr_eq(&self.buffer, &other.buffer)
+    }
+
+    //consumer side: everything up to `epoch` is handled. the watermark only
+    //moves forward, so late or duplicate acks are harmless
+    pub fn ack(&self, epoch: u64){
+        self.acked.fetch_max(epoch, Ordering::SeqCst);
+    }
+
+    //producer side: resources tied to epochs <= this may be reused
+    pub fn acked_epoch(&self) -> u64{
+        self.acked.load(Ordering::SeqCst)
+    }
+
+    pub fn subscribe_latest_only(self: &Arc<Self>) -> LatestSubscriber{
+        LatestSubscriber::new(Arc::clone(self))
+    }
+
+    //read and write this topic as T through the frame codec; raw byte
+    //publishers (ffi, bridge) and typed users then share one buffer
+    pub fn as_typed<T: FromFrame + ToFrame>(&self) -> TypedView<'_, T>{
+        assert!(!self.tagged, "topic '{}' carries publisher ids, its payloads are not plain frames bruddaa!!", self.name);
+        TypedView::new(self)
+    }
 }
 impl Clone for ByteTopic{
     fn clone(&self) -> Self{
         ByteTopic{
             name: self.name.clone(),
             buffer: Arc::clone(&self.buffer),
+            rate_limit: self.rate_limit.clone(),
+            acked: Arc::clone(&self.acked),
+            signal: self.signal.clone(),
+            tagged: self.tagged,
         }
     }
 }
@@ -170,6 +461,34 @@ mod tests{
         assert_eq!(topic.len(), 3);
     }
 
+    #[test]
+    fn test_typed_topic_with_latest_borrows(){
+        use std::sync::atomic::AtomicUsize;
+
+        static CLONES: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Default)]
+        struct Frame{
+            pixels: Vec<u8>,
+            exposure: u32,
+        }
+
+        impl Clone for Frame{
+            fn clone(&self) -> Self{
+                CLONES.fetch_add(1, Ordering::SeqCst);
+                Frame{ pixels: self.pixels.clone(), exposure: self.exposure }
+            }
+        }
+
+        let topic: Topic<Frame> = Topic::new("/camera", 4);
+        topic.publish(Frame{ pixels: vec![0; 1024], exposure: 10 });
+        topic.publish(Frame{ pixels: vec![0; 1024], exposure: 20 });
+
+        let exposure = topic.with_latest(|frame, epoch| (frame.exposure, frame.pixels.len(), epoch));
+        assert_eq!(exposure, Some((20, 1024, 2)));
+        assert_eq!(CLONES.load(Ordering::SeqCst), 0);
+    }
+
     #[test]
     fn test_byte_topic_publish_subscribe(){
         let topic = ByteTopic::new("/camera/raw", 8);
@@ -186,6 +505,117 @@ mod tests{
         assert_eq!(data2, frame2);
     }
     
+    #[test]
+    fn test_byte_topic_publish_checked(){
+        let topic = ByteTopic::new("/checked", 4);
+        assert_eq!(topic.publish_checked(&[1, 2, 3]), Ok(1));
+
+        let too_large = vec![0u8; MAX_PAYLOAD_SIZE + 10];
+        let err = topic.publish_checked(&too_large).unwrap_err();
+        assert_eq!(err, PublishError::TooLarge{ len: MAX_PAYLOAD_SIZE + 10, max: MAX_PAYLOAD_SIZE });
+        assert_eq!(topic.len(), 1);
+    }
+
+    #[test]
+    fn test_byte_topic_max_rate(){
+        let topic = ByteTopic::new("/cam", 64).with_max_rate(100.0);
+        let start = Instant::now();
+
+        //1 kHz publisher against a 100 Hz limit for one second
+        let accepted = (0..1000u64)
+            .filter(|&i| topic.publish_at(&[1], 0, start + Duration::from_millis(i)).is_ok())
+            .count();
+
+        assert_eq!(accepted, 100);
+        assert_eq!(topic.rate_limited_count(), 900);
+        assert_eq!(topic.latest_epoch(), 100);
+        assert_eq!(topic.publish_at(&[1], 0, start + Duration::from_millis(995)), Err(PublishError::RateLimited));
+    }
+
+    #[test]
+    fn test_byte_topic_rate_limit_shared_by_clones(){
+        let topic = ByteTopic::new("/cam", 8).with_max_rate(10.0);
+        let clone = topic.clone();
+        assert!(topic.publish(&[1]).is_some());
+        assert_eq!(clone.publish_checked(&[2]), Err(PublishError::RateLimited));
+        assert_eq!(topic.rate_limited_count(), 1);
+        assert_eq!(ByteTopic::new("/free", 8).rate_limited_count(), 0);
+    }
+
+    #[test]
+    fn test_byte_topic_publish_all_summary(){
+        let topic = ByteTopic::new("/replay", 4);
+        let oversized = [0u8; MAX_PAYLOAD_SIZE + 1];
+        let batch: Vec<&[u8]> = vec![&[1], &[2], &oversized, &[3], &[4], &[5], &[6]];
+
+        let summary = topic.publish_all(&batch);
+        assert_eq!(summary, PublishSummary{ published: 6, dropped_oversize: 1, rate_limited: 0, lapped: 2 });
+        assert_eq!(summary.retained(), topic.len());
+        assert_eq!(topic.dropped_count(), 2);
+
+        let survivors: Vec<_> = std::iter::from_fn(|| topic.try_receive()).map(|(data, _)| data[0]).collect();
+        assert_eq!(survivors, vec![3, 4, 5, 6]);
+
+        //a batch that fits laps nothing
+        assert_eq!(topic.publish_all(&[&[7], &[8]]).lapped, 0);
+    }
+
+    #[test]
+    fn test_byte_topic_changed_since(){
+        let topic = ByteTopic::new("/depth", 4);
+        assert!(!topic.changed_since(0));
+        assert_eq!(topic.peek_latest_if_changed(0), None);
+
+        topic.publish(&[1]);
+        let (_, used) = topic.peek_latest().unwrap();
+        assert!(!topic.changed_since(used));
+        assert_eq!(topic.peek_latest_if_changed(used), None);
+
+        topic.publish(&[2]);
+        topic.publish(&[3]);
+        assert!(topic.changed_since(used));
+        assert_eq!(topic.peek_latest_if_changed(used), Some((vec![3], 3)));
+        //peeking does not consume, the check stays true until `since` moves
+        assert!(topic.changed_since(used));
+        assert!(!topic.changed_since(3));
+    }
+
+    #[test]
+    fn test_byte_topic_same_buffer(){
+        let topic = ByteTopic::new("/imu", 8);
+        let clone = topic.clone();
+        let other = ByteTopic::new("/imu", 8);
+
+        assert!(topic.same_buffer(&clone));
+        assert!(clone.same_buffer(&topic));
+        assert!(!topic.same_buffer(&other));
+    }
+
+    #[test]
+    fn test_byte_topic_ack_watermark(){
+        let topic = ByteTopic::new("/ack", 8);
+        let producer_view = topic.clone();
+        assert_eq!(producer_view.acked_epoch(), 0);
+
+        topic.ack(3);
+        topic.ack(2);
+        assert_eq!(producer_view.acked_epoch(), 3);
+    }
+
+    #[test]
+    fn test_byte_topic_estop_overtakes_routine(){
+        const ESTOP: u8 = 3;
+        let commands = ByteTopic::new_prioritized("/cmd", 16);
+        commands.publish(b"surge");
+        commands.publish(b"yaw");
+        commands.publish_with_priority(b"estop", ESTOP).unwrap();
+        assert!(commands.buffer().is_prioritized());
+
+        assert_eq!(commands.try_receive().unwrap().0, b"estop");
+        assert_eq!(commands.try_receive().unwrap().0, b"surge");
+        assert_eq!(commands.try_receive().unwrap().0, b"yaw");
+    }
+
     #[test]
     fn test_topic_clone_shares_buffer(){
         let topic1: Topic<i32> = Topic::new("/shared", 8);
@@ -0,0 +1,129 @@
+use std::marker::PhantomData;
+use std::time::Duration;
+use crate::ring_buffer::WaitStrategy;
+use crate::uart::{FromFrame, ToFrame};
+use super::topic::{ByteTopic, PublishError};
+
+//typed publish/receive over a byte topic's own buffer, for when C writes raw
+//frames and Rust wants ImuMsg back (or the other way round). nothing is
+//copied or converted up front: every call goes through the frame codec.
+pub struct TypedView<'a, T>{
+    topic: &'a ByteTopic,
+    _msg: PhantomData<fn() -> T>,
+}
+
+impl<'a, T: FromFrame + ToFrame> TypedView<'a, T>{
+    pub(crate) fn new(topic: &'a ByteTopic) -> Self{
+        TypedView{ topic, _msg: PhantomData }
+    }
+
+    pub fn topic(&self) -> &'a ByteTopic{
+        self.topic
+    }
+
+    pub fn publish(&self, msg: &T) -> Option<u64>{
+        self.topic.publish(&msg.to_frame())
+    }
+
+    pub fn publish_checked(&self, msg: &T) -> Result<u64, PublishError>{
+        self.topic.publish_checked(&msg.to_frame())
+    }
+
+    //payloads too short for T are consumed and skipped, like corrupt slots
+    pub fn try_receive(&self) -> Option<(T, u64)>{
+        loop{
+            let (data, epoch) = self.topic.try_receive()?;
+            if let Some(msg) = T::from_frame(&data){
+                return Some((msg, epoch));
+            }
+        }
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration, strategy: WaitStrategy) -> Option<(T, u64)>{
+        let (data, epoch) = self.topic.recv_timeout(timeout, strategy)?;
+        match T::from_frame(&data){
+            Some(msg) => Some((msg, epoch)),
+            None => self.try_receive(),
+        }
+    }
+
+    //None if empty or the latest payload does not decode as T. decodes a
+    //checked copy, so a publish racing the read can't tear the frame
+    pub fn peek_latest(&self) -> Option<(T, u64)>{
+        let (data, epoch) = self.topic.peek_latest()?;
+        T::from_frame(&data).map(|msg| (msg, epoch))
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::uart::{ImuMsg, DepthMsg};
+
+    fn imu(seed: f32) -> ImuMsg{
+        ImuMsg{
+            accel_x: seed, accel_y: seed + 1.0, accel_z: 9.81,
+            gyro_x: 0.1, gyro_y: 0.2, gyro_z: 0.3,
+            mag_x: 20.0, mag_y: -5.0, mag_z: 40.0,
+        }
+    }
+
+    #[test]
+    fn test_bytes_and_typed_share_one_buffer(){
+        let topic = ByteTopic::new("/imu", 8);
+        let view = topic.as_typed::<ImuMsg>();
+
+        //raw bytes in, as the C side would publish them
+        topic.publish(&imu(1.0).to_frame());
+        assert_eq!(view.peek_latest(), Some((imu(1.0), 1)));
+        assert_eq!(view.try_receive(), Some((imu(1.0), 1)));
+
+        //typed in, raw bytes out
+        assert_eq!(view.publish(&imu(2.0)), Some(2));
+        let (data, epoch) = topic.try_receive().unwrap();
+        assert_eq!(epoch, 2);
+        assert_eq!(ImuMsg::from_bytes(&data), Some(imu(2.0)));
+        assert_eq!(view.try_receive(), None);
+    }
+
+    #[test]
+    fn test_peek_latest_never_decodes_a_torn_frame(){
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let topic = Arc::new(ByteTopic::new("/imu", 2));
+        topic.publish(&imu(0.0).to_frame());
+        let done = Arc::new(AtomicBool::new(false));
+
+        //raw publishes from another thread, as C would make them
+        let writer ={
+            let topic = Arc::clone(&topic);
+            let done = Arc::clone(&done);
+            std::thread::spawn(move ||{
+                let mut seed = 0.0;
+                while !done.load(Ordering::Relaxed){
+                    seed += 1.0;
+                    topic.publish(&imu(seed).to_frame());
+                }
+            })
+        };
+
+        let view = topic.as_typed::<ImuMsg>();
+        for _ in 0..20_000{
+            let (msg, _) = view.peek_latest().unwrap();
+            assert_eq!(msg, imu(msg.accel_x), "frame mixed from two publishes");
+        }
+        done.store(true, Ordering::Relaxed);
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn test_short_payloads_are_skipped(){
+        let topic = ByteTopic::new("/imu", 8);
+        let view = topic.as_typed::<ImuMsg>();
+        topic.publish(&DepthMsg{ depth: 3.0 }.to_frame());
+        assert_eq!(view.peek_latest(), None);
+        topic.publish(&imu(4.0).to_frame());
+        assert_eq!(view.try_receive(), Some((imu(4.0), 2)));
+    }
+}
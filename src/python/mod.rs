@@ -1,7 +1,11 @@
 use pyo3::prelude::*;
+use pyo3::buffer::PyBuffer;
 use pyo3::exceptions::PyValueError;
+use pyo3::types::{PyBytes, PyDict, PyList};
 use std::sync::Arc;
 use crate::pubsub::{TopicRegistry, ByteTopic};
+use crate::ring_buffer::WaitStrategy;
+use crate::ring_buffer::byte_buffer::MAX_PAYLOAD_SIZE;
 
 #[pyclass]
 pub struct PyBibiRegistry{
@@ -17,9 +21,22 @@ impl PyBibiRegistry{
         }
     }
 
-    fn get_byte_topic(&self, name: &str, capacity: usize) -> PyBibiByteTopic{
-        let topic = self.inner.get_or_create_byte(name, capacity);
-        PyBibiByteTopic{ inner: topic }
+    fn get_byte_topic(&self, name: &str, capacity: usize) -> PyResult<PyBibiByteTopic>{
+        let topic = self.inner.try_get_or_create_byte(name, capacity)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(PyBibiByteTopic::new(topic))
+    }
+
+    //byte topic that only takes msg_size-byte messages, e.g. 36 for ImuMsg
+    fn get_typed_topic(&self, name: &str, capacity: usize, msg_size: usize) -> PyResult<PyBibiTypedTopic>{
+        if msg_size == 0 || msg_size > MAX_PAYLOAD_SIZE{
+            return Err(PyValueError::new_err(
+                format!("msg_size must be 1..={}, got {}", MAX_PAYLOAD_SIZE, msg_size)
+            ));
+        }
+        let topic = self.inner.try_get_or_create_byte(name, capacity)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(PyBibiTypedTopic{ inner: topic, msg_size })
     }
 
     fn topic_count(&self) -> usize{
@@ -27,9 +44,39 @@ impl PyBibiRegistry{
     }
 }
 
+//raw bytes of any buffer-protocol object (bytes, bytearray, memoryview,
+//array.array, numpy arrays of any dtype) without copying. the memory must
+//be C-contiguous; its dtype doesn't matter, the bytes are taken as they lie
+fn byte_view(obj: &PyAny, writable: bool) -> PyResult<PyBuffer<u8>>{
+    let view = obj.py().import("builtins")?.getattr("memoryview")?.call1((obj,))?;
+    if !view.getattr("c_contiguous")?.extract::<bool>()?{
+        return Err(PyValueError::new_err("buffer is not C-contiguous, copy it first (numpy.ascontiguousarray)"));
+    }
+    if writable && view.getattr("readonly")?.extract::<bool>()?{
+        return Err(PyValueError::new_err("buffer is read-only"));
+    }
+    PyBuffer::get(view.call_method1("cast", ("B",))?)
+}
+
+//only valid while the GIL is held, like the buffer itself
+fn view_bytes<'a>(_py: Python<'a>, buf: &'a PyBuffer<u8>) -> &'a [u8]{
+    if buf.len_bytes() == 0{
+        return &[];
+    }
+    unsafe{ std::slice::from_raw_parts(buf.buf_ptr() as *const u8, buf.len_bytes()) }
+}
+
 #[pyclass]
 pub struct PyBibiByteTopic{
     inner: Arc<ByteTopic>,
+    //reused by try_receive_into so it doesn't allocate per message
+    scratch: Mutex<Vec<u8>>,
+}
+
+impl PyBibiByteTopic{
+    fn new(inner: Arc<ByteTopic>) -> Self{
+        PyBibiByteTopic{ inner, scratch: Mutex::new(Vec::new()) }
+    }
 }
 
 #[pymethods]
@@ -38,15 +85,54 @@ impl PyBibiByteTopic{
         self.inner.name().to_string()
     }
 
-    fn publish(&self, data: &[u8]) -> PyResult<u64>{
-        match self.inner.publish(data){
-            Some(epoch) => Ok(epoch),
-            None => Err(PyValueError::new_err("Data too large for slot")),
-        }
+    //takes bytes or any C-contiguous buffer (numpy array, memoryview, ...).
+    //bytes can't change under us, so the GIL is released while they are
+    //copied in. any other buffer keeps the GIL held: it stops other threads
+    //from resizing or writing `data` while we read it
+    fn publish(&self, py: Python<'_>, data: &PyAny) -> PyResult<u64>{
+        let published = match data.downcast::<PyBytes>(){
+            Ok(bytes) =>{
+                let bytes = bytes.as_bytes();
+                py.allow_threads(|| self.inner.publish(bytes))
+            }
+            Err(_) =>{
+                let buf = byte_view(data, false)?;
+                self.inner.publish(view_bytes(py, &buf))
+            }
+        };
+        published.ok_or_else(|| PyValueError::new_err("Data too large for slot"))
     }
 
-    fn try_receive(&self) -> Option<(Vec<u8>, u64)>{
-        self.inner.try_receive()
+    //touches no python objects, so other threads run during the copy out
+    fn try_receive(&self, py: Python<'_>) -> Option<(Vec<u8>, u64)>{
+        py.allow_threads(|| self.inner.try_receive())
+    }
+
+    //block until a message arrives or `timeout` seconds pass, with the GIL
+    //released so other python threads run while we wait
+    fn recv_timeout(&self, py: Python<'_>, timeout: f64) -> PyResult<Option<(Vec<u8>, u64)>>{
+        let timeout = Duration::try_from_secs_f64(timeout)
+            .map_err(|_| PyValueError::new_err("timeout must be a non-negative number of seconds"))?;
+        Ok(py.allow_threads(|| self.inner.recv_timeout(timeout, WaitStrategy::default())))
+    }
+
+    //copy the next message into a writable C-contiguous buffer and return
+    //(bytes written, epoch). a message longer than `out` is dropped with a
+    //ValueError, so size `out` for the largest payload (244 bytes always fits)
+    fn try_receive_into(&self, out: &PyAny) -> PyResult<Option<(usize, u64)>>{
+        let buf = byte_view(out, true)?;
+        let mut scratch = self.scratch.lock_unpoisoned();
+        let Some(epoch) = self.inner.try_receive_into(&mut scratch) else{
+            return Ok(None);
+        };
+        let len = scratch.len();
+        if len > buf.len_bytes(){
+            return Err(PyValueError::new_err(
+                format!("message of {} bytes does not fit in {} (epoch {} dropped)", len, buf.len_bytes(), epoch)
+            ));
+        }
+        unsafe{ std::ptr::copy_nonoverlapping(scratch.as_ptr(), buf.buf_ptr() as *mut u8, len); }
+        Ok(Some((len, epoch)))
     }
 
     fn peek_latest(&self) -> Option<(Vec<u8>, u64)>{
@@ -68,6 +154,14 @@ impl PyBibiByteTopic{
     fn capacity(&self) -> usize{
         self.inner.capacity()
     }
+
+    fn is_full(&self) -> bool{
+        self.inner.is_full()
+    }
+
+    fn dropped_count(&self) -> u64{
+        self.inner.dropped_count()
+    }
 }
 
 #[pyclass]
@@ -121,11 +215,60 @@ impl PyBibiTypedTopic{
 
 // AUV Controller Python bindings
 use crate::auv::{AuvController, thrust_mixer::ThrustCommand};
+use crate::poison::MutexExt;
+use std::sync::{Mutex, Weak};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+// How long a shutdown waits for the loop to write the stop PWM and exit
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Every controller still running, so the atexit hook can stop them even if
+// Python never collects the objects before the process ends
+static LIVE_CONTROLLERS: Mutex<Vec<Weak<ControllerLife>>> = Mutex::new(Vec::new());
+
+// A controller plus its loop thread; finish() is the single shutdown path
+// shared by shutdown(), __exit__, Drop and the atexit hook
+struct ControllerLife {
+    controller: Arc<AuvController>,
+    handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl ControllerLife {
+    // Idempotent: whoever takes the handle does the work, later calls
+    // (and calls after the loop already died) return Ok
+    fn finish(&self, timeout: Duration) -> std::io::Result<()> {
+        let Some(handle) = self.handle.lock_unpoisoned().take() else {
+            return Ok(());
+        };
+        self.controller.stop();
+        if handle.is_finished() {
+            return Ok(());
+        }
+        let result = self.controller.shutdown_graceful(timeout);
+        // On timeout the thread is still in its loop; leave it detached
+        // rather than block interpreter exit on it
+        if !matches!(&result, Err(e) if e.kind() == std::io::ErrorKind::TimedOut) {
+            let _ = handle.join();
+        }
+        result
+    }
+}
+
+#[pyfunction]
+fn _shutdown_all_controllers() {
+    let live: Vec<_> = LIVE_CONTROLLERS.lock_unpoisoned().drain(..).collect();
+    for life in live.iter().filter_map(Weak::upgrade) {
+        if let Err(e) = life.finish(SHUTDOWN_TIMEOUT) {
+            eprintln!("[AUV] Shutdown at exit failed: {}", e);
+        }
+    }
+}
 
 #[pyclass]
 pub struct PyAuvController {
     inner: Arc<AuvController>,
-    _handle: Option<std::thread::JoinHandle<()>>,
+    life: Arc<ControllerLife>,
 }
 
 #[pymethods]
@@ -136,16 +279,33 @@ impl PyAuvController {
         let controller = Arc::new(AuvController::new(port).with_baud(baud));
         let ctrl = controller.clone();
         let handle = ctrl.start_background();
+        let life = Arc::new(ControllerLife {
+            controller: controller.clone(),
+            handle: Mutex::new(Some(handle)),
+        });
+        let mut live = LIVE_CONTROLLERS.lock_unpoisoned();
+        live.retain(|w| w.strong_count() > 0);
+        live.push(Arc::downgrade(&life));
+        drop(live);
         
         // Give it time to connect
         std::thread::sleep(std::time::Duration::from_millis(500));
         
         PyAuvController {
             inner: controller,
-            _handle: Some(handle),
+            life,
         }
     }
     
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+    
+    fn __exit__(&self, _exc_type: Option<&PyAny>, _exc: Option<&PyAny>, _tb: Option<&PyAny>) -> PyResult<bool> {
+        self.shutdown(SHUTDOWN_TIMEOUT.as_secs_f64())?;
+        Ok(false)
+    }
+    
     fn set_surge(&self, value: f32) {
         self.inner.set_surge(value);
     }
@@ -180,6 +340,34 @@ impl PyAuvController {
         self.inner.stop();
     }
     
+    fn arm(&self) {
+        self.inner.arm();
+    }
+    
+    // Commands set while disarmed are kept but only act after arm()
+    fn disarm(&self) {
+        self.inner.disarm();
+    }
+    
+    fn is_armed(&self) -> bool {
+        self.inner.is_armed()
+    }
+    
+    // Metres; None releases the hold
+    fn set_depth_setpoint(&self, depth: Option<f32>) {
+        self.inner.set_depth_setpoint(depth);
+    }
+    
+    // Degrees, turning the shorter way; None releases the hold
+    fn set_heading_setpoint(&self, heading: Option<f32>) {
+        self.inner.set_heading_setpoint(heading);
+    }
+    
+    // Disarm and send neutral at once, without waiting for the next 50Hz slot
+    fn emergency_stop(&self) {
+        self.inner.emergency_stop();
+    }
+    
     fn get_orientation(&self) -> Option<(f32, f32, f32)> {
         self.inner.get_orientation()
     }
@@ -188,25 +376,80 @@ impl PyAuvController {
         self.inner.get_depth()
     }
     
-    fn shutdown(&self) {
-        self.inner.stop();
-        self.inner.shutdown();
+    fn get_orientation_or(&self, default: (f32, f32, f32)) -> (f32, f32, f32) {
+        self.inner.get_orientation_or(default)
+    }
+    
+    fn get_depth_or(&self, default: f32) -> f32 {
+        self.inner.get_depth_or(default)
+    }
+    
+    // False while the STM32 has gone silent and thrusters are held at neutral
+    fn is_connected(&self) -> bool {
+        self.inner.is_connected()
+    }
+    
+    // Same fields as the Rust AuvStatus, led as its variant name
+    fn status(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let status = self.inner.status();
+        
+        let topics = PyList::empty(py);
+        for topic in status.topics {
+            let entry = PyDict::new(py);
+            entry.set_item("name", topic.name)?;
+            entry.set_item("len", topic.len)?;
+            entry.set_item("capacity", topic.capacity)?;
+            entry.set_item("published", topic.published)?;
+            entry.set_item("dropped", topic.dropped)?;
+            entry.set_item("rate_hz", topic.rate_hz)?;
+            topics.append(entry)?;
+        }
+        
+        let dict = PyDict::new(py);
+        dict.set_item("armed", status.armed)?;
+        dict.set_item("failsafe", status.failsafe)?;
+        dict.set_item("led", format!("{:?}", status.led))?;
+        dict.set_item("link_ok", status.link_ok)?;
+        dict.set_item("tx_errors", status.tx_errors)?;
+        dict.set_item("rx_bad_lengths", status.rx_bad_lengths)?;
+        dict.set_item("heartbeat_age_ms", status.heartbeat_age_ms)?;
+        dict.set_item("imu_age_ms", status.imu_age_ms)?;
+        dict.set_item("orientation_age_ms", status.orientation_age_ms)?;
+        dict.set_item("depth_age_ms", status.depth_age_ms)?;
+        dict.set_item("orientation", status.orientation)?;
+        dict.set_item("depth", status.depth)?;
+        dict.set_item("last_pwm", status.last_pwm.to_vec())?;
+        dict.set_item("topics", topics)?;
+        Ok(dict.into())
+    }
+    
+    // Stop the loop, wait for the stop PWM to go out and join the thread.
+    // Safe to call again; raises OSError if the loop didn't finish in time
+    #[pyo3(signature = (timeout = 1.0))]
+    fn shutdown(&self, timeout: f64) -> PyResult<()> {
+        let timeout = Duration::try_from_secs_f64(timeout)
+            .map_err(|_| PyValueError::new_err("timeout must be a non-negative number of seconds"))?;
+        Ok(self.life.finish(timeout)?)
     }
 }
 
 impl Drop for PyAuvController {
     fn drop(&mut self) {
-        self.inner.stop();
-        self.inner.shutdown();
+        if let Err(e) = self.life.finish(SHUTDOWN_TIMEOUT) {
+            eprintln!("[AUV] Shutdown on drop failed: {}", e);
+        }
     }
 }
 
 #[pymodule]
-fn bibi_sync(_py: Python, m: &PyModule) -> PyResult<()>{
+fn bibi_sync(py: Python, m: &PyModule) -> PyResult<()>{
     m.add_class::<PyBibiRegistry>()?;
     m.add_class::<PyBibiByteTopic>()?;
     m.add_class::<PyBibiTypedTopic>()?;
     m.add_class::<PyAuvController>()?;
+    //atexit runs before the interpreter tears down objects and threads
+    m.add_function(wrap_pyfunction!(_shutdown_all_controllers, m)?)?;
+    py.import("atexit")?.call_method1("register", (m.getattr("_shutdown_all_controllers")?,))?;
     Ok(())
 }
 
@@ -217,31 +460,46 @@ mod tests{
     #[test]
     fn test_py_registry(){
         let registry = PyBibiRegistry::new();
-        let topic = registry.get_byte_topic("/test", 8);
+        let topic = registry.get_byte_topic("/test", 8).unwrap();
         assert_eq!(topic.name(), "/test");
     }
 
     #[test]
     fn test_py_publish_receive(){
         let registry = PyBibiRegistry::new();
-        let topic = registry.get_byte_topic("/test", 8);
+        let topic = registry.get_byte_topic("/test", 8).unwrap();
         
-        let epoch = topic.publish(&[1, 2, 3]).unwrap();
+        let epoch = Python::with_gil(|py| topic.publish(py, PyBytes::new(py, &[1, 2, 3]))).unwrap();
         assert_eq!(epoch, 1);
 
-        let (data, _) = topic.try_receive().unwrap();
+        let (data, _) = Python::with_gil(|py| topic.try_receive(py)).unwrap();
         assert_eq!(data, vec![1, 2, 3]);
     }
 
     #[test]
     fn test_py_shared_topic(){
         let registry = PyBibiRegistry::new();
-        let topic1 = registry.get_byte_topic("/shared", 8);
-        let topic2 = registry.get_byte_topic("/shared", 8);
+        let topic1 = registry.get_byte_topic("/shared", 8).unwrap();
+        let topic2 = registry.get_byte_topic("/shared", 8).unwrap();
 
-        topic1.publish(&[0xAB, 0xCD]).unwrap();
+        Python::with_gil(|py| topic1.publish(py, PyBytes::new(py, &[0xAB, 0xCD]))).unwrap();
         
-        let (data, _) = topic2.try_receive().unwrap();
+        let (data, _) = Python::with_gil(|py| topic2.try_receive(py)).unwrap();
         assert_eq!(data, vec![0xAB, 0xCD]);
     }
+
+    #[test]
+    fn test_py_typed_topic_imu(){
+        let registry = PyBibiRegistry::new();
+        let topic = registry.get_typed_topic("/imu", 8, crate::uart::IMU_MSG_SIZE).unwrap();
+
+        let imu = [0x11; 36];
+        assert_eq!(topic.publish(&imu).unwrap(), 1);
+        assert!(topic.publish(&imu[..12]).is_err());
+        assert_eq!(topic.try_receive().unwrap(), Some((imu.to_vec(), 1)));
+
+        //same buffer as the byte view of that name
+        assert!(registry.get_byte_topic("/imu", 8).unwrap().inner.same_buffer(&topic.inner));
+        assert!(registry.get_typed_topic("/bad", 8, 0).is_err());
+    }
 }
\ No newline at end of file
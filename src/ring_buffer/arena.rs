@@ -0,0 +1,283 @@
+use std::sync::Mutex;
+
+//per-message header: payload length
+const LEN_HEADER: usize = 4;
+//written where a message would straddle the arena end: "continue at offset 0"
+const WRAP_MARKER: u32 = u32::MAX;
+
+struct Arena{
+    bytes: Vec<u8>,
+    head: usize,        //next write offset
+    tail: usize,        //oldest resident message
+    used: usize,        //bytes from tail to head, wrap padding included
+    count: usize,       //resident messages
+    latest_at: usize,   //offset of the newest message
+    tail_epoch: u64,    //epoch of the message at tail
+    write_epoch: u64,
+    dropped: u64,
+}
+
+impl Arena{
+    fn read_len(&self, at: usize) -> u32{
+        let mut b = [0u8; LEN_HEADER];
+        b.copy_from_slice(&self.bytes[at..at + LEN_HEADER]);
+        u32::from_le_bytes(b)
+    }
+
+    fn write_len(&mut self, at: usize, len: u32){
+        self.bytes[at..at + LEN_HEADER].copy_from_slice(&len.to_le_bytes());
+    }
+
+    //step tail over wrap padding so it sits on a real message
+    fn skip_wrap(&mut self){
+        let cap = self.bytes.len();
+        if cap - self.tail < LEN_HEADER || self.read_len(self.tail) == WRAP_MARKER{
+            self.used -= cap - self.tail;
+            self.tail = 0;
+        }
+    }
+
+    fn take_oldest(&mut self) -> Option<(Vec<u8>, u64)>{
+        if self.count == 0{
+            return None;
+        }
+        self.skip_wrap();
+
+        let len = self.read_len(self.tail) as usize;
+        let start = self.tail + LEN_HEADER;
+        let data = self.bytes[start..start + len].to_vec();
+        let epoch = self.tail_epoch;
+
+        self.tail = (start + len) % self.bytes.len();
+        self.used -= LEN_HEADER + len;
+        self.count -= 1;
+        self.tail_epoch += 1;
+        if self.count == 0{
+            //empty: restart at 0 so the next messages are contiguous
+            self.head = 0;
+            self.tail = 0;
+            self.used = 0;
+        }
+        Some((data, epoch))
+    }
+}
+
+//variable-length messages packed end-to-end in one byte ring: each costs its
+//length plus a 4 byte header, and anything up to the arena size fits.
+//a message that would straddle the end is written at offset 0 instead
+//(skip-to-start). when full, the oldest messages are evicted.
+//
+//unlike the slot buffers this one takes a short mutex per call: evicting on
+//overflow means the producer moves the consumer's read position.
+pub struct ArenaByteBuffer{
+    arena: Mutex<Arena>,
+}
+
+impl ArenaByteBuffer{
+    pub fn new(capacity_bytes: usize) -> Self{
+        assert!(capacity_bytes > LEN_HEADER, "Arena must hold more than a header bruddaa!!");
+
+        ArenaByteBuffer{
+            arena: Mutex::new(Arena{
+                bytes: vec![0u8; capacity_bytes],
+                head: 0,
+                tail: 0,
+                used: 0,
+                count: 0,
+                latest_at: 0,
+                tail_epoch: 1,
+                write_epoch: 0,
+                dropped: 0,
+            }),
+        }
+    }
+
+    //None if the message can never fit
+    pub fn push(&self, data: &[u8]) -> Option<u64>{
+        let mut a = self.arena.lock().unwrap();
+        let cap = a.bytes.len();
+        let need = LEN_HEADER + data.len();
+        if need > cap || data.len() >= WRAP_MARKER as usize{
+            return None;
+        }
+
+        loop{
+            let padding = if cap - a.head < need{ cap - a.head }else{ 0 };
+            if cap - a.used >= padding + need{
+                if padding > 0{
+                    if padding >= LEN_HEADER{
+                        let head = a.head;
+                        a.write_len(head, WRAP_MARKER);
+                    }
+                    a.used += padding;
+                    a.head = 0;
+                }
+                break;
+            }
+            //evicting the last message resets head, so padding is recomputed
+            a.take_oldest();
+            a.dropped += 1;
+        }
+
+        let at = a.head;
+        a.write_len(at, data.len() as u32);
+        a.bytes[at + LEN_HEADER..at + need].copy_from_slice(data);
+        a.head = (at + need) % cap;
+        a.used += need;
+        a.latest_at = at;
+        a.count += 1;
+        a.write_epoch += 1;
+        if a.count == 1{
+            a.tail = at;
+            a.tail_epoch = a.write_epoch;
+        }
+        Some(a.write_epoch)
+    }
+
+    pub fn pop(&self) -> Option<(Vec<u8>, u64)>{
+        self.arena.lock().unwrap().take_oldest()
+    }
+
+    pub fn peek_latest(&self) -> Option<(Vec<u8>, u64)>{
+        let a = self.arena.lock().unwrap();
+        if a.count == 0{
+            return None;
+        }
+        let len = a.read_len(a.latest_at) as usize;
+        let start = a.latest_at + LEN_HEADER;
+        Some((a.bytes[start..start + len].to_vec(), a.write_epoch))
+    }
+
+    pub fn latest_epoch(&self) -> u64{
+        self.arena.lock().unwrap().write_epoch
+    }
+
+    pub fn len(&self) -> usize{
+        self.arena.lock().unwrap().count
+    }
+
+    pub fn is_empty(&self) -> bool{
+        self.len() == 0
+    }
+
+    pub fn capacity_bytes(&self) -> usize{
+        self.arena.lock().unwrap().bytes.len()
+    }
+
+    //bytes currently taken by resident messages, headers and wrap padding
+    pub fn used_bytes(&self) -> usize{
+        self.arena.lock().unwrap().used
+    }
+
+    pub fn dropped_count(&self) -> u64{
+        self.arena.lock().unwrap().dropped
+    }
+
+    pub fn memory_footprint(&self) -> usize{
+        std::mem::size_of::<Self>() + self.capacity_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn test_mixed_sizes_fifo(){
+        let rb = ArenaByteBuffer::new(4096);
+        let small = vec![1u8; 1];
+        let imu = vec![2u8; 40];
+        let big = vec![3u8; 1024];
+
+        assert_eq!(rb.push(&small), Some(1));
+        assert_eq!(rb.push(&imu), Some(2));
+        assert_eq!(rb.push(&big), Some(3));
+        assert_eq!(rb.used_bytes(), 3 * LEN_HEADER + 1 + 40 + 1024);
+
+        assert_eq!(rb.pop(), Some((small, 1)));
+        assert_eq!(rb.pop(), Some((imu, 2)));
+        assert_eq!(rb.peek_latest(), Some((big.clone(), 3)));
+        assert_eq!(rb.pop(), Some((big, 3)));
+        assert_eq!(rb.pop(), None);
+        assert_eq!(rb.used_bytes(), 0);
+    }
+
+    #[test]
+    fn test_packs_densely(){
+        //ten 40 byte frames in 440 bytes, where slots would need 2560
+        let rb = ArenaByteBuffer::new(440);
+        for i in 0..10u8{
+            rb.push(&[i; 40]);
+        }
+        assert_eq!(rb.len(), 10);
+        assert_eq!(rb.dropped_count(), 0);
+        assert_eq!(rb.pop().unwrap().0, vec![0; 40]);
+    }
+
+    #[test]
+    fn test_rejects_oversized(){
+        let rb = ArenaByteBuffer::new(64);
+        assert_eq!(rb.push(&[0; 61]), None);
+        assert_eq!(rb.push(&[0; 60]), Some(1));
+    }
+
+    #[test]
+    fn test_wraparound_straddle_skips_to_start(){
+        let rb = ArenaByteBuffer::new(100);
+        rb.push(&[1; 40]);
+        rb.push(&[2; 40]);
+        assert_eq!(rb.pop().unwrap().1, 1);
+
+        //34 bytes needed, 12 left before the end: padded, written at 0
+        assert_eq!(rb.push(&[3; 30]), Some(3));
+        assert_eq!(rb.used_bytes(), 44 + 12 + 34);
+
+        assert_eq!(rb.pop(), Some((vec![2; 40], 2)));
+        assert_eq!(rb.pop(), Some((vec![3; 30], 3)));
+        assert!(rb.is_empty());
+    }
+
+    #[test]
+    fn test_wrap_with_too_little_room_for_marker(){
+        let rb = ArenaByteBuffer::new(50);
+        rb.push(&[1; 20]);
+        rb.push(&[2; 18]);
+        rb.pop();
+
+        //2 bytes left at the end: no room for a marker, reader wraps implicitly
+        assert_eq!(rb.push(&[3; 10]), Some(3));
+        assert_eq!(rb.pop(), Some((vec![2; 18], 2)));
+        assert_eq!(rb.pop(), Some((vec![3; 10], 3)));
+    }
+
+    #[test]
+    fn test_overwrite_oldest(){
+        let rb = ArenaByteBuffer::new(100);
+        rb.push(&[1; 40]);
+        rb.push(&[2; 40]);
+        rb.push(&[3; 40]);
+
+        assert_eq!(rb.dropped_count(), 1);
+        assert_eq!(rb.len(), 2);
+        assert_eq!(rb.pop(), Some((vec![2; 40], 2)));
+        assert_eq!(rb.pop(), Some((vec![3; 40], 3)));
+        assert_eq!(rb.pop(), None);
+    }
+
+    #[test]
+    fn test_large_message_evicts_many(){
+        let rb = ArenaByteBuffer::new(256);
+        for i in 0..20u8{
+            rb.push(&[i; 8]);
+        }
+        rb.push(&[0xEE; 200]);
+
+        let mut out = Vec::new();
+        while let Some((data, epoch)) = rb.pop(){
+            out.push((data.len(), epoch));
+        }
+        assert_eq!(out.last(), Some(&(200, 21)));
+        assert!(out.windows(2).all(|w| w[1].1 == w[0].1 + 1));
+        assert_eq!(rb.dropped_count() as usize + out.len(), 21);
+    }
+}
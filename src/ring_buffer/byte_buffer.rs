@@ -1,9 +1,44 @@
 use std::cell::UnsafeCell;
-use std::sync::atomic::{AtomicUsize, AtomicU64, Ordering};
+use std::fmt;
+use std::ops::Deref;
+use std::hint;
+use std::sync::atomic::{self, AtomicBool, AtomicUsize, AtomicU8, AtomicU32, AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use super::metrics::BufferMetrics;
+use super::wait::{Notifier, WaitStrategy};
 
 pub const SLOT_SIZE: usize = 256;
 pub const HEADER_SIZE: usize = 12;
 pub const MAX_PAYLOAD_SIZE: usize = SLOT_SIZE - HEADER_SIZE;
+//lanes of a prioritized buffer, 0 (routine) up to PRIORITY_LANES - 1 (urgent)
+pub const PRIORITY_LANES: u8 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotError{
+    TooLarge{ len: usize, max_payload: usize },
+}
+
+impl SlotError{
+    //bytes that would have to go elsewhere (next fragment) for the payload to fit
+    pub fn overage(&self) -> usize{
+        match self{
+            SlotError::TooLarge{ len, max_payload } => len - max_payload,
+        }
+    }
+}
+
+impl fmt::Display for SlotError{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        match self{
+            SlotError::TooLarge{ len, max_payload } =>{
+                write!(f, "payload of {} bytes is {} over the {} byte slot limit", len, self.overage(), max_payload)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SlotError{}
 
 #[repr(C)]
 struct ByteSlotInner{
@@ -14,150 +49,497 @@ struct ByteSlotInner{
 
 pub struct ByteSlot{
     inner: UnsafeCell<ByteSlotInner>,
+    //live ReadGuards on this slot; a push that laps onto it waits for zero
+    readers: AtomicUsize,
 }
 
 impl ByteSlot{
-    fn new() -> Self{
-        ByteSlot{
-            inner: UnsafeCell::new(ByteSlotInner{
-                len: 0,
-                epoch: AtomicU64::new(0),
-                data: [0u8; MAX_PAYLOAD_SIZE],
-            }),
+    //all-zero bytes are a valid empty slot (len 0, epoch 0, no readers). a zeroed
+    //allocation comes straight from the os untouched, so a slot's pages are
+    //only faulted in the first time it is written
+    fn zeroed(count: usize) -> Vec<ByteSlot>{
+        let layout = std::alloc::Layout::array::<ByteSlot>(count).expect("ring buffer too large");
+        unsafe{
+            let ptr = std::alloc::alloc_zeroed(layout) as *mut ByteSlot;
+            if ptr.is_null(){
+                std::alloc::handle_alloc_error(layout);
+            }
+            Vec::from_raw_parts(ptr, count, count)
+        }
+    }
+}
+
+//crc32 over the little-endian length followed by the payload
+fn slot_crc(len: u32, data: &[u8]) -> u32{
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in len.to_le_bytes().iter().chain(data){
+        crc ^= byte as u32;
+        for _ in 0..8{
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
         }
     }
+    !crc
+}
+
+//side tables of a prioritized buffer, one entry per slot
+struct Lanes{
+    priority: Vec<AtomicU8>,
+    //epoch of the message popped out of order from this slot, 0 if none
+    taken: Vec<AtomicU64>,
+}
+
+impl Lanes{
+    fn is_taken(&self, index: usize, epoch: u64) -> bool{
+        self.taken[index].load(Ordering::SeqCst) == epoch
+    }
+}
+
+//one slot as ByteRingBuffer::dump_slots sees it. valid: holds a message that
+//passes its checksum; consumed: that message was already popped
+#[cfg(debug_assertions)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlotDebug{
+    pub index: usize,
+    pub epoch: u64,
+    pub len: usize,
+    pub valid: bool,
+    pub consumed: bool,
 }
 
-pub struct ByteRingBuffer{
+//POW2 as on RingBuffer: new_pow2 buffers index with a mask
+pub struct ByteRingBuffer<const POW2: bool = false>{
     buffer: Vec<ByteSlot>,
+    //held by the one push allowed to touch slots at a time, as in RingBuffer
+    writing: AtomicBool,
     head: AtomicUsize,
-    tail: AtomicUsize,
     write_epoch: AtomicU64,
     read_epoch: AtomicU64,
     capacity: usize,
+    //capacity - 1, only used to index when POW2
+    mask: usize,
+    //per-slot checksums, only for buffers whose slots another writer can reach
+    crcs: Option<Vec<AtomicU32>>,
+    lanes: Option<Lanes>,
+    //per-slot publish time in us since the unix epoch, once someone asked
+    //for it with record_publish_times
+    stamps: OnceLock<Vec<AtomicU64>>,
+    corrupted: AtomicU64,
+    dropped: AtomicU64,
+    notifier: Notifier,
 }
 
-unsafe impl Send for ByteRingBuffer{}
-unsafe impl Sync for ByteRingBuffer{}
+//shareable under the contract in ring_buffer/mod.rs: pushes take `writing`,
+//pops claim read_epoch with a cas and every copy is re-checked against
+//write_epoch before it is handed out
+unsafe impl<const POW2: bool> Send for ByteRingBuffer<POW2>{}
+unsafe impl<const POW2: bool> Sync for ByteRingBuffer<POW2>{}
 
 impl ByteRingBuffer{
     pub fn new(capacity: usize) -> Self{
-        assert!(capacity > 0, "Capacity must be greater than 0 bruddaa!!");
+        Self::with_capacity(capacity)
+    }
 
-        let mut buffer = Vec::with_capacity(capacity);
-        for _ in 0..capacity{
-            buffer.push(ByteSlot::new());
-        }
+    //new + prefault: full footprint resident up front, no first-lap page faults
+    pub fn new_prefaulted(capacity: usize) -> Self{
+        let rb = Self::new(capacity);
+        rb.prefault();
+        rb
+    }
+
+    pub fn new_with_crc(capacity: usize) -> Self{
+        let mut rb = Self::new(capacity);
+        rb.crcs = Some((0..capacity).map(|_| AtomicU32::new(0)).collect());
+        rb
+    }
+
+    //pop hands out the highest-priority unread message in the resident
+    //window instead of the oldest; equal priorities stay oldest-first.
+    //starvation is real: while urgent messages keep arriving, routine ones
+    //wait, and any that age out of the window are overwritten and counted as
+    //dropped. size the buffer for the longest urgent burst you expect.
+    //pop and len scan the window, so they cost O(capacity) here. each pop
+    //claims its message with a cas on the slot's taken epoch, so any number
+    //of consumers can share the buffer and each message goes to one of them
+    pub fn new_prioritized(capacity: usize) -> Self{
+        let mut rb = Self::new(capacity);
+        rb.lanes = Some(Lanes{
+            priority: (0..capacity).map(|_| AtomicU8::new(0)).collect(),
+            taken: (0..capacity).map(|_| AtomicU64::new(0)).collect(),
+        });
+        rb
+    }
+}
+
+impl ByteRingBuffer<true>{
+    //power-of-two capacity, indexed with a mask; see RingBuffer::new_pow2
+    pub fn new_pow2(capacity: usize) -> Self{
+        Self::with_capacity(capacity.next_power_of_two())
+    }
+}
+
+impl<const POW2: bool> ByteRingBuffer<POW2>{
+    fn with_capacity(capacity: usize) -> Self{
+        assert!(capacity > 0, "Capacity must be greater than 0 bruddaa!!");
+        debug_assert!(!POW2 || capacity.is_power_of_two());
 
         ByteRingBuffer{
-            buffer,
+            buffer: ByteSlot::zeroed(capacity),
+            writing: AtomicBool::new(false),
             head: AtomicUsize::new(0),
-            tail: AtomicUsize::new(0),
             write_epoch: AtomicU64::new(0),
             read_epoch: AtomicU64::new(0),
             capacity,
+            mask: capacity - 1,
+            crcs: None,
+            lanes: None,
+            stamps: OnceLock::new(),
+            corrupted: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            notifier: Notifier::default(),
+        }
+    }
+
+    //slots start as untouched zero pages, so the first write to each one
+    //takes a page fault (a few us, far worse under memory pressure). this
+    //touches every page now instead, before latency matters. the tradeoff is
+    //rss: the whole memory_footprint() becomes resident immediately, even
+    //for a big buffer that would never fill. safe while in use, since it
+    //only does no-op atomic writes to the slot epochs (every page holds some)
+    pub fn prefault(&self){
+        for index in 0..self.capacity{
+            unsafe{ (*self.buffer[index].inner.get()).epoch.fetch_add(0, Ordering::Relaxed); }
+        }
+        if let Some(crcs) = &self.crcs{
+            for crc in crcs{
+                crc.fetch_add(0, Ordering::Relaxed);
+            }
         }
     }
 
+    //stamp every push from now on with the wall-clock time, for
+    //pop_from_stamped. costs a clock read per push, so it is opt-in
+    pub fn record_publish_times(&self){
+        self.stamps.get_or_init(|| (0..self.capacity).map(|_| AtomicU64::new(0)).collect());
+    }
+
+    pub fn is_prioritized(&self) -> bool{
+        self.lanes.is_some()
+    }
+
     #[inline]
+    #[allow(clippy::mut_from_ref)]
     unsafe fn slot_inner(&self, index: usize) -> &mut ByteSlotInner{
         unsafe{ &mut *self.buffer[index].inner.get() }
     }
 
+    #[inline]
+    fn slot_of(&self, epoch: u64) -> usize{
+        if POW2{
+            (epoch - 1) as usize & self.mask
+        }else{
+            ((epoch - 1) % self.capacity as u64) as usize
+        }
+    }
+
+    #[inline]
+    fn slot_after(&self, index: usize) -> usize{
+        if POW2{
+            (index + 1) & self.mask
+        }else{
+            (index + 1) % self.capacity
+        }
+    }
+
     #[inline]
     fn slot_epoch(&self, index: usize) -> u64{
         unsafe{ (*self.buffer[index].inner.get()).epoch.load(Ordering::SeqCst) }
     }
 
+    fn slot_intact(&self, index: usize, slot: &ByteSlotInner) -> bool{
+        match &self.crcs{
+            Some(crcs) =>{
+                let len = slot.len as usize;
+                len <= MAX_PAYLOAD_SIZE
+                    && slot_crc(slot.len, &slot.data[..len]) == crcs[index].load(Ordering::SeqCst)
+            }
+            None => true,
+        }
+    }
+
     pub fn push(&self, data: &[u8]) -> Option<u64>{
+        self.push_checked(data).ok()
+    }
+
+    //seed the buffer (fixtures, replay); oversized items are skipped, so
+    //the return is how many were accepted
+    pub fn fill_from_iter(&self, items: impl IntoIterator<Item = Vec<u8>>) -> usize{
+        items.into_iter().filter(|item| self.push(item).is_some()).count()
+    }
+
+    //push that says by how much an oversized payload missed, so a serializer
+    //can decide whether to fragment or split
+    pub fn push_checked(&self, data: &[u8]) -> Result<u64, SlotError>{
+        self.push_with_priority(data, 0)
+    }
+
+    //priority is clamped to PRIORITY_LANES - 1 and ignored unless the buffer
+    //was made with new_prioritized
+    pub fn push_with_priority(&self, data: &[u8], priority: u8) -> Result<u64, SlotError>{
         if data.len() > MAX_PAYLOAD_SIZE{
-            return None;
+            return Err(SlotError::TooLarge{ len: data.len(), max_payload: MAX_PAYLOAD_SIZE });
         }
 
+        //same writer serialization as RingBuffer::push_returning
+        while self.writing.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err(){
+            hint::spin_loop();
+        }
         let head = self.head.load(Ordering::Relaxed);
 
         let new_epoch = self.write_epoch.load(Ordering::Relaxed) + 1;
-        self.write_epoch.store(new_epoch, Ordering::Relaxed);
+        //full means this write overwrites a message nobody read
+        let unread = (new_epoch - 1).saturating_sub(self.read_epoch.load(Ordering::SeqCst));
+        let evicted = new_epoch.saturating_sub(self.capacity as u64);
+        let popped_early = evicted > 0 && self.lanes.as_ref().is_some_and(|lanes| lanes.is_taken(head, evicted));
+        if unread >= self.capacity as u64 && !popped_early{
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        self.write_epoch.store(new_epoch, Ordering::SeqCst);
+        //publish the new epoch before touching the slot so copies can tell
+        //when a write may have started on the slot they read, then wait out
+        //any ReadGuard that pinned it before it could see the new epoch
+        atomic::fence(Ordering::Release);
+        while self.buffer[head].readers.load(Ordering::SeqCst) != 0{
+            hint::spin_loop();
+        }
 
         unsafe{
             let slot = self.slot_inner(head);
+            //same guard as RingBuffer::push_returning
+            let old_epoch = slot.epoch.load(Ordering::SeqCst);
+            debug_assert!(
+                new_epoch > old_epoch,
+                "epoch reuse in slot {head}: storing {new_epoch} over {old_epoch}, read_epoch {} bruddaa!!",
+                self.read_epoch.load(Ordering::SeqCst),
+            );
             slot.len = data.len() as u32;
             slot.data[..data.len()].copy_from_slice(data);
+            if let Some(crcs) = &self.crcs{
+                crcs[head].store(slot_crc(slot.len, data), Ordering::SeqCst);
+            }
+            if let Some(lanes) = &self.lanes{
+                lanes.priority[head].store(priority.min(PRIORITY_LANES - 1), Ordering::SeqCst);
+            }
+            if let Some(stamps) = self.stamps.get(){
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_micros() as u64);
+                stamps[head].store(now, Ordering::SeqCst);
+            }
             slot.epoch.store(new_epoch, Ordering::SeqCst);
         }
 
-        let new_head = (head + 1) % self.capacity;
+        let new_head = self.slot_after(head);
         self.head.store(new_head, Ordering::SeqCst);
+        self.writing.store(false, Ordering::Release);
+        self.notifier.notify();
+
+        Ok(new_epoch)
+    }
+
+    #[cfg(all(test, debug_assertions))]
+    pub(crate) fn force_write_epoch(&self, epoch: u64){
+        self.write_epoch.store(epoch, Ordering::SeqCst);
+    }
 
-        Some(new_epoch)
+    //same epoch -> slot mapping as RingBuffer::next_unread
+    fn next_unread(&self) -> Option<(usize, u64)>{
+        self.next_after(self.read_epoch.load(Ordering::SeqCst))
+    }
+
+    fn next_after(&self, read_epoch: u64) -> Option<(usize, u64)>{
+        let write_epoch = self.write_epoch.load(Ordering::SeqCst);
+        if read_epoch >= write_epoch{
+            return None;
+        }
+
+        let oldest_resident = write_epoch.saturating_sub(self.capacity as u64 - 1);
+        let epoch = (read_epoch + 1).max(oldest_resident);
+        Some((self.slot_of(epoch), epoch))
+    }
+
+    //copy out a slot's payload, None if it fails its checksum
+    fn copy_slot(&self, index: usize) -> Option<Vec<u8>>{
+        let mut out = Vec::new();
+        self.copy_slot_into(index, &mut out).then_some(out)
+    }
+
+    //replace `out` with a slot's payload, reusing its allocation; a slot
+    //that fails its checksum leaves `out` alone
+    fn copy_slot_into(&self, index: usize, out: &mut Vec<u8>) -> bool{
+        unsafe{
+            let slot = &*self.buffer[index].inner.get();
+            if !self.slot_intact(index, slot){
+                return false;
+            }
+            out.clear();
+            out.extend_from_slice(&slot.data[..slot.len as usize]);
+            true
+        }
     }
 
     pub fn pop(&self) -> Option<(Vec<u8>, u64)>{
+        let mut data = Vec::new();
+        let epoch = self.pop_into(&mut data)?;
+        Some((data, epoch))
+    }
+
+    //true once the producer may have started rewriting the slot that held
+    //`epoch`. checked after a copy, so the copy is only kept if it was whole
+    fn overwritten(&self, epoch: u64) -> bool{
+        atomic::fence(Ordering::Acquire);
+        self.write_epoch.load(Ordering::Relaxed) >= epoch + self.capacity as u64
+    }
+
+    //pop without allocating once `out` has grown to the largest payload seen.
+    //`out` holds the message when one is returned; on None it may hold a
+    //copy that was thrown away because the producer overwrote it mid-read
+    pub fn pop_into(&self, out: &mut Vec<u8>) -> Option<u64>{
+        if let Some(lanes) = &self.lanes{
+            return self.pop_prioritized(lanes, out);
+        }
         loop{
-            let tail = self.tail.load(Ordering::SeqCst);
-            let head = self.head.load(Ordering::SeqCst);
             let read_epoch = self.read_epoch.load(Ordering::SeqCst);
-            let write_epoch = self.write_epoch.load(Ordering::SeqCst);
+            let (index, epoch) = self.next_after(read_epoch)?;
+            let slot_epoch = self.slot_epoch(index);
 
-            if write_epoch == 0{
+            if slot_epoch < epoch{
                 return None;
             }
+            if slot_epoch > epoch{
+                continue;
+            }
 
-            let slot_epoch = self.slot_epoch(tail);
+            let intact = self.copy_slot_into(index, out);
+            if self.overwritten(epoch){
+                continue;
+            }
 
-            //already consumed this slot?
-            if slot_epoch <= read_epoch{
-                if tail == head{
-                    return None;
-                }
-                let new_tail = (tail + 1) % self.capacity;
-                self.tail.store(new_tail, Ordering::SeqCst);
+            //claim it, same as RingBuffer::pop
+            if self.read_epoch.compare_exchange(read_epoch, epoch, Ordering::SeqCst, Ordering::SeqCst).is_err(){
                 continue;
             }
 
-            //check if slot was overwritten
-            let min_valid_epoch = write_epoch.saturating_sub(self.capacity as u64 - 1);
-            if slot_epoch < min_valid_epoch{
-                self.read_epoch.store(slot_epoch, Ordering::SeqCst);
-                let new_tail = (tail + 1) % self.capacity;
-                self.tail.store(new_tail, Ordering::SeqCst);
+            //never hand back bytes that disagree with their checksum
+            if !intact{
+                self.corrupted.fetch_add(1, Ordering::SeqCst);
                 continue;
             }
 
-            //valid slot - read data
-            let (data, epoch) = unsafe{
-                let slot = &*self.buffer[tail].inner.get();
-                let len = slot.len as usize;
-                (slot.data[..len].to_vec(), slot.epoch.load(Ordering::SeqCst))
-            };
+            return Some(epoch);
+        }
+    }
 
-            self.read_epoch.store(epoch, Ordering::SeqCst);
+    //resident unread messages as (slot index, epoch), oldest first, skipping
+    //ones popped out of order and writes still in flight
+    fn unread_window<'a>(&'a self, lanes: &'a Lanes) -> impl Iterator<Item = (usize, u64)> + 'a{
+        let first = self.next_unread().map_or(u64::MAX, |(_, epoch)| epoch);
+        let last = self.write_epoch.load(Ordering::SeqCst);
+        (first..=last)
+            .map(|epoch| (self.slot_of(epoch), epoch))
+            .filter(move |&(index, epoch)| self.slot_epoch(index) == epoch && !lanes.is_taken(index, epoch))
+    }
 
-            let new_tail = (tail + 1) % self.capacity;
-            self.tail.store(new_tail, Ordering::SeqCst);
+    //safe with several consumers: losing the claim on a message means
+    //another pop took it, so look again
+    fn pop_prioritized(&self, lanes: &Lanes, out: &mut Vec<u8>) -> Option<u64>{
+        loop{
+            //max_by_key keeps the last maximum, so compare on reversed epoch
+            //to prefer the oldest of the highest lane
+            let (index, epoch) = self.unread_window(lanes)
+                .max_by_key(|&(index, epoch)| (lanes.priority[index].load(Ordering::SeqCst), std::cmp::Reverse(epoch)))?;
 
-            return Some((data, epoch));
+            let intact = self.copy_slot_into(index, out);
+            if self.overwritten(epoch){
+                continue;
+            }
+            let taken = lanes.taken[index].load(Ordering::SeqCst);
+            if taken >= epoch || lanes.taken[index].compare_exchange(taken, epoch, Ordering::SeqCst, Ordering::SeqCst).is_err(){
+                continue;
+            }
+
+            //read_epoch follows the oldest message not yet handed out. max,
+            //so a consumer that fell behind can't move it back
+            while let Some((index, epoch)) = self.next_unread(){
+                if self.slot_epoch(index) != epoch || !lanes.is_taken(index, epoch){
+                    break;
+                }
+                self.read_epoch.fetch_max(epoch, Ordering::SeqCst);
+            }
+
+            if intact{
+                return Some(epoch);
+            }
+            self.corrupted.fetch_add(1, Ordering::SeqCst);
         }
     }
 
-    pub fn peek_latest(&self) -> Option<(Vec<u8>, u64)>{
-        let write_epoch = self.write_epoch.load(Ordering::SeqCst);
-        if write_epoch == 0{
-            return None;
-        }
+    //cursor read, see RingBuffer::pop_from. always in epoch order, even on a
+    //prioritized buffer. a copy the producer overwrote while it was taken is
+    //thrown away and the read retried, so the bytes always match the epoch
+    pub fn pop_from(&self, cursor: u64) -> Option<(Vec<u8>, u64)>{
+        self.pop_from_stamped(cursor).map(|(data, epoch, _)| (data, epoch))
+    }
 
-        let head = self.head.load(Ordering::SeqCst);
-        let latest_idx = if head == 0{ self.capacity - 1 }else{ head - 1 };
+    //pop_from plus the publish time (us since the unix epoch), 0 for a
+    //message pushed before record_publish_times
+    pub fn pop_from_stamped(&self, cursor: u64) -> Option<(Vec<u8>, u64, u64)>{
+        let mut cursor = cursor;
+        loop{
+            let (index, epoch) = self.next_after(cursor)?;
+            let slot_epoch = self.slot_epoch(index);
+            if slot_epoch < epoch{
+                return None;
+            }
+            if slot_epoch > epoch{
+                continue;
+            }
 
-        unsafe{
-            let slot = &*self.buffer[latest_idx].inner.get();
-            let len = slot.len as usize;
-            let epoch = slot.epoch.load(Ordering::SeqCst);
-            Some((slot.data[..len].to_vec(), epoch))
+            let copied = self.copy_slot(index);
+            let stamp = self.stamps.get().map_or(0, |stamps| stamps[index].load(Ordering::SeqCst));
+            if self.overwritten(epoch){
+                continue;
+            }
+            match copied{
+                Some(data) => return Some((data, epoch, stamp)),
+                None =>{
+                    //corrupt slots are skipped like pop does, but not
+                    //counted again for every reader that trips on them
+                    cursor = epoch;
+                }
+            }
         }
     }
 
-    pub fn peek_latest_ref(&self) -> Option<(&[u8], u64)>{
+    //blocking pop; None once `timeout` passes with nothing to read
+    pub fn pop_timeout(&self, timeout: Duration, strategy: WaitStrategy) -> Option<(Vec<u8>, u64)>{
+        self.notifier.wait_for(timeout, strategy, || self.pop())
+    }
+
+    //see RingBuffer::pop_blocking
+    pub fn pop_blocking(&self, timeout: Option<Duration>) -> Option<(Vec<u8>, u64)>{
+        self.pop_timeout(timeout.unwrap_or(Duration::MAX), WaitStrategy::Park)
+    }
+
+    //checked copy of the latest message, see peek_at_offset
+    pub fn peek_latest(&self) -> Option<(Vec<u8>, u64)>{
+        self.peek_at_offset(0)
+    }
+
+    //latest payload in place, without pinning it; read_guard is the safe form
+    /// # Safety
+    /// The slot is not pinned: a push that laps onto it rewrites the bytes
+    /// while the slice is alive. The caller must make sure no push to this
+    /// buffer can reach that slot before the slice is dropped.
+    pub unsafe fn peek_latest_ref(&self) -> Option<(&[u8], u64)>{
         let write_epoch = self.write_epoch.load(Ordering::SeqCst);
         if write_epoch == 0{
             return None;
@@ -174,24 +556,100 @@ impl ByteRingBuffer{
         }
     }
 
-    pub fn peek_oldest_ref(&self) -> Option<(&[u8], u64)>{
-        let write_epoch = self.write_epoch.load(Ordering::SeqCst);
-        if write_epoch == 0{
+    //the message `offset` back from the latest (0 = latest) without consuming,
+    //None past the resident window. read_epoch is ignored like peek_latest
+    pub fn peek_at_offset(&self, offset: usize) -> Option<(Vec<u8>, u64)>{
+        if offset >= self.capacity{
             return None;
         }
+        loop{
+            let write_epoch = self.write_epoch.load(Ordering::SeqCst);
+            if offset as u64 >= write_epoch{
+                return None;
+            }
+
+            let epoch = write_epoch - offset as u64;
+            let index = self.slot_of(epoch);
+            if self.slot_epoch(index) != epoch{
+                //a newer write moved the window, look again from the new latest
+                continue;
+            }
+
+            let copied = self.copy_slot(index);
+            if self.overwritten(epoch){
+                continue;
+            }
+            return copied.map(|data| (data, epoch));
+        }
+    }
 
-        let tail = self.tail.load(Ordering::SeqCst);
+    //every slot's raw state, for chasing read_epoch/write_epoch bugs. racy
+    //against a live producer, like any peek. debug builds only
+    #[cfg(debug_assertions)]
+    pub fn dump_slots(&self) -> Vec<SlotDebug>{
         let read_epoch = self.read_epoch.load(Ordering::SeqCst);
-        let slot_epoch = self.slot_epoch(tail);
+        (0..self.capacity).map(|index|{
+            let slot = unsafe{ &*self.buffer[index].inner.get() };
+            let epoch = slot.epoch.load(Ordering::SeqCst);
+            let taken = self.lanes.as_ref().is_some_and(|lanes| lanes.is_taken(index, epoch));
+            SlotDebug{
+                index,
+                epoch,
+                len: slot.len as usize,
+                valid: epoch != 0 && self.slot_intact(index, slot),
+                consumed: epoch != 0 && (epoch <= read_epoch || taken),
+            }
+        }).collect()
+    }
+
+    //zero-copy view of the latest message, pinned until the guard drops.
+    //None while empty, including while the first push is still in flight
+    pub fn read_guard(&self) -> Option<ReadGuard<'_, POW2>>{
+        loop{
+            if self.write_epoch.load(Ordering::SeqCst) == 0{
+                return None;
+            }
 
-        if slot_epoch <= read_epoch{
+            let head = self.head.load(Ordering::SeqCst);
+            let index = if head == 0{ self.capacity - 1 }else{ head - 1 };
+            let slot = &self.buffer[index];
+            slot.readers.fetch_add(1, Ordering::SeqCst);
+
+            //same handshake as RingBuffer::with_slot: either the push that
+            //laps onto this slot sees our pin and waits, or we see its
+            //write_epoch here and let go. the next write to it is epoch + capacity
+            let epoch = self.slot_epoch(index);
+            if epoch == 0{
+                slot.readers.fetch_sub(1, Ordering::Release);
+                return None;
+            }
+            if self.write_epoch.load(Ordering::SeqCst) >= epoch + self.capacity as u64{
+                slot.readers.fetch_sub(1, Ordering::Release);
+                continue;
+            }
+
+            let len = unsafe{ (*slot.inner.get()).len as usize }.min(MAX_PAYLOAD_SIZE);
+            return Some(ReadGuard{ rb: self, index, epoch, len });
+        }
+    }
+
+    //oldest message not popped yet. on a prioritized buffer that skips any
+    //popped out of order, and isn't necessarily what pop returns next
+    /// # Safety
+    /// Same as peek_latest_ref: no push may reach the slot while the slice
+    /// is alive.
+    pub unsafe fn peek_oldest_ref(&self) -> Option<(&[u8], u64)>{
+        let (index, epoch) = match &self.lanes{
+            Some(lanes) => self.unread_window(lanes).next()?,
+            None => self.next_unread()?,
+        };
+        if self.slot_epoch(index) != epoch{
             return None;
         }
 
         unsafe{
-            let slot = &*self.buffer[tail].inner.get();
+            let slot = &*self.buffer[index].inner.get();
             let len = slot.len as usize;
-            let epoch = slot.epoch.load(Ordering::SeqCst);
             Some((&slot.data[..len], epoch))
         }
     }
@@ -200,6 +658,8 @@ impl ByteRingBuffer{
         self.write_epoch.load(Ordering::SeqCst)
     }
 
+    //unread messages pop would still return; all `capacity` slots are
+    //readable after a lap, so this matches the pop count and is_full
     pub fn len(&self) -> usize{
         let write_epoch = self.write_epoch.load(Ordering::SeqCst);
         let read_epoch = self.read_epoch.load(Ordering::SeqCst);
@@ -208,6 +668,10 @@ impl ByteRingBuffer{
             return 0;
         }
 
+        if let Some(lanes) = &self.lanes{
+            return self.unread_window(lanes).count();
+        }
+
         let unread = write_epoch.saturating_sub(read_epoch) as usize;
         std::cmp::min(unread, self.capacity)
     }
@@ -223,6 +687,69 @@ impl ByteRingBuffer{
     pub fn capacity(&self) -> usize{
         self.capacity
     }
+
+    pub fn memory_footprint(&self) -> usize{
+        let crc_bytes = self.crcs.as_ref().map_or(0, |crcs| crcs.len() * std::mem::size_of::<AtomicU32>());
+        let lane_bytes = self.lanes.as_ref().map_or(0, |_| self.capacity * (std::mem::size_of::<AtomicU8>() + std::mem::size_of::<AtomicU64>()));
+        let stamp_bytes = self.stamps.get().map_or(0, |stamps| stamps.len() * std::mem::size_of::<AtomicU64>());
+        std::mem::size_of::<Self>() + self.capacity * std::mem::size_of::<ByteSlot>() + crc_bytes + lane_bytes + stamp_bytes
+    }
+
+    pub fn corrupted_count(&self) -> u64{
+        self.corrupted.load(Ordering::SeqCst)
+    }
+
+    pub fn dropped_count(&self) -> u64{
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn metrics(&self) -> BufferMetrics{
+        BufferMetrics{
+            len: self.len(),
+            capacity: self.capacity,
+            published: self.latest_epoch(),
+            dropped: self.dropped_count(),
+            corrupted: self.corrupted_count(),
+        }
+    }
+}
+
+//borrowed view of one slot of a ByteRingBuffer, pinned like
+//RingBuffer::with_slot.
+//
+//while the guard is alive its slot is not reused:
+//- the bytes are exactly the message published at epoch(), for as long as
+//  the guard lives
+//- pushes that don't reach the slot go ahead as usual; the one that laps
+//  onto it spins until the guard drops
+//so keep guards short, and never push to the same buffer from the thread
+//holding one: that push would wait on the guard forever
+pub struct ReadGuard<'a, const POW2: bool = false>{
+    rb: &'a ByteRingBuffer<POW2>,
+    index: usize,
+    epoch: u64,
+    len: usize,
+}
+
+impl<const POW2: bool> ReadGuard<'_, POW2>{
+    pub fn epoch(&self) -> u64{
+        self.epoch
+    }
+}
+
+impl<const POW2: bool> Drop for ReadGuard<'_, POW2>{
+    fn drop(&mut self){
+        self.rb.buffer[self.index].readers.fetch_sub(1, Ordering::Release);
+    }
+}
+
+impl<const POW2: bool> Deref for ReadGuard<'_, POW2>{
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8]{
+        let slot = unsafe{ &*self.rb.buffer[self.index].inner.get() };
+        &slot.data[..self.len]
+    }
 }
 
 #[cfg(test)]
@@ -248,6 +775,42 @@ mod tests{
         assert_eq!(data, vec![100]);
     }
 
+    #[test]
+    fn test_pop_into_reuses_buffer(){
+        let rb = ByteRingBuffer::new(4);
+        let mut out = Vec::with_capacity(8);
+        let reserved = out.as_ptr();
+
+        rb.push(&[1, 2, 3]);
+        rb.push(&[4]);
+        assert_eq!(rb.pop_into(&mut out), Some(1));
+        assert_eq!(out, vec![1, 2, 3]);
+        assert_eq!(rb.pop_into(&mut out), Some(2));
+        assert_eq!(out, vec![4]);
+        assert_eq!(out.as_ptr(), reserved);
+
+        //empty leaves the last payload in place
+        assert_eq!(rb.pop_into(&mut out), None);
+        assert_eq!(out, vec![4]);
+
+        //a payload bigger than `out` grows it
+        rb.push(&[9; 20]);
+        assert_eq!(rb.pop_into(&mut out), Some(3));
+        assert_eq!(out, vec![9; 20]);
+    }
+
+    #[test]
+    fn test_pop_into_skips_corrupted_without_touching_out(){
+        let rb = ByteRingBuffer::new_with_crc(4);
+        rb.push(&[1]);
+        unsafe{ rb.slot_inner(0).data[0] = 0xFF; }
+
+        let mut out = vec![7, 7];
+        assert_eq!(rb.pop_into(&mut out), None);
+        assert_eq!(out, vec![7, 7]);
+        assert_eq!(rb.corrupted_count(), 1);
+    }
+
     #[test]
     fn test_imu_sized_message(){
         let rb = ByteRingBuffer::new(8);
@@ -269,13 +832,117 @@ mod tests{
         assert!(rb.push(&too_large).is_none());
     }
 
+    #[test]
+    fn test_fill_from_iter_skips_oversized(){
+        let rb = ByteRingBuffer::new(8);
+        let items = vec![vec![1], vec![0; MAX_PAYLOAD_SIZE + 1], vec![2, 2], vec![3; 3]];
+        assert_eq!(rb.fill_from_iter(items), 3);
+
+        let drained: Vec<_> = std::iter::from_fn(|| rb.pop()).collect();
+        assert_eq!(drained, vec![(vec![1], 1), (vec![2, 2], 2), (vec![3; 3], 3)]);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "epoch reuse in slot 1")]
+    fn test_push_catches_epoch_reuse(){
+        let rb = ByteRingBuffer::new(2);
+        rb.fill_from_iter([vec![1], vec![2], vec![3]]);
+        rb.force_write_epoch(1);
+        rb.push(&[4]);
+    }
+
+    #[test]
+    fn test_push_checked_reports_overage(){
+        let rb = ByteRingBuffer::new(4);
+        assert_eq!(rb.push_checked(&[0; MAX_PAYLOAD_SIZE]), Ok(1));
+
+        for len in [MAX_PAYLOAD_SIZE + 1, MAX_PAYLOAD_SIZE + 17, 1024]{
+            let err = rb.push_checked(&vec![0; len]).unwrap_err();
+            assert_eq!(err, SlotError::TooLarge{ len, max_payload: MAX_PAYLOAD_SIZE });
+            assert_eq!(err.overage(), len - MAX_PAYLOAD_SIZE);
+        }
+        //rejected pushes take no epoch
+        assert_eq!(rb.latest_epoch(), 1);
+    }
+
+    #[test]
+    fn test_prioritized_pops_urgent_first(){
+        let rb = ByteRingBuffer::new_prioritized(8);
+        rb.push_with_priority(&[1], 0).unwrap();
+        rb.push_with_priority(&[2], 3).unwrap();
+        rb.push_with_priority(&[3], 0).unwrap();
+        rb.push_with_priority(&[4], 1).unwrap();
+        rb.push_with_priority(&[5], 3).unwrap();
+        //out of range lands in the top lane
+        rb.push_with_priority(&[6], 200).unwrap();
+        assert_eq!(rb.len(), 6);
+
+        let order: Vec<(u8, u64)> = std::iter::from_fn(|| rb.pop()).map(|(d, e)| (d[0], e)).collect();
+        assert_eq!(order, vec![(2, 2), (5, 5), (6, 6), (4, 4), (1, 1), (3, 3)]);
+        assert!(rb.is_empty());
+
+        //plain buffers ignore the priority
+        let fifo = ByteRingBuffer::new(4);
+        fifo.push_with_priority(&[1], 0).unwrap();
+        fifo.push_with_priority(&[2], 3).unwrap();
+        assert_eq!(fifo.pop().unwrap().0, vec![1]);
+    }
+
+    #[test]
+    fn test_prioritized_window_and_drops(){
+        let rb = ByteRingBuffer::new_prioritized(3);
+        rb.push_with_priority(&[1], 0).unwrap();
+        rb.push_with_priority(&[2], 0).unwrap();
+        rb.push_with_priority(&[3], 2).unwrap();
+        assert_eq!(rb.pop(), Some((vec![3], 3)));
+        assert_eq!(rb.len(), 2);
+
+        //overwrites the routine 1 (a drop), then the already popped 3 (not one)
+        rb.push_with_priority(&[4], 0).unwrap();
+        rb.push_with_priority(&[5], 0).unwrap();
+        rb.push_with_priority(&[6], 1).unwrap();
+        assert_eq!(rb.dropped_count(), 2);
+        assert_eq!(rb.len(), 3);
+
+        assert_eq!(rb.pop(), Some((vec![6], 6)));
+        assert_eq!(rb.pop(), Some((vec![4], 4)));
+        rb.push_with_priority(&[7], 0).unwrap();
+        assert_eq!(rb.pop(), Some((vec![5], 5)));
+        assert_eq!(rb.pop(), Some((vec![7], 7)));
+        assert_eq!(rb.pop(), None);
+        assert!(rb.memory_footprint() > ByteRingBuffer::new(3).memory_footprint());
+    }
+
+    #[test]
+    fn test_prioritized_consumers_never_share_a_message(){
+        const MESSAGES: u32 = 2000;
+        let rb = Arc::new(ByteRingBuffer::new_prioritized(MESSAGES as usize));
+        for i in 0..MESSAGES{
+            rb.push_with_priority(&i.to_le_bytes(), (i % 3) as u8).unwrap();
+        }
+
+        let consumers: Vec<_> = (0..4).map(|_|{
+            let rb = Arc::clone(&rb);
+            thread::spawn(move ||{
+                std::iter::from_fn(|| rb.pop()).map(|(_, epoch)| epoch).collect::<Vec<u64>>()
+            })
+        }).collect();
+        let mut epochs: Vec<u64> = consumers.into_iter().flat_map(|c| c.join().unwrap()).collect();
+
+        //a message claimed twice shows up twice, one skipped goes missing
+        epochs.sort_unstable();
+        assert_eq!(epochs, (1..=MESSAGES as u64).collect::<Vec<_>>());
+        assert!(rb.is_empty());
+    }
+
     #[test]
     fn test_zero_copy_peek(){
         let rb = ByteRingBuffer::new(4);
         rb.push(&[1, 2, 3, 4, 5]);
         rb.push(&[10, 20, 30]);
 
-        let (slice, epoch) = rb.peek_latest_ref().unwrap();
+        let (slice, epoch) = unsafe{ rb.peek_latest_ref() }.unwrap();
         assert_eq!(slice, &[10, 20, 30]);
         assert_eq!(epoch, 2);
         assert_eq!(rb.len(), 2);
@@ -294,7 +961,60 @@ mod tests{
         while let Some((data, _)) = rb.pop(){
             values.push(data);
         }
-        assert_eq!(values, vec![vec![4], vec![5]]);
+        assert_eq!(values, vec![vec![3, 3, 3, 3], vec![4], vec![5]]);
+    }
+
+    #[test]
+    fn test_pow2_wraps_like_modulo(){
+        let masked = ByteRingBuffer::new_pow2(3);
+        let plain = ByteRingBuffer::new(4);
+        assert_eq!(masked.capacity(), 4);
+
+        let mut next = 0u8;
+        for round in 0..30{
+            for _ in 0..round % 6{
+                assert_eq!(masked.push(&[next]), plain.push(&[next]));
+                next = next.wrapping_add(1);
+            }
+            for _ in 0..round % 5{
+                assert_eq!(masked.pop(), plain.pop());
+            }
+            assert_eq!(unsafe{ masked.peek_oldest_ref() }, unsafe{ plain.peek_oldest_ref() });
+        }
+        assert_eq!(masked.dropped_count(), plain.dropped_count());
+    }
+
+    #[test]
+    fn test_full_capacity_usable_after_wraparound(){
+        let rb = ByteRingBuffer::new(3);
+        for i in 0..5u8{
+            rb.push(&[i]);
+            assert_eq!(rb.pop().unwrap().0, vec![i]);
+        }
+        //head now sits right where the last read left off
+        rb.fill_from_iter([vec![7], vec![8], vec![9]]);
+        assert_eq!(rb.len(), 3);
+        assert!(rb.is_full());
+        let drained: Vec<_> = std::iter::from_fn(|| rb.pop()).map(|(data, _)| data).collect();
+        assert_eq!(drained, vec![vec![7], vec![8], vec![9]]);
+    }
+
+    #[test]
+    fn test_is_full_matches_pop_count_after_lap(){
+        let rb = ByteRingBuffer::new(4);
+        for i in 0..10u8{
+            rb.push(&[i]);
+        }
+        assert!(rb.is_full());
+        assert_eq!(rb.len(), 4);
+        assert_eq!(unsafe{ rb.peek_oldest_ref() }, Some((&[6u8][..], 7)));
+
+        let mut epochs = vec![];
+        while let Some((_, epoch)) = rb.pop(){
+            epochs.push(epoch);
+        }
+        assert_eq!(epochs, vec![7, 8, 9, 10]);
+        assert_eq!(rb.dropped_count(), 6);
     }
 
     #[test]
@@ -309,6 +1029,51 @@ mod tests{
         assert_eq!(rb.len(), 2);
     }
 
+    #[test]
+    #[cfg(debug_assertions)]
+    fn test_dump_slots_shows_epochs_and_state(){
+        let buffer = ByteRingBuffer::new_with_crc(4);
+        let state = |b: &ByteRingBuffer| b.dump_slots().iter().map(|s| (s.epoch, s.len, s.valid, s.consumed)).collect::<Vec<_>>();
+        assert_eq!(state(&buffer), vec![(0, 0, false, false); 4]);
+
+        //epochs 1..=6 over 4 slots: 5 and 6 have lapped slots 0 and 1
+        for i in 1..=6u8{
+            buffer.push(&vec![i; i as usize]);
+        }
+        buffer.pop();
+        assert_eq!(state(&buffer), vec![
+            (5, 5, true, false),
+            (6, 6, true, false),
+            (3, 3, true, true),
+            (4, 4, true, false),
+        ]);
+        assert_eq!(buffer.dump_slots()[3].index, 3);
+
+        unsafe{ buffer.slot_inner(3).data[0] ^= 0xFF; }
+        assert!(!buffer.dump_slots()[3].valid);
+    }
+
+    #[test]
+    fn test_peek_at_offset_walks_back_from_latest(){
+        let buffer = ByteRingBuffer::new(4);
+        assert_eq!(buffer.peek_at_offset(0), None);
+
+        for i in 1..=6u8{
+            buffer.push(&[i; 3]);
+        }
+        //latest is 6, the window holds 3..=6
+        for offset in 0..4{
+            let (data, epoch) = buffer.peek_at_offset(offset).unwrap();
+            assert_eq!(data, vec![6 - offset as u8; 3]);
+            assert_eq!(epoch, 6 - offset as u64);
+        }
+        assert_eq!(buffer.peek_at_offset(4), None);
+        assert_eq!(buffer.peek_at_offset(100), None);
+
+        //peeking consumes nothing
+        assert_eq!(buffer.pop().unwrap().1, 3);
+    }
+
     #[test]
     fn test_peek_oldest_ref(){
         let rb = ByteRingBuffer::new(4);
@@ -316,18 +1081,89 @@ mod tests{
         rb.push(&[10, 20]);
         rb.push(&[100]);
 
-        let (slice, epoch) = rb.peek_oldest_ref().unwrap();
+        let (slice, epoch) = unsafe{ rb.peek_oldest_ref() }.unwrap();
         assert_eq!(slice, &[1, 2, 3]);
         assert_eq!(epoch, 1);
         assert_eq!(rb.len(), 3);
     }
 
+    #[test]
+    fn test_peek_oldest_ref_skips_messages_popped_by_priority(){
+        let rb = ByteRingBuffer::new_prioritized(4);
+        rb.push_with_priority(&[1], 0).unwrap();
+        rb.push_with_priority(&[2], 0).unwrap();
+        rb.push_with_priority(&[3], 3).unwrap();
+        rb.push_with_priority(&[4], 0).unwrap();
+        assert_eq!(rb.pop(), Some((vec![3], 3)));
+
+        //1 and 2 are overwritten, leaving the popped 3 as the oldest resident
+        rb.push(&[5]);
+        rb.push(&[6]);
+        assert_eq!(unsafe{ rb.peek_oldest_ref() }, Some((&[4u8][..], 4)));
+        assert_eq!(rb.pop(), Some((vec![4], 4)));
+    }
+
     #[test]
     fn test_peek_methods_empty_buffer(){
         let rb = ByteRingBuffer::new(4);
         assert!(rb.peek_latest().is_none());
-        assert!(rb.peek_latest_ref().is_none());
-        assert!(rb.peek_oldest_ref().is_none());
+        assert!(unsafe{ rb.peek_latest_ref() }.is_none());
+        assert!(unsafe{ rb.peek_oldest_ref() }.is_none());
+    }
+
+    #[test]
+    fn test_crc_detects_corrupted_slot(){
+        let rb = ByteRingBuffer::new_with_crc(4);
+        rb.push(&[1, 2, 3]);
+        rb.push(&[4, 5, 6]);
+
+        //a misbehaving writer flips payload bytes without touching the epoch
+        unsafe{ rb.slot_inner(0).data[1] ^= 0xFF; }
+
+        let (data, epoch) = rb.pop().unwrap();
+        assert_eq!(data, vec![4, 5, 6]);
+        assert_eq!(epoch, 2);
+        assert_eq!(rb.corrupted_count(), 1);
+        assert!(rb.pop().is_none());
+    }
+
+    #[test]
+    fn test_crc_detects_bad_length(){
+        let rb = ByteRingBuffer::new_with_crc(4);
+        rb.push(&[1, 2, 3]);
+
+        unsafe{ rb.slot_inner(0).len = (MAX_PAYLOAD_SIZE + 1) as u32; }
+
+        assert!(rb.pop().is_none());
+        assert_eq!(rb.corrupted_count(), 1);
+    }
+
+    #[test]
+    fn test_prefault_leaves_contents_alone(){
+        let rb = ByteRingBuffer::new_prefaulted(64);
+        assert!(rb.is_empty());
+        assert_eq!(rb.latest_epoch(), 0);
+        assert!(rb.pop().is_none());
+
+        rb.push(&[1, 2, 3]);
+        rb.push(&[4]);
+        rb.prefault();
+        assert_eq!(rb.pop(), Some((vec![1, 2, 3], 1)));
+        assert_eq!(rb.peek_latest(), Some((vec![4], 2)));
+        assert_eq!(rb.len(), 1);
+    }
+
+    #[test]
+    fn test_memory_footprint(){
+        let slot = std::mem::size_of::<ByteSlot>();
+        //padding after the u32 length puts a slot slightly over SLOT_SIZE
+        assert!(slot >= SLOT_SIZE);
+
+        let rb = ByteRingBuffer::new(16);
+        assert_eq!(rb.memory_footprint(), std::mem::size_of::<ByteRingBuffer>() + 16 * slot);
+
+        let rb = ByteRingBuffer::new_with_crc(16);
+        assert_eq!(rb.memory_footprint(), std::mem::size_of::<ByteRingBuffer>() + 16 * (slot + 4));
     }
 
     #[test]
@@ -389,4 +1225,110 @@ mod tests{
             assert_eq!(val, i as u32);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_concurrent_producers_never_share_a_slot(){
+        const PER_PRODUCER: u32 = 2000;
+        let rb = Arc::new(ByteRingBuffer::new(4 * PER_PRODUCER as usize));
+
+        let producers: Vec<_> = (0..4u8).map(|id|{
+            let rb = Arc::clone(&rb);
+            thread::spawn(move ||{
+                for seq in 0..PER_PRODUCER{
+                    let mut msg = vec![id];
+                    msg.extend_from_slice(&seq.to_le_bytes());
+                    rb.push(&msg).unwrap();
+                }
+            })
+        }).collect();
+        for producer in producers{
+            producer.join().unwrap();
+        }
+
+        //two pushes claiming one slot would lose a message and repeat an epoch
+        let mut next = [0u32; 4];
+        let mut epochs = Vec::new();
+        while let Some((data, epoch)) = rb.pop(){
+            let id = data[0] as usize;
+            assert_eq!(u32::from_le_bytes(data[1..5].try_into().unwrap()), next[id]);
+            next[id] += 1;
+            epochs.push(epoch);
+        }
+        assert_eq!(next, [PER_PRODUCER; 4]);
+        assert_eq!(epochs, (1..=4 * PER_PRODUCER as u64).collect::<Vec<_>>());
+        assert_eq!(rb.dropped_count(), 0);
+    }
+
+    #[test]
+    fn test_read_guard_pins_its_slot(){
+        let rb = Arc::new(ByteRingBuffer::new(3));
+        assert!(rb.read_guard().is_none());
+
+        rb.push(&[1, 1]);
+        rb.push(&[2, 2, 2]);
+        let guard = rb.read_guard().unwrap();
+        assert_eq!(&*guard, &[2, 2, 2]);
+        assert_eq!(guard.epoch(), 2);
+
+        //the other two slots get reused, the third push laps onto ours and
+        //has to wait for the guard
+        let producer ={
+            let rb = Arc::clone(&rb);
+            thread::spawn(move ||{
+                rb.push(&[3]);
+                rb.push(&[4]);
+                rb.push(&[5]);
+            })
+        };
+        while rb.latest_epoch() < 5{
+            thread::yield_now();
+        }
+        thread::sleep(std::time::Duration::from_millis(20));
+        assert!(!producer.is_finished());
+        assert_eq!(&*guard, &[2, 2, 2]);
+
+        drop(guard);
+        producer.join().unwrap();
+        assert_eq!(rb.peek_latest(), Some((vec![5], 5)));
+    }
+
+    #[test]
+    fn test_read_guard_skips_first_push_in_flight(){
+        let rb = ByteRingBuffer::new(2);
+        //write_epoch announced, slot not written yet
+        rb.write_epoch.store(1, Ordering::SeqCst);
+        assert!(rb.read_guard().is_none());
+    }
+
+    #[test]
+    fn test_read_guard_reads_are_never_torn(){
+        use std::sync::atomic::AtomicBool;
+
+        let rb = Arc::new(ByteRingBuffer::new(2));
+        let done = Arc::new(AtomicBool::new(false));
+
+        let rb_producer = Arc::clone(&rb);
+        let done_flag = Arc::clone(&done);
+        let producer = thread::spawn(move ||{
+            for i in 0..50_000u32{
+                rb_producer.push(&[(i % 251) as u8; 200]);
+            }
+            done_flag.store(true, Ordering::SeqCst);
+        });
+
+        let mut reads = 0;
+        while !done.load(Ordering::SeqCst) || reads < 1000{
+            let Some(guard) = rb.read_guard() else{ continue };
+            //read twice through the guard: a write landing in between would
+            //show up as a mismatch or a mixed slot
+            let first = guard.to_vec();
+            assert_eq!(first.len(), 200);
+            assert!(first.iter().all(|&b| b == first[0]), "torn read through a pinned guard");
+            assert_eq!(&*guard, &first[..]);
+            reads += 1;
+        }
+        producer.join().unwrap();
+
+        assert_eq!(rb.read_guard().unwrap().epoch(), 50_000);
+    }
+}
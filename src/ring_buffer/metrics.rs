@@ -0,0 +1,18 @@
+//point-in-time counters for one buffer; rates come from diffing two snapshots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BufferMetrics{
+    pub len: usize,
+    pub capacity: usize,
+    //total messages ever published (the latest epoch)
+    pub published: u64,
+    //unread messages overwritten by the producer
+    pub dropped: u64,
+    //messages discarded on a crc mismatch
+    pub corrupted: u64,
+}
+
+impl BufferMetrics{
+    pub fn fill_ratio(&self) -> f32{
+        self.len as f32 / self.capacity as f32
+    }
+}
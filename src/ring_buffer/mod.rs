@@ -1,7 +1,17 @@
+pub mod arena;
 pub mod byte_buffer;
+pub mod metrics;
+pub mod wait;
+
+pub use arena::ArenaByteBuffer;
+pub use metrics::BufferMetrics;
+pub use wait::WaitStrategy;
 
 use std::cell::UnsafeCell;
-use std::sync::atomic::{AtomicUsize, AtomicU64, Ordering};
+use std::hint;
+use std::sync::atomic::{AtomicBool, AtomicUsize, AtomicU64, Ordering};
+use std::time::Duration;
+use wait::Notifier;
 
 struct SlotInner<T>{
     data: T,
@@ -10,6 +20,8 @@ struct SlotInner<T>{
 
 pub struct Slot<T>{
     inner: UnsafeCell<SlotInner<T>>,
+    //readers currently cloning or borrowing data, see RingBuffer::with_slot
+    readers: AtomicUsize,
 }
 
 impl<T: Default> Slot<T>{
@@ -19,25 +31,61 @@ impl<T: Default> Slot<T>{
                 data: T::default(),
                 epoch: AtomicU64::new(0),
             }),
+            readers: AtomicUsize::new(0),
         }
     }
 }
 
-pub struct RingBuffer<T>{
+//concurrency contract, shared through &self (usually an Arc):
+//  producers  any number. pushes are serialized by the `writing` flag, so two
+//             publishers never hold the same slot mutably
+//  consumers  any number. pop claims each epoch with a cas on read_epoch, so
+//             every message goes to at most one of them
+//  peekers    any number. peek_latest/with_latest/peek_*_ref hand out &T, which
+//             is why Sync needs T: Sync as well as T: Send
+//pop, pop_from, peek_latest and with_latest pin the slot they read (see
+//with_slot), so a producer lapping onto it waits instead of replacing the value mid-clone.
+//the exception is peek_latest_ref/peek_oldest_ref: their refs are not
+//pinned, which is why they are unsafe fns (see their # Safety sections).
+//ByteRingBuffer follows the same rules, with copies checked after the fact
+//instead of pinned, since torn bytes can be thrown away safely.
+//POW2 buffers come from new_pow2: their capacity is a power of two and slots
+//are found with & mask instead of %. it is a type parameter rather than a
+//runtime flag so neither kind of buffer pays a branch on every push and pop
+pub struct RingBuffer<T, const POW2: bool = false>{
     buffer: Vec<Slot<T>>,
+    //held by the one push allowed to touch slots at a time
+    writing: AtomicBool,
     head: AtomicUsize,
-    tail: AtomicUsize,
     write_epoch: AtomicU64,
     read_epoch: AtomicU64,  //last epoch consumed by reader
     capacity: usize,
+    //capacity - 1, only used to index when POW2
+    mask: usize,
+    notifier: Notifier,
 }
 
-unsafe impl<T: Send> Send for RingBuffer<T>{}
-unsafe impl<T: Send> Sync for RingBuffer<T>{}
+unsafe impl<T: Send, const POW2: bool> Send for RingBuffer<T, POW2>{}
+unsafe impl<T: Send + Sync, const POW2: bool> Sync for RingBuffer<T, POW2>{}
 
 impl<T: Clone + Default> RingBuffer<T>{
     pub fn new(capacity: usize) -> Self{
+        Self::with_capacity(capacity)
+    }
+}
+
+impl<T: Clone + Default> RingBuffer<T, true>{
+    //capacity rounded up to a power of two, so the hot paths can mask
+    //instead of dividing. behaves exactly like new otherwise
+    pub fn new_pow2(capacity: usize) -> Self{
+        Self::with_capacity(capacity.next_power_of_two())
+    }
+}
+
+impl<T: Clone + Default, const POW2: bool> RingBuffer<T, POW2>{
+    fn with_capacity(capacity: usize) -> Self{
         assert!(capacity > 0, "Capacity must be greater than 0 bruddaa!!");
+        debug_assert!(!POW2 || capacity.is_power_of_two());
 
         let mut buffer = Vec::with_capacity(capacity);
         for _ in 0..capacity{
@@ -46,15 +94,37 @@ impl<T: Clone + Default> RingBuffer<T>{
 
         RingBuffer{
             buffer,
+            writing: AtomicBool::new(false),
             head: AtomicUsize::new(0),
-            tail: AtomicUsize::new(0),
             write_epoch: AtomicU64::new(0),
             read_epoch: AtomicU64::new(0),
             capacity,
+            mask: capacity - 1,
+            notifier: Notifier::default(),
+        }
+    }
+
+    //epoch -> slot index; POW2 is a constant, so only one arm is compiled
+    #[inline]
+    fn slot_of(&self, epoch: u64) -> usize{
+        if POW2{
+            (epoch - 1) as usize & self.mask
+        }else{
+            ((epoch - 1) % self.capacity as u64) as usize
         }
     }
 
     #[inline]
+    fn slot_after(&self, index: usize) -> usize{
+        if POW2{
+            (index + 1) & self.mask
+        }else{
+            (index + 1) % self.capacity
+        }
+    }
+
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
     unsafe fn slot_inner(&self, index: usize) -> &mut SlotInner<T>{
         unsafe{ &mut *self.buffer[index].inner.get() }
     }
@@ -65,93 +135,188 @@ impl<T: Clone + Default> RingBuffer<T>{
     }
 
     pub fn push(&self, item: T) -> u64{
+        self.push_returning(item).0
+    }
+
+    //seed the buffer (fixtures, replay); returns how many were pushed
+    pub fn fill_from_iter(&self, items: impl IntoIterator<Item = T>) -> usize{
+        let mut pushed = 0;
+        for item in items{
+            self.push(item);
+            pushed += 1;
+        }
+        pushed
+    }
+
+    //push, handing back the value it overwrote so pooled allocations can be
+    //reused. only a value the consumer never read is returned: consumed
+    //slots and the initial defaults give None
+    pub fn push_returning(&self, item: T) -> (u64, Option<T>){
+        //uncontended with a single producer, a short spin otherwise
+        while self.writing.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err(){
+            hint::spin_loop();
+        }
         let head = self.head.load(Ordering::Relaxed);
 
         let new_epoch = self.write_epoch.load(Ordering::Relaxed) + 1;
-        self.write_epoch.store(new_epoch, Ordering::Relaxed);
+        //publish the new epoch before touching the slot, then wait out anyone
+        //who pinned the slot before they could see it (see with_slot)
+        self.write_epoch.store(new_epoch, Ordering::SeqCst);
+        while self.buffer[head].readers.load(Ordering::SeqCst) != 0{
+            hint::spin_loop();
+        }
 
-        unsafe{
+        let evicted = unsafe{
             let slot = self.slot_inner(head);
-            slot.data = item;
+            let old_epoch = slot.epoch.load(Ordering::SeqCst);
+            //an epoch handed out twice would make consumers skip or repeat
+            //messages without any other symptom
+            debug_assert!(
+                new_epoch > old_epoch,
+                "epoch reuse in slot {head}: storing {new_epoch} over {old_epoch}, read_epoch {} bruddaa!!",
+                self.read_epoch.load(Ordering::SeqCst),
+            );
+            let old = std::mem::replace(&mut slot.data, item);
             slot.epoch.store(new_epoch, Ordering::SeqCst);
-        }
+            let unread = old_epoch > self.read_epoch.load(Ordering::SeqCst);
+            if unread{ Some(old) }else{ None }
+        };
 
-        let new_head = (head + 1) % self.capacity;
+        let new_head = self.slot_after(head);
         self.head.store(new_head, Ordering::SeqCst);
+        self.writing.store(false, Ordering::Release);
+        self.notifier.notify();
+
+        (new_epoch, evicted)
+    }
+
+    //lets a test hand out an epoch twice, as a broken producer path would
+    #[cfg(all(test, debug_assertions))]
+    pub(crate) fn force_write_epoch(&self, epoch: u64){
+        self.write_epoch.store(epoch, Ordering::SeqCst);
+    }
+
+    //message e always lives in slot (e - 1) % capacity, so the next one to
+    //read is found from epochs alone: the one after the last consumed, or the
+    //oldest still resident once the producer has lapped the reader
+    fn next_unread(&self) -> Option<(usize, u64)>{
+        self.next_after(self.read_epoch.load(Ordering::SeqCst))
+    }
+
+    fn next_after(&self, read_epoch: u64) -> Option<(usize, u64)>{
+        let write_epoch = self.write_epoch.load(Ordering::SeqCst);
+        if read_epoch >= write_epoch{
+            return None;
+        }
 
-        new_epoch
+        let oldest_resident = write_epoch.saturating_sub(self.capacity as u64 - 1);
+        let epoch = (read_epoch + 1).max(oldest_resident);
+        Some((self.slot_of(epoch), epoch))
+    }
+
+    //runs f on message `epoch` with its slot pinned: a producer lapping onto
+    //the slot spins until f returns instead of replacing the value under it.
+    //None if the slot no longer (or not yet) holds that message. f must not
+    //push to this buffer, since that push could be the one left spinning
+    fn with_slot<R>(&self, index: usize, epoch: u64, f: impl FnOnce(&T) -> R) -> Option<R>{
+        let slot = &self.buffer[index];
+        slot.readers.fetch_add(1, Ordering::SeqCst);
+        //pairs with push_returning: either it sees our pin and waits, or we
+        //see its write_epoch here and back off. the next write to this slot
+        //is epoch + capacity
+        let pinned = self.slot_epoch(index) == epoch
+            && self.write_epoch.load(Ordering::SeqCst) < epoch + self.capacity as u64;
+        let result = pinned.then(|| f(unsafe{ &(*slot.inner.get()).data }));
+        slot.readers.fetch_sub(1, Ordering::Release);
+        result
     }
 
     pub fn pop(&self) -> Option<T>{
         loop{
-            let tail = self.tail.load(Ordering::SeqCst);
-            let head = self.head.load(Ordering::SeqCst);
             let read_epoch = self.read_epoch.load(Ordering::SeqCst);
-            let write_epoch = self.write_epoch.load(Ordering::SeqCst);
+            let (index, epoch) = self.next_after(read_epoch)?;
+            let slot_epoch = self.slot_epoch(index);
 
-            //empty check: nothing written yet
-            if write_epoch == 0{
+            //newest write announced but not in the slot yet
+            if slot_epoch < epoch{
                 return None;
             }
+            //producer lapped us since next_unread, look again
+            if slot_epoch > epoch{
+                continue;
+            }
 
-            let slot_epoch = self.slot_epoch(tail);
+            //lapped between the check above and the pin, look again
+            let Some(item) = self.with_slot(index, epoch, T::clone) else{ continue };
 
-            //already consumed this slot?
-            if slot_epoch <= read_epoch{
-                //check if there's newer data ahead
-                if tail == head{
-                    return None; //truly empty - caught up
-                }
-                //advance tail to find unread slot
-                let new_tail = (tail + 1) % self.capacity;
-                self.tail.store(new_tail, Ordering::SeqCst);
+            //claim it; losing means another consumer took this one (or a
+            //newer one), so drop our copy and look again
+            if self.read_epoch.compare_exchange(read_epoch, epoch, Ordering::SeqCst, Ordering::SeqCst).is_err(){
                 continue;
             }
 
-            //check if slot was overwritten (producer lapped us)
-            let min_valid_epoch = write_epoch.saturating_sub(self.capacity as u64 - 1);
-            if slot_epoch < min_valid_epoch{
-                //slot overwritten, skip it
-                self.read_epoch.store(slot_epoch, Ordering::SeqCst);
-                let new_tail = (tail + 1) % self.capacity;
-                self.tail.store(new_tail, Ordering::SeqCst);
+            return Some(item);
+        }
+    }
+
+    //read the first resident message after `cursor` without consuming it,
+    //so any number of readers can each walk the whole stream with their own
+    //cursor. the epoch comes back as the next cursor; a jump of more than one
+    //means the producer lapped this reader. read_epoch is left alone
+    pub fn pop_from(&self, cursor: u64) -> Option<(T, u64)>{
+        loop{
+            let (index, epoch) = self.next_after(cursor)?;
+            let slot_epoch = self.slot_epoch(index);
+            if slot_epoch < epoch{
+                return None;
+            }
+            if slot_epoch > epoch{
                 continue;
             }
 
-            //valid slot - read data
-            let item = unsafe{
-                let slot = &*self.buffer[tail].inner.get();
-                slot.data.clone()
-            };
-
-            //mark as consumed
-            self.read_epoch.store(slot_epoch, Ordering::SeqCst);
+            if let Some(item) = self.with_slot(index, epoch, T::clone){
+                return Some((item, epoch));
+            }
+        }
+    }
 
-            //advance tail
-            let new_tail = (tail + 1) % self.capacity;
-            self.tail.store(new_tail, Ordering::SeqCst);
+    //blocking pop; None once `timeout` passes with nothing to read
+    pub fn pop_timeout(&self, timeout: Duration, strategy: WaitStrategy) -> Option<T>{
+        self.notifier.wait_for(timeout, strategy, || self.pop())
+    }
 
-            return Some(item);
-        }
+    //parks until push signals, no spinning; None timeout waits forever
+    pub fn pop_blocking(&self, timeout: Option<Duration>) -> Option<T>{
+        self.pop_timeout(timeout.unwrap_or(Duration::MAX), WaitStrategy::Park)
     }
 
     pub fn peek_latest(&self) -> Option<(T, u64)>{
-        let write_epoch = self.write_epoch.load(Ordering::SeqCst);
-        if write_epoch == 0{
-            return None;
-        }
+        loop{
+            if self.write_epoch.load(Ordering::SeqCst) == 0{
+                return None;
+            }
 
-        let head = self.head.load(Ordering::SeqCst);
-        let latest_idx = if head == 0{ self.capacity - 1 }else{ head - 1 };
+            let head = self.head.load(Ordering::SeqCst);
+            let latest_idx = if head == 0{ self.capacity - 1 }else{ head - 1 };
+            let epoch = self.slot_epoch(latest_idx);
+            //first push still in flight
+            if epoch == 0{
+                return None;
+            }
 
-        unsafe{
-            let slot = &*self.buffer[latest_idx].inner.get();
-            let epoch = slot.epoch.load(Ordering::SeqCst);
-            Some((slot.data.clone(), epoch))
+            if let Some(item) = self.with_slot(latest_idx, epoch, T::clone){
+                return Some((item, epoch));
+            }
         }
     }
 
-    pub fn peek_latest_ref(&self) -> Option<(&T, u64)>{
+    //latest value in place, without pinning it; with_latest is the safe form
+    /// # Safety
+    /// The slot is not pinned: a push that laps onto it replaces the value
+    /// while the reference is alive. The caller must make sure no push to
+    /// this buffer can reach that slot (capacity further pushes) before the
+    /// reference is dropped.
+    pub unsafe fn peek_latest_ref(&self) -> Option<(&T, u64)>{
         let write_epoch = self.write_epoch.load(Ordering::SeqCst);
         if write_epoch == 0{
             return None;
@@ -167,23 +332,42 @@ impl<T: Clone + Default> RingBuffer<T>{
         }
     }
 
-    pub fn peek_oldest_ref(&self) -> Option<(&T, u64)>{
-        let write_epoch = self.write_epoch.load(Ordering::SeqCst);
-        if write_epoch == 0{
-            return None;
-        }
+    //runs f on the latest value in place, no clone; None only when empty.
+    //the slot stays pinned while f runs (see with_slot), so a producer that
+    //laps onto it waits for f: keep f short, and never push from inside it
+    pub fn with_latest<R>(&self, f: impl FnOnce(&T, u64) -> R) -> Option<R>{
+        let mut f = Some(f);
+        loop{
+            if self.write_epoch.load(Ordering::SeqCst) == 0{
+                return None;
+            }
 
-        let tail = self.tail.load(Ordering::SeqCst);
-        let read_epoch = self.read_epoch.load(Ordering::SeqCst);
-        let slot_epoch = self.slot_epoch(tail);
+            let head = self.head.load(Ordering::SeqCst);
+            let latest_idx = if head == 0{ self.capacity - 1 }else{ head - 1 };
+            let epoch = self.slot_epoch(latest_idx);
+            if epoch == 0{
+                return None;
+            }
 
-        if slot_epoch <= read_epoch{
-            return None; //already consumed
+            //only taken once the pin holds, so a retry still has f
+            if let Some(result) = self.with_slot(latest_idx, epoch, |data| f.take().unwrap()(data, epoch)){
+                return Some(result);
+            }
+        }
+    }
+
+    //oldest unread value in place, without pinning or consuming it
+    /// # Safety
+    /// Same as peek_latest_ref: no push may reach the slot while the
+    /// reference is alive.
+    pub unsafe fn peek_oldest_ref(&self) -> Option<(&T, u64)>{
+        let (index, epoch) = self.next_unread()?;
+        if self.slot_epoch(index) != epoch{
+            return None; //in flight or lapped
         }
 
         unsafe{
-            let slot = &*self.buffer[tail].inner.get();
-            let epoch = slot.epoch.load(Ordering::SeqCst);
+            let slot = &*self.buffer[index].inner.get();
             Some((&slot.data, epoch))
         }
     }
@@ -192,6 +376,9 @@ impl<T: Clone + Default> RingBuffer<T>{
         self.write_epoch.load(Ordering::SeqCst)
     }
 
+    //unread messages pop would still return. every slot is usable: after a
+    //lap the newest `capacity` messages are all readable, so len, is_full
+    //and the number of successful pops always agree
     pub fn len(&self) -> usize{
         let write_epoch = self.write_epoch.load(Ordering::SeqCst);
         let read_epoch = self.read_epoch.load(Ordering::SeqCst);
@@ -216,6 +403,11 @@ impl<T: Clone + Default> RingBuffer<T>{
     pub fn capacity(&self) -> usize{
         self.capacity
     }
+
+    //heap owned by T itself is not counted
+    pub fn memory_footprint(&self) -> usize{
+        std::mem::size_of::<Self>() + self.capacity * std::mem::size_of::<Slot<T>>()
+    }
 }
 
 #[cfg(test)]
@@ -272,7 +464,70 @@ mod tests{
         while let Some(v) = rb.pop(){
             values.push(v);
         }
-        assert_eq!(values, vec![4, 5]); //when head wraps to tail, that slot becomes inaccessible
+        assert_eq!(values, vec![3, 4, 5]); //the newest `capacity` survive a lap
+    }
+
+    #[test]
+    fn test_pow2_wraps_like_modulo(){
+        let masked = RingBuffer::<u32, true>::new_pow2(5);
+        let plain = RingBuffer::<u32>::new(8);
+        assert_eq!(masked.capacity(), 8);
+
+        //uneven push/pop rounds so head and the read cursor land everywhere
+        let mut next = 0;
+        for round in 0..40{
+            for _ in 0..round % 11{
+                assert_eq!(masked.push(next), plain.push(next));
+                next += 1;
+            }
+            for _ in 0..round % 7{
+                assert_eq!(masked.pop(), plain.pop());
+            }
+            assert_eq!(masked.len(), plain.len());
+            assert_eq!(masked.pop_from(0), plain.pop_from(0));
+        }
+    }
+
+    #[test]
+    fn test_full_capacity_usable_after_wraparound(){
+        let rb: RingBuffer<i32> = RingBuffer::new(3);
+        //leave head one past a partly consumed lap, then refill to capacity
+        for round in 0..4{
+            rb.push(round * 10 + 1);
+            rb.push(round * 10 + 2);
+            assert_eq!(rb.pop(), Some(round * 10 + 1));
+            assert_eq!(rb.pop(), Some(round * 10 + 2));
+        }
+        rb.fill_from_iter([7, 8, 9]);
+        assert_eq!(rb.len(), 3);
+        assert!(rb.is_full());
+        assert_eq!(std::iter::from_fn(|| rb.pop()).collect::<Vec<_>>(), vec![7, 8, 9]);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "epoch reuse in slot 0: storing 1 over 1")]
+    fn test_push_catches_epoch_reuse(){
+        let rb: RingBuffer<i32> = RingBuffer::new(3);
+        rb.fill_from_iter([1, 2, 3]);
+        rb.force_write_epoch(0);
+        rb.push(4);
+    }
+
+    #[test]
+    fn test_push_returning_hands_back_unread(){
+        let rb: RingBuffer<Vec<u8>> = RingBuffer::new(3);
+        assert_eq!(rb.push_returning(vec![1]), (1, None));
+        assert_eq!(rb.push_returning(vec![2]), (2, None));
+        assert_eq!(rb.push_returning(vec![3]), (3, None));
+
+        //consumed values are not handed back
+        assert_eq!(rb.pop(), Some(vec![1]));
+        assert_eq!(rb.push_returning(vec![4]), (4, None));
+
+        //wrapped onto unread messages
+        assert_eq!(rb.push_returning(vec![5]), (5, Some(vec![2])));
+        assert_eq!(rb.push_returning(vec![6]), (6, Some(vec![3])));
     }
 
     #[test]
@@ -285,6 +540,39 @@ mod tests{
         assert!(rb.is_full());
     }
 
+    #[test]
+    fn test_is_full_len_and_pops_agree(){
+        for capacity in 1..6{
+            for pushes in 0..3 * capacity{
+                for pops_first in 0..=pushes.min(capacity){
+                    let rb: RingBuffer<usize> = RingBuffer::new(capacity);
+                    let mut next = 0;
+                    for _ in 0..pops_first{
+                        rb.push(next);
+                        next += 1;
+                        assert_eq!(rb.pop(), Some(next - 1));
+                    }
+                    while next < pushes{
+                        rb.push(next);
+                        next += 1;
+                    }
+
+                    let len = rb.len();
+                    let full = rb.is_full();
+                    let mut popped = Vec::new();
+                    while let Some(v) = rb.pop(){
+                        popped.push(v);
+                    }
+                    assert_eq!(popped.len(), len, "capacity {} pushes {}", capacity, pushes);
+                    assert_eq!(full, len == capacity);
+                    //oldest-first, ending at the newest
+                    let expected: Vec<usize> = (pushes - len..pushes).collect();
+                    assert_eq!(popped, expected);
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_peek_latest(){
         let rb: RingBuffer<i32> = RingBuffer::new(5);
@@ -302,10 +590,24 @@ mod tests{
         rb.push(10);
         rb.push(20);
         rb.push(30);
-        let (val_ref, _) = rb.peek_latest_ref().unwrap();
+        //no push while the ref is held
+        let (val_ref, _) = unsafe{ rb.peek_latest_ref() }.unwrap();
         assert_eq!(*val_ref, 30);
     }
 
+    #[test]
+    fn test_memory_footprint(){
+        use std::mem::{align_of, size_of};
+        let rb: RingBuffer<[f32; 9]> = RingBuffer::new(10);
+        //36 bytes of data, but the u64 epoch makes the slot 8-aligned: 4 bytes
+        //of padding before it, 48 in all, then the reader count on top
+        assert_eq!(align_of::<SlotInner<[f32; 9]>>(), 8);
+        assert_eq!(size_of::<SlotInner<[f32; 9]>>(), 48);
+        let slot = size_of::<SlotInner<[f32; 9]>>() + size_of::<AtomicUsize>();
+        assert_eq!(size_of::<Slot<[f32; 9]>>(), slot);
+        assert_eq!(rb.memory_footprint(), size_of::<RingBuffer<[f32; 9]>>() + 10 * slot);
+    }
+
     #[test]
     fn test_spsc_threaded(){
         use std::sync::atomic::AtomicBool;
@@ -360,4 +662,190 @@ mod tests{
             assert_eq!(val, i as i32);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_pop_under_lapping_producer_never_sees_a_replaced_value(){
+        //tiny buffer and heap items: the producer laps the consumer constantly,
+        //so pop keeps landing on the very slot the next push replaces
+        let rb = Arc::new(RingBuffer::<Vec<u64>>::new(2));
+        let producer ={
+            let rb = Arc::clone(&rb);
+            thread::spawn(move ||{
+                for i in 1..=20_000u64{
+                    rb.push(vec![i; 32]);
+                }
+            })
+        };
+
+        let mut last = 0;
+        while last < 20_000{
+            if let Some(item) = rb.pop(){
+                assert!(item.iter().all(|&v| v == item[0]), "torn item {:?}", item);
+                assert!(item[0] > last);
+                last = item[0];
+            }
+        }
+        producer.join().unwrap();
+    }
+
+    #[test]
+    fn test_mpmc_delivers_each_message_once(){
+        const PER_PRODUCER: u64 = 5000;
+        //big enough that nothing is overwritten, so every message must arrive
+        let rb = Arc::new(RingBuffer::<u64>::new(4 * PER_PRODUCER as usize));
+
+        let producers: Vec<_> = (0..2u64).map(|p|{
+            let rb = Arc::clone(&rb);
+            thread::spawn(move ||{
+                for i in 0..PER_PRODUCER{
+                    rb.push(p * PER_PRODUCER + i);
+                }
+            })
+        }).collect();
+        let consumers: Vec<_> = (0..3).map(|_|{
+            let rb = Arc::clone(&rb);
+            thread::spawn(move ||{
+                let mut got = Vec::new();
+                while let Some(v) = rb.pop_timeout(Duration::from_millis(200), WaitStrategy::default()){
+                    got.push(v);
+                }
+                got
+            })
+        }).collect();
+
+        for producer in producers{
+            producer.join().unwrap();
+        }
+        let mut all: Vec<u64> = consumers.into_iter().flat_map(|c| c.join().unwrap()).collect();
+        all.sort_unstable();
+        assert_eq!(all, (0..2 * PER_PRODUCER).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_fill_from_iter_drains_in_order(){
+        let rb = RingBuffer::<u32>::new(8);
+        assert_eq!(rb.fill_from_iter(1..=5), 5);
+        assert_eq!(std::iter::from_fn(|| rb.pop()).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_pop_from_leaves_messages_for_other_readers(){
+        let buffer = RingBuffer::<u32>::new(4);
+        for i in 1..=6{
+            buffer.push(i);
+        }
+
+        //lapped cursors jump to the oldest resident message
+        assert_eq!(buffer.pop_from(0), Some((3, 3)));
+        assert_eq!(buffer.pop_from(3), Some((4, 4)));
+        assert_eq!(buffer.pop_from(6), None);
+        assert_eq!(buffer.len(), 4);
+        assert_eq!(buffer.pop(), Some(3));
+    }
+
+    #[test]
+    fn test_pop_timeout_expires_when_empty(){
+        let rb: RingBuffer<i32> = RingBuffer::new(4);
+        for strategy in [WaitStrategy::Spin, WaitStrategy::Park, WaitStrategy::SpinThenPark{ spins: 8 }]{
+            let start = std::time::Instant::now();
+            assert_eq!(rb.pop_timeout(Duration::from_millis(20), strategy), None);
+            assert!(start.elapsed() >= Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn test_pop_blocking_wakes_on_push(){
+        let rb = Arc::new(RingBuffer::<u64>::new(8));
+        let producer_rb = Arc::clone(&rb);
+
+        let producer = thread::spawn(move ||{
+            thread::sleep(Duration::from_millis(50));
+            producer_rb.push(5);
+            thread::sleep(Duration::from_millis(50));
+            producer_rb.push(6);
+        });
+
+        let start = std::time::Instant::now();
+        assert_eq!(rb.pop_blocking(Some(Duration::from_secs(5))), Some(5));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(rb.pop_blocking(None), Some(6));
+        producer.join().unwrap();
+
+        assert_eq!(rb.pop_blocking(Some(Duration::from_millis(10))), None);
+        assert_eq!(rb.notifier.waiters(), 0);
+    }
+
+    #[test]
+    fn test_spin_window_receives_quickly(){
+        let rb = Arc::new(RingBuffer::<u64>::new(8));
+        let consumer_rb = Arc::clone(&rb);
+
+        let consumer = thread::spawn(move ||{
+            let item = consumer_rb.pop_timeout(Duration::from_secs(5), WaitStrategy::SpinThenPark{ spins: u32::MAX });
+            (item, std::time::Instant::now())
+        });
+
+        thread::sleep(Duration::from_millis(10));
+        let sent = std::time::Instant::now();
+        rb.push(7);
+
+        let (item, received) = consumer.join().unwrap();
+        assert_eq!(item, Some(7));
+        assert!(received.duration_since(sent) < Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_park_after_spin_window_still_wakes(){
+        let rb = Arc::new(RingBuffer::<u64>::new(8));
+        let consumer_rb = Arc::clone(&rb);
+
+        let start = std::time::Instant::now();
+        let consumer = thread::spawn(move ||{
+            consumer_rb.pop_timeout(Duration::from_secs(5), WaitStrategy::SpinThenPark{ spins: 16 })
+        });
+
+        //wait until the consumer has exhausted its spins and parked
+        while rb.notifier.waiters() == 0{
+            thread::yield_now();
+        }
+        rb.push(9);
+
+        assert_eq!(consumer.join().unwrap(), Some(9));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(rb.notifier.waiters(), 0);
+    }
+
+    #[test]
+    fn test_with_latest_holds_off_a_lapping_producer(){
+        let rb = Arc::new(RingBuffer::<Vec<u64>>::new(3));
+        assert_eq!(rb.with_latest(|v, _| v[0]), None);
+
+        rb.push(vec![1; 4]);
+        rb.push(vec![2; 4]);
+        assert_eq!(rb.with_latest(|v, epoch| (v[0], epoch)), Some((2, 2)));
+
+        //producer laps onto the slot being read: it has to wait for f
+        let writer ={
+            let rb = Arc::clone(&rb);
+            move ||{
+                for i in 3..6{
+                    rb.push(vec![i; 4]);
+                }
+            }
+        };
+        let mut producer = None;
+        let seen = rb.with_latest(|v, _|{
+            producer = Some(thread::spawn(writer));
+            //epoch 5 is claimed but its write is parked on our slot
+            while rb.latest_epoch() < 5{
+                thread::yield_now();
+            }
+            thread::sleep(std::time::Duration::from_millis(20));
+            v.clone()
+        });
+        assert_eq!(seen, Some(vec![2; 4]));
+
+        producer.unwrap().join().unwrap();
+        assert_eq!(rb.with_latest(|v, epoch| (v[0], epoch)), Some((5, 5)));
+    }
+}
@@ -0,0 +1,91 @@
+use std::hint;
+use std::sync::{Condvar, Mutex, PoisonError};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use crate::poison::MutexExt;
+
+//how a consumer waits for data in the blocking pop/recv calls
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitStrategy{
+    //busy-poll until data or timeout: lowest latency, burns a core
+    Spin,
+    //sleep until the producer signals
+    Park,
+    //poll `spins` times first, then park
+    SpinThenPark{ spins: u32 },
+}
+
+impl Default for WaitStrategy{
+    fn default() -> Self{
+        WaitStrategy::SpinThenPark{ spins: 1024 }
+    }
+}
+
+//wakes parked consumers; producers only touch the lock when someone is parked
+#[derive(Default)]
+pub(crate) struct Notifier{
+    lock: Mutex<()>,
+    cond: Condvar,
+    waiters: AtomicUsize,
+}
+
+impl Notifier{
+    pub(crate) fn notify(&self){
+        if self.waiters.load(Ordering::SeqCst) > 0{
+            let _guard = self.lock.lock_unpoisoned();
+            self.cond.notify_all();
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn waiters(&self) -> usize{
+        self.waiters.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn wait_for<R>(&self, timeout: Duration, strategy: WaitStrategy, mut poll: impl FnMut() -> Option<R>) -> Option<R>{
+        //None = timeout too large to represent, i.e. wait forever
+        let deadline = Instant::now().checked_add(timeout);
+        let expired = || deadline.is_some_and(|d| Instant::now() >= d);
+
+        let spin_limit = match strategy{
+            WaitStrategy::Spin => None,
+            WaitStrategy::Park => Some(0),
+            WaitStrategy::SpinThenPark{ spins } => Some(spins),
+        };
+
+        let mut spun = 0u32;
+        loop{
+            if let Some(item) = poll(){
+                return Some(item);
+            }
+            if expired(){
+                return None;
+            }
+            if spin_limit.is_some_and(|limit| spun >= limit){
+                break;
+            }
+            spun = spun.saturating_add(1);
+            hint::spin_loop();
+        }
+
+        loop{
+            //register under the lock and re-check, so a push between the
+            //check and the wait can't slip past unnoticed
+            let guard = self.lock.lock_unpoisoned();
+            self.waiters.fetch_add(1, Ordering::SeqCst);
+
+            let item = poll();
+            let remaining = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            if item.is_some() || remaining == Some(Duration::ZERO){
+                self.waiters.fetch_sub(1, Ordering::SeqCst);
+                return item;
+            }
+
+            let _guard = match remaining{
+                Some(remaining) => self.cond.wait_timeout(guard, remaining).unwrap_or_else(PoisonError::into_inner).0,
+                None => self.cond.wait(guard).unwrap_or_else(PoisonError::into_inner),
+            };
+            self.waiters.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
@@ -1,18 +1,22 @@
 pub mod protocol;
+pub mod transport;
 pub use protocol::*;
+pub use transport::{Transport, LoopbackTransport, RetryPolicy, write_fully, write_with_retry, is_retryable};
 
-use std::io::{Read, Write};
+use std::io::Read;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::thread::{self, JoinHandle};
-use std::time::Duration;
-use serialport::SerialPort;
-use crate::pubsub::{TopicRegistry, ByteTopic};
+use std::time::{Duration, Instant};
+use crate::pubsub::TopicRegistry;
 
-pub const SYNC_BYTE: u8 = 0xAA;
-pub const MAX_MSG_SIZE: usize = 244;
+//how long autobaud listens at each candidate rate
+pub const AUTOBAUD_WINDOW: Duration = Duration::from_millis(250);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+//every frame as [type][payload...], for sniffers that want all traffic on one subscription
+pub const MERGED_TOPIC: &str = "/stm32/frames";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum MsgType{
     Imu = 0x01,
@@ -27,22 +31,11 @@ pub enum MsgType{
 }
 
 impl MsgType{
-    fn from_u8(val: u8) -> Option<Self>{
-        match val{
-            0x01 => Some(MsgType::Imu),
-            0x02 => Some(MsgType::Depth),
-            0x03 => Some(MsgType::Thruster),
-            0x04 => Some(MsgType::Heartbeat),
-            0x05 => Some(MsgType::Orientation),
-            0x10 => Some(MsgType::Command),
-            0x11 => Some(MsgType::Ack),
-            0x12 => Some(MsgType::Led),
-            0x13 => Some(MsgType::Calibration),
-            _ => None,
-        }
+    pub(crate) fn from_u8(val: u8) -> Option<Self>{
+        Self::try_from(val).ok()
     }
 
-    fn to_topic_name(&self) -> &'static str{
+    pub(crate) fn to_topic_name(self) -> &'static str{
         match self{
             MsgType::Imu => "/stm32/imu",
             MsgType::Depth => "/stm32/depth",
@@ -57,17 +50,51 @@ impl MsgType{
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct UartFrame{
-    pub msg_type: MsgType,
-    pub payload: Vec<u8>,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownMsgType(pub u8);
+
+impl std::fmt::Display for UnknownMsgType{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        write!(f, "unknown message type 0x{:02X}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownMsgType{}
+
+impl TryFrom<u8> for MsgType{
+    type Error = UnknownMsgType;
+
+    fn try_from(val: u8) -> Result<Self, Self::Error>{
+        match val{
+            0x01 => Ok(MsgType::Imu),
+            0x02 => Ok(MsgType::Depth),
+            0x03 => Ok(MsgType::Thruster),
+            0x04 => Ok(MsgType::Heartbeat),
+            0x05 => Ok(MsgType::Orientation),
+            0x10 => Ok(MsgType::Command),
+            0x11 => Ok(MsgType::Ack),
+            0x12 => Ok(MsgType::Led),
+            0x13 => Ok(MsgType::Calibration),
+            _ => Err(UnknownMsgType(val)),
+        }
+    }
+}
+
+impl From<MsgType> for u8{
+    fn from(msg_type: MsgType) -> u8{
+        msg_type as u8
+    }
 }
 
 pub struct UartBridge{
-    port: Box<dyn SerialPort>,
+    port: Box<dyn Transport>,
     registry: Arc<TopicRegistry>,
     running: Arc<AtomicBool>,
-    rx_buffer: Vec<u8>,
+    decoder: FrameDecoder,
+    codec: FrameCodec,
+    publish_merged: bool,
+    //frames whose payload plus the type byte didn't fit a MERGED_TOPIC slot
+    merged_dropped: AtomicU64,
 }
 
 impl UartBridge{
@@ -76,14 +103,147 @@ impl UartBridge{
             .timeout(Duration::from_millis(10))
             .open()?;
 
-        Ok(UartBridge{
+        Ok(Self::with_transport(Box::new(port), registry))
+    }
+
+    pub fn new_with_checksum(port_name: &str, baud_rate: u32, registry: Arc<TopicRegistry>, kind: ChecksumKind) -> Result<Self, serialport::Error>{
+        Ok(Self::new(port_name, baud_rate, registry)?.with_checksum(kind))
+    }
+
+    pub fn with_transport(port: Box<dyn Transport>, registry: Arc<TopicRegistry>) -> Self{
+        UartBridge{
             port,
             registry,
             running: Arc::new(AtomicBool::new(false)),
-            rx_buffer: Vec::with_capacity(512),
+            decoder: FrameDecoder::new(),
+            codec: FrameCodec::new(),
+            publish_merged: false,
+            merged_dropped: AtomicU64::new(0),
+        }
+    }
+
+    //try each baud rate in turn, keeping the first one that yields a frame
+    //passing the checksum within AUTOBAUD_WINDOW. that frame is published
+    //like any other, so nothing received while probing is lost
+    pub fn autobaud(port_name: &str, candidates: &[u32], registry: Arc<TopicRegistry>) -> Result<(Self, u32), serialport::Error>{
+        Self::autobaud_with(candidates, registry, AUTOBAUD_WINDOW, |baud|{
+            let port = serialport::new(port_name, baud)
+                .timeout(Duration::from_millis(10))
+                .open()?;
+            Ok(Box::new(port) as Box<dyn Transport>)
         })
     }
 
+    pub(crate) fn autobaud_with<F>(candidates: &[u32], registry: Arc<TopicRegistry>, window: Duration, mut open: F) -> Result<(Self, u32), serialport::Error>
+    where
+        F: FnMut(u32) -> Result<Box<dyn Transport>, serialport::Error>,
+    {
+        let mut last_err = None;
+        for &baud in candidates{
+            let port = match open(baud){
+                Ok(port) => port,
+                Err(e) =>{
+                    last_err = Some(e);
+                    continue;
+                }
+            };
+
+            let mut bridge = Self::with_transport(port, Arc::clone(&registry));
+            if bridge.probe(window){
+                return Ok((bridge, baud));
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| serialport::Error::new(
+            serialport::ErrorKind::Io(std::io::ErrorKind::TimedOut),
+            "no candidate baud rate produced a valid frame",
+        )))
+    }
+
+    //read until one valid frame decodes or the window closes
+    fn probe(&mut self, window: Duration) -> bool{
+        let deadline = Instant::now() + window;
+        let mut read_buf = [0u8; 256];
+
+        while Instant::now() < deadline{
+            match self.port.read(&mut read_buf){
+                Ok(n) if n > 0 => self.decoder.extend(&read_buf[..n]),
+                Ok(_) => {}
+                Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+                Err(_) => return false,
+            }
+            if let Some(frame) = self.decoder.next_frame(){
+                self.publish_frame(&frame);
+                return true;
+            }
+        }
+        false
+    }
+
+    //match the firmware's checksum convention for both directions
+    pub fn with_checksum_coverage(mut self, coverage: ChecksumCoverage) -> Self{
+        self.codec = self.codec.with_checksum_coverage(coverage);
+        self.match_decoder();
+        self
+    }
+
+    //frames open with `preamble` both ways, instead of the lone SYNC_BYTE
+    pub fn with_preamble(mut self, preamble: Preamble) -> Self{
+        self.codec = self.codec.with_preamble(preamble);
+        self.match_decoder();
+        self
+    }
+
+    //checksum algorithm for both directions, Sum8 unless changed
+    pub fn with_checksum(mut self, kind: ChecksumKind) -> Self{
+        self.codec = self.codec.with_checksum(kind);
+        self.match_decoder();
+        self
+    }
+
+    //byte-stuffed frames both ways, see FrameCodec::with_escaping
+    pub fn with_escaping(mut self, enabled: bool) -> Self{
+        self.codec = self.codec.with_escaping(enabled);
+        self.match_decoder();
+        self
+    }
+
+    //rebuild the decoder to parse what the codec writes, keeping its capacity
+    fn match_decoder(&mut self){
+        self.decoder = FrameDecoder::with_coverage(self.codec.coverage())
+            .with_preamble(self.codec.preamble())
+            .with_checksum(self.codec.checksum_kind())
+            .with_escaping(self.codec.escaping())
+            .with_buffer_capacity(self.decoder.buffer_capacity());
+    }
+
+    //rx buffer sized up front instead of RX_BUFFER_CAPACITY, for links that
+    //burst more than a few frames between reads
+    pub fn with_rx_capacity(mut self, capacity: usize) -> Self{
+        self.decoder = self.decoder.with_buffer_capacity(capacity);
+        self
+    }
+
+    //swap in a freshly opened port. a partial frame from the old link is
+    //dropped so it can't splice with the new one's bytes; the rx buffer
+    //keeps its allocation
+    pub fn reconnect(&mut self, port: Box<dyn Transport>){
+        self.port = port;
+        self.decoder.clear();
+    }
+
+    //also publish every frame, type-prefixed, to MERGED_TOPIC
+    pub fn with_merged_topic(mut self, enabled: bool) -> Self{
+        self.publish_merged = enabled;
+        self
+    }
+
+    //frames missing from MERGED_TOPIC because a full-size payload leaves no
+    //room for the type byte; they still reach their per-type topic
+    pub fn merged_dropped(&self) -> u64{
+        self.merged_dropped.load(Ordering::Relaxed)
+    }
+
     pub fn start(mut self) -> (JoinHandle<()>, Arc<AtomicBool>){
         let running = Arc::clone(&self.running);
         self.running.store(true, Ordering::SeqCst);
@@ -101,7 +261,7 @@ impl UartBridge{
         while self.running.load(Ordering::SeqCst){
             match self.port.read(&mut read_buf){
                 Ok(n) if n > 0 =>{
-                    self.rx_buffer.extend_from_slice(&read_buf[..n]);
+                    self.decoder.extend(&read_buf[..n]);
                     self.process_buffer();
                 }
                 Ok(_) => {}
@@ -114,73 +274,31 @@ impl UartBridge{
     }
 
     fn process_buffer(&mut self){
-        loop{
-            if let Some(frame) = self.try_parse_frame(){
-                self.publish_frame(&frame);
-            }else{
-                break;
-            }
-        }
-    }
-
-    fn try_parse_frame(&mut self) -> Option<UartFrame>{
-        //frame format: [SYNC][TYPE][LEN][PAYLOAD...][CHECKSUM]
-        //              0xAA  1byte 1byte  LEN bytes   1byte
-
-        if self.rx_buffer.len() < 4{
-            return None;
-        }
-
-        //find sync byte
-        let sync_pos = self.rx_buffer.iter().position(|&b| b == SYNC_BYTE)?;
-        
-        if sync_pos > 0{
-            self.rx_buffer.drain(0..sync_pos);
+        while let Some(frame) = self.decoder.next_frame(){
+            self.publish_frame(&frame);
         }
-
-        if self.rx_buffer.len() < 4{
-            return None;
-        }
-
-        let msg_type_byte = self.rx_buffer[1];
-        let len = self.rx_buffer[2] as usize;
-
-        if len > MAX_MSG_SIZE{
-            self.rx_buffer.remove(0);
-            return None;
-        }
-
-        let frame_len = 4 + len; //sync + type + len + payload + checksum
-
-        if self.rx_buffer.len() < frame_len{
-            return None;
-        }
-
-        //verify checksum
-        let checksum = self.rx_buffer[3 + len];
-        let calculated = self.calculate_checksum(&self.rx_buffer[1..3 + len]);
-
-        if checksum != calculated{
-            self.rx_buffer.remove(0);
-            return None;
-        }
-
-        let msg_type = MsgType::from_u8(msg_type_byte)?;
-        let payload = self.rx_buffer[3..3 + len].to_vec();
-
-        self.rx_buffer.drain(0..frame_len);
-
-        Some(UartFrame{ msg_type, payload })
-    }
-
-    fn calculate_checksum(&self, data: &[u8]) -> u8{
-        data.iter().fold(0u8, |acc, &b| acc.wrapping_add(b))
     }
 
     fn publish_frame(&self, frame: &UartFrame){
         let topic_name = frame.msg_type.to_topic_name();
         let topic = self.registry.get_or_create_byte(topic_name, 32);
         topic.publish(&frame.payload);
+
+        if self.publish_merged{
+            let mut tagged = Vec::with_capacity(1 + frame.payload.len());
+            tagged.push(frame.msg_type as u8);
+            tagged.extend_from_slice(&frame.payload);
+            if self.registry.get_or_create_byte(MERGED_TOPIC, 64).publish(&tagged).is_none()
+                && self.merged_dropped.fetch_add(1, Ordering::Relaxed) == 0{
+                eprintln!("{:?} frame of {} bytes too large for {} once type-prefixed, see merged_dropped()", frame.msg_type, frame.payload.len(), MERGED_TOPIC);
+            }
+        }
+    }
+
+    //runs the publish path on an already-decoded frame, for routing tests
+    #[cfg(test)]
+    pub(crate) fn inject_frame(&self, frame: UartFrame){
+        self.publish_frame(&frame);
     }
 
     pub fn send_frame(&mut self, msg_type: MsgType, payload: &[u8]) -> std::io::Result<()>{
@@ -191,19 +309,8 @@ impl UartBridge{
             ));
         }
 
-        let mut frame = Vec::with_capacity(4 + payload.len());
-        frame.push(SYNC_BYTE);
-        frame.push(msg_type as u8);
-        frame.push(payload.len() as u8);
-        frame.extend_from_slice(payload);
-
-        let checksum = self.calculate_checksum(&frame[1..]);
-        frame.push(checksum);
-
-        self.port.write_all(&frame)?;
-        self.port.flush()?;
-
-        Ok(())
+        let frame = self.codec.encode(msg_type, payload);
+        write_fully(&mut self.port, &frame)
     }
 }
 
@@ -219,34 +326,251 @@ mod tests{
     fn test_msg_type_conversion(){
         assert_eq!(MsgType::from_u8(0x01), Some(MsgType::Imu));
         assert_eq!(MsgType::from_u8(0x02), Some(MsgType::Depth));
+        assert_eq!(MsgType::from_u8(0x05), Some(MsgType::Orientation));
+        assert_eq!(MsgType::Orientation.to_topic_name(), "/stm32/orientation");
         assert_eq!(MsgType::from_u8(0xFF), None);
     }
 
+    #[test]
+    fn test_msg_type_try_from_round_trip(){
+        let all = [
+            MsgType::Imu, MsgType::Depth, MsgType::Thruster, MsgType::Heartbeat, MsgType::Orientation,
+            MsgType::Command, MsgType::Ack, MsgType::Led, MsgType::Calibration,
+        ];
+        for msg_type in all{
+            let byte: u8 = msg_type.into();
+            assert_eq!(MsgType::try_from(byte), Ok(msg_type));
+        }
+
+        assert_eq!(MsgType::try_from(0x00), Err(UnknownMsgType(0x00)));
+        assert_eq!(MsgType::try_from(0xFF), Err(UnknownMsgType(0xFF)));
+        assert_eq!(UnknownMsgType(0xFF).to_string(), "unknown message type 0xFF");
+    }
+
     #[test]
     fn test_topic_names(){
         assert_eq!(MsgType::Imu.to_topic_name(), "/stm32/imu");
         assert_eq!(MsgType::Depth.to_topic_name(), "/stm32/depth");
     }
 
+    fn encode(msg_type: MsgType, payload: &[u8]) -> Vec<u8>{
+        FrameCodec::new().encode(msg_type, payload)
+    }
+
+    #[test]
+    fn test_injected_frames_route_to_type_topics(){
+        let registry = Arc::new(TopicRegistry::new());
+        let bridge = UartBridge::with_transport(Box::new(LoopbackTransport::new()), Arc::clone(&registry));
+
+        let all = [
+            MsgType::Imu, MsgType::Depth, MsgType::Thruster, MsgType::Heartbeat, MsgType::Orientation,
+            MsgType::Command, MsgType::Ack, MsgType::Led, MsgType::Calibration,
+        ];
+        for msg_type in all{
+            bridge.inject_frame(UartFrame{ msg_type, payload: vec![msg_type as u8, 0xEE] });
+        }
+
+        for msg_type in all{
+            let topic = registry.get_or_create_byte(msg_type.to_topic_name(), 32);
+            assert_eq!(topic.try_receive().unwrap().0, vec![msg_type as u8, 0xEE], "{:?}", msg_type);
+            assert!(topic.try_receive().is_none());
+        }
+        assert_eq!(registry.topic_count(), all.len());
+    }
+
+    #[test]
+    fn test_merged_topic_carries_type_prefix(){
+        let registry = Arc::new(TopicRegistry::new());
+        let mut bridge = UartBridge::with_transport(Box::new(LoopbackTransport::new()), Arc::clone(&registry))
+            .with_merged_topic(true);
+
+        bridge.decoder.extend(&encode(MsgType::Depth, &[1, 2, 3, 4]));
+        bridge.decoder.extend(&encode(MsgType::Heartbeat, &[]));
+        bridge.decoder.extend(&encode(MsgType::Imu, &[9; 12]));
+        bridge.process_buffer();
+
+        let merged = registry.get_or_create_byte(MERGED_TOPIC, 64);
+        let (data, _) = merged.try_receive().unwrap();
+        assert_eq!(data, vec![MsgType::Depth as u8, 1, 2, 3, 4]);
+        let (data, _) = merged.try_receive().unwrap();
+        assert_eq!(data, vec![MsgType::Heartbeat as u8]);
+        let (data, _) = merged.try_receive().unwrap();
+        assert_eq!(data[0], MsgType::Imu as u8);
+        assert_eq!(&data[1..], &[9; 12]);
+        assert!(merged.try_receive().is_none());
+
+        //per-type topics still get the bare payload
+        let depth = registry.get_or_create_byte("/stm32/depth", 32);
+        assert_eq!(depth.try_receive().unwrap().0, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_merged_topic_counts_frames_too_large_to_prefix(){
+        let registry = Arc::new(TopicRegistry::new());
+        let mut bridge = UartBridge::with_transport(Box::new(LoopbackTransport::new()), Arc::clone(&registry))
+            .with_merged_topic(true);
+
+        bridge.decoder.extend(&encode(MsgType::Command, &[7; MAX_MSG_SIZE]));
+        bridge.decoder.extend(&encode(MsgType::Command, &[8; MAX_MSG_SIZE - 1]));
+        bridge.process_buffer();
+
+        let merged = registry.get_or_create_byte(MERGED_TOPIC, 64);
+        assert_eq!(merged.try_receive().unwrap().0.len(), MAX_MSG_SIZE);
+        assert!(merged.try_receive().is_none());
+        assert_eq!(bridge.merged_dropped(), 1);
+        //the per-type topic still gets both
+        assert_eq!(registry.get_or_create_byte("/stm32/command", 32).len(), 2);
+    }
+
+    #[test]
+    fn test_merged_topic_off_by_default(){
+        let registry = Arc::new(TopicRegistry::new());
+        let mut bridge = UartBridge::with_transport(Box::new(LoopbackTransport::new()), Arc::clone(&registry));
+
+        bridge.decoder.extend(&encode(MsgType::Depth, &[1, 2, 3, 4]));
+        bridge.process_buffer();
+
+        assert!(registry.get_or_create_byte(MERGED_TOPIC, 64).is_empty());
+    }
+
     #[test]
-    fn test_checksum(){
-        let bridge = create_mock_bridge();
-        let data = [0x01, 0x05, 0xAB, 0xCD];
-        let checksum = bridge.calculate_checksum(&data);
-        assert_eq!(checksum, 0x01u8.wrapping_add(0x05).wrapping_add(0xAB).wrapping_add(0xCD));
+    fn test_unknown_type_does_not_stall_bridge(){
+        let registry = Arc::new(TopicRegistry::new());
+        let mut bridge = UartBridge::with_transport(Box::new(LoopbackTransport::new()), Arc::clone(&registry));
+
+        bridge.decoder.extend(&[SYNC_BYTE, 0x7F, 0x00, 0x7F]);
+        bridge.decoder.extend(&encode(MsgType::Depth, &[1, 2, 3, 4]));
+        bridge.process_buffer();
+
+        let depth = registry.get_or_create_byte("/stm32/depth", 32);
+        assert_eq!(depth.try_receive().unwrap().0, vec![1, 2, 3, 4]);
+        assert_eq!(bridge.decoder.buffered(), 0);
     }
 
-    fn create_mock_bridge() -> MockBridge{
-        MockBridge{ rx_buffer: Vec::new() }
+    #[test]
+    fn test_reconnect_drops_partial_frame(){
+        let registry = Arc::new(TopicRegistry::new());
+        let mut bridge = UartBridge::with_transport(Box::new(LoopbackTransport::new()), Arc::clone(&registry))
+            .with_rx_capacity(1024);
+        let capacity = bridge.decoder.buffer_capacity();
+        assert!(capacity >= 1024);
+
+        //the old link died mid-frame
+        let depth = encode(MsgType::Depth, &[1, 2, 3, 4]);
+        bridge.decoder.extend(&depth[..4]);
+        bridge.process_buffer();
+
+        bridge.reconnect(Box::new(LoopbackTransport::new()));
+        assert_eq!(bridge.decoder.buffered(), 0);
+        assert_eq!(bridge.decoder.buffer_capacity(), capacity);
+
+        //the tail alone must not complete the old frame
+        bridge.decoder.extend(&depth[4..]);
+        bridge.decoder.extend(&encode(MsgType::Depth, &[5, 6, 7, 8]));
+        bridge.process_buffer();
+        let topic = registry.get_or_create_byte(MsgType::Depth.to_topic_name(), 32);
+        assert_eq!(topic.try_receive().unwrap().0, vec![5, 6, 7, 8]);
+        assert!(topic.try_receive().is_none());
     }
 
-    struct MockBridge{
-        rx_buffer: Vec<u8>,
+    #[test]
+    fn test_bridge_two_byte_preamble(){
+        let registry = Arc::new(TopicRegistry::new());
+        let preamble = Preamble::double(0xAA, 0x55);
+        let mut bridge = UartBridge::with_transport(Box::new(LoopbackTransport::new()), Arc::clone(&registry))
+            .with_preamble(preamble)
+            .with_checksum_coverage(ChecksumCoverage::WithSync);
+        let codec = FrameCodec::with_coverage(ChecksumCoverage::WithSync).with_preamble(preamble);
+
+        //a complete single-sync depth frame is just noise now
+        bridge.decoder.extend(&FrameCodec::with_coverage(ChecksumCoverage::WithSync).encode(MsgType::Depth, &[7; 4]));
+        bridge.decoder.extend(&codec.encode(MsgType::Depth, &[0xAA; 4]));
+        bridge.process_buffer();
+
+        let depth = registry.get_or_create_byte("/stm32/depth", 32);
+        assert_eq!(depth.try_receive().unwrap().0, vec![0xAA; 4]);
+        assert!(depth.try_receive().is_none());
     }
 
-    impl MockBridge{
-        fn calculate_checksum(&self, data: &[u8]) -> u8{
-            data.iter().fold(0u8, |acc, &b| acc.wrapping_add(b))
+    #[test]
+    fn test_bridge_crc16_both_directions(){
+        let registry = Arc::new(TopicRegistry::new());
+        let link = LoopbackTransport::new();
+        let mut bridge = UartBridge::with_transport(Box::new(link.clone()), Arc::clone(&registry))
+            .with_checksum_coverage(ChecksumCoverage::WithSync)
+            .with_checksum(ChecksumKind::Crc16)
+            .with_preamble(Preamble::double(0xAA, 0x55));
+        let codec = FrameCodec::with_coverage(ChecksumCoverage::WithSync)
+            .with_checksum(ChecksumKind::Crc16)
+            .with_preamble(Preamble::double(0xAA, 0x55));
+
+        //later builders keep the checksum kind
+        bridge.send_frame(MsgType::Thruster, &[3; 24]).unwrap();
+        let mut written = link.take_written();
+        assert_eq!(written.len(), codec.overhead() + 24);
+        assert_eq!(codec.decode(&mut written).unwrap().payload, vec![3; 24]);
+
+        //a Sum8 frame is noise to a Crc16 bridge
+        bridge.decoder.extend(&FrameCodec::with_coverage(ChecksumCoverage::WithSync)
+            .with_preamble(Preamble::double(0xAA, 0x55))
+            .encode(MsgType::Depth, &[7; 4]));
+        bridge.decoder.extend(&codec.encode(MsgType::Depth, &[8; 4]));
+        bridge.process_buffer();
+        let depth = registry.get_or_create_byte("/stm32/depth", 32);
+        assert_eq!(depth.try_receive().unwrap().0, vec![8; 4]);
+        assert!(depth.try_receive().is_none());
+    }
+
+    //a port that hears the given bytes only when opened at `good` baud,
+    //and line noise at every other rate
+    fn open_at(good: u32, frames: Vec<u8>) -> impl FnMut(u32) -> Result<Box<dyn Transport>, serialport::Error>{
+        move |baud|{
+            let link = LoopbackTransport::new();
+            if baud == good{
+                link.feed(&frames);
+            }else{
+                link.feed(&[0x00, 0x3C, 0xF0, 0x0F, 0x00, 0x81]);
+            }
+            Ok(Box::new(link) as Box<dyn Transport>)
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_autobaud_picks_rate_with_valid_frames(){
+        let registry = Arc::new(TopicRegistry::new());
+        let open = open_at(115200, encode(MsgType::Depth, &[5, 6, 7, 8]));
+
+        let (_bridge, baud) = UartBridge::autobaud_with(
+            &[9600, 57600, 115200, 230400], Arc::clone(&registry), Duration::from_millis(20), open,
+        ).unwrap();
+        assert_eq!(baud, 115200);
+
+        //the probing frame was published, not swallowed
+        let depth = registry.get_or_create_byte("/stm32/depth", 32);
+        assert_eq!(depth.try_receive().unwrap().0, vec![5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn test_autobaud_fails_when_no_rate_works(){
+        let registry = Arc::new(TopicRegistry::new());
+        let open = open_at(0, Vec::new());
+
+        let err = UartBridge::autobaud_with(&[9600, 115200], registry, Duration::from_millis(10), open).err().unwrap();
+        assert_eq!(err.kind(), serialport::ErrorKind::Io(std::io::ErrorKind::TimedOut));
+    }
+
+    #[test]
+    fn test_autobaud_skips_rates_that_fail_to_open(){
+        let registry = Arc::new(TopicRegistry::new());
+        let mut good = open_at(57600, encode(MsgType::Heartbeat, &[]));
+        let open = move |baud|{
+            if baud == 9600{
+                return Err(serialport::Error::new(serialport::ErrorKind::InvalidInput, "unsupported rate"));
+            }
+            good(baud)
+        };
+
+        let (_bridge, baud) = UartBridge::autobaud_with(&[9600, 57600], registry, Duration::from_millis(10), open).unwrap();
+        assert_eq!(baud, 57600);
+    }
+}
@@ -1,5 +1,18 @@
+use super::MsgType;
+
+pub const SYNC_BYTE: u8 = 0xAA;
+pub const MAX_MSG_SIZE: usize = 244;
+//sync + type + len + checksum
+pub const FRAME_OVERHEAD: usize = 4;
+//initial rx buffer size for the bridge and controller, a couple of max frames
+pub const RX_BUFFER_CAPACITY: usize = 512;
+//with escaping on, a SYNC or ESCAPE byte inside a frame goes out as
+//ESCAPE_BYTE followed by the byte xor ESCAPE_XOR
+pub const ESCAPE_BYTE: u8 = 0x7D;
+const ESCAPE_XOR: u8 = 0x20;
+
 #[repr(C, packed)]
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct ImuMsg{
     pub accel_x: f32,     //m/s² (already multiplied by G on STM32)
     pub accel_y: f32,
@@ -13,7 +26,7 @@ pub struct ImuMsg{
 }
 
 #[repr(C, packed)]
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct OrientationMsg{
     pub roll: f32,        //degrees
     pub pitch: f32,
@@ -21,7 +34,7 @@ pub struct OrientationMsg{
 }
 
 #[repr(C, packed)]
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct DepthMsg{
     pub depth: f32,       //meters
 }
@@ -52,87 +65,1999 @@ pub const THRUSTER_PWM_SIZE: usize = 24;    //6 * i32
 pub const LED_CMD_SIZE: usize = 2;          //1 * i16
 pub const CALIBRATION_CMD_SIZE: usize = 1;  //1 * bool
 
+//pulse widths the ESCs accept, in µs
+pub const PWM_MIN: i32 = 1000;
+pub const PWM_MAX: i32 = 2000;
+//a vehicle with more thrusters than the 6 channel frame appends the extra
+//channels to it, up to this many in all
+pub const MAX_THRUSTER_CHANNELS: usize = 8;
+
+//byte order of multi-byte payload fields on the wire. the STM32 is little
+//endian, so that's the default whatever the host is, and what
+//from_bytes/to_bytes use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Endianness{
+    #[default]
+    Little,
+    Big,
+}
+
+impl Endianness{
+    fn read4(self, bytes: [u8; 4]) -> u32{
+        match self{
+            Endianness::Little => u32::from_le_bytes(bytes),
+            Endianness::Big => u32::from_be_bytes(bytes),
+        }
+    }
+
+    fn write4(self, value: u32) -> [u8; 4]{
+        match self{
+            Endianness::Little => value.to_le_bytes(),
+            Endianness::Big => value.to_be_bytes(),
+        }
+    }
+
+    fn read2(self, bytes: [u8; 2]) -> u16{
+        match self{
+            Endianness::Little => u16::from_le_bytes(bytes),
+            Endianness::Big => u16::from_be_bytes(bytes),
+        }
+    }
+
+    fn write2(self, value: u16) -> [u8; 2]{
+        match self{
+            Endianness::Little => value.to_le_bytes(),
+            Endianness::Big => value.to_be_bytes(),
+        }
+    }
+}
+
+//field-by-field payload decode; callers check the length first
+struct FieldReader<'a>{
+    data: &'a [u8],
+    order: Endianness,
+}
+
+impl FieldReader<'_>{
+    fn u32(&mut self) -> u32{
+        let (field, rest) = self.data.split_at(4);
+        self.data = rest;
+        self.order.read4(field.try_into().unwrap())
+    }
+
+    fn f32(&mut self) -> f32{
+        f32::from_bits(self.u32())
+    }
+
+    fn i32(&mut self) -> i32{
+        self.u32() as i32
+    }
+
+    fn i16(&mut self) -> i16{
+        let (field, rest) = self.data.split_at(2);
+        self.data = rest;
+        self.order.read2(field.try_into().unwrap()) as i16
+    }
+}
+
+struct FieldWriter{
+    out: Vec<u8>,
+    order: Endianness,
+}
+
+impl FieldWriter{
+    fn new(size: usize, order: Endianness) -> Self{
+        FieldWriter{ out: Vec::with_capacity(size), order }
+    }
+
+    fn f32(&mut self, value: f32){
+        self.out.extend_from_slice(&self.order.write4(value.to_bits()));
+    }
+
+    fn i32(&mut self, value: i32){
+        self.out.extend_from_slice(&self.order.write4(value as u32));
+    }
+
+    fn i16(&mut self, value: i16){
+        self.out.extend_from_slice(&self.order.write2(value as u16));
+    }
+}
+
+impl MsgType{
+    //fixed payload length for types with a struct layout
+    pub fn payload_size(self) -> Option<usize>{
+        match self{
+            MsgType::Imu => Some(IMU_MSG_SIZE),
+            MsgType::Depth => Some(DEPTH_MSG_SIZE),
+            MsgType::Thruster => Some(THRUSTER_PWM_SIZE),
+            MsgType::Orientation => Some(ORIENTATION_MSG_SIZE),
+            MsgType::Led => Some(LED_CMD_SIZE),
+            MsgType::Calibration => Some(CALIBRATION_CMD_SIZE),
+            MsgType::Heartbeat | MsgType::Command | MsgType::Ack => None,
+        }
+    }
+
+    //longest payload this type can carry: thruster frames append extra
+    //channels to the 6 channel layout, up to MAX_THRUSTER_CHANNELS
+    pub fn max_payload_size(self) -> Option<usize>{
+        match self{
+            MsgType::Thruster => Some(MAX_THRUSTER_CHANNELS * 4),
+            _ => self.payload_size(),
+        }
+    }
+
+    //payload has this type's exact size and passes its value checks
+    //(finite sensor readings, PWM in range, bool as 0/1); types without a
+    //layout accept anything. thruster frames may carry extra channels
+    pub fn validate_payload(self, payload: &[u8]) -> bool{
+        if self == MsgType::Thruster{
+            return ThrusterPwmCmd::channels_from_bytes(payload, Endianness::Little)
+                .is_some_and(|pwm| pwm.iter().all(|p| (PWM_MIN..=PWM_MAX).contains(p)));
+        }
+        if self.payload_size().is_some_and(|size| payload.len() != size){
+            return false;
+        }
+        match self{
+            MsgType::Imu => ImuMsg::from_bytes(payload).is_some_and(|m| m.is_valid()),
+            MsgType::Orientation => OrientationMsg::from_bytes(payload).is_some_and(|m| m.is_valid()),
+            MsgType::Depth => DepthMsg::from_bytes(payload).is_some_and(|m| m.is_valid()),
+            MsgType::Calibration => payload[0] <= 1,
+            MsgType::Thruster | MsgType::Led | MsgType::Heartbeat | MsgType::Command | MsgType::Ack => true,
+        }
+    }
+}
+
+//physical thruster positions, for naming PWM channels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Thruster{
+    FrontLeft,
+    FrontRight,
+    RearLeft,
+    RearRight,
+    VerticalLeft,
+    VerticalRight,
+}
+
+//which thruster each PWM channel drives, channel 0 first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelLayout([Thruster; 6]);
+
+impl ChannelLayout{
+    //the STM32 wiring the Vectored6 mixer assumes
+    pub const VECTORED6: ChannelLayout = ChannelLayout([
+        Thruster::FrontLeft, Thruster::FrontRight, Thruster::RearLeft,
+        Thruster::RearRight, Thruster::VerticalLeft, Thruster::VerticalRight,
+    ]);
+
+    //None if a thruster is wired to two channels
+    pub fn new(channels: [Thruster; 6]) -> Option<Self>{
+        for (i, a) in channels.iter().enumerate(){
+            if channels[i + 1..].contains(a){
+                return None;
+            }
+        }
+        Some(ChannelLayout(channels))
+    }
+
+    pub fn channel_of(&self, thruster: Thruster) -> usize{
+        //every thruster appears exactly once, see new()
+        self.0.iter().position(|&t| t == thruster).unwrap()
+    }
+}
+
+impl Default for ChannelLayout{
+    fn default() -> Self{
+        Self::VECTORED6
+    }
+}
+
+//sets channels by thruster name; unset channels stay neutral (1500)
+#[derive(Debug, Clone, Copy)]
+pub struct ThrusterPwmBuilder{
+    layout: ChannelLayout,
+    pwm: [i32; 6],
+}
+
+impl ThrusterPwmBuilder{
+    pub fn set(mut self, thruster: Thruster, pwm: i32) -> Self{
+        self.pwm[self.layout.channel_of(thruster)] = pwm;
+        self
+    }
+
+    pub fn front_left(self, pwm: i32) -> Self{
+        self.set(Thruster::FrontLeft, pwm)
+    }
+
+    pub fn front_right(self, pwm: i32) -> Self{
+        self.set(Thruster::FrontRight, pwm)
+    }
+
+    pub fn rear_left(self, pwm: i32) -> Self{
+        self.set(Thruster::RearLeft, pwm)
+    }
+
+    pub fn rear_right(self, pwm: i32) -> Self{
+        self.set(Thruster::RearRight, pwm)
+    }
+
+    pub fn vertical_left(self, pwm: i32) -> Self{
+        self.set(Thruster::VerticalLeft, pwm)
+    }
+
+    pub fn vertical_right(self, pwm: i32) -> Self{
+        self.set(Thruster::VerticalRight, pwm)
+    }
+
+    pub fn build(self) -> ThrusterPwmCmd{
+        ThrusterPwmCmd::new(self.pwm)
+    }
+}
+
 impl ThrusterPwmCmd{
     pub fn new(pwm_values: [i32; 6]) -> Self{
         ThrusterPwmCmd{ pwm: pwm_values }
     }
 
+    pub fn builder() -> ThrusterPwmBuilder{
+        Self::builder_for(ChannelLayout::default())
+    }
+
+    pub fn builder_for(layout: ChannelLayout) -> ThrusterPwmBuilder{
+        ThrusterPwmBuilder{ layout, pwm: [1500; 6] }
+    }
+
+    //little-endian fields, whatever the host
     pub fn from_bytes(data: &[u8]) -> Option<Self>{
+        Self::from_bytes_with(data, Endianness::Little)
+    }
+
+    pub fn is_valid(&self) -> bool{
+        {self.pwm}.iter().all(|pwm| (PWM_MIN..=PWM_MAX).contains(pwm))
+    }
+
+    pub fn from_bytes_with(data: &[u8], order: Endianness) -> Option<Self>{
         if data.len() < THRUSTER_PWM_SIZE{
             return None;
         }
-        unsafe{
-            Some(std::ptr::read_unaligned(data.as_ptr() as *const Self))
+        let mut f = FieldReader{ data, order };
+        Some(ThrusterPwmCmd{ pwm: std::array::from_fn(|_| f.i32()) })
+    }
+
+    pub fn to_bytes_with(&self, order: Endianness) -> Vec<u8>{
+        let mut out = FieldWriter::new(THRUSTER_PWM_SIZE, order);
+        for pwm in self.pwm{
+            out.i32(pwm);
         }
+        out.out
     }
 
     pub fn to_bytes(&self) -> Vec<u8>{
-        let mut bytes = vec![0u8; THRUSTER_PWM_SIZE];
-        unsafe{
-            std::ptr::copy_nonoverlapping(
-                self as *const Self as *const u8,
-                bytes.as_mut_ptr(),
-                THRUSTER_PWM_SIZE
-            );
+        self.to_bytes_with(Endianness::Little)
+    }
+
+    //frame for any channel count up to MAX_THRUSTER_CHANNELS: the first 6
+    //exactly as to_bytes_with lays them out, the extra ones right after
+    pub fn channels_to_bytes(pwm: &[i32], order: Endianness) -> Vec<u8>{
+        assert!(pwm.len() <= MAX_THRUSTER_CHANNELS, "{} PWM channels, a frame carries at most {} bruddaa!!", pwm.len(), MAX_THRUSTER_CHANNELS);
+        let mut out = FieldWriter::new(pwm.len() * 4, order);
+        for &value in pwm{
+            out.i32(value);
+        }
+        out.out
+    }
+
+    //every channel of a frame from channels_to_bytes; None unless it holds
+    //6 to MAX_THRUSTER_CHANNELS whole channels
+    pub fn channels_from_bytes(data: &[u8], order: Endianness) -> Option<Vec<i32>>{
+        let count = data.len() / 4;
+        if !data.len().is_multiple_of(4) || !(6..=MAX_THRUSTER_CHANNELS).contains(&count){
+            return None;
         }
-        bytes
+        let mut f = FieldReader{ data, order };
+        Some((0..count).map(|_| f.i32()).collect())
     }
 }
 
 impl ImuMsg{
+    //little-endian fields, whatever the host
     pub fn from_bytes(data: &[u8]) -> Option<Self>{
+        Self::from_bytes_with(data, Endianness::Little)
+    }
+
+    pub fn from_bytes_with(data: &[u8], order: Endianness) -> Option<Self>{
         if data.len() < IMU_MSG_SIZE{
             return None;
         }
-        unsafe{
-            Some(std::ptr::read_unaligned(data.as_ptr() as *const Self))
+        let mut f = FieldReader{ data, order };
+        Some(ImuMsg{
+            accel_x: f.f32(), accel_y: f.f32(), accel_z: f.f32(),
+            gyro_x: f.f32(), gyro_y: f.f32(), gyro_z: f.f32(),
+            mag_x: f.f32(), mag_y: f.f32(), mag_z: f.f32(),
+        })
+    }
+
+    pub fn to_bytes_with(&self, order: Endianness) -> Vec<u8>{
+        let mut out = FieldWriter::new(IMU_MSG_SIZE, order);
+        for value in self.values(){
+            out.f32(value);
         }
+        out.out
     }
 }
 
 impl OrientationMsg{
+    //little-endian fields, whatever the host
     pub fn from_bytes(data: &[u8]) -> Option<Self>{
+        Self::from_bytes_with(data, Endianness::Little)
+    }
+
+    pub fn from_bytes_with(data: &[u8], order: Endianness) -> Option<Self>{
         if data.len() < ORIENTATION_MSG_SIZE{
             return None;
         }
-        unsafe{
-            Some(std::ptr::read_unaligned(data.as_ptr() as *const Self))
-        }
+        let mut f = FieldReader{ data, order };
+        Some(OrientationMsg{ roll: f.f32(), pitch: f.f32(), yaw: f.f32() })
+    }
+
+    pub fn to_bytes_with(&self, order: Endianness) -> Vec<u8>{
+        let mut out = FieldWriter::new(ORIENTATION_MSG_SIZE, order);
+        out.f32(self.roll);
+        out.f32(self.pitch);
+        out.f32(self.yaw);
+        out.out
     }
 }
 
 impl DepthMsg{
+    //little-endian fields, whatever the host
     pub fn from_bytes(data: &[u8]) -> Option<Self>{
+        Self::from_bytes_with(data, Endianness::Little)
+    }
+
+    pub fn from_bytes_with(data: &[u8], order: Endianness) -> Option<Self>{
         if data.len() < DEPTH_MSG_SIZE{
             return None;
         }
-        unsafe{
-            Some(std::ptr::read_unaligned(data.as_ptr() as *const Self))
+        Some(DepthMsg{ depth: FieldReader{ data, order }.f32() })
+    }
+
+    pub fn to_bytes_with(&self, order: Endianness) -> Vec<u8>{
+        let mut out = FieldWriter::new(DEPTH_MSG_SIZE, order);
+        out.f32(self.depth);
+        out.out
+    }
+}
+
+impl LedCmd{
+    //little-endian fields, whatever the host
+    pub fn from_bytes(data: &[u8]) -> Option<Self>{
+        Self::from_bytes_with(data, Endianness::Little)
+    }
+
+    pub fn from_bytes_with(data: &[u8], order: Endianness) -> Option<Self>{
+        if data.len() < LED_CMD_SIZE{
+            return None;
         }
+        Some(LedCmd{ indicator: FieldReader{ data, order }.i16() })
+    }
+
+    pub fn to_bytes_with(&self, order: Endianness) -> Vec<u8>{
+        let mut out = FieldWriter::new(LED_CMD_SIZE, order);
+        out.i16(self.indicator);
+        out.out
     }
 }
 
-#[cfg(test)]
-mod tests{
-    use super::*;
+//one message off the front of a batch plus the unconsumed tail, so a
+//concatenated buffer decodes with `while let Some((msg, rest)) = ...`
+macro_rules! impl_from_bytes_with_rest{
+    ($($msg:ty => $size:expr),*) => {$(
+        impl $msg{
+            pub fn from_bytes_with_rest(data: &[u8]) -> Option<(Self, &[u8])>{
+                let msg = Self::from_bytes(data)?;
+                Some((msg, &data[$size..]))
+            }
+        }
+    )*};
+}
 
-    #[test]
-    fn test_thruster_pwm_cmd(){
-        let cmd = ThrusterPwmCmd::new([1500, 1600, 1400, 1550, 1450, 1500]);
-        let bytes = cmd.to_bytes();
-        assert_eq!(bytes.len(), 24);
+impl_from_bytes_with_rest!(
+    ImuMsg => IMU_MSG_SIZE,
+    OrientationMsg => ORIENTATION_MSG_SIZE,
+    DepthMsg => DEPTH_MSG_SIZE,
+    ThrusterPwmCmd => THRUSTER_PWM_SIZE
+);
 
-        let decoded = ThrusterPwmCmd::from_bytes(&bytes).unwrap();
-        let pwm = decoded.pwm; //copy to avoid unaligned access
-        assert_eq!(pwm[0], 1500);
-        assert_eq!(pwm[2], 1400);
-        assert_eq!(pwm[3], 1550);
-        assert_eq!(pwm[1], 1600);
-        assert_eq!(pwm[5], 1500);
+//the sensor side of ThrusterPwmCmd::to_bytes, for firmware simulators and
+//loopback tests
+macro_rules! impl_to_bytes{
+    ($($msg:ty),*) => {$(
+        impl $msg{
+            pub fn to_bytes(&self) -> Vec<u8>{
+                self.to_bytes_with(Endianness::Little)
+            }
+        }
+    )*};
+}
+
+impl_to_bytes!(ImuMsg, OrientationMsg, DepthMsg, LedCmd);
+
+//payload codec of the fixed-layout messages, so generic code (typed views
+//over byte topics, loggers) can move them in and out of raw frames
+pub trait FromFrame: Sized{
+    //None if the payload is shorter than the message
+    fn from_frame(payload: &[u8]) -> Option<Self>;
+}
+
+pub trait ToFrame{
+    fn to_frame(&self) -> Vec<u8>;
+}
+
+macro_rules! impl_frame_codec{
+    ($($msg:ty),*) => {$(
+        impl FromFrame for $msg{
+            fn from_frame(payload: &[u8]) -> Option<Self>{
+                Self::from_bytes(payload)
+            }
+        }
+
+        impl ToFrame for $msg{
+            fn to_frame(&self) -> Vec<u8>{
+                self.to_bytes()
+            }
+        }
+    )*};
+}
+
+impl_frame_codec!(ImuMsg, OrientationMsg, DepthMsg, ThrusterPwmCmd);
+
+//fields are copied out first: references into packed structs are not allowed
+fn all_within(a: &[f32], b: &[f32], epsilon: f32) -> bool{
+    a.iter().zip(b).all(|(x, y)| (x - y).abs() <= epsilon)
+}
+
+impl ImuMsg{
+    pub fn values(&self) -> [f32; 9]{
+        [
+            {self.accel_x}, {self.accel_y}, {self.accel_z},
+            {self.gyro_x}, {self.gyro_y}, {self.gyro_z},
+            {self.mag_x}, {self.mag_y}, {self.mag_z},
+        ]
     }
 
-    #[test]
-    fn test_imu_msg_size(){
-        assert_eq!(std::mem::size_of::<ImuMsg>(), IMU_MSG_SIZE);
+    //every field within epsilon; NaN never compares equal
+    pub fn approx_eq(&self, other: &Self, epsilon: f32) -> bool{
+        all_within(&self.values(), &other.values(), epsilon)
+    }
+
+    //no NaN or infinite readings
+    pub fn is_valid(&self) -> bool{
+        self.values().iter().all(|v| v.is_finite())
+    }
+}
+
+impl OrientationMsg{
+    pub fn values(&self) -> [f32; 3]{
+        [{self.roll}, {self.pitch}, {self.yaw}]
+    }
+
+    pub fn approx_eq(&self, other: &Self, epsilon: f32) -> bool{
+        all_within(&self.values(), &other.values(), epsilon)
+    }
+
+    pub fn is_valid(&self) -> bool{
+        self.values().iter().all(|v| v.is_finite())
+    }
+}
+
+impl DepthMsg{
+    pub fn approx_eq(&self, other: &Self, epsilon: f32) -> bool{
+        all_within(&[{self.depth}], &[{other.depth}], epsilon)
+    }
+
+    pub fn is_valid(&self) -> bool{
+        {self.depth}.is_finite()
+    }
+}
+
+#[cfg(feature = "approx")]
+macro_rules! impl_abs_diff_eq{
+    ($($msg:ty),*) => {$(
+        impl approx::AbsDiffEq for $msg{
+            type Epsilon = f32;
+
+            fn default_epsilon() -> f32{
+                f32::EPSILON
+            }
+
+            fn abs_diff_eq(&self, other: &Self, epsilon: f32) -> bool{
+                self.approx_eq(other, epsilon)
+            }
+        }
+    )*};
+}
+
+#[cfg(feature = "approx")]
+impl_abs_diff_eq!(ImuMsg, OrientationMsg, DepthMsg);
+
+#[derive(Debug, Clone)]
+pub struct UartFrame{
+    pub msg_type: MsgType,
+    pub payload: Vec<u8>,
+}
+
+fn hex(bytes: &[u8]) -> String{
+    bytes.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ")
+}
+
+impl UartFrame{
+    //one-line human readable form for logs: decoded fields where the type
+    //has a known layout, hex for everything else (including short payloads)
+    pub fn describe(&self) -> String{
+        let p = &self.payload;
+        let decoded = match self.msg_type{
+            MsgType::Imu => ImuMsg::from_bytes(p).map(|m|{
+                format!("Imu accel=({:.3}, {:.3}, {:.3}) gyro=({:.3}, {:.3}, {:.3}) mag=({:.3}, {:.3}, {:.3})",
+                    {m.accel_x}, {m.accel_y}, {m.accel_z},
+                    {m.gyro_x}, {m.gyro_y}, {m.gyro_z},
+                    {m.mag_x}, {m.mag_y}, {m.mag_z})
+            }),
+            MsgType::Orientation => OrientationMsg::from_bytes(p).map(|m|{
+                format!("Orientation roll={:.2} pitch={:.2} yaw={:.2}", {m.roll}, {m.pitch}, {m.yaw})
+            }),
+            MsgType::Depth => DepthMsg::from_bytes(p).map(|m| format!("Depth depth={:.3}m", {m.depth})),
+            MsgType::Thruster => ThrusterPwmCmd::from_bytes(p).map(|m| format!("Thruster pwm={:?}", {m.pwm})),
+            MsgType::Led => LedCmd::from_bytes(p).map(|m| format!("Led indicator={}", {m.indicator})),
+            MsgType::Calibration if p.len() >= CALIBRATION_CMD_SIZE =>{
+                Some(format!("Calibration enable={}", p[0] != 0))
+            }
+            MsgType::Heartbeat if p.is_empty() => Some("Heartbeat".to_string()),
+            _ => None,
+        };
+
+        decoded.unwrap_or_else(|| format!("{:?} len={} [{}]", self.msg_type, p.len(), hex(p)))
     }
-}
\ No newline at end of file
+}
+
+//which header bytes the checksum sums; firmware differs on the sync byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChecksumCoverage{
+    //SYNC, TYPE, LEN and PAYLOAD
+    WithSync,
+    //TYPE, LEN and PAYLOAD
+    #[default]
+    WithoutSync,
+}
+
+impl ChecksumCoverage{
+    //index of the first covered byte in a frame opening with `preamble`
+    fn start(self, preamble: &Preamble) -> usize{
+        match self{
+            ChecksumCoverage::WithSync => 0,
+            ChecksumCoverage::WithoutSync => preamble.width(),
+        }
+    }
+}
+
+//what the trailing check is. Sum8 misses transposed bytes and errors that
+//cancel out, so noisy tethers want Crc16. both cover the same bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChecksumKind{
+    //wrapping byte sum, one trailing byte
+    #[default]
+    Sum8,
+    //CRC16-CCITT (poly 0x1021, init 0xFFFF), two trailing bytes little-endian
+    Crc16,
+}
+
+impl ChecksumKind{
+    //trailing bytes it adds to a frame
+    pub fn width(self) -> usize{
+        match self{
+            ChecksumKind::Sum8 => 1,
+            ChecksumKind::Crc16 => 2,
+        }
+    }
+
+    fn init(self) -> u16{
+        match self{
+            ChecksumKind::Sum8 => 0,
+            ChecksumKind::Crc16 => 0xFFFF,
+        }
+    }
+
+    //fold more covered bytes into a running check
+    fn update(self, state: u16, data: &[u8]) -> u16{
+        match self{
+            ChecksumKind::Sum8 => FrameCodec::checksum(data).wrapping_add(state as u8) as u16,
+            ChecksumKind::Crc16 => data.iter().fold(state, |crc, &b|{
+                let mut crc = crc ^ ((b as u16) << 8);
+                for _ in 0..8{
+                    crc = if crc & 0x8000 != 0{ (crc << 1) ^ 0x1021 }else{ crc << 1 };
+                }
+                crc
+            }),
+        }
+    }
+
+    //what the check holds before TYPE: the preamble bytes, if they count
+    fn seed(self, coverage: ChecksumCoverage, preamble: &Preamble) -> u16{
+        self.update(self.init(), &preamble.as_bytes()[coverage.start(preamble)..])
+    }
+
+    fn append(self, frame: &mut Vec<u8>, check: u16){
+        frame.extend_from_slice(&check.to_le_bytes()[..self.width()]);
+    }
+
+    fn read(self, trailer: &[u8]) -> u16{
+        match self{
+            ChecksumKind::Sum8 => trailer[0] as u16,
+            ChecksumKind::Crc16 => u16::from_le_bytes([trailer[0], trailer[1]]),
+        }
+    }
+}
+
+//the bytes opening every frame: SYNC_BYTE alone by default, or a two-byte
+//sequence like 0xAA 0x55. with two, a payload byte that happens to equal the
+//first one no longer looks like a frame start on its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Preamble{
+    bytes: [u8; 2],
+    width: usize,
+}
+
+impl Preamble{
+    pub const fn single(sync: u8) -> Self{
+        Preamble{ bytes: [sync, 0], width: 1 }
+    }
+
+    pub const fn double(first: u8, second: u8) -> Self{
+        Preamble{ bytes: [first, second], width: 2 }
+    }
+
+    pub fn as_bytes(&self) -> &[u8]{
+        &self.bytes[..self.width]
+    }
+
+    fn width(&self) -> usize{
+        self.width
+    }
+
+    //first position a frame could start at: the whole preamble matches
+    //there, or as much of it as the buffer holds before running out
+    fn find(&self, buffer: &[u8]) -> Option<usize>{
+        let preamble = self.as_bytes();
+        (0..buffer.len()).find(|&pos|{
+            let n = preamble.len().min(buffer.len() - pos);
+            buffer[pos..pos + n] == preamble[..n]
+        })
+    }
+}
+
+impl Default for Preamble{
+    fn default() -> Self{
+        Preamble::single(SYNC_BYTE)
+    }
+}
+
+//frame format: [SYNC][TYPE][LEN][PAYLOAD...][CHECKSUM]
+//              0xAA  1byte 1byte  LEN bytes   1byte
+//checksum is the wrapping sum of TYPE, LEN and PAYLOAD, plus SYNC under
+//ChecksumCoverage::WithSync. SYNC is the codec's Preamble, one or two bytes.
+//under ChecksumKind::Crc16 CHECKSUM is two bytes of CRC over the same range.
+//with escaping, everything after SYNC is byte-stuffed so the first preamble
+//byte only ever appears at a frame start; the checksum covers unescaped bytes
+//
+//one message type can be made a fast frame: [SYNC][TYPE][PAYLOAD], with a
+//fixed payload length and neither LEN nor CHECKSUM. nothing catches a
+//corrupted fast frame, and a stray sync followed by that type byte reads
+//as one, so only use it on a short, clean, direct link. FrameDecoder
+//doesn't parse fast frames
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameCodec{
+    coverage: ChecksumCoverage,
+    preamble: Preamble,
+    kind: ChecksumKind,
+    endianness: Endianness,
+    escaped: bool,
+    fast: Option<(MsgType, usize)>,
+}
+
+//how far an escaped frame scan got
+enum Scan{
+    //every byte of the frame is in, ending at this raw offset
+    Complete(usize),
+    Incomplete,
+    //a new frame starts at this raw offset before the current one ended
+    Resync(usize),
+    BadLength,
+    BadEscape,
+}
+
+impl FrameCodec{
+    pub fn new() -> Self{
+        Self::with_coverage(ChecksumCoverage::default())
+    }
+
+    pub fn with_coverage(coverage: ChecksumCoverage) -> Self{
+        FrameCodec{
+            coverage,
+            preamble: Preamble::default(),
+            kind: ChecksumKind::default(),
+            endianness: Endianness::default(),
+            escaped: false,
+            fast: None,
+        }
+    }
+
+    pub fn with_preamble(mut self, preamble: Preamble) -> Self{
+        self.preamble = preamble;
+        self
+    }
+
+    //with_coverage on an existing codec, keeping its other settings
+    pub fn with_checksum_coverage(mut self, coverage: ChecksumCoverage) -> Self{
+        self.coverage = coverage;
+        self
+    }
+
+    pub fn with_checksum(mut self, kind: ChecksumKind) -> Self{
+        self.kind = kind;
+        self
+    }
+
+    //byte order of the payload fields, for users of the *_with (de)serializers;
+    //the frame bytes themselves don't change
+    pub fn with_endianness(mut self, endianness: Endianness) -> Self{
+        self.endianness = endianness;
+        self
+    }
+
+    //byte-stuff frames so a dropped byte can't make the parser lock onto a
+    //sync byte inside a payload. the firmware must escape the same way
+    pub fn with_escaping(mut self, enabled: bool) -> Self{
+        assert!(!(enabled && self.fast.is_some()), "fast frames can't be escaped bruddaa!!");
+        self.escaped = enabled;
+        self
+    }
+
+    //send and expect every `msg_type` frame as a checksum-free fast frame
+    //of exactly `len` payload bytes; other types keep their checksum.
+    //replaces any previous fast type
+    pub fn with_fast_frame(mut self, msg_type: MsgType, len: usize) -> Self{
+        assert!(len <= MAX_MSG_SIZE, "fast frame of {} bytes exceeds MAX_MSG_SIZE", len);
+        assert!(!self.escaped, "fast frames can't be escaped bruddaa!!");
+        self.fast = Some((msg_type, len));
+        self
+    }
+
+    pub fn escaping(&self) -> bool{
+        self.escaped
+    }
+
+    pub fn fast_frame(&self) -> Option<(MsgType, usize)>{
+        self.fast
+    }
+
+    //the fast type and its payload length, if that is what `type_byte` is
+    fn fast_type(&self, type_byte: u8) -> Option<(MsgType, usize)>{
+        self.fast.filter(|&(msg_type, _)| msg_type as u8 == type_byte)
+    }
+
+    pub fn coverage(&self) -> ChecksumCoverage{
+        self.coverage
+    }
+
+    pub fn preamble(&self) -> Preamble{
+        self.preamble
+    }
+
+    pub fn endianness(&self) -> Endianness{
+        self.endianness
+    }
+
+    pub fn checksum_kind(&self) -> ChecksumKind{
+        self.kind
+    }
+
+    //bytes a frame adds around its payload; FRAME_OVERHEAD for the default
+    //preamble and checksum. escaping can add up to one byte per frame byte
+    pub fn overhead(&self) -> usize{
+        self.preamble.width() + 2 + self.kind.width()
+    }
+
+    pub fn checksum(data: &[u8]) -> u8{
+        data.iter().fold(0u8, |acc, &b| acc.wrapping_add(b))
+    }
+
+    pub fn crc16(data: &[u8]) -> u16{
+        ChecksumKind::Crc16.update(ChecksumKind::Crc16.init(), data)
+    }
+
+    fn check(&self, covered: &[u8]) -> u16{
+        self.kind.update(self.kind.init(), covered)
+    }
+
+    //panics if payload is longer than MAX_MSG_SIZE; callers taking foreign
+    //payloads must check first
+    pub fn encode(&self, msg_type: MsgType, payload: &[u8]) -> Vec<u8>{
+        assert!(payload.len() <= MAX_MSG_SIZE, "payload of {} bytes exceeds MAX_MSG_SIZE", payload.len());
+
+        if let Some((_, len)) = self.fast_type(msg_type as u8){
+            assert_eq!(payload.len(), len, "{:?} is a fast frame of fixed length", msg_type);
+            let mut frame = Vec::with_capacity(self.preamble.width() + 1 + len);
+            frame.extend_from_slice(self.preamble.as_bytes());
+            frame.push(msg_type as u8);
+            frame.extend_from_slice(payload);
+            return frame;
+        }
+
+        let mut frame = Vec::with_capacity(self.overhead() + payload.len());
+        frame.extend_from_slice(self.preamble.as_bytes());
+        frame.push(msg_type as u8);
+        frame.push(payload.len() as u8);
+        frame.extend_from_slice(payload);
+        let check = self.check(&frame[self.coverage.start(&self.preamble)..]);
+        self.kind.append(&mut frame, check);
+        if self.escaped{
+            let body = frame.split_off(self.preamble.width());
+            let sync = self.preamble.as_bytes()[0];
+            for b in body{
+                if b == sync || b == ESCAPE_BYTE{
+                    frame.extend_from_slice(&[ESCAPE_BYTE, b ^ ESCAPE_XOR]);
+                }else{
+                    frame.push(b);
+                }
+            }
+        }
+        frame
+    }
+
+    //pulls the next valid frame off the front of buffer. garbage, corrupt
+    //frames and unknown types are consumed on the way; None means more bytes
+    //are needed
+    pub fn decode(&self, buffer: &mut Vec<u8>) -> Option<UartFrame>{
+        self.decode_counting(buffer, &mut 0)
+    }
+
+    //decode that adds every header dropped for declaring more than
+    //MAX_MSG_SIZE to `bad_lengths`, see FrameDecoder::bad_length_count
+    pub fn decode_counting(&self, buffer: &mut Vec<u8>, bad_lengths: &mut u64) -> Option<UartFrame>{
+        if self.escaped{
+            return self.decode_escaped(buffer, bad_lengths);
+        }
+        let header = self.preamble.width();
+        loop{
+            let sync_pos = match self.preamble.find(buffer){
+                Some(pos) => pos,
+                None =>{
+                    buffer.clear();
+                    return None;
+                }
+            };
+            buffer.drain(..sync_pos);
+
+            if buffer.len() <= header{
+                return None;
+            }
+            if let Some((msg_type, len)) = self.fast_type(buffer[header]){
+                let frame_len = header + 1 + len;
+                if buffer.len() < frame_len{
+                    return None;
+                }
+                let payload = buffer[header + 1..frame_len].to_vec();
+                buffer.drain(..frame_len);
+                return Some(UartFrame{ msg_type, payload });
+            }
+
+            if buffer.len() < self.overhead(){
+                return None;
+            }
+
+            let len = buffer[header + 1] as usize;
+            if len > MAX_MSG_SIZE{
+                //not a real header; the next frame may start anywhere after
+                //the sync, so look again from the very next byte rather than
+                //skipping the length it claims
+                *bad_lengths += 1;
+                buffer.remove(0);
+                continue;
+            }
+
+            let frame_len = self.overhead() + len;
+            if buffer.len() < frame_len{
+                return None;
+            }
+
+            let end = header + 2 + len;
+            if self.kind.read(&buffer[end..]) != self.check(&buffer[self.coverage.start(&self.preamble)..end]){
+                buffer.remove(0);
+                continue;
+            }
+
+            let msg_type = MsgType::from_u8(buffer[header]);
+            let payload = buffer[header + 2..end].to_vec();
+            buffer.drain(..frame_len);
+
+            if let Some(msg_type) = msg_type{
+                return Some(UartFrame{ msg_type, payload });
+            }
+        }
+    }
+}
+
+impl FrameCodec{
+    //decode for escaped frames. a raw sync byte always starts a frame, so a
+    //frame cut short by a dropped byte is abandoned right at the next one
+    fn decode_escaped(&self, buffer: &mut Vec<u8>, bad_lengths: &mut u64) -> Option<UartFrame>{
+        let mut frame = Vec::new();
+        loop{
+            match self.preamble.find(buffer){
+                Some(pos) =>{
+                    buffer.drain(..pos);
+                }
+                None =>{
+                    buffer.clear();
+                    return None;
+                }
+            }
+
+            let end = match self.scan_escaped(buffer, &mut frame){
+                Scan::Complete(end) => end,
+                Scan::Incomplete => return None,
+                Scan::Resync(at) =>{
+                    buffer.drain(..at);
+                    continue;
+                }
+                Scan::BadLength =>{
+                    *bad_lengths += 1;
+                    buffer.remove(0);
+                    continue;
+                }
+                Scan::BadEscape =>{
+                    buffer.remove(0);
+                    continue;
+                }
+            };
+
+            if !self.escaped_check_passes(&frame){
+                buffer.remove(0);
+                continue;
+            }
+            buffer.drain(..end);
+
+            let body = frame.len() - self.kind.width();
+            if let Some(msg_type) = MsgType::from_u8(frame[0]){
+                return Some(UartFrame{ msg_type, payload: frame[2..body].to_vec() });
+            }
+        }
+    }
+
+    //unescape the frame opening `buffer` into `frame`, TYPE and LEN first,
+    //then the rest once LEN says how much
+    fn scan_escaped(&self, buffer: &[u8], frame: &mut Vec<u8>) -> Scan{
+        let sync = self.preamble.as_bytes()[0];
+        let header = self.preamble.width();
+        if buffer.len() < header{
+            return Scan::Incomplete;
+        }
+
+        frame.clear();
+        let mut pos = header;
+        let mut want = 2;
+        loop{
+            if frame.len() == 2 && want == 2{
+                let len = frame[1] as usize;
+                if len > MAX_MSG_SIZE{
+                    return Scan::BadLength;
+                }
+                want = 2 + len + self.kind.width();
+            }
+            if frame.len() == want{
+                return Scan::Complete(pos);
+            }
+            let Some(&b) = buffer.get(pos) else{ return Scan::Incomplete };
+            if b == sync{
+                return Scan::Resync(pos);
+            }
+            if b != ESCAPE_BYTE{
+                frame.push(b);
+                pos += 1;
+                continue;
+            }
+            let Some(&next) = buffer.get(pos + 1) else{ return Scan::Incomplete };
+            let b = next ^ ESCAPE_XOR;
+            if b != sync && b != ESCAPE_BYTE{
+                return Scan::BadEscape;
+            }
+            frame.push(b);
+            pos += 2;
+        }
+    }
+
+    //`frame` is an unescaped frame without its preamble
+    fn escaped_check_passes(&self, frame: &[u8]) -> bool{
+        let body = frame.len() - self.kind.width();
+        let check = self.kind.update(self.kind.seed(self.coverage, &self.preamble), &frame[..body]);
+        self.kind.read(&frame[body..]) == check
+    }
+
+    //what the bytes opening `buffer` (a preamble, or the start of one) hold
+    fn probe(&self, buffer: &[u8]) -> Probe{
+        if self.escaped{
+            let mut frame = Vec::new();
+            return match self.scan_escaped(buffer, &mut frame){
+                Scan::Complete(end) if self.escaped_check_passes(&frame) => Probe::Complete(end),
+                Scan::Incomplete => Probe::Partial,
+                Scan::Complete(_) | Scan::Resync(_) | Scan::BadLength | Scan::BadEscape => Probe::Invalid,
+            };
+        }
+
+        let header = self.preamble.width();
+        if let Some((_, len)) = buffer.get(header).and_then(|&t| self.fast_type(t)){
+            let frame_len = header + 1 + len;
+            return if buffer.len() < frame_len{ Probe::Partial }else{ Probe::Complete(frame_len) };
+        }
+        if buffer.len() < header + 2{
+            return Probe::Partial;
+        }
+        let len = buffer[header + 1] as usize;
+        if len > MAX_MSG_SIZE{
+            return Probe::Invalid;
+        }
+        let frame_len = self.overhead() + len;
+        if buffer.len() < frame_len{
+            return Probe::Partial;
+        }
+        let end = header + 2 + len;
+        if self.kind.read(&buffer[end..]) == self.check(&buffer[self.coverage.start(&self.preamble)..end]){
+            Probe::Complete(frame_len)
+        }else{
+            Probe::Invalid
+        }
+    }
+
+    //total length of the valid frame at the very start of `buffer`; None if
+    //there is none, or it hasn't fully arrived
+    pub fn complete_frame_len(&self, buffer: &[u8]) -> Option<usize>{
+        if !buffer.starts_with(self.preamble.as_bytes()){
+            return None;
+        }
+        match self.probe(buffer){
+            Probe::Complete(len) => Some(len),
+            Probe::Partial | Probe::Invalid => None,
+        }
+    }
+
+    //where the next frame starts: a complete valid frame, or one that could
+    //still turn out valid once more bytes arrive. garbage and sync bytes
+    //that can't open a valid frame are skipped
+    pub fn find_frame_start(&self, buffer: &[u8]) -> Option<usize>{
+        let mut from = 0;
+        loop{
+            let at = from + self.preamble.find(&buffer[from..])?;
+            match self.probe(&buffer[at..]){
+                Probe::Invalid => from = at + 1,
+                Probe::Complete(_) | Probe::Partial => return Some(at),
+            }
+        }
+    }
+}
+
+//what sits at a frame candidate, see FrameCodec::probe
+enum Probe{
+    Complete(usize),
+    Partial,
+    Invalid,
+}
+
+//default framing (single SYNC_BYTE, Sum8 without sync) as plain functions,
+//for tools and examples that don't configure a codec
+pub fn encode_frame(msg_type: MsgType, payload: &[u8]) -> Vec<u8>{
+    FrameCodec::new().encode(msg_type, payload)
+}
+
+pub fn decode_frame(buffer: &mut Vec<u8>) -> Option<UartFrame>{
+    FrameCodec::new().decode(buffer)
+}
+
+//boundary primitives for custom parsers fed from byte topics, see
+//FrameCodec::find_frame_start and FrameCodec::complete_frame_len
+pub fn find_frame_start(buffer: &[u8]) -> Option<usize>{
+    FrameCodec::new().find_frame_start(buffer)
+}
+
+pub fn frame_is_complete(buffer: &[u8]) -> Option<usize>{
+    FrameCodec::new().complete_frame_len(buffer)
+}
+
+//stateful decoder owning its RX buffer. the checksum is summed as bytes
+//arrive, so a long frame trickling in is scanned once rather than on every
+//poll; any resync restarts the sum at the new sync candidate.
+#[derive(Debug)]
+pub struct FrameDecoder{
+    buffer: Vec<u8>,
+    summed: usize,  //end of the bytes already folded into sum
+    sum: u16,
+    coverage: ChecksumCoverage,
+    preamble: Preamble,
+    kind: ChecksumKind,
+    escaped: bool,
+    bad_lengths: u64,
+}
+
+impl FrameDecoder{
+    pub fn new() -> Self{
+        Self::with_coverage(ChecksumCoverage::default())
+    }
+
+    pub fn with_coverage(coverage: ChecksumCoverage) -> Self{
+        let mut decoder = FrameDecoder{
+            buffer: Vec::with_capacity(RX_BUFFER_CAPACITY),
+            summed: 1,
+            sum: 0,
+            coverage,
+            preamble: Preamble::default(),
+            kind: ChecksumKind::default(),
+            escaped: false,
+            bad_lengths: 0,
+        };
+        decoder.restart();
+        decoder
+    }
+
+    //must match the sender's; drops anything buffered under the old one
+    pub fn with_preamble(mut self, preamble: Preamble) -> Self{
+        self.preamble = preamble;
+        self.clear();
+        self
+    }
+
+    //must match the sender's; drops anything buffered under the old one
+    pub fn with_checksum(mut self, kind: ChecksumKind) -> Self{
+        self.kind = kind;
+        self.clear();
+        self
+    }
+
+    //must match the sender's; drops anything buffered
+    pub fn with_escaping(mut self, enabled: bool) -> Self{
+        self.escaped = enabled;
+        self.clear();
+        self
+    }
+
+    //start with room for `capacity` bytes; drops anything buffered
+    pub fn with_buffer_capacity(mut self, capacity: usize) -> Self{
+        self.buffer = Vec::with_capacity(capacity);
+        self.restart();
+        self
+    }
+
+    pub fn coverage(&self) -> ChecksumCoverage{
+        self.coverage
+    }
+
+    pub fn preamble(&self) -> Preamble{
+        self.preamble
+    }
+
+    pub fn checksum_kind(&self) -> ChecksumKind{
+        self.kind
+    }
+
+    pub fn escaping(&self) -> bool{
+        self.escaped
+    }
+
+    pub fn buffer_capacity(&self) -> usize{
+        self.buffer.capacity()
+    }
+
+    pub fn extend(&mut self, bytes: &[u8]){
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    //headers dropped for declaring more than MAX_MSG_SIZE; a climbing count
+    //means the length byte is getting corrupted on the wire
+    pub fn bad_length_count(&self) -> u64{
+        self.bad_lengths
+    }
+
+    //bytes waiting for the rest of their frame
+    pub fn buffered(&self) -> usize{
+        self.buffer.len()
+    }
+
+    pub fn clear(&mut self){
+        self.buffer.clear();
+        self.restart();
+    }
+
+    fn restart(&mut self){
+        //the sum always starts on a full preamble
+        self.summed = self.preamble.width();
+        self.sum = self.kind.seed(self.coverage, &self.preamble);
+    }
+
+    //drop the front byte and look for the next sync
+    fn resync(&mut self){
+        self.buffer.remove(0);
+        self.restart();
+    }
+
+    //same results as FrameCodec::decode on the accumulated bytes
+    pub fn next_frame(&mut self) -> Option<UartFrame>{
+        if self.escaped{
+            //escaped frames are rescanned from their sync on every call; the
+            //running sum only works where raw and frame bytes line up
+            let codec = FrameCodec::with_coverage(self.coverage)
+                .with_preamble(self.preamble)
+                .with_checksum(self.kind);
+            return codec.decode_escaped(&mut self.buffer, &mut self.bad_lengths);
+        }
+        let header = self.preamble.width();
+        loop{
+            match self.preamble.find(&self.buffer){
+                Some(0) => {}
+                Some(pos) =>{
+                    self.buffer.drain(..pos);
+                    self.restart();
+                }
+                None =>{
+                    self.clear();
+                    return None;
+                }
+            }
+
+            if self.buffer.len() < header + 2{
+                return None;
+            }
+
+            let len = self.buffer[header + 1] as usize;
+            if len > MAX_MSG_SIZE{
+                //same as decode: don't wait for the bytes it claims
+                self.bad_lengths += 1;
+                self.resync();
+                continue;
+            }
+
+            let end = header + 2 + len;
+            let upto = end.min(self.buffer.len());
+            self.sum = self.kind.update(self.sum, &self.buffer[self.summed..upto]);
+            self.summed = upto;
+            if self.buffer.len() < end + self.kind.width(){
+                return None;
+            }
+
+            if self.kind.read(&self.buffer[end..]) != self.sum{
+                self.resync();
+                continue;
+            }
+
+            let msg_type = MsgType::from_u8(self.buffer[header]);
+            let payload = self.buffer[header + 2..end].to_vec();
+            self.buffer.drain(..end + self.kind.width());
+            self.restart();
+
+            if let Some(msg_type) = msg_type{
+                return Some(UartFrame{ msg_type, payload });
+            }
+        }
+    }
+}
+
+impl Default for FrameDecoder{
+    fn default() -> Self{
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn test_codec_round_trip(){
+        let codec = FrameCodec::new();
+        let payload: Vec<u8> = (0..20).collect();
+        let mut buffer = codec.encode(MsgType::Imu, &payload);
+        assert_eq!(buffer.len(), FRAME_OVERHEAD + 20);
+        buffer.extend(codec.encode(MsgType::Heartbeat, &[]));
+
+        let frame = codec.decode(&mut buffer).unwrap();
+        assert_eq!(frame.msg_type, MsgType::Imu);
+        assert_eq!(frame.payload, payload);
+        let frame = codec.decode(&mut buffer).unwrap();
+        assert_eq!(frame.msg_type, MsgType::Heartbeat);
+        assert!(frame.payload.is_empty());
+        assert!(codec.decode(&mut buffer).is_none());
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_codec_checksum(){
+        let data = [0x01, 0x05, 0xAB, 0xCD];
+        assert_eq!(FrameCodec::checksum(&data), 0x01u8.wrapping_add(0x05).wrapping_add(0xAB).wrapping_add(0xCD));
+    }
+
+    #[test]
+    fn test_codec_waits_for_partial_frame(){
+        let codec = FrameCodec::new();
+        let frame = codec.encode(MsgType::Depth, &[1, 2, 3, 4]);
+        let mut buffer = frame[..5].to_vec();
+        assert!(codec.decode(&mut buffer).is_none());
+        assert_eq!(buffer, frame[..5]);
+
+        buffer.extend_from_slice(&frame[5..]);
+        assert_eq!(codec.decode(&mut buffer).unwrap().payload, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_codec_resyncs_past_garbage(){
+        let codec = FrameCodec::new();
+        let mut buffer = vec![0x00, 0x13, 0x37];
+        //sync byte with an impossible length
+        buffer.extend([SYNC_BYTE, 0x01, 0xFF]);
+        //well-formed frame with a bad checksum
+        let mut corrupt = codec.encode(MsgType::Depth, &[9, 9, 9, 9]);
+        *corrupt.last_mut().unwrap() ^= 0xFF;
+        buffer.extend(corrupt);
+        //valid frame of an unknown type
+        buffer.extend([SYNC_BYTE, 0x7F, 0x01, 0x42, 0x7F + 0x01 + 0x42]);
+        buffer.extend(codec.encode(MsgType::Orientation, &[7; 12]));
+
+        let frame = codec.decode(&mut buffer).unwrap();
+        assert_eq!(frame.msg_type, MsgType::Orientation);
+        assert_eq!(frame.payload, vec![7; 12]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_impossible_length_does_not_hide_next_frame(){
+        let codec = FrameCodec::new();
+        let mut stream = vec![SYNC_BYTE, MsgType::Depth as u8, 0xF5];
+        stream.extend(codec.encode(MsgType::Depth, &[1, 2, 3, 4]));
+
+        let mut buffer = stream.clone();
+        let mut bad_lengths = 0;
+        assert_eq!(codec.decode_counting(&mut buffer, &mut bad_lengths).unwrap().payload, vec![1, 2, 3, 4]);
+        assert_eq!(bad_lengths, 1);
+
+        //recovered in the very poll that sees the frame, not after the
+        //claimed 245 bytes have gone by
+        let mut decoder = FrameDecoder::new();
+        decoder.extend(&stream);
+        assert_eq!(decoder.next_frame().unwrap().payload, vec![1, 2, 3, 4]);
+        assert_eq!(decoder.bad_length_count(), 1);
+        assert_eq!(decoder.buffered(), 0);
+    }
+
+    #[test]
+    fn test_codec_discards_buffer_without_sync(){
+        let codec = FrameCodec::new();
+        let mut buffer = vec![0x01, 0x02, 0x03, 0x04, 0x05];
+        assert!(codec.decode(&mut buffer).is_none());
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_streaming_decoder_matches_codec_across_fragments(){
+        let codec = FrameCodec::new();
+        let mut stream = vec![0x00, 0x13, SYNC_BYTE, 0x01, 0xFF];
+        stream.extend(codec.encode(MsgType::Imu, &(0..200).collect::<Vec<u8>>()));
+        let mut corrupt = codec.encode(MsgType::Depth, &[9; 40]);
+        corrupt[10] ^= 0x01;
+        stream.extend(corrupt);
+        stream.extend([SYNC_BYTE, 0x7F, 0x01, 0x42, 0x7F + 0x01 + 0x42]);
+        stream.extend(codec.encode(MsgType::Heartbeat, &[]));
+        stream.extend(codec.encode(MsgType::Orientation, &[SYNC_BYTE; 12]));
+        stream.extend(codec.encode(MsgType::Calibration, &[1; MAX_MSG_SIZE]));
+
+        let summary = |f: UartFrame| (f.msg_type, f.payload);
+        let mut naive = Vec::new();
+        let mut buffer = stream.clone();
+        while let Some(frame) = codec.decode(&mut buffer){
+            naive.push(summary(frame));
+        }
+        assert_eq!(naive.len(), 4);
+
+        for chunk in 1..=9{
+            let mut decoder = FrameDecoder::new();
+            let mut streamed = Vec::new();
+            for piece in stream.chunks(chunk){
+                decoder.extend(piece);
+                while let Some(frame) = decoder.next_frame(){
+                    streamed.push(summary(frame));
+                }
+            }
+            assert_eq!(streamed, naive, "chunk size {}", chunk);
+            assert_eq!(decoder.buffered(), 0);
+        }
+    }
+
+    #[test]
+    fn test_checksum_coverage_modes(){
+        for coverage in [ChecksumCoverage::WithSync, ChecksumCoverage::WithoutSync]{
+            let codec = FrameCodec::with_coverage(coverage);
+            let frame = codec.encode(MsgType::Depth, &[1, 2, 3, 4]);
+
+            let mut buffer = frame.clone();
+            assert_eq!(codec.decode(&mut buffer).unwrap().payload, vec![1, 2, 3, 4]);
+
+            let mut decoder = FrameDecoder::with_coverage(coverage);
+            decoder.extend(&frame);
+            assert_eq!(decoder.next_frame().unwrap().payload, vec![1, 2, 3, 4]);
+        }
+
+        let with_sync = FrameCodec::with_coverage(ChecksumCoverage::WithSync).encode(MsgType::Depth, &[1, 2, 3, 4]);
+        let without_sync = FrameCodec::new().encode(MsgType::Depth, &[1, 2, 3, 4]);
+        assert_eq!(*with_sync.last().unwrap(), without_sync.last().unwrap().wrapping_add(SYNC_BYTE));
+        assert_eq!(FrameCodec::new().coverage(), ChecksumCoverage::WithoutSync);
+    }
+
+    #[test]
+    fn test_standalone_frame_functions(){
+        let frame = encode_frame(MsgType::Depth, &[1, 2, 3, 4]);
+        assert_eq!(frame, FrameCodec::new().encode(MsgType::Depth, &[1, 2, 3, 4]));
+
+        //partial frame: nothing yet, and nothing lost
+        let mut buffer = frame[..5].to_vec();
+        assert!(decode_frame(&mut buffer).is_none());
+        assert_eq!(buffer, frame[..5]);
+        buffer.extend_from_slice(&frame[5..]);
+        assert_eq!(decode_frame(&mut buffer).unwrap().payload, vec![1, 2, 3, 4]);
+        assert!(buffer.is_empty());
+
+        //leading garbage before the sync byte
+        let mut buffer = vec![0x00, 0x13, 0x37];
+        buffer.extend(encode_frame(MsgType::Heartbeat, &[]));
+        assert_eq!(decode_frame(&mut buffer).unwrap().msg_type, MsgType::Heartbeat);
+
+        //checksum mismatch: dropped, the next good frame still comes through
+        let mut buffer = frame.clone();
+        *buffer.last_mut().unwrap() ^= 0xFF;
+        buffer.extend(encode_frame(MsgType::Depth, &[5, 6, 7, 8]));
+        assert_eq!(decode_frame(&mut buffer).unwrap().payload, vec![5, 6, 7, 8]);
+        assert!(decode_frame(&mut buffer).is_none());
+    }
+
+    #[test]
+    fn test_escaping_keeps_sync_out_of_frames(){
+        let payload = [SYNC_BYTE, ESCAPE_BYTE, 0x01, SYNC_BYTE ^ ESCAPE_XOR, SYNC_BYTE];
+        for preamble in [Preamble::default(), Preamble::double(0xAA, 0x55)]{
+            let codec = FrameCodec::new().with_preamble(preamble).with_escaping(true);
+            let frame = codec.encode(MsgType::Depth, &payload);
+            assert_eq!(frame.iter().filter(|&&b| b == SYNC_BYTE).count(), 1);
+            assert_eq!(frame.len(), codec.overhead() + payload.len() + 3);
+
+            let mut stream = frame.clone();
+            stream.extend(codec.encode(MsgType::Heartbeat, &[]));
+            let mut buffer = stream.clone();
+            assert_eq!(codec.decode(&mut buffer).unwrap().payload, payload);
+            assert_eq!(codec.decode(&mut buffer).unwrap().msg_type, MsgType::Heartbeat);
+            assert!(buffer.is_empty());
+
+            for chunk in 1..=4{
+                let mut decoder = FrameDecoder::new().with_preamble(preamble).with_escaping(true);
+                let mut frames = Vec::new();
+                for piece in stream.chunks(chunk){
+                    decoder.extend(piece);
+                    while let Some(frame) = decoder.next_frame(){
+                        frames.push(frame.payload);
+                    }
+                }
+                assert_eq!(frames, vec![payload.to_vec(), vec![]], "chunk {}", chunk);
+                assert_eq!(decoder.buffered(), 0);
+            }
+        }
+
+        //an unescaping codec doesn't understand stuffed frames
+        let mut buffer = FrameCodec::new().with_escaping(true).encode(MsgType::Depth, &[ESCAPE_BYTE; 4]);
+        assert!(FrameCodec::new().decode(&mut buffer).is_none());
+    }
+
+    #[test]
+    fn test_fast_frame_skips_len_and_checksum(){
+        let pwm = ThrusterPwmCmd::new([1500, 1600, 1400, 1500, 1700, 1300]).to_bytes();
+        let codec = FrameCodec::new().with_fast_frame(MsgType::Thruster, THRUSTER_PWM_SIZE);
+
+        let fast = codec.encode(MsgType::Thruster, &pwm);
+        assert_eq!(fast.len(), 2 + THRUSTER_PWM_SIZE);
+        assert_eq!(fast[..2], [SYNC_BYTE, MsgType::Thruster as u8]);
+        assert_eq!(fast[2..], pwm[..]);
+
+        //other types keep LEN and checksum, and still have them checked
+        let depth = codec.encode(MsgType::Depth, &2.5f32.to_le_bytes());
+        assert_eq!(depth, FrameCodec::new().encode(MsgType::Depth, &2.5f32.to_le_bytes()));
+        let mut corrupt = depth.clone();
+        corrupt[4] ^= 0x01;
+
+        let mut stream = [fast.clone(), corrupt, depth, fast.clone()].concat();
+        assert_eq!(codec.complete_frame_len(&stream), Some(fast.len()));
+        let frames: Vec<_> = std::iter::from_fn(|| codec.decode(&mut stream)).collect();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].msg_type, MsgType::Thruster);
+        assert_eq!(frames[0].payload, pwm);
+        assert_eq!(frames[1].payload, 2.5f32.to_le_bytes());
+        assert_eq!(frames[2].msg_type, MsgType::Thruster);
+
+        //a partial fast frame waits for the rest
+        let mut partial = fast[..10].to_vec();
+        assert!(codec.decode(&mut partial).is_none());
+        assert_eq!(partial.len(), 10);
+        assert_eq!(codec.find_frame_start(&partial), Some(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "fast frame of fixed length")]
+    fn test_fast_frame_rejects_wrong_length(){
+        FrameCodec::new().with_fast_frame(MsgType::Heartbeat, 0).encode(MsgType::Heartbeat, &[1]);
+    }
+
+    #[test]
+    fn test_escaped_stream_recovers_after_drops(){
+        //xorshift, so every run sees the same "random" streams
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        let mut next = move ||{
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let codec = FrameCodec::new().with_checksum(ChecksumKind::Crc16).with_escaping(true);
+
+        for round in 0..50{
+            let mut sent = Vec::new();
+            let mut intact = Vec::new();
+            let mut stream = Vec::new();
+            for i in 0..40{
+                //payloads heavy in sync and escape bytes
+                let len = (next() % 48) as usize;
+                let payload: Vec<u8> = (0..len).map(|_| match next() % 4{
+                    0 => SYNC_BYTE,
+                    1 => ESCAPE_BYTE,
+                    _ => next() as u8,
+                }).collect();
+                let mut frame = codec.encode(MsgType::Imu, &payload);
+                let dropped = next() % 3 == 0;
+                if dropped{
+                    let at = (next() % frame.len() as u64) as usize;
+                    frame.remove(at);
+                }else{
+                    intact.push(i);
+                }
+                stream.extend(frame);
+                sent.push(payload);
+            }
+
+            let chunk = 1 + (next() % 16) as usize;
+            let mut decoder = FrameDecoder::new().with_checksum(ChecksumKind::Crc16).with_escaping(true);
+            let mut received = Vec::new();
+            for piece in stream.chunks(chunk){
+                decoder.extend(piece);
+                while let Some(frame) = decoder.next_frame(){
+                    received.push(frame.payload);
+                }
+            }
+
+            //every undamaged frame, in order, and nothing invented; a frame
+            //right after a damaged one is picked up at its own sync byte
+            let expected: Vec<Vec<u8>> = intact.iter().map(|&i| sent[i].clone()).collect();
+            assert_eq!(received, expected, "round {}", round);
+        }
+    }
+
+    #[test]
+    fn test_frame_boundary_utilities(){
+        let frame = encode_frame(MsgType::Depth, &[SYNC_BYTE, 2, 3, 4]);
+
+        //complete
+        assert_eq!(frame_is_complete(&frame), Some(frame.len()));
+        assert_eq!(find_frame_start(&frame), Some(0));
+        let mut two = frame.clone();
+        two.extend(encode_frame(MsgType::Heartbeat, &[]));
+        assert_eq!(frame_is_complete(&two), Some(frame.len()));
+
+        //partial: not complete yet, but still a frame start
+        for cut in 1..frame.len(){
+            assert_eq!(frame_is_complete(&frame[..cut]), None);
+            assert_eq!(find_frame_start(&frame[..cut]), Some(0), "cut {}", cut);
+        }
+
+        //garbage first, including a sync byte with an impossible length
+        let mut stream = vec![0x00, SYNC_BYTE, 0x02, 0xFF, 0x42];
+        stream.extend(&frame);
+        assert_eq!(frame_is_complete(&stream), None);
+        assert_eq!(find_frame_start(&stream), Some(5));
+        assert_eq!(frame_is_complete(&stream[5..]), Some(frame.len()));
+
+        //corrupted: the sync byte inside the payload is the next candidate,
+        //which could still be a frame until the bytes after it rule it out
+        let mut corrupt = frame.clone();
+        *corrupt.last_mut().unwrap() ^= 0xFF;
+        assert_eq!(frame_is_complete(&corrupt), None);
+        assert_eq!(find_frame_start(&corrupt), Some(3));
+        corrupt.extend(encode_frame(MsgType::Heartbeat, &[]));
+        assert_eq!(find_frame_start(&corrupt), Some(frame.len()));
+        assert_eq!(find_frame_start(&[0x01, 0x02]), None);
+
+        //a configured codec answers for its own framing
+        let codec = FrameCodec::new().with_checksum(ChecksumKind::Crc16).with_escaping(true);
+        let escaped = codec.encode(MsgType::Depth, &[SYNC_BYTE, ESCAPE_BYTE, 3, 4]);
+        let mut stream = vec![SYNC_BYTE, 0x02];
+        stream.extend(&escaped);
+        assert_eq!(codec.find_frame_start(&stream), Some(2));
+        assert_eq!(codec.complete_frame_len(&stream[2..]), Some(escaped.len()));
+        assert_eq!(codec.complete_frame_len(&escaped[..escaped.len() - 1]), None);
+    }
+
+    #[test]
+    fn test_crc16_matches_ccitt_check_value(){
+        assert_eq!(FrameCodec::crc16(b"123456789"), 0x29B1);
+        assert_eq!(FrameCodec::crc16(&[]), 0xFFFF);
+    }
+
+    #[test]
+    fn test_checksum_kinds_round_trip(){
+        for kind in [ChecksumKind::Sum8, ChecksumKind::Crc16]{
+            for coverage in [ChecksumCoverage::WithSync, ChecksumCoverage::WithoutSync]{
+                let codec = FrameCodec::with_coverage(coverage).with_checksum(kind);
+                let mut stream = codec.encode(MsgType::Imu, &[0xAA; 36]);
+                stream.extend(codec.encode(MsgType::Heartbeat, &[]));
+                assert_eq!(stream.len(), 2 * codec.overhead() + 36);
+
+                let mut buffer = stream.clone();
+                assert_eq!(codec.decode(&mut buffer).unwrap().payload, vec![0xAA; 36]);
+                assert_eq!(codec.decode(&mut buffer).unwrap().msg_type, MsgType::Heartbeat);
+
+                for chunk in 1..=3{
+                    let mut decoder = FrameDecoder::with_coverage(coverage).with_checksum(kind);
+                    let mut frames = 0;
+                    for piece in stream.chunks(chunk){
+                        decoder.extend(piece);
+                        while decoder.next_frame().is_some(){
+                            frames += 1;
+                        }
+                    }
+                    assert_eq!(frames, 2, "{:?} {:?} chunk {}", kind, coverage, chunk);
+                    assert_eq!(decoder.buffered(), 0);
+                }
+            }
+        }
+        assert_eq!(FrameCodec::new().checksum_kind(), ChecksumKind::Sum8);
+        assert_eq!(FrameCodec::new().overhead(), FRAME_OVERHEAD);
+    }
+
+    #[test]
+    fn test_crc16_catches_flips_sum8_misses(){
+        //one bit set in one byte and cleared in another leaves the sum alone
+        let flip = |frame: &mut Vec<u8>|{
+            frame[3] ^= 0x01;
+            frame[4] ^= 0x01;
+        };
+        let payload = [0x10, 0x21, 0x30, 0x40];
+
+        let sum8 = FrameCodec::new();
+        let mut buffer = sum8.encode(MsgType::Depth, &payload);
+        flip(&mut buffer);
+        assert_eq!(sum8.decode(&mut buffer).unwrap().payload, vec![0x11, 0x20, 0x30, 0x40]);
+
+        let crc16 = FrameCodec::new().with_checksum(ChecksumKind::Crc16);
+        let mut buffer = crc16.encode(MsgType::Depth, &payload);
+        flip(&mut buffer);
+        assert!(crc16.decode(&mut buffer).is_none());
+
+        let mut decoder = FrameDecoder::new().with_checksum(ChecksumKind::Crc16);
+        let mut frame = crc16.encode(MsgType::Depth, &payload);
+        flip(&mut frame);
+        decoder.extend(&frame);
+        assert!(decoder.next_frame().is_none());
+
+        //swapped bytes too
+        let mut buffer = crc16.encode(MsgType::Depth, &payload);
+        buffer.swap(5, 6);
+        assert!(crc16.decode(&mut buffer).is_none());
+    }
+
+    #[test]
+    fn test_checksum_coverage_rejects_cross_mode_frames(){
+        let with_sync = FrameCodec::with_coverage(ChecksumCoverage::WithSync);
+        let without_sync = FrameCodec::with_coverage(ChecksumCoverage::WithoutSync);
+
+        let mut buffer = with_sync.encode(MsgType::Imu, &[5; 12]);
+        assert!(without_sync.decode(&mut buffer).is_none());
+        let mut buffer = without_sync.encode(MsgType::Imu, &[5; 12]);
+        assert!(with_sync.decode(&mut buffer).is_none());
+
+        let mut decoder = FrameDecoder::with_coverage(ChecksumCoverage::WithSync);
+        decoder.extend(&without_sync.encode(MsgType::Imu, &[5; 12]));
+        assert!(decoder.next_frame().is_none());
+    }
+
+    #[test]
+    fn test_two_byte_preamble_ignores_lone_first_byte(){
+        let preamble = Preamble::double(0xAA, 0x55);
+        let codec = FrameCodec::new().with_preamble(preamble);
+        assert_eq!(codec.overhead(), FRAME_OVERHEAD + 1);
+
+        //payloads full of 0xAA, plus loose 0xAA noise between frames, with
+        //header-like bytes after it that a single-byte sync would latch onto
+        let mut stream = vec![0xAA, 0x02, 0x04, 0xAA, 0xAA, 0xAA];
+        stream.extend(codec.encode(MsgType::Depth, &[0xAA; 4]));
+        stream.extend([0xAA, 0x01, 0x00, 0x01]);
+        stream.extend(codec.encode(MsgType::Orientation, &[0xAA, 0x55, 0xAA, 0x01, 0x0C, 0xAA, 0, 0, 0, 0, 0, 0]));
+        stream.extend(codec.encode(MsgType::Heartbeat, &[]));
+        assert_eq!(&stream[6..8], &[0xAA, 0x55]);
+
+        let expected = vec![
+            (MsgType::Depth, vec![0xAA; 4]),
+            (MsgType::Orientation, vec![0xAA, 0x55, 0xAA, 0x01, 0x0C, 0xAA, 0, 0, 0, 0, 0, 0]),
+            (MsgType::Heartbeat, vec![]),
+        ];
+        let summary = |f: UartFrame| (f.msg_type, f.payload);
+
+        let mut buffer = stream.clone();
+        let mut decoded = Vec::new();
+        while let Some(frame) = codec.decode(&mut buffer){
+            decoded.push(summary(frame));
+        }
+        assert_eq!(decoded, expected);
+
+        for coverage in [ChecksumCoverage::WithSync, ChecksumCoverage::WithoutSync]{
+            let codec = FrameCodec::with_coverage(coverage).with_preamble(preamble);
+            let mut stream = vec![0xAA, 0x04, 0x00, 0x04, 0xAA];
+            stream.extend(codec.encode(MsgType::Depth, &[0xAA; 4]));
+            for chunk in 1..=5{
+                let mut decoder = FrameDecoder::with_coverage(coverage).with_preamble(preamble);
+                let mut streamed = Vec::new();
+                for piece in stream.chunks(chunk){
+                    decoder.extend(piece);
+                    while let Some(frame) = decoder.next_frame(){
+                        streamed.push(summary(frame));
+                    }
+                }
+                assert_eq!(streamed, vec![(MsgType::Depth, vec![0xAA; 4])], "{:?} chunk {}", coverage, chunk);
+                assert_eq!(decoder.buffered(), 0);
+            }
+        }
+
+        //a single-byte codec does not understand the longer preamble
+        let mut buffer = codec.encode(MsgType::Depth, &[1, 2, 3, 4]);
+        assert!(FrameCodec::new().decode(&mut buffer).is_none());
+    }
+
+    #[test]
+    fn test_preamble_keeps_a_split_start(){
+        let codec = FrameCodec::new().with_preamble(Preamble::double(0xAA, 0x55));
+        let mut buffer = vec![0x01, 0x02, 0xAA];
+        assert!(codec.decode(&mut buffer).is_none());
+        assert_eq!(buffer, vec![0xAA]);
+        assert_eq!(Preamble::default().as_bytes(), &[SYNC_BYTE]);
+    }
+
+    #[test]
+    fn test_thruster_pwm_cmd(){
+        let cmd = ThrusterPwmCmd::new([1500, 1600, 1400, 1550, 1450, 1500]);
+        let bytes = cmd.to_bytes();
+        assert_eq!(bytes.len(), 24);
+
+        let decoded = ThrusterPwmCmd::from_bytes(&bytes).unwrap();
+        let pwm = decoded.pwm; //copy to avoid unaligned access
+        assert_eq!(pwm[0], 1500);
+        assert_eq!(pwm[2], 1400);
+        assert_eq!(pwm[3], 1550);
+        assert_eq!(pwm[1], 1600);
+        assert_eq!(pwm[5], 1500);
+    }
+
+    #[test]
+    fn test_thruster_pwm_builder_maps_names_to_channels(){
+        let cmd = ThrusterPwmCmd::builder()
+            .front_right(1480)
+            .front_left(1520)
+            .vertical_right(1600)
+            .rear_left(1410)
+            .build();
+        let pwm = cmd.pwm;
+        assert_eq!(pwm, [1520, 1480, 1410, 1500, 1500, 1600]);
+
+        //same names, different wiring
+        let swapped = ChannelLayout::new([
+            Thruster::FrontRight, Thruster::FrontLeft, Thruster::RearLeft,
+            Thruster::RearRight, Thruster::VerticalRight, Thruster::VerticalLeft,
+        ]).unwrap();
+        let cmd = ThrusterPwmCmd::builder_for(swapped).front_left(1520).front_right(1480).vertical_left(1300).build();
+        let pwm = cmd.pwm;
+        assert_eq!(pwm, [1480, 1520, 1500, 1500, 1500, 1300]);
+
+        assert!(ChannelLayout::new([Thruster::FrontLeft; 6]).is_none());
+    }
+
+    #[test]
+    fn test_frame_describe(){
+        let mut payload = Vec::new();
+        for v in [0.25f32, -1.5, 9.81, 0.0, 0.0, 0.1, 20.0, 0.0, -40.0]{
+            payload.extend_from_slice(&v.to_le_bytes());
+        }
+        let imu = UartFrame{ msg_type: MsgType::Imu, payload };
+        let text = imu.describe();
+        assert!(text.starts_with("Imu accel=(0.250, -1.500, 9.810)"), "{}", text);
+        assert!(text.contains("gyro=(0.000, 0.000, 0.100)"));
+
+        let depth = UartFrame{ msg_type: MsgType::Depth, payload: 2.5f32.to_le_bytes().to_vec() };
+        assert_eq!(depth.describe(), "Depth depth=2.500m");
+
+        let ack = UartFrame{ msg_type: MsgType::Ack, payload: vec![0x10, 0xAB] };
+        assert_eq!(ack.describe(), "Ack len=2 [10 AB]");
+
+        //too short to decode falls back to hex
+        let short = UartFrame{ msg_type: MsgType::Imu, payload: vec![1, 2, 3] };
+        assert_eq!(short.describe(), "Imu len=3 [01 02 03]");
+    }
+
+    #[test]
+    fn test_explicit_endianness_is_host_independent(){
+        //payloads built a field at a time, as each byte order puts them on the wire
+        let values = [1.5f32, -2.25, 9.81];
+        let le: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        let be: Vec<u8> = values.iter().flat_map(|v| v.to_be_bytes()).collect();
+        assert_eq!(&le[..4], &[0x00, 0x00, 0xC0, 0x3F]);
+
+        for (bytes, order) in [(&le, Endianness::Little), (&be, Endianness::Big)]{
+            let orientation = OrientationMsg::from_bytes_with(bytes, order).unwrap();
+            assert_eq!(({orientation.roll}, {orientation.pitch}, {orientation.yaw}), (1.5, -2.25, 9.81));
+            assert_eq!(&orientation.to_bytes_with(order), bytes);
+        }
+        assert_eq!(Endianness::default(), Endianness::Little);
+        assert_eq!(OrientationMsg::from_bytes_with(&le[..8], Endianness::Little), None);
+
+        let pwm = ThrusterPwmCmd::new([1000, 1100, 1200, 1300, 1400, -1]);
+        for order in [Endianness::Little, Endianness::Big]{
+            let bytes = pwm.to_bytes_with(order);
+            assert_eq!(bytes.len(), THRUSTER_PWM_SIZE);
+            assert_eq!({ThrusterPwmCmd::from_bytes_with(&bytes, order).unwrap().pwm}, {pwm.pwm});
+        }
+        assert_eq!(&pwm.to_bytes_with(Endianness::Big)[..4], &[0x00, 0x00, 0x03, 0xE8]);
+
+        let imu = ImuMsg::from_bytes_with(&[0x3F, 0x80, 0x00, 0x00].repeat(9), Endianness::Big).unwrap();
+        assert_eq!(imu.values(), [1.0; 9]);
+        assert_eq!(DepthMsg::from_bytes_with(&2.5f32.to_be_bytes(), Endianness::Big), Some(DepthMsg{ depth: 2.5 }));
+
+        //the plain (de)serializers are the little-endian ones
+        assert_eq!(OrientationMsg::from_bytes(&le), OrientationMsg::from_bytes_with(&le, Endianness::Little));
+        assert_eq!(pwm.to_bytes(), pwm.to_bytes_with(Endianness::Little));
+    }
+
+    #[test]
+    fn test_from_bytes_decodes_known_little_endian_bytes(){
+        //written out by hand as the STM32 sends them, so this holds on any host
+        let depth = [0x00, 0x00, 0x20, 0x40];
+        assert_eq!({DepthMsg::from_bytes(&depth).unwrap().depth}, 2.5);
+
+        let orientation = [
+            0x00, 0x00, 0x80, 0x3F,
+            0x00, 0x00, 0x00, 0xC0,
+            0x00, 0x00, 0xB4, 0x42,
+        ];
+        let o = OrientationMsg::from_bytes(&orientation).unwrap();
+        assert_eq!(({o.roll}, {o.pitch}, {o.yaw}), (1.0, -2.0, 90.0));
+        assert_eq!(o.to_bytes(), orientation);
+
+        let mut imu = vec![0x00, 0x00, 0x80, 0xBF];
+        imu.extend([0x00; 32]);
+        let m = ImuMsg::from_bytes(&imu).unwrap();
+        assert_eq!(m.values(), [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(m.to_bytes(), imu);
+
+        let mut pwm = vec![0xDC, 0x05, 0x00, 0x00];
+        pwm.extend([0xE8, 0x03, 0x00, 0x00].repeat(4));
+        pwm.extend([0xFF, 0xFF, 0xFF, 0xFF]);
+        let cmd = ThrusterPwmCmd::from_bytes(&pwm).unwrap();
+        assert_eq!({cmd.pwm}, [1500, 1000, 1000, 1000, 1000, -1]);
+        assert_eq!(cmd.to_bytes(), pwm);
+    }
+
+    #[test]
+    fn test_sensor_to_bytes_round_trip_bit_exact(){
+        //awkward floats: negative zero, subnormal, NaN with a payload, infinity
+        let odd = [-0.0f32, f32::from_bits(1), f32::from_bits(0x7FC0_1234), f32::INFINITY, 9.81, -1e-30, f32::MAX, 0.1, 2.5];
+        let bits = |values: &[f32]| values.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+
+        let imu = ImuMsg{
+            accel_x: odd[0], accel_y: odd[1], accel_z: odd[2],
+            gyro_x: odd[3], gyro_y: odd[4], gyro_z: odd[5],
+            mag_x: odd[6], mag_y: odd[7], mag_z: odd[8],
+        };
+        let bytes = imu.to_bytes();
+        assert_eq!(bytes.len(), IMU_MSG_SIZE);
+        assert_eq!(bits(&ImuMsg::from_bytes(&bytes).unwrap().values()), bits(&odd));
+
+        let orientation = OrientationMsg{ roll: odd[0], pitch: odd[2], yaw: odd[5] };
+        let bytes = orientation.to_bytes();
+        assert_eq!(bytes.len(), ORIENTATION_MSG_SIZE);
+        let back = OrientationMsg::from_bytes(&bytes).unwrap();
+        assert_eq!(bits(&[back.roll, back.pitch, back.yaw]), bits(&[odd[0], odd[2], odd[5]]));
+
+        let bytes = DepthMsg{ depth: odd[1] }.to_bytes();
+        assert_eq!(bytes.len(), DEPTH_MSG_SIZE);
+        assert_eq!({DepthMsg::from_bytes(&bytes).unwrap().depth}.to_bits(), 1);
+        assert_eq!(DepthMsg{ depth: 2.5 }.to_bytes(), DepthMsg{ depth: 2.5 }.to_frame());
+    }
+
+    #[test]
+    fn test_imu_msg_size(){
+        assert_eq!(std::mem::size_of::<ImuMsg>(), IMU_MSG_SIZE);
+    }
+
+    #[test]
+    fn test_validate_payload(){
+        let depth = |d: f32| d.to_le_bytes();
+        assert!(MsgType::Depth.validate_payload(&depth(2.5)));
+        assert!(!MsgType::Depth.validate_payload(&depth(f32::NAN)));
+        assert!(!MsgType::Depth.validate_payload(&[0; 5]));
+
+        assert!(MsgType::Thruster.validate_payload(&ThrusterPwmCmd::new([1500; 6]).to_bytes()));
+        assert!(!MsgType::Thruster.validate_payload(&ThrusterPwmCmd::new([999; 6]).to_bytes()));
+        assert!(MsgType::Thruster.validate_payload(&ThrusterPwmCmd::channels_to_bytes(&[1500; 8], Endianness::Little)));
+        assert!(!MsgType::Thruster.validate_payload(&ThrusterPwmCmd::channels_to_bytes(&[1500; 5], Endianness::Little)));
+        assert!(!MsgType::Thruster.validate_payload(&[0xDC, 0x05, 0, 0].repeat(6)[..23]));
+        assert!(!MsgType::Calibration.validate_payload(&[2]));
+        assert!(MsgType::Heartbeat.validate_payload(&[1, 2, 3]));
+    }
+
+    #[test]
+    fn test_from_bytes_with_rest_walks_a_batch(){
+        let mut batch = Vec::new();
+        for depth in [1.5f32, 2.0, 2.5]{
+            batch.extend_from_slice(&depth.to_le_bytes());
+        }
+        batch.push(0xFF); //trailing partial message
+
+        let mut rest = &batch[..];
+        let mut depths = Vec::new();
+        while let Some((msg, tail)) = DepthMsg::from_bytes_with_rest(rest){
+            depths.push(msg.depth);
+            rest = tail;
+        }
+        assert_eq!(depths, vec![1.5, 2.0, 2.5]);
+        assert_eq!(rest, &[0xFF]);
+    }
+
+    fn imu(accel_x: f32) -> ImuMsg{
+        ImuMsg{ accel_x, accel_y: 0.1, accel_z: 9.81, gyro_z: -0.02, mag_x: 21.5, ..Default::default() }
+    }
+
+    #[test]
+    fn test_imu_approx_eq(){
+        let a = imu(0.500);
+        let b = imu(0.5004);
+
+        assert!(a.approx_eq(&b, 1e-3));
+        assert!(!a.approx_eq(&b, 1e-4));
+        assert!(a.approx_eq(&a, 0.0));
+        assert_ne!(a, b);
+
+        let nan = imu(f32::NAN);
+        assert!(!nan.approx_eq(&nan, 1.0));
+    }
+
+    #[test]
+    fn test_orientation_and_depth_approx_eq(){
+        let a = OrientationMsg{ roll: 1.0, pitch: -2.0, yaw: 179.9 };
+        let b = OrientationMsg{ roll: 1.0, pitch: -2.0, yaw: 180.0 };
+        assert!(a.approx_eq(&b, 0.2));
+        assert!(!a.approx_eq(&b, 0.05));
+
+        let decoded = DepthMsg::from_bytes(&1.25f32.to_le_bytes()).unwrap();
+        assert!(decoded.approx_eq(&DepthMsg{ depth: 1.25 }, 0.0));
+        assert!(!decoded.approx_eq(&DepthMsg{ depth: 1.5 }, 0.1));
+    }
+
+    #[cfg(feature = "approx")]
+    #[test]
+    fn test_abs_diff_eq(){
+        approx::assert_abs_diff_eq!(imu(0.5), imu(0.5004), epsilon = 1e-3);
+        approx::assert_abs_diff_ne!(imu(0.5), imu(0.6), epsilon = 1e-3);
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+//consecutive zero-progress writes tolerated before giving up on a frame
+const MAX_WRITE_STALLS: usize = 16;
+//first pause of the default policy; 16 doubling retries add up to ~0.3s
+//before a stalled link is reported
+const DEFAULT_BACKOFF: Duration = Duration::from_micros(20);
+//longest pause between two retries, however many have failed
+const MAX_BACKOFF: Duration = Duration::from_millis(50);
+
+//anything byte-oriented the bridge or controller can talk through;
+//Box<dyn SerialPort> gets this for free
+pub trait Transport: Read + Write + Send{}
+
+impl<T: Read + Write + Send + ?Sized> Transport for T{}
+
+//how hard a frame write tries before the link is declared down. only
+//WouldBlock, Interrupted and TimedOut (and zero-length writes) are retried,
+//anything else fails straight away. the budget counts consecutive failed
+//attempts, progress on the frame resets it; the pause doubles per failure.
+//once the budget is spent the last error comes back (WriteZero if the port
+//only ever accepted nothing)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy{
+    pub retries: usize,
+    pub backoff: Duration,
+}
+
+impl RetryPolicy{
+    pub const fn new(retries: usize, backoff: Duration) -> Self{
+        RetryPolicy{ retries, backoff }
+    }
+
+    fn delay(&self, failures: usize) -> Duration{
+        let factor = 1u32 << (failures.saturating_sub(1)).min(16);
+        self.backoff.saturating_mul(factor).min(MAX_BACKOFF)
+    }
+}
+
+impl Default for RetryPolicy{
+    fn default() -> Self{
+        RetryPolicy::new(MAX_WRITE_STALLS, DEFAULT_BACKOFF)
+    }
+}
+
+pub fn is_retryable(e: &io::Error) -> bool{
+    matches!(e.kind(), io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut)
+}
+
+//write_all that also rides out short writes and transient WouldBlock/TimedOut
+//from non-blocking or timed ports; only a real error or a stalled link fails
+pub fn write_fully<W: Write + ?Sized>(port: &mut W, buf: &[u8]) -> io::Result<()>{
+    write_with_retry(port, buf, &RetryPolicy::default())
+}
+
+pub fn write_with_retry<W: Write + ?Sized>(port: &mut W, mut buf: &[u8], policy: &RetryPolicy) -> io::Result<()>{
+    let mut stalls = 0;
+    while !buf.is_empty(){
+        let stall = match port.write(buf){
+            Ok(0) => io::Error::new(io::ErrorKind::WriteZero, "transport stopped accepting data"),
+            Ok(n) =>{
+                buf = &buf[n..];
+                stalls = 0;
+                continue;
+            }
+            Err(e) if is_retryable(&e) => e,
+            Err(e) => return Err(e),
+        };
+        stalls += 1;
+        if stalls > policy.retries{
+            return Err(stall);
+        }
+        let delay = policy.delay(stalls);
+        if !delay.is_zero(){
+            thread::sleep(delay);
+        }
+    }
+    port.flush()
+}
+
+//in-memory link standing in for the STM32: feed() queues bytes the host will
+//read, take_written() drains what the host sent
+#[derive(Clone, Default)]
+pub struct LoopbackTransport{
+    rx: Arc<Mutex<VecDeque<u8>>>,
+    tx: Arc<Mutex<Vec<u8>>>,
+    //max bytes accepted per write call, 0 = unlimited
+    write_chunk: Arc<AtomicUsize>,
+    broken: Arc<AtomicBool>,
+}
+
+impl LoopbackTransport{
+    pub fn new() -> Self{
+        Self::default()
+    }
+
+    pub fn feed(&self, bytes: &[u8]){
+        self.rx.lock().unwrap().extend(bytes.iter().copied());
+    }
+
+    pub fn take_written(&self) -> Vec<u8>{
+        std::mem::take(&mut *self.tx.lock().unwrap())
+    }
+
+    pub fn pending_rx(&self) -> usize{
+        self.rx.lock().unwrap().len()
+    }
+
+    //accept at most `chunk` bytes per write, like a slow or buffered link
+    pub fn set_write_chunk(&self, chunk: usize){
+        self.write_chunk.store(chunk, Ordering::SeqCst);
+    }
+
+    //fail every write with BrokenPipe, like an unplugged cable
+    pub fn set_broken(&self, broken: bool){
+        self.broken.store(broken, Ordering::SeqCst);
+    }
+}
+
+impl Read for LoopbackTransport{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>{
+        let mut rx = self.rx.lock().unwrap();
+        if rx.is_empty(){
+            //behave like a serial port whose read timeout expired
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "no data"));
+        }
+        let n = buf.len().min(rx.len());
+        for (dst, src) in buf.iter_mut().zip(rx.drain(..n)){
+            *dst = src;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for LoopbackTransport{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize>{
+        if self.broken.load(Ordering::SeqCst){
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe, "link down"));
+        }
+        let n = match self.write_chunk.load(Ordering::SeqCst){
+            0 => buf.len(),
+            chunk => buf.len().min(chunk),
+        };
+        self.tx.lock().unwrap().extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()>{
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn test_loopback_read_write(){
+        let link = LoopbackTransport::new();
+        let mut host: Box<dyn Transport> = Box::new(link.clone());
+
+        let mut buf = [0u8; 8];
+        assert_eq!(host.read(&mut buf).unwrap_err().kind(), io::ErrorKind::TimedOut);
+
+        link.feed(&[1, 2, 3]);
+        assert_eq!(host.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf[..3], &[1, 2, 3]);
+
+        host.write_all(&[9, 8]).unwrap();
+        assert_eq!(link.take_written(), vec![9, 8]);
+        assert!(link.take_written().is_empty());
+    }
+
+    //accepts two bytes per call and refuses every third call outright
+    struct Trickle{
+        written: Vec<u8>,
+        calls: usize,
+    }
+
+    impl Write for Trickle{
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize>{
+            self.calls += 1;
+            if self.calls.is_multiple_of(3){
+                return Err(io::Error::new(io::ErrorKind::WouldBlock, "busy"));
+            }
+            let n = buf.len().min(2);
+            self.written.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()>{
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_fully_survives_partial_writes(){
+        let mut port = Trickle{ written: Vec::new(), calls: 0 };
+        let frame: Vec<u8> = (0..31).collect();
+        write_fully(&mut port, &frame).unwrap();
+        assert_eq!(port.written, frame);
+    }
+
+    #[test]
+    fn test_write_fully_reports_dead_link(){
+        let link = LoopbackTransport::new();
+        let mut port = link.clone();
+        link.set_broken(true);
+        assert_eq!(write_fully(&mut port, &[1, 2, 3]).unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+
+        link.set_broken(false);
+        link.set_write_chunk(1);
+        write_fully(&mut port, &[1, 2, 3]).unwrap();
+        assert_eq!(link.take_written(), vec![1, 2, 3]);
+    }
+
+    //refuses the first `failures` calls with `kind`, then takes everything
+    struct Flaky{
+        kind: io::ErrorKind,
+        failures: usize,
+        calls: usize,
+        written: Vec<u8>,
+    }
+
+    impl Write for Flaky{
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize>{
+            self.calls += 1;
+            if self.calls <= self.failures{
+                return Err(io::Error::new(self.kind, "flaky"));
+            }
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()>{
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_with_retry_budget(){
+        let policy = RetryPolicy::new(2, Duration::from_micros(100));
+        let mut port = Flaky{ kind: io::ErrorKind::WouldBlock, failures: 2, calls: 0, written: Vec::new() };
+        write_with_retry(&mut port, &[1, 2, 3], &policy).unwrap();
+        assert_eq!(port.calls, 3);
+        assert_eq!(port.written, vec![1, 2, 3]);
+
+        //out of retries: the port's own error comes back
+        let mut port = Flaky{ kind: io::ErrorKind::TimedOut, failures: 3, calls: 0, written: Vec::new() };
+        assert_eq!(write_with_retry(&mut port, &[1], &policy).unwrap_err().kind(), io::ErrorKind::TimedOut);
+        assert_eq!(port.calls, 3);
+
+        //fatal errors are not retried at all
+        let mut port = Flaky{ kind: io::ErrorKind::BrokenPipe, failures: 1, calls: 0, written: Vec::new() };
+        assert_eq!(write_with_retry(&mut port, &[1], &policy).unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+        assert_eq!(port.calls, 1);
+    }
+
+    #[test]
+    fn test_retry_backoff_doubles_up_to_cap(){
+        let policy = RetryPolicy::new(10, Duration::from_millis(2));
+        assert_eq!(policy.delay(1), Duration::from_millis(2));
+        assert_eq!(policy.delay(2), Duration::from_millis(4));
+        assert_eq!(policy.delay(3), Duration::from_millis(8));
+        assert_eq!(policy.delay(10), MAX_BACKOFF);
+        assert_eq!(RetryPolicy::default().delay(1), DEFAULT_BACKOFF);
+        assert_eq!(RetryPolicy::default().delay(50), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn test_write_fully_backs_off_then_gives_up(){
+        let mut port = Flaky{ kind: io::ErrorKind::WouldBlock, failures: usize::MAX, calls: 0, written: Vec::new() };
+        let start = std::time::Instant::now();
+        assert_eq!(write_fully(&mut port, &[1, 2, 3]).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(port.calls, MAX_WRITE_STALLS + 1);
+        //sleeps between attempts instead of spinning
+        let slept: Duration = (1..=MAX_WRITE_STALLS).map(|n| RetryPolicy::default().delay(n)).sum();
+        assert!(start.elapsed() >= slept);
+    }
+}
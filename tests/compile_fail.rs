@@ -0,0 +1,6 @@
+//the Send/Sync contract of the buffers and topics, checked by the compiler
+#[test]
+fn concurrency_contract(){
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,14 @@
+//runs examples/loopback_demo.rs and checks its report, so the demo can't rot
+#[path = "../examples/loopback_demo.rs"]
+#[allow(dead_code)]
+mod demo;
+
+#[test]
+fn loopback_demo_round_trip(){
+    assert_eq!(demo::run(), vec![
+        "[RX] depth 2.50 m",
+        "[RX] orientation roll 1.0 pitch -2.0 yaw 90.0",
+        "[TX] surge 50% -> pwm [1700, 1700, 1300, 1300, 1500, 1500]",
+        "[TX] shutdown -> pwm [1500, 1500, 1500, 1500, 1500, 1500]",
+    ]);
+}
@@ -0,0 +1,160 @@
+//a ByteRingBuffer living in a child process, fed over a unix socket. the test
+//binary re-runs itself as the consumer: the child connects back, pushes every
+//message it receives into its own buffer, drains it once the parent hangs up
+//and reports what came out. the buffer is never shared between the two
+//processes, only the socket crosses the boundary; this checks framing over
+//the socket plus the child-side buffer's ordering and overwrite behaviour
+#![cfg(unix)]
+
+use std::io::{self, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use bibi_sync::{ByteRingBuffer, MAX_PAYLOAD_SIZE};
+
+const SOCKET_ENV: &str = "BIBI_CONSUMER_SOCKET";
+const CAPACITY_ENV: &str = "BIBI_CONSUMER_CAPACITY";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+//socket file removed however the test exits, panics included
+struct SocketPath(PathBuf);
+
+impl SocketPath{
+    fn new(name: &str) -> Self{
+        let path = std::env::temp_dir().join(format!("bibi-{}-{}.sock", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        SocketPath(path)
+    }
+}
+
+impl Drop for SocketPath{
+    fn drop(&mut self){
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+//child never outlives the test, even when an assert fails first
+struct ChildGuard(Child);
+
+impl Drop for ChildGuard{
+    fn drop(&mut self){
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn write_message(stream: &mut UnixStream, data: &[u8]) -> io::Result<()>{
+    stream.write_all(&(data.len() as u32).to_le_bytes())?;
+    stream.write_all(data)
+}
+
+//None on a clean hang-up between messages
+fn read_message(stream: &mut UnixStream) -> io::Result<Option<Vec<u8>>>{
+    let mut len = [0u8; 4];
+    match stream.read_exact(&mut len){
+        Ok(()) =>{}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let mut data = vec![0u8; u32::from_le_bytes(len) as usize];
+    stream.read_exact(&mut data)?;
+    Ok(Some(data))
+}
+
+struct Report{
+    dropped: u64,
+    received: Vec<(Vec<u8>, u64)>,
+}
+
+//send `messages` to a consumer process with a buffer of `capacity` slots
+fn run_consumer(name: &str, capacity: usize, messages: &[Vec<u8>]) -> Report{
+    let socket = SocketPath::new(name);
+    let listener = UnixListener::bind(&socket.0).unwrap();
+    listener.set_nonblocking(true).unwrap();
+
+    let child = Command::new(std::env::current_exe().unwrap())
+        .args(["consumer_child", "--exact", "--nocapture", "--test-threads=1"])
+        .env(SOCKET_ENV, &socket.0)
+        .env(CAPACITY_ENV, capacity.to_string())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut child = ChildGuard(child);
+
+    let deadline = Instant::now() + CONNECT_TIMEOUT;
+    let mut stream = loop{
+        match listener.accept(){
+            Ok((stream, _)) => break stream,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock =>{
+                assert!(child.0.try_wait().unwrap().is_none(), "consumer exited before connecting");
+                assert!(Instant::now() < deadline, "consumer never connected");
+                thread::sleep(Duration::from_millis(5));
+            }
+            Err(e) => panic!("accept failed: {}", e),
+        }
+    };
+    stream.set_nonblocking(false).unwrap();
+    stream.set_read_timeout(Some(CONNECT_TIMEOUT)).unwrap();
+
+    for message in messages{
+        write_message(&mut stream, message).unwrap();
+    }
+    stream.shutdown(std::net::Shutdown::Write).unwrap();
+
+    let header = read_message(&mut stream).unwrap().expect("consumer sent no report");
+    let dropped = u64::from_le_bytes(header.try_into().unwrap());
+    let mut received = Vec::new();
+    while let Some(data) = read_message(&mut stream).unwrap(){
+        let epoch = read_message(&mut stream).unwrap().expect("message without epoch");
+        received.push((data, u64::from_le_bytes(epoch.try_into().unwrap())));
+    }
+
+    assert!(child.0.wait().unwrap().success(), "consumer failed");
+    Report{ dropped, received }
+}
+
+//the child side; a no-op when the test runner calls it directly
+#[test]
+fn consumer_child(){
+    let Ok(path) = std::env::var(SOCKET_ENV) else{ return };
+    let capacity = std::env::var(CAPACITY_ENV).unwrap().parse().unwrap();
+    let buffer = ByteRingBuffer::new(capacity);
+
+    let mut stream = UnixStream::connect(path).unwrap();
+    while let Some(data) = read_message(&mut stream).unwrap(){
+        buffer.push_checked(&data).unwrap();
+    }
+
+    write_message(&mut stream, &buffer.dropped_count().to_le_bytes()).unwrap();
+    while let Some((data, epoch)) = buffer.pop(){
+        write_message(&mut stream, &data).unwrap();
+        write_message(&mut stream, &epoch.to_le_bytes()).unwrap();
+    }
+}
+
+#[test]
+fn child_buffer_delivers_socket_messages_in_order(){
+    let messages: Vec<Vec<u8>> = (0..8usize)
+        .map(|i| (0..(i * 31).min(MAX_PAYLOAD_SIZE)).map(|b| (b + i) as u8).collect())
+        .chain(std::iter::once(vec![0xAB; MAX_PAYLOAD_SIZE]))
+        .collect();
+
+    let report = run_consumer("delivery", 16, &messages);
+    assert_eq!(report.dropped, 0);
+    let data: Vec<_> = report.received.iter().map(|(d, _)| d.clone()).collect();
+    assert_eq!(data, messages);
+    let epochs: Vec<_> = report.received.iter().map(|&(_, e)| e).collect();
+    assert_eq!(epochs, (1..=9).collect::<Vec<u64>>());
+}
+
+#[test]
+fn child_buffer_overflow_keeps_newest(){
+    let messages: Vec<Vec<u8>> = (0..10u8).map(|i| vec![i; 4]).collect();
+
+    let report = run_consumer("overflow", 4, &messages);
+    assert_eq!(report.dropped, 6);
+    let received: Vec<_> = report.received.iter().map(|(d, e)| (d[0], *e)).collect();
+    assert_eq!(received, vec![(6, 7), (7, 8), (8, 9), (9, 10)]);
+}
@@ -0,0 +1,72 @@
+#!/usr/bin/env python3
+"""
+Buffer-protocol test: PyBibiByteTopic.publish reads any C-contiguous
+buffer in place, and try_receive_into fills a caller-provided one.
+
+Run with: pytest tests/test_buffer_protocol.py
+"""
+
+import array
+
+import pytest
+
+bibi_sync = pytest.importorskip("bibi_sync")
+
+
+def topic(name, capacity=8):
+    return bibi_sync.PyBibiRegistry().get_byte_topic(name, capacity)
+
+
+def test_publish_accepts_buffer_objects():
+    t = topic("/buffers")
+    payloads = [
+        b"\x01\x02",
+        bytearray(b"\x03\x04"),
+        memoryview(b"\x05\x06\x07"),
+        array.array("f", [1.5, -2.0]),
+    ]
+    for payload in payloads:
+        t.publish(payload)
+
+    for payload in payloads:
+        data, _ = t.try_receive()
+        assert bytes(data) == bytes(payload)
+
+
+def test_publish_rejects_non_contiguous():
+    t = topic("/strided")
+    strided = memoryview(bytearray(range(8)))[::2]
+    with pytest.raises(ValueError, match="C-contiguous"):
+        t.publish(strided)
+    assert t.is_empty()
+
+
+def test_try_receive_into_fills_caller_buffer():
+    t = topic("/into")
+    out = bytearray(16)
+    assert t.try_receive_into(out) is None
+
+    t.publish(b"\xAA\xBB\xCC")
+    assert t.try_receive_into(out) == (3, 1)
+    assert out[:4] == b"\xAA\xBB\xCC\x00"
+
+    with pytest.raises(ValueError, match="read-only"):
+        t.try_receive_into(b"\x00" * 16)
+
+    t.publish(bytes(32))
+    with pytest.raises(ValueError, match="does not fit"):
+        t.try_receive_into(out)
+
+
+def test_numpy_round_trip():
+    np = pytest.importorskip("numpy")
+    t = topic("/imu")
+    imu = np.arange(9, dtype=np.float32)
+    t.publish(imu)
+
+    out = np.zeros(9, dtype=np.float32)
+    assert t.try_receive_into(out) == (36, 1)
+    assert (out == imu).all()
+
+    with pytest.raises(ValueError, match="C-contiguous"):
+        t.publish(np.arange(18, dtype=np.float32)[::2])
@@ -0,0 +1,103 @@
+#!/usr/bin/env python3
+"""
+Property-based test: fuzz PyBibiByteTopic publish/receive sequences
+against a pure-Python model of the ring buffer.
+
+Run with: pytest tests/test_byte_topic_properties.py
+"""
+
+import pytest
+
+hypothesis = pytest.importorskip("hypothesis")
+from hypothesis import settings, strategies as st
+from hypothesis.stateful import RuleBasedStateMachine, initialize, invariant, rule
+
+import bibi_sync
+
+MAX_PAYLOAD = 244  # MAX_PAYLOAD_SIZE in src/ring_buffer/byte_buffer.rs
+
+
+class ByteTopicModel:
+    """What the buffer must look like from outside.
+
+    Overwrite-oldest makes the exact message returned after a lap an
+    implementation detail, so the model tracks epochs rather than a queue:
+    every receive must be newer than the last, still resident, and carry
+    the bytes published under its epoch.
+    """
+
+    def __init__(self, capacity):
+        self.capacity = capacity
+        self.published = {}  # epoch -> payload
+        self.latest = 0
+        self.last_received = 0
+        self.dropped = 0
+
+    def unread(self):
+        return self.latest - self.last_received
+
+    def publish(self, payload):
+        if self.unread() >= self.capacity:
+            self.dropped += 1
+        self.latest += 1
+        self.published[self.latest] = payload
+        return self.latest
+
+    def expected_len(self):
+        return min(self.unread(), self.capacity)
+
+
+class ByteTopicMachine(RuleBasedStateMachine):
+    @initialize(capacity=st.integers(min_value=1, max_value=8))
+    def setup(self, capacity):
+        registry = bibi_sync.PyBibiRegistry()
+        self.topic = registry.get_byte_topic("/fuzz", capacity)
+        self.model = ByteTopicModel(capacity)
+
+    @rule(payload=st.binary(max_size=MAX_PAYLOAD))
+    def publish(self, payload):
+        assert self.topic.publish(payload) == self.model.publish(payload)
+
+    @rule(payload=st.binary(min_size=MAX_PAYLOAD + 1, max_size=MAX_PAYLOAD + 16))
+    def publish_oversized(self, payload):
+        with pytest.raises(ValueError):
+            self.topic.publish(payload)
+
+    @rule()
+    def try_receive(self):
+        result = self.topic.try_receive()
+        if self.model.expected_len() == 0:
+            assert result is None
+            return
+
+        assert result is not None
+        data, epoch = result
+        assert self.model.last_received < epoch <= self.model.latest
+        assert epoch > self.model.latest - self.model.capacity, "returned an overwritten message"
+        assert bytes(data) == self.model.published[epoch]
+        self.model.last_received = epoch
+
+    @rule()
+    def peek_latest(self):
+        result = self.topic.peek_latest()
+        if self.model.latest == 0:
+            assert result is None
+            return
+        data, epoch = result
+        assert epoch == self.model.latest
+        assert bytes(data) == self.model.published[epoch]
+
+    @invariant()
+    def counters_match(self):
+        if not hasattr(self, "model"):
+            return
+        assert self.topic.capacity() == self.model.capacity
+        assert self.topic.latest_epoch() == self.model.latest
+        assert self.topic.len() == self.model.expected_len()
+        assert self.topic.is_empty() == (self.model.expected_len() == 0)
+        assert self.topic.is_full() == (self.model.expected_len() == self.model.capacity)
+        assert self.topic.dropped_count() == self.model.dropped
+
+
+ByteTopicMachine.TestCase.settings = settings(max_examples=200, stateful_step_count=60, deadline=None)
+test_byte_topic_matches_model = ByteTopicMachine.TestCase
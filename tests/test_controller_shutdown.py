@@ -0,0 +1,111 @@
+#!/usr/bin/env python3
+"""
+Shutdown test: a script that ends abruptly (uncaught exception, no
+shutdown() call) must still leave the thrusters at neutral PWM.
+
+The controller talks to a pseudo-terminal standing in for the STM32; the
+test reads every frame it wrote and checks the last thruster command.
+
+Run with: pytest tests/test_controller_shutdown.py
+"""
+
+import os
+import pty
+import struct
+import subprocess
+import sys
+import threading
+import tty
+
+import pytest
+
+pytest.importorskip("bibi_sync")
+
+SYNC = 0xAA
+THRUSTER = 0x03
+NEUTRAL = [1500] * 6
+
+
+def thruster_commands(stream):
+    """Decode every thruster frame in a captured byte stream, in order."""
+    commands = []
+    i = 0
+    while i + 4 <= len(stream):
+        if stream[i] != SYNC:
+            i += 1
+            continue
+        msg_type, length = stream[i + 1], stream[i + 2]
+        end = i + 3 + length
+        if end >= len(stream):
+            break
+        if stream[end] != (msg_type + length + sum(stream[i + 3:end])) & 0xFF:
+            i += 1
+            continue
+        if msg_type == THRUSTER and length == 24:
+            commands.append(list(struct.unpack("<6i", stream[i + 3:end])))
+        i = end + 1
+    return commands
+
+
+def run_script(body):
+    """Run `body` in a fresh interpreter with `port` bound to a pty, and
+    return everything the controller wrote to it."""
+    master, slave = pty.openpty()
+    tty.setraw(master)
+    port = os.ttyname(slave)
+
+    captured = bytearray()
+
+    def drain():
+        while True:
+            try:
+                chunk = os.read(master, 4096)
+            except OSError:
+                return
+            if not chunk:
+                return
+            captured.extend(chunk)
+
+    reader = threading.Thread(target=drain, daemon=True)
+    reader.start()
+
+    script = f"import bibi_sync, time\nport = {port!r}\n{body}"
+    proc = subprocess.run([sys.executable, "-c", script], capture_output=True, text=True, timeout=30)
+    os.close(slave)
+    reader.join(timeout=5)
+    os.close(master)
+    return proc, bytes(captured)
+
+
+def test_abrupt_script_end_neutralizes_thrusters():
+    # The controller sits in a reference cycle, which the interpreter is
+    # not obliged to collect at exit, so Drop alone can't be relied on
+    proc, stream = run_script(
+        "ctrl = bibi_sync.PyAuvController(port=port)\n"
+        "cycle = [ctrl]\n"
+        "cycle.append(cycle)\n"
+        "del ctrl\n"
+        "cycle[0].set_surge(50.0)\n"
+        "time.sleep(0.3)\n"
+        "raise RuntimeError('mission script crashed')\n"
+    )
+    assert proc.returncode != 0
+    assert "mission script crashed" in proc.stderr
+
+    commands = thruster_commands(stream)
+    assert any(cmd != NEUTRAL for cmd in commands), "surge never reached the thrusters"
+    assert commands[-1] == NEUTRAL, f"thrusters left at {commands[-1]}"
+
+
+def test_shutdown_is_idempotent():
+    proc, stream = run_script(
+        "with bibi_sync.PyAuvController(port=port) as ctrl:\n"
+        "    ctrl.set_surge(50.0)\n"
+        "    time.sleep(0.2)\n"
+        "ctrl.shutdown()\n"
+        "ctrl.shutdown(timeout=0.1)\n"
+        "del ctrl\n"
+    )
+    assert proc.returncode == 0, proc.stderr
+    assert "failed" not in proc.stderr
+    assert thruster_commands(stream)[-1] == NEUTRAL
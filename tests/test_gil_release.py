@@ -0,0 +1,87 @@
+#!/usr/bin/env python3
+"""
+GIL test: recv_timeout, try_receive and publish of bytes release the GIL
+inside Rust, so a receiving thread and a publishing thread run side by side
+instead of taking turns.
+
+Run with: pytest tests/test_gil_release.py
+"""
+
+import threading
+import time
+
+import pytest
+
+bibi_sync = pytest.importorskip("bibi_sync")
+
+MESSAGES = 50
+
+
+def test_blocked_receiver_lets_publisher_run():
+    topic = bibi_sync.PyBibiRegistry().get_byte_topic("/gil", 64)
+    received = []
+
+    def receive():
+        for _ in range(MESSAGES):
+            msg = topic.recv_timeout(5.0)
+            if msg is None:
+                return
+            received.append(bytes(msg[0]))
+
+    def publish():
+        for i in range(MESSAGES):
+            # Each publish lands while the receiver is parked inside Rust
+            time.sleep(0.002)
+            topic.publish(bytes([i]))
+
+    receiver = threading.Thread(target=receive)
+    publisher = threading.Thread(target=publish)
+    start = time.monotonic()
+    receiver.start()
+    publisher.start()
+    publisher.join(timeout=10)
+    receiver.join(timeout=10)
+
+    # Holding the GIL while parked would stall the publisher until every
+    # recv_timeout expired
+    assert received == [bytes([i]) for i in range(MESSAGES)]
+    assert time.monotonic() - start < 5.0
+
+
+def test_polling_receiver_and_publisher_progress_together():
+    topic = bibi_sync.PyBibiRegistry().get_byte_topic("/gil_poll", 1024)
+    received = []
+    published = threading.Event()
+
+    def receive():
+        # Poll with try_receive until the publisher is done and drained
+        while True:
+            msg = topic.try_receive()
+            if msg is not None:
+                received.append(bytes(msg[0]))
+            elif published.is_set():
+                return
+
+    def publish():
+        for i in range(MESSAGES * 10):
+            topic.publish(bytes([i % 256]) * 200)
+        published.set()
+
+    receiver = threading.Thread(target=receive)
+    publisher = threading.Thread(target=publish)
+    start = time.monotonic()
+    receiver.start()
+    publisher.start()
+    publisher.join(timeout=10)
+    receiver.join(timeout=10)
+
+    assert not receiver.is_alive() and not publisher.is_alive()
+    assert received == [bytes([i % 256]) * 200 for i in range(MESSAGES * 10)]
+    assert time.monotonic() - start < 5.0
+
+
+def test_recv_timeout_expires_empty():
+    topic = bibi_sync.PyBibiRegistry().get_byte_topic("/quiet", 4)
+    assert topic.recv_timeout(0.05) is None
+    with pytest.raises(ValueError):
+        topic.recv_timeout(-1.0)
@@ -0,0 +1,15 @@
+//peek_latest_ref is not pinned, so safe code can't hold one across a push
+use bibi_sync::{ByteTopic, Topic};
+
+fn main(){
+    let topic = Topic::<u32>::new("/count", 4);
+    topic.publish(1);
+    let (latest, _) = topic.peek_latest_ref().unwrap();
+
+    let bytes = ByteTopic::new("/raw", 4);
+    bytes.publish(&[1, 2, 3]);
+    let buffer = bytes.buffer();
+    let (raw, _) = buffer.peek_oldest_ref().unwrap();
+
+    println!("{} {:?}", latest, raw.len());
+}
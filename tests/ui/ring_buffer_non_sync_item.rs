@@ -0,0 +1,14 @@
+//with_latest hands out &T, so a buffer of non-Sync items must not be shared
+use std::cell::Cell;
+use std::sync::Arc;
+use std::thread;
+use bibi_sync::RingBuffer;
+
+fn main(){
+    let rb = Arc::new(RingBuffer::<Cell<u8>>::new(4));
+    let other = Arc::clone(&rb);
+    thread::spawn(move ||{
+        other.with_latest(|cell, _| cell.set(1));
+    });
+    rb.push(Cell::new(0));
+}
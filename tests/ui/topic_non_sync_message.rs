@@ -0,0 +1,8 @@
+//a Send-only type is not enough: subscribers borrow the latest message in place
+use std::cell::Cell;
+use bibi_sync::Topic;
+
+fn main(){
+    let topic = Topic::<Cell<u8>>::new("/cell", 4);
+    topic.publish(Cell::new(1));
+}